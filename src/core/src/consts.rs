@@ -11,3 +11,5 @@ pub const BOHR_RADIUS: f64 = 0.52917720859;
 pub const NA: f64 = 6.02214179e23;
 /// 4 * pi * epsilon_0
 pub const ELCC: f64 = 7.197589831304046;
+/// Reduced Planck constant
+pub const H_BAR: f64 = 6.350780094188417e-3;
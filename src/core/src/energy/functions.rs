@@ -459,6 +459,59 @@ impl BondPotential for MorsePotential {}
 impl AnglePotential for MorsePotential {}
 impl DihedralPotential for MorsePotential {}
 
+/// Soft repulsive potential, used as the conservative interaction in
+/// dissipative particle dynamics (Groot and Warren, J. Chem. Phys. 107, 4423,
+/// 1997).
+///
+/// The following energy expression is used: `V(r) = 1/2 * a * rc * (1 - r /
+/// rc)^2` for `r < rc` and `V(r) = 0` otherwise, where `a` is the maximal
+/// repulsion force, and `rc` the range of the potential.
+///
+/// # Examples
+///
+/// ```
+/// use lumol_core::energy::Potential;
+/// use lumol_core::energy::SoftRepulsion;
+///
+/// let potential = SoftRepulsion{a: 25.0, rc: 1.0};
+/// assert_eq!(potential.energy(0.5), 3.125);
+/// assert_eq!(potential.energy(1.5), 0.0);
+///
+/// assert_eq!(potential.force(0.5), 12.5);
+/// assert_eq!(potential.force(1.5), 0.0);
+/// ```
+#[derive(Clone, Copy)]
+pub struct SoftRepulsion {
+    /// Maximal repulsion force
+    pub a: f64,
+    /// Range of the potential
+    pub rc: f64,
+}
+
+impl Potential for SoftRepulsion {
+    fn energy(&self, r: f64) -> f64 {
+        if r < self.rc {
+            let dr = 1.0 - r / self.rc;
+            0.5 * self.a * self.rc * dr * dr
+        } else {
+            0.0
+        }
+    }
+
+    fn force(&self, r: f64) -> f64 {
+        if r < self.rc {
+            self.a * (1.0 - r / self.rc)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl PairPotential for SoftRepulsion {
+    fn tail_energy(&self, _: f64) -> f64 {0.0}
+    fn tail_virial(&self, _: f64) -> f64 {0.0}
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -598,4 +651,23 @@ mod tests {
         let e1 = morse.energy(1.3 + EPS);
         assert_relative_eq!((e0 - e1) / EPS, morse.force(1.3), epsilon=1e-6);
     }
+
+    #[test]
+    fn soft_repulsion() {
+        let soft = SoftRepulsion{a: 25.0, rc: 2.0};
+        assert_eq!(soft.energy(0.0), 25.0);
+        assert_eq!(soft.energy(1.0), 6.25);
+        assert_eq!(soft.energy(2.0), 0.0);
+
+        assert_eq!(soft.force(0.0), 25.0);
+        assert_eq!(soft.force(1.0), 12.5);
+        assert_eq!(soft.force(3.0), 0.0);
+
+        assert_eq!(soft.tail_energy(1.0), 0.0);
+        assert_eq!(soft.tail_virial(1.0), 0.0);
+
+        let e0 = soft.energy(1.3);
+        let e1 = soft.energy(1.3 + EPS);
+        assert_relative_eq!((e0 - e1) / EPS, soft.force(1.3), epsilon=1e-6);
+    }
 }
@@ -319,6 +319,29 @@ impl Ewald {
 
         return e_new - e_old;
     }
+
+    /// Real space energy contribution of the particles at `idxes`: the
+    /// interactions of these particles with the other particles and between
+    /// themselves.
+    fn real_space_particles_energy(&self, configuration: &Configuration, idxes: &[usize]) -> f64 {
+        let charges = configuration.particles().charge;
+        let mut energy = 0.0;
+        for (idx, &i) in idxes.iter().enumerate() {
+            let qi = charges[i];
+            if qi == 0.0 {continue}
+            for j in (0..configuration.size()).filter(|x| !idxes[..idx + 1].contains(x)) {
+                let qj = charges[j];
+                if qj == 0.0 {continue}
+
+                let distance = configuration.bond_distance(i, j);
+                let info = self.restriction.information(distance);
+
+                let r = configuration.distance(i, j);
+                energy += self.real_space_energy_pair(info, qi, qj, r);
+            }
+        }
+        return energy;
+    }
 }
 
 /// Self-interaction correction
@@ -332,6 +355,13 @@ impl Ewald {
                               .sum::<f64>();
         return -self.alpha / sqrt(PI) * q2 / ELCC;
     }
+
+    /// Self-interaction contribution of the particles at `idxes`
+    fn self_particles_energy(&self, configuration: &Configuration, idxes: &[usize]) -> f64 {
+        let charges = configuration.particles().charge;
+        let q2 = idxes.iter().map(|&i| charges[i] * charges[i]).sum::<f64>();
+        return -self.alpha / sqrt(PI) * q2 / ELCC;
+    }
 }
 
 /// k-space part of the summation
@@ -558,6 +588,38 @@ impl Ewald {
 
         return e_new - e_old;
     }
+
+    /// k-space energy contribution of the particles at `idxes`. This sets
+    /// `self.delta_rho` to the modification of the electrostatic density
+    /// when removing these particles.
+    fn kspace_particles_energy(&mut self, configuration: &Configuration, idxes: &[usize]) -> f64 {
+        // This also compute the Fourier phases for all the particles
+        let e_all = self.kspace_energy(configuration);
+        let charges = configuration.particles().charge;
+
+        let mut e_without = 0.0;
+        for ikx in 0..self.kmax {
+            for iky in 0..self.kmax {
+                for ikz in 0..self.kmax {
+                    let mut delta_rho = Complex::zero();
+                    for &i in idxes {
+                        let phi = self.fourier_phases[(ikx, i, 0)] * self.fourier_phases[(iky, i, 1)] * self.fourier_phases[(ikz, i, 2)];
+                        delta_rho = delta_rho - charges[i] * phi;
+                    }
+                    self.delta_rho[(ikx, iky, ikz)] = delta_rho;
+
+                    // The k = 0 case and the cutoff in k-space are already
+                    // handled in `expfactors`.
+                    if self.expfactors[(ikx, iky, ikz)].abs() < f64::EPSILON {continue}
+                    let density = (self.rho[(ikx, iky, ikz)] + delta_rho).norm();
+                    e_without += self.expfactors[(ikx, iky, ikz)] * density * density;
+                }
+            }
+        }
+        e_without *= 2.0 * PI / (configuration.cell.volume() * ELCC);
+
+        return e_all - e_without;
+    }
 }
 
 /// Molecular correction for Ewald summation
@@ -717,6 +779,39 @@ impl Ewald {
 
         return e_new - e_old;
     }
+
+    /// Molecular correction energy contribution of the particles at `idxes`
+    fn molcorrect_particles_energy(&self, configuration: &Configuration, idxes: &[usize]) -> f64 {
+        let charges = configuration.particles().charge;
+        let mut energy = 0.0;
+        for (idx, &i) in idxes.iter().enumerate() {
+            let qi = charges[i];
+            if qi == 0.0 {continue}
+            for j in (0..configuration.size()).filter(|x| !idxes[..idx + 1].contains(x)) {
+                let distance = configuration.bond_distance(i, j);
+                let info = self.restriction.information(distance);
+                if !info.excluded {continue}
+
+                let qj = charges[j];
+                if qj == 0.0 {continue}
+
+                let r = configuration.distance(i, j);
+                energy += self.molcorrect_energy_pair(info, qi, qj, r);
+            }
+        }
+        return energy;
+    }
+
+    /// Total energy contribution of the particles at `idxes`, setting
+    /// `self.delta_rho` for the removal of these particles.
+    fn particles_energy(&mut self, configuration: &Configuration, idxes: &[usize]) -> f64 {
+        self.precompute(&configuration.cell);
+        let real = self.real_space_particles_energy(configuration, idxes);
+        let self_e = self.self_particles_energy(configuration, idxes);
+        let kspace = self.kspace_particles_energy(configuration, idxes);
+        let molecular = self.molcorrect_particles_energy(configuration, idxes);
+        return real + self_e + kspace + molecular;
+    }
 }
 
 /// Thread-sade wrapper around Ewald implementing `CoulombicPotential`.
@@ -814,6 +909,19 @@ impl GlobalCache for SharedEwald {
         return real + kspace + molecular;
     }
 
+    fn add_particles_cost(&self, configuration: &Configuration, idxes: &[usize]) -> f64 {
+        let mut ewald = self.write();
+        let energy = ewald.particles_energy(configuration, idxes);
+        // The density already contains the new particles
+        ewald.delta_rho.fill(Complex::zero());
+        return energy;
+    }
+
+    fn remove_particles_cost(&self, configuration: &Configuration, idxes: &[usize]) -> f64 {
+        let mut ewald = self.write();
+        return -ewald.particles_energy(configuration, idxes);
+    }
+
     fn update(&self) {
         let mut ewald = self.write();
         for ikx in 0..ewald.kmax {
@@ -1184,5 +1292,23 @@ mod tests {
             let new_e = ewald_check.read().molcorrect_energy(&system);
             assert_ulps_eq!(cost, new_e - old_e);
         }
+
+        #[test]
+        fn add_remove_particles() {
+            let mut system = testing_system();
+            let mut ewald = SharedEwald::new(Ewald::new(8.0, 10));
+            ewald.set_restriction(PairRestriction::InterMolecular);
+
+            let ewald_check = ewald.clone();
+
+            let old_e = ewald_check.energy(&system);
+            let removed = ewald.remove_particles_cost(&system, &[3, 4, 5]);
+            let added = ewald.add_particles_cost(&system, &[3, 4, 5]);
+
+            system.remove_molecule(1);
+            let new_e = ewald_check.energy(&system);
+            assert_ulps_eq!(removed, new_e - old_e, epsilon=1e-12);
+            assert_ulps_eq!(added, old_e - new_e, epsilon=1e-12);
+        }
     }
 }
@@ -155,6 +155,15 @@ impl_box_clone!(GlobalPotential, BoxCloneGlobal, box_clone_gobal);
 ///         return 0.0
 ///     }
 ///
+///     fn add_particles_cost(&self, _: &Configuration, idxes: &[usize]) -> f64 {
+///         // Each new particle shifts the energy by delta
+///         self.delta * idxes.len() as f64
+///     }
+///
+///     fn remove_particles_cost(&self, _: &Configuration, idxes: &[usize]) -> f64 {
+///         -self.delta * idxes.len() as f64
+///     }
+///
 ///     fn update(&self) {
 ///         // We are not storing anything in the ShiftAll struct, so this
 ///         // function is a no-op.
@@ -171,7 +180,34 @@ pub trait GlobalCache {
     /// the particles are still in the system.
     fn move_particles_cost(&self, configuration: &Configuration, idxes: &[usize], newpos: &[Vector3D]) -> f64;
 
-    /// Update the cache as needed after a call to `move_particles_cost`.
+    /// Get the cost of adding particles to the system.
+    ///
+    /// The new particles are already in the `configuration`, at the indexes
+    /// in `idxes`. The cost is the difference between the energy of the
+    /// `configuration`, and the energy of the same configuration without the
+    /// new particles.
+    ///
+    /// The default implementation panics, meaning that the potential can not
+    /// be used with moves inserting particles in the system.
+    fn add_particles_cost(&self, _: &Configuration, _: &[usize]) -> f64 {
+        fatal_error!("This global potential does not support particles insertion")
+    }
+
+    /// Get the cost of removing particles from the system.
+    ///
+    /// The particles to remove are still in the `configuration`, at the
+    /// indexes in `idxes`. The cost is the difference between the energy of
+    /// the configuration without the removed particles, and the energy of the
+    /// `configuration`.
+    ///
+    /// The default implementation panics, meaning that the potential can not
+    /// be used with moves deleting particles from the system.
+    fn remove_particles_cost(&self, _: &Configuration, _: &[usize]) -> f64 {
+        fatal_error!("This global potential does not support particles deletion")
+    }
+
+    /// Update the cache as needed after a call to `move_particles_cost`,
+    /// `add_particles_cost` or `remove_particles_cost`.
     ///
     /// If the Monte Carlo move is accepted, this function will be called and
     /// should update any cached quantity so that further call to
@@ -199,3 +235,6 @@ pub use self::wolf::Wolf;
 
 mod ewald;
 pub use self::ewald::{Ewald, SharedEwald};
+
+mod pulling;
+pub use self::pulling::Pulling;
@@ -0,0 +1,436 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! External pulling potential for steered molecular dynamics
+use std::sync::{Arc, RwLock};
+
+use sys::Configuration;
+use types::{Matrix3, Vector3D, Zero};
+
+use super::{GlobalPotential, GlobalCache};
+
+/// The different ways of pulling the particles
+#[derive(Clone, Copy, Debug)]
+enum Mode {
+    /// Harmonic spring with force constant `k`, and an anchor moving at
+    /// `velocity` along the pulling direction
+    Spring {
+        k: f64,
+        velocity: f64,
+    },
+    /// Constant force along the pulling direction
+    Force(f64),
+}
+
+/// Time-dependent state of a pulling potential
+#[derive(Clone, Copy, Debug)]
+struct State {
+    /// Position of the spring anchor along the pulling direction. This is
+    /// `None` until the pulling is initialized.
+    anchor: Option<f64>,
+    /// Position of the center of mass of the pulled particles at the last
+    /// update, followed continuously across the periodic boundaries. This is
+    /// `None` until the pulling is initialized.
+    center: Option<Vector3D>,
+    /// Last value of the pulling coordinate
+    coordinate: f64,
+    /// Last value of the pulling force
+    force: f64,
+    /// Accumulated work
+    work: f64,
+}
+
+/// Pulling potential for steered molecular dynamics.
+///
+/// This potential pulls a group of particles along a fixed `direction`. The
+/// pulling coordinate $\xi$ is the projection of the center of mass of the
+/// group on the direction. The particles can either be pulled by a constant
+/// force $F$, with $V = - F \xi$; or by an harmonic spring with an anchor
+/// $\lambda(t)$ moving at constant velocity $v$, with $V = \frac{1}{2} k (\xi -
+/// \lambda(t))^2$.
+///
+/// The center of mass is followed continuously from one update to the next,
+/// so that $\xi$ does not jump by a cell length when the pulled particles
+/// are wrapped in the cell.
+///
+/// The anchor is moved, and the work done on the particles accumulated, by
+/// the [`Steering`][Steering] control. For a moving spring, the work is $W =
+/// \int k (\lambda - \xi) v dt$, and can be used with the Jarzynski equality
+/// to compute free energy differences. For a constant force, the work is $W =
+/// F (\xi - \xi_0)$.
+///
+/// Clones of a `Pulling` potential share the same state, so that the same
+/// potential can be used in a system, a control and an output. The pulling
+/// forces do not contribute to the virial.
+///
+/// [Steering]: ../sim/md/struct.Steering.html
+#[derive(Clone)]
+pub struct Pulling {
+    /// Indexes of the pulled particles
+    particles: Vec<usize>,
+    /// Normalized pulling direction
+    direction: Vector3D,
+    /// Pulling mode
+    mode: Mode,
+    /// Shared state of the pulling
+    state: Arc<RwLock<State>>,
+}
+
+impl Pulling {
+    /// Create a new pulling potential using an harmonic spring with force
+    /// constant `k`, pulling the `particles` along `direction`. The spring
+    /// anchor moves with the given `velocity`, starting from the initial value
+    /// of the pulling coordinate.
+    pub fn spring(particles: Vec<usize>, direction: Vector3D, k: f64, velocity: f64) -> Pulling {
+        assert!(k >= 0.0, "The spring constant must be positive in pulling potential");
+        Pulling::with_mode(particles, direction, Mode::Spring{k: k, velocity: velocity})
+    }
+
+    /// Create a new pulling potential applying a constant `force` on the
+    /// center of mass of the `particles`, along `direction`.
+    pub fn constant_force(particles: Vec<usize>, direction: Vector3D, force: f64) -> Pulling {
+        Pulling::with_mode(particles, direction, Mode::Force(force))
+    }
+
+    fn with_mode(particles: Vec<usize>, direction: Vector3D, mode: Mode) -> Pulling {
+        assert!(!particles.is_empty(), "Can not pull an empty group of particles");
+        assert!(direction.norm2() > 0.0, "The pulling direction can not be null");
+        Pulling {
+            particles: particles,
+            direction: direction.normalized(),
+            mode: mode,
+            state: Arc::new(RwLock::new(State {
+                anchor: None,
+                center: None,
+                coordinate: 0.0,
+                force: 0.0,
+                work: 0.0,
+            })),
+        }
+    }
+
+    /// Set the initial position of the spring anchor along the pulling
+    /// direction. By default, the anchor starts at the initial value of the
+    /// pulling coordinate.
+    pub fn set_anchor(&mut self, anchor: f64) {
+        self.state.write().expect("poisoned lock").anchor = Some(anchor);
+    }
+
+    /// Get the indexes of the pulled particles
+    pub fn particles(&self) -> &[usize] {
+        &self.particles
+    }
+
+    /// Get the current position of the spring anchor, if any.
+    pub fn anchor(&self) -> Option<f64> {
+        self.state.read().expect("poisoned lock").anchor
+    }
+
+    /// Get the value of the pulling coordinate at the last update
+    pub fn coordinate(&self) -> f64 {
+        self.state.read().expect("poisoned lock").coordinate
+    }
+
+    /// Get the pulling force along the direction at the last update
+    pub fn force(&self) -> f64 {
+        self.state.read().expect("poisoned lock").force
+    }
+
+    /// Get the work accumulated since the pulling was initialized
+    pub fn work(&self) -> f64 {
+        self.state.read().expect("poisoned lock").work
+    }
+
+    /// Initialize the pulling with the `configuration` at the beginning of a
+    /// simulation. This sets the anchor if needed, and resets the work.
+    pub fn initialize(&self, configuration: &Configuration) {
+        let positions = configuration.particles().position;
+        let center = self.group_center(configuration, |i| positions[i]);
+        let xi = self.direction * center;
+        let mut state = self.state.write().expect("poisoned lock");
+        state.center = Some(center);
+        if state.anchor.is_none() {
+            state.anchor = Some(xi);
+        }
+        state.coordinate = xi;
+        state.force = self.pulling_force(xi, state.anchor);
+        state.work = 0.0;
+    }
+
+    /// Advance the pulling by a time `dt`, moving the spring anchor and
+    /// accumulating the work done with the current `configuration`.
+    pub fn advance(&self, configuration: &Configuration, dt: f64) {
+        let positions = configuration.particles().position;
+        let center = self.center_with(configuration, |i| positions[i]);
+        let xi = self.direction * center;
+        let mut state = self.state.write().expect("poisoned lock");
+        state.center = Some(center);
+        match self.mode {
+            Mode::Spring{k, velocity} => {
+                let anchor = state.anchor.unwrap_or(xi);
+                state.work += k * (anchor - xi) * velocity * dt;
+                state.anchor = Some(anchor + velocity * dt);
+            }
+            Mode::Force(force) => {
+                state.work += force * (xi - state.coordinate);
+            }
+        }
+        state.coordinate = xi;
+        state.force = self.pulling_force(xi, state.anchor);
+    }
+
+    /// Compute the pulling coordinate for the `configuration`
+    fn value(&self, configuration: &Configuration) -> f64 {
+        let positions = configuration.particles().position;
+        self.value_with(configuration, |i| positions[i])
+    }
+
+    /// Compute the pulling coordinate for the `configuration`, using the
+    /// `position` function to get the position of the pulled particles.
+    fn value_with<F>(&self, configuration: &Configuration, position: F) -> f64 where F: Fn(usize) -> Vector3D {
+        self.direction * self.center_with(configuration, position)
+    }
+
+    /// Compute the center of mass of the pulled particles, using the
+    /// `position` function to get their positions. The center of mass is
+    /// followed continuously from its value at the last update, so that the
+    /// pulling coordinate does not jump when the particles are wrapped in the
+    /// cell.
+    fn center_with<F>(&self, configuration: &Configuration, position: F) -> Vector3D where F: Fn(usize) -> Vector3D {
+        let center = self.group_center(configuration, position);
+        match self.state.read().expect("poisoned lock").center {
+            Some(previous) => {
+                let mut delta = center - previous;
+                configuration.cell.vector_image(&mut delta);
+                previous + delta
+            }
+            None => center,
+        }
+    }
+
+    /// Compute the center of mass of the pulled particles in the
+    /// `configuration`, using the `position` function to get their
+    /// positions. The particles are unwrapped relative to the first pulled
+    /// particle, to account for groups split across the periodic boundaries.
+    fn group_center<F>(&self, configuration: &Configuration, position: F) -> Vector3D where F: Fn(usize) -> Vector3D {
+        let masses = configuration.particles().mass;
+        let first = position(self.particles[0]);
+        let mut total_mass = 0.0;
+        let mut com = Vector3D::zero();
+        for &i in &self.particles {
+            let mut delta = position(i) - first;
+            configuration.cell.vector_image(&mut delta);
+            total_mass += masses[i];
+            com += masses[i] * (first + delta);
+        }
+        return com / total_mass;
+    }
+
+    /// Get the total mass of the pulled particles
+    fn total_mass(&self, configuration: &Configuration) -> f64 {
+        let particles = configuration.particles();
+        self.particles.iter().map(|&i| particles.mass[i]).sum()
+    }
+
+    /// Get the pulling force along the direction for the coordinate `xi`
+    fn pulling_force(&self, xi: f64, anchor: Option<f64>) -> f64 {
+        match self.mode {
+            Mode::Spring{k, ..} => k * (anchor.unwrap_or(xi) - xi),
+            Mode::Force(force) => force,
+        }
+    }
+
+    /// Get the pulling energy for the coordinate `xi`
+    fn energy_at(&self, xi: f64) -> f64 {
+        match self.mode {
+            Mode::Spring{k, ..} => {
+                let anchor = self.anchor().unwrap_or(xi);
+                0.5 * k * (xi - anchor) * (xi - anchor)
+            }
+            Mode::Force(force) => -force * xi,
+        }
+    }
+}
+
+impl GlobalPotential for Pulling {
+    fn cutoff(&self) -> Option<f64> {
+        None
+    }
+
+    fn energy(&self, configuration: &Configuration) -> f64 {
+        self.energy_at(self.value(configuration))
+    }
+
+    fn forces(&self, configuration: &Configuration, forces: &mut [Vector3D]) {
+        let force = self.pulling_force(self.value(configuration), self.anchor());
+        let total_mass = self.total_mass(configuration);
+        let particles = configuration.particles();
+        for &i in &self.particles {
+            forces[i] += particles.mass[i] / total_mass * force * self.direction;
+        }
+    }
+
+    fn virial(&self, _: &Configuration) -> Matrix3 {
+        Matrix3::zero()
+    }
+}
+
+impl GlobalCache for Pulling {
+    fn move_particles_cost(&self, configuration: &Configuration, idxes: &[usize], newpos: &[Vector3D]) -> f64 {
+        let positions = configuration.particles().position;
+        let xi = self.value(configuration);
+        let new_xi = self.value_with(configuration, |i| {
+            match idxes.iter().position(|&moved| moved == i) {
+                Some(k) => newpos[k],
+                None => positions[i],
+            }
+        });
+        return self.energy_at(new_xi) - self.energy_at(xi);
+    }
+
+    fn add_particles_cost(&self, _: &Configuration, idxes: &[usize]) -> f64 {
+        if idxes.iter().any(|i| self.particles.contains(i)) {
+            fatal_error!("Pulled particles can not be added to the system");
+        }
+        // New particles do not change the pulling coordinate
+        return 0.0;
+    }
+
+    fn remove_particles_cost(&self, _: &Configuration, idxes: &[usize]) -> f64 {
+        if idxes.iter().any(|&i| self.particles.iter().any(|&pulled| pulled >= i)) {
+            fatal_error!("Particles before or in the pulled particles can not be removed from the system");
+        }
+        return 0.0;
+    }
+
+    fn update(&self) {
+        // Nothing to do
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sys::{Particle, System, UnitCell};
+    use utils::unit_from;
+
+    fn testing_system() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        system.add_particle(Particle::with_position("He", Vector3D::new(0.0, 0.0, 0.0)));
+        system.add_particle(Particle::with_position("He", Vector3D::new(1.0, 0.0, 2.0)));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(5.0, 5.0, 5.0)));
+        return system;
+    }
+
+    #[test]
+    fn spring() {
+        let system = testing_system();
+        let k = unit_from(100.0, "kJ/mol/A^2");
+        let velocity = unit_from(1.0, "A/ps");
+        let pulling = Pulling::spring(vec![0, 1], Vector3D::new(0.0, 0.0, 2.0), k, velocity);
+
+        pulling.initialize(&system);
+        assert_eq!(pulling.coordinate(), 1.0);
+        assert_eq!(pulling.anchor(), Some(1.0));
+        assert_eq!(pulling.energy(&system), 0.0);
+
+        let dt = unit_from(1.0, "fs");
+        pulling.advance(&system, dt);
+        assert_ulps_eq!(pulling.anchor().unwrap(), 1.0 + velocity * dt);
+        // The work is computed with the anchor at the beginning of the step
+        assert_eq!(pulling.work(), 0.0);
+
+        let extension = pulling.anchor().unwrap() - 1.0;
+        assert_ulps_eq!(pulling.force(), k * extension);
+        assert_ulps_eq!(pulling.energy(&system), 0.5 * k * extension * extension);
+
+        let mut forces = vec![Vector3D::zero(); 3];
+        pulling.forces(&system, &mut forces);
+        assert_ulps_eq!(forces[0], Vector3D::new(0.0, 0.0, 0.5 * k * extension));
+        assert_ulps_eq!(forces[1], Vector3D::new(0.0, 0.0, 0.5 * k * extension));
+        assert_eq!(forces[2], Vector3D::zero());
+
+        pulling.advance(&system, dt);
+        assert_ulps_eq!(pulling.work(), k * extension * velocity * dt);
+    }
+
+    #[test]
+    fn constant_force() {
+        let mut system = testing_system();
+        let force = unit_from(10.0, "kJ/mol/A");
+        let pulling = Pulling::constant_force(vec![0, 1], Vector3D::new(0.0, 0.0, 1.0), force);
+        pulling.initialize(&system);
+        assert_eq!(pulling.force(), force);
+        assert_eq!(pulling.energy(&system), -force);
+
+        system.particles_mut().position[0][2] += 1.0;
+        pulling.advance(&system, 1.0);
+        assert_ulps_eq!(pulling.coordinate(), 1.5);
+        assert_ulps_eq!(pulling.work(), 0.5 * force);
+    }
+
+    #[test]
+    fn shared_state() {
+        let system = testing_system();
+        let pulling = Pulling::spring(vec![2], Vector3D::new(1.0, 0.0, 0.0), 1.0, 1.0);
+        let clone = pulling.clone();
+        pulling.initialize(&system);
+        clone.advance(&system, 2.0);
+        assert_eq!(pulling.anchor(), Some(7.0));
+    }
+
+    #[test]
+    fn move_particles_cost() {
+        let system = testing_system();
+        let pulling = Pulling::spring(vec![0, 1], Vector3D::new(0.0, 0.0, 1.0), 2.0, 1.0);
+        pulling.initialize(&system);
+
+        let idxes = &[1, 2];
+        let newpos = &[Vector3D::new(1.0, 0.0, 4.0), Vector3D::zero()];
+        // The center of mass moves by 1 A along z
+        assert_ulps_eq!(pulling.move_particles_cost(&system, idxes, newpos), 1.0);
+    }
+
+    #[test]
+    fn periodic_boundaries() {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        system.add_particle(Particle::with_position("He", Vector3D::new(9.5, 0.0, 0.0)));
+        system.add_particle(Particle::with_position("He", Vector3D::new(-9.5, 0.0, 0.0)));
+        let pulling = Pulling::spring(vec![0, 1], Vector3D::new(1.0, 0.0, 0.0), 2.0, 1.0);
+
+        // The group is unwrapped across the boundary
+        pulling.initialize(&system);
+        assert_ulps_eq!(pulling.coordinate(), 10.0);
+
+        // Moving the second particle by 0.5 A moves the center of mass by
+        // 0.25 A
+        let cost = pulling.move_particles_cost(&system, &[1], &[Vector3D::new(-9.0, 0.0, 0.0)]);
+        assert_ulps_eq!(cost, 0.5 * 2.0 * 0.25 * 0.25, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn wrapped_group() {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        system.add_particle(Particle::with_position("He", Vector3D::new(9.9, 0.0, 0.0)));
+        system.add_particle(Particle::with_position("He", Vector3D::new(9.0, 0.0, 0.0)));
+        let force = unit_from(10.0, "kJ/mol/A");
+        let pulling = Pulling::constant_force(vec![0, 1], Vector3D::new(1.0, 0.0, 0.0), force);
+        pulling.initialize(&system);
+        assert_ulps_eq!(pulling.coordinate(), 9.45, epsilon = 1e-12);
+
+        // The first pulled particle crosses the boundary and is wrapped in
+        // the cell
+        system.particles_mut().position[0] = Vector3D::new(-9.9, 0.0, 0.0);
+        assert_ulps_eq!(pulling.energy(&system), -force * 9.55, epsilon = 1e-12);
+
+        pulling.advance(&system, 1.0);
+        assert_ulps_eq!(pulling.coordinate(), 9.55, epsilon = 1e-12);
+        assert_ulps_eq!(pulling.work(), force * 0.1, epsilon = 1e-12);
+
+        // The coordinate keeps following the group
+        system.particles_mut().position[1] = Vector3D::new(-9.8, 0.0, 0.0);
+        pulling.advance(&system, 1.0);
+        assert_ulps_eq!(pulling.coordinate(), 10.15, epsilon = 1e-12);
+        assert_ulps_eq!(pulling.work(), force * 0.7, epsilon = 1e-12);
+    }
+}
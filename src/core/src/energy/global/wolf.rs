@@ -113,6 +113,29 @@ impl Wolf {
                      2.0 * self.alpha / sqrt(PI) * exp(-self.alpha * self.alpha * d * d) / d;
         return info.scaling * qiqj * (factor - self.force_cst) * rij.normalized() / ELCC;
     }
+
+    /// Get the energy contribution of the particles at `idxes` in the
+    /// configuration: the interactions of these particles with the other
+    /// particles and between themselves, and their self interaction.
+    fn particles_energy(&self, config: &Configuration, idxes: &[usize]) -> f64 {
+        let charges = config.particles().charge;
+        let mut energy = 0.0;
+        for (idx, &i) in idxes.iter().enumerate() {
+            let qi = charges[i];
+            if qi == 0.0 {continue;}
+            energy -= self.energy_self(qi);
+
+            for j in (0..config.size()).filter(|x| !idxes[..idx + 1].contains(x)) {
+                let qj = charges[j];
+                if qj == 0.0 {continue;}
+
+                let distance = config.bond_distance(i, j);
+                let info = self.restriction.information(distance);
+                energy += self.energy_pair(info, qi * qj, config.distance(i, j));
+            }
+        }
+        return energy;
+    }
 }
 
 impl GlobalCache for Wolf {
@@ -164,6 +187,14 @@ impl GlobalCache for Wolf {
         return e_new - e_old;
     }
 
+    fn add_particles_cost(&self, config: &Configuration, idxes: &[usize]) -> f64 {
+        self.particles_energy(config, idxes)
+    }
+
+    fn remove_particles_cost(&self, config: &Configuration, idxes: &[usize]) -> f64 {
+        -self.particles_energy(config, idxes)
+    }
+
     fn update(&self) {
         // Nothing to do
     }
@@ -365,5 +396,20 @@ mod tests {
             let new_e = check.energy(&system);
             assert_ulps_eq!(cost, new_e - old_e);
         }
+
+        #[test]
+        fn add_remove_particles() {
+            let mut system = testing_system();
+            let mut wolf = Wolf::new(8.0);
+            wolf.set_restriction(PairRestriction::InterMolecular);
+
+            let energy = wolf.energy(&system);
+            let cost = wolf.remove_particles_cost(&system, &[3, 4, 5]);
+            let added = wolf.add_particles_cost(&system, &[3, 4, 5]);
+            system.remove_molecule(1);
+            let new_e = wolf.energy(&system);
+            assert_ulps_eq!(cost, new_e - energy);
+            assert_ulps_eq!(added, energy - new_e);
+        }
     }
 }
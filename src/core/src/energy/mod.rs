@@ -242,6 +242,7 @@ impl_box_clone!(DihedralPotential, BoxCloneDihedral, box_clone_dihedral);
 mod functions;
 pub use self::functions::{NullPotential, LennardJones, WCA, Harmonic, CosineHarmonic};
 pub use self::functions::{Torsion, Buckingham, BornMayerHuggins, MorsePotential};
+pub use self::functions::SoftRepulsion;
 
 mod computations;
 pub use self::computations::{Computation, TableComputation};
@@ -252,6 +253,7 @@ pub use self::restrictions::{PairRestriction, RestrictionInfo};
 mod global;
 pub use self::global::{GlobalPotential, GlobalCache, CoulombicPotential};
 pub use self::global::{Wolf, Ewald, SharedEwald};
+pub use self::global::Pulling;
 
 mod pairs;
 pub use self::pairs::PairInteraction;
@@ -11,7 +11,9 @@ use utils;
 use sys::System;
 
 /// The `EnergyOutput` writes the energy of the system to a text file, organized
-/// as: `PotentialEnergy     KineticEnergy     TotalEnergy`.
+/// as: `PotentialEnergy     KineticEnergy     TotalEnergy     ConservedEnergy`.
+/// The conserved energy includes the energy of the extended degrees of freedom
+/// of some integrators, and can be used to check the energy drift.
 pub struct EnergyOutput {
     file: File,
     path: PathBuf
@@ -33,7 +35,7 @@ impl Output for EnergyOutput {
         if let Err(err) = writeln!(&mut self.file, "# Energy of the simulation (kJ/mol)") {
             fatal_error!("Could not write to file '{}': {}", self.path.display(), err);
         }
-        if let Err(err) = writeln!(&mut self.file, "# Step Potential Kinetic Total") {
+        if let Err(err) = writeln!(&mut self.file, "# Step Potential Kinetic Total Conserved") {
             fatal_error!("Could not write to file '{}': {}", self.path.display(), err);
         }
     }
@@ -42,7 +44,8 @@ impl Output for EnergyOutput {
         let potential = utils::unit_to(system.potential_energy(), "kJ/mol");
         let kinetic = utils::unit_to(system.kinetic_energy(), "kJ/mol");
         let total = utils::unit_to(system.total_energy(), "kJ/mol");
-        if let Err(err) = writeln!(&mut self.file, "{} {} {} {}", system.step(), potential, kinetic, total) {
+        let conserved = utils::unit_to(system.conserved_energy(), "kJ/mol");
+        if let Err(err) = writeln!(&mut self.file, "{} {} {} {} {}", system.step(), potential, kinetic, total, conserved) {
             error!("Could not write to file '{}': {}", self.path.display(), err);
         }
     }
@@ -59,8 +62,8 @@ mod tests {
             Box::new(EnergyOutput::new(path).unwrap())
         },
 "# Energy of the simulation (kJ/mol)
-# Step Potential Kinetic Total
-0 1.5000000000000027 949.9201593348566 951.4201593348566
+# Step Potential Kinetic Total Conserved
+0 1.5000000000000027 949.9201593348566 951.4201593348566 951.4201593348566
 "
         );
     }
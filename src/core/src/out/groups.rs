@@ -0,0 +1,84 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use std::io::prelude::*;
+use std::io;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use super::Output;
+
+use utils;
+use sys::{System, Selection};
+use sys::compute::{Compute, GroupKineticEnergy, GroupTemperature};
+
+/// The `GroupTemperatureOutput` writes the temperature and kinetic energy of
+/// some groups of particles to a file. For each group, the file contains two
+/// columns: `Temperature KineticEnergy`, in the same order as the groups.
+pub struct GroupTemperatureOutput {
+    file: File,
+    path: PathBuf,
+    groups: Vec<Selection>,
+}
+
+impl GroupTemperatureOutput {
+    /// Create a new `GroupTemperatureOutput` writing the temperature and
+    /// kinetic energy of the `groups` to `filename`. The file is replaced if
+    /// it already exists.
+    pub fn new<P: AsRef<Path>>(filename: P, groups: Vec<Selection>) -> Result<GroupTemperatureOutput, io::Error> {
+        Ok(GroupTemperatureOutput{
+            file: try!(File::create(filename.as_ref())),
+            path: filename.as_ref().to_owned(),
+            groups: groups,
+        })
+    }
+}
+
+impl Output for GroupTemperatureOutput {
+    fn setup(&mut self, _: &System) {
+        if let Err(err) = writeln!(&mut self.file, "# Temperature (K) and kinetic energy (kJ/mol) of groups") {
+            fatal_error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+        let mut header = String::from("# Step");
+        for i in 0..self.groups.len() {
+            header.push_str(&format!(" Temperature_{} Kinetic_{}", i, i));
+        }
+        if let Err(err) = writeln!(&mut self.file, "{}", header) {
+            fatal_error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+    }
+
+    fn write(&mut self, system: &System) {
+        let mut line = format!("{}", system.step());
+        for selection in &self.groups {
+            let temperature = GroupTemperature{selection: selection}.compute(system);
+            let kinetic = GroupKineticEnergy{selection: selection}.compute(system);
+            line.push_str(&format!(" {} {}",
+                utils::unit_to(temperature, "K"),
+                utils::unit_to(kinetic, "kJ/mol")
+            ));
+        }
+        if let Err(err) = writeln!(&mut self.file, "{}", line) {
+            error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::test_output;
+
+    #[test]
+    fn groups() {
+        test_output(|path| {
+            let groups = vec![Selection::Range(0..1), Selection::Range(1..2)];
+            Box::new(GroupTemperatureOutput::new(path, groups).unwrap())
+        },
+"# Temperature (K) and kinetic energy (kJ/mol) of groups
+# Step Temperature_0 Kinetic_0 Temperature_1 Kinetic_1
+0 76166.08778344624 949.9201593348566 0 0
+"
+        );
+    }
+}
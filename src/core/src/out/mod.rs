@@ -24,11 +24,21 @@ mod tests;
 mod cell;
 mod energy;
 mod custom;
+mod groups;
+mod pimd;
 mod properties;
+mod pulling;
+mod stress;
 mod trajectory;
+mod widom;
 
 pub use self::cell::CellOutput;
 pub use self::energy::EnergyOutput;
 pub use self::custom::{CustomOutput, CustomOutputError};
+pub use self::groups::GroupTemperatureOutput;
+pub use self::pimd::PathIntegralOutput;
 pub use self::properties::PropertiesOutput;
+pub use self::pulling::PullingOutput;
+pub use self::stress::StressOutput;
 pub use self::trajectory::TrajectoryOutput;
+pub use self::widom::WidomOutput;
@@ -0,0 +1,74 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use std::io::prelude::*;
+use std::io;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use super::Output;
+
+use utils;
+use sys::System;
+use sim::md::CentroidVirial;
+
+/// The `PathIntegralOutput` writes the quantum kinetic energy of a path
+/// integral molecular dynamics simulation to a file, using the
+/// [`CentroidVirial`][CentroidVirial] estimator.
+///
+/// [CentroidVirial]: ../sim/md/struct.CentroidVirial.html
+pub struct PathIntegralOutput {
+    file: File,
+    path: PathBuf,
+    estimator: CentroidVirial,
+}
+
+impl PathIntegralOutput {
+    /// Create a new `PathIntegralOutput` writing the value of `estimator` to
+    /// `filename`. The file is replaced if it already exists.
+    pub fn new<P: AsRef<Path>>(filename: P, estimator: CentroidVirial) -> Result<PathIntegralOutput, io::Error> {
+        Ok(PathIntegralOutput{
+            file: try!(File::create(filename.as_ref())),
+            path: filename.as_ref().to_owned(),
+            estimator: estimator,
+        })
+    }
+}
+
+impl Output for PathIntegralOutput {
+    fn setup(&mut self, _: &System) {
+        if let Err(err) = writeln!(&mut self.file, "# Path integral molecular dynamics") {
+            fatal_error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+        if let Err(err) = writeln!(&mut self.file, "# Step CentroidVirialKinetic/(kJ/mol)") {
+            fatal_error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+    }
+
+    fn write(&mut self, system: &System) {
+        let kinetic = utils::unit_to(self.estimator.kinetic_energy(), "kJ/mol");
+        if let Err(err) = writeln!(&mut self.file, "{} {}", system.step(), kinetic) {
+            error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::test_output;
+    use sim::PathIntegral;
+
+    #[test]
+    fn path_integral() {
+        test_output(|path| {
+            let propagator = PathIntegral::new(1.0, 300.0, 4);
+            Box::new(PathIntegralOutput::new(path, propagator.estimator()).unwrap())
+        },
+"# Path integral molecular dynamics
+# Step CentroidVirialKinetic/(kJ/mol)
+0 0
+"
+        );
+    }
+}
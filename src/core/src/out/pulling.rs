@@ -0,0 +1,78 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use std::io::prelude::*;
+use std::io;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use super::Output;
+
+use utils;
+use sys::System;
+use energy::Pulling;
+
+/// The `PullingOutput` writes the state of a [`Pulling`][Pulling] potential
+/// to a file, for steered molecular dynamics. The columns in the file contain
+/// the following values: `coordinate force work`. The work can be used with
+/// the Jarzynski equality to compute free energy differences.
+///
+/// [Pulling]: ../energy/struct.Pulling.html
+pub struct PullingOutput {
+    file: File,
+    path: PathBuf,
+    pulling: Pulling,
+}
+
+impl PullingOutput {
+    /// Create a new `PullingOutput` writing the state of `pulling` to
+    /// `filename`. The file is replaced if it already exists.
+    pub fn new<P: AsRef<Path>>(filename: P, pulling: Pulling) -> Result<PullingOutput, io::Error> {
+        Ok(PullingOutput{
+            file: try!(File::create(filename.as_ref())),
+            path: filename.as_ref().to_owned(),
+            pulling: pulling,
+        })
+    }
+}
+
+impl Output for PullingOutput {
+    fn setup(&mut self, _: &System) {
+        if let Err(err) = writeln!(&mut self.file, "# Steered molecular dynamics") {
+            fatal_error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+        if let Err(err) = writeln!(&mut self.file, "# Step Coordinate/A Force/(kJ/mol/A) Work/(kJ/mol)") {
+            fatal_error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+    }
+
+    fn write(&mut self, system: &System) {
+        let coordinate = utils::unit_to(self.pulling.coordinate(), "A");
+        let force = utils::unit_to(self.pulling.force(), "kJ/mol/A");
+        let work = utils::unit_to(self.pulling.work(), "kJ/mol");
+        if let Err(err) = writeln!(&mut self.file, "{} {} {} {}", system.step(), coordinate, force, work) {
+            error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::{test_output, testing_system};
+    use types::Vector3D;
+
+    #[test]
+    fn pulling() {
+        test_output(|path| {
+            let pulling = Pulling::constant_force(vec![1], Vector3D::new(1.0, 0.0, 0.0), 0.0);
+            pulling.initialize(&testing_system());
+            Box::new(PullingOutput::new(path, pulling).unwrap())
+        },
+"# Steered molecular dynamics
+# Step Coordinate/A Force/(kJ/mol/A) Work/(kJ/mol)
+0 1.3 0 0
+"
+        );
+    }
+}
@@ -0,0 +1,82 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use std::io::prelude::*;
+use std::io;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use super::Output;
+
+use utils;
+use sys::System;
+
+/// The `StressOutput` writes the off-diagonal components of the stress tensor
+/// of the system to a file. The columns in the file contain the following
+/// values: `Pxy Pxz Pyz`.
+///
+/// Together with the [`Sllod`][Sllod] integrator, the `Pxy` component can be
+/// used to compute the shear viscosity as $\eta = - \langle P_{xy} \rangle /
+/// \dot\gamma$.
+///
+/// [Sllod]: ../sim/md/struct.Sllod.html
+pub struct StressOutput {
+    file: File,
+    path: PathBuf,
+}
+
+impl StressOutput {
+    /// Create a new `StressOutput` writing to `filename`. The file is replaced
+    /// if it already exists.
+    pub fn new<P: AsRef<Path>>(filename: P) -> Result<StressOutput, io::Error> {
+        Ok(StressOutput{
+            file: try!(File::create(filename.as_ref())),
+            path: filename.as_ref().to_owned(),
+        })
+    }
+}
+
+impl Output for StressOutput {
+    fn setup(&mut self, _: &System) {
+        if let Err(err) = writeln!(&mut self.file, "# Shear stress of the simulation") {
+            fatal_error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+        if let Err(err) = writeln!(&mut self.file, "# Step Pxy/bar Pxz/bar Pyz/bar") {
+            fatal_error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+    }
+
+    fn write(&mut self, system: &System) {
+        let stress = system.stress();
+        let xy = utils::unit_to(stress[(0, 1)], "bar");
+        let xz = utils::unit_to(stress[(0, 2)], "bar");
+        let yz = utils::unit_to(stress[(1, 2)], "bar");
+        if let Err(err) = writeln!(&mut self.file, "{} {} {} {}", system.step(), xy, xz, yz) {
+            error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::{test_output, testing_system};
+
+    #[test]
+    fn stress() {
+        let stress = testing_system().stress();
+        let expected = format!(
+"# Shear stress of the simulation
+# Step Pxy/bar Pxz/bar Pyz/bar
+0 {} {} {}
+",
+            utils::unit_to(stress[(0, 1)], "bar"),
+            utils::unit_to(stress[(0, 2)], "bar"),
+            utils::unit_to(stress[(1, 2)], "bar"),
+        );
+
+        test_output(|path| {
+            Box::new(StressOutput::new(path).unwrap())
+        }, &expected);
+    }
+}
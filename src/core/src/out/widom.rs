@@ -0,0 +1,302 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use rand::distributions::Normal;
+use rand::{self, Rng, SeedableRng};
+
+use std::io::prelude::*;
+use std::io;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::f64;
+
+use super::Output;
+
+use consts::K_BOLTZMANN;
+use types::{Vector3D, Zero};
+use sys::{System, Molecule, Particle, ParticleKind, ParticleVec};
+use sim::mc::{random_rotation, center_particles, copy_charge};
+use utils;
+
+/// The `WidomOutput` computes the excess chemical potential of a molecule
+/// using Widom test particle insertion, and writes its running average to a
+/// file.
+///
+/// At each step, ghost copies of the molecule are inserted at random
+/// positions and with random orientations, and the energy of interaction of
+/// the ghost molecule with the system is computed. The system itself is not
+/// modified. The ghost molecule is rigid, and its intramolecular energy is
+/// not included in the excess chemical potential.
+///
+/// The error on the excess chemical potential is estimated from the standard
+/// error of the mean of the Boltzmann factors computed at each step, and
+/// does not account for the correlation between successive steps.
+pub struct WidomOutput {
+    file: File,
+    path: PathBuf,
+    /// The ghost molecule, with indexes starting at 0
+    molecule: Molecule,
+    /// The particles in the ghost molecule, with positions relative to its
+    /// center-of-mass
+    particles: ParticleVec,
+    /// Temperature of the system
+    temperature: f64,
+    /// Number of ghost insertions at each step
+    insertions: usize,
+    /// Energy of the isolated ghost molecule
+    reference: f64,
+    /// Number of steps used in the averages
+    samples: usize,
+    /// Sum of the average `V exp(-beta ΔU)` at each step
+    boltzmann: f64,
+    /// Sum of the squared average `V exp(-beta ΔU)` at each step
+    boltzmann2: f64,
+    /// Sum of the volume at each step
+    volume: f64,
+    /// Normal distribution, for the generation of random orientations
+    normal: Normal,
+    rng: Box<Rng + Send>,
+    /// Copy of the system with the ghost molecule added at the end, reused
+    /// between steps as long as the particles in the system do not change
+    ghost: Option<System>,
+}
+
+impl WidomOutput {
+    /// Create a new `WidomOutput` writing the excess chemical potential of
+    /// the `molecule` containing the `particles` to `filename`, using
+    /// `insertions` ghost insertions at each step. The `temperature` of the
+    /// simulation is used in the Boltzmann factors. The indexes in `molecule`
+    /// must start at 0, as returned by `read_molecule`. The file is replaced
+    /// if it already exists.
+    pub fn new<P: AsRef<Path>>(
+        filename: P,
+        molecule: Molecule,
+        mut particles: ParticleVec,
+        temperature: f64,
+        insertions: usize
+    ) -> Result<WidomOutput, io::Error> {
+        assert_eq!(molecule.start(), 0, "the molecule indexes must start at 0 in Widom output");
+        assert_eq!(molecule.size(), particles.len(), "wrong number of particles in Widom output");
+        assert!(temperature > 0.0, "the temperature must be positive in Widom output");
+        assert!(insertions > 0, "the number of insertions must be positive in Widom output");
+
+        let mass = center_particles(&mut particles);
+        assert!(mass > 0.0, "the molecule mass must be positive in Widom output");
+
+        let mut rng = Box::new(rand::XorShiftRng::new_unseeded());
+        rng.reseed([2015u32, 42u32, 3u32, 12u32]);
+
+        Ok(WidomOutput {
+            file: try!(File::create(filename.as_ref())),
+            path: filename.as_ref().to_owned(),
+            molecule: molecule,
+            particles: particles,
+            temperature: temperature,
+            insertions: insertions,
+            reference: 0.0,
+            samples: 0,
+            boltzmann: 0.0,
+            boltzmann2: 0.0,
+            volume: 0.0,
+            normal: Normal::new(0.0, 1.0),
+            rng: rng,
+            ghost: None,
+        })
+    }
+
+    /// Update the copy of the system containing the ghost molecule to match
+    /// the `system`. The copy is rebuilt if the particles in the system
+    /// changed, and only the cell, positions and charges are updated
+    /// otherwise.
+    fn update_ghost(&mut self, system: &System) {
+        let size = system.size();
+        let rebuild = match self.ghost {
+            Some(ref ghost) => {
+                ghost.size() != size + self.particles.len() ||
+                ghost.molecules().len() != system.molecules().len() + 1 ||
+                ghost.particles().name[..size] != *system.particles().name
+            }
+            None => true,
+        };
+
+        if rebuild {
+            let mut particles = Vec::with_capacity(self.particles.len());
+            for i in 0..self.particles.len() {
+                particles.push(Particle {
+                    name: self.particles.name[i].clone(),
+                    kind: ParticleKind::invalid(),
+                    charge: self.particles.charge[i],
+                    mass: self.particles.mass[i],
+                    position: self.particles.position[i],
+                    velocity: Vector3D::zero(),
+                    frozen: false,
+                });
+            }
+            let mut ghost = system.clone();
+            ghost.add_molecule(self.molecule.clone(), particles);
+            self.ghost = Some(ghost);
+        } else if let Some(ref mut ghost) = self.ghost {
+            ghost.cell = system.cell;
+            ghost.particles_mut().position[..size].copy_from_slice(system.particles().position);
+            ghost.particles_mut().charge[..size].copy_from_slice(system.particles().charge);
+        }
+    }
+
+    /// Compute the energy of `count` insertions of the ghost molecule in the
+    /// `system`, at random positions and with random orientations.
+    fn insertion_energies(&mut self, system: &System, count: usize) -> Vec<f64> {
+        self.update_ghost(system);
+        let ghost = self.ghost.as_mut().expect("missing ghost system in Widom output");
+        let molid = ghost.molecules().len() - 1;
+        let indexes = ghost.molecule(molid).iter();
+        let idxes = indexes.clone().collect::<Vec<_>>();
+
+        // The long range corrections do not depend on the ghost position
+        let tail = ghost.energy_evaluator().pairs_tail() - system.energy_evaluator().pairs_tail();
+
+        let mut energies = Vec::with_capacity(count);
+        for _ in 0..count {
+            let fractional = Vector3D::new(
+                self.rng.next_f64(), self.rng.next_f64(), self.rng.next_f64()
+            );
+            let com = ghost.cell.cartesian(&fractional);
+            let rotation = random_rotation(&mut self.normal, &mut self.rng);
+            {
+                let positions = &mut ghost.particles_mut().position[indexes.clone()];
+                for (position, relative) in izip!(positions, &self.particles.position) {
+                    *position = com + rotation * *relative;
+                }
+            }
+            energies.push(tail + ghost_energy(ghost, &idxes));
+        }
+        return energies;
+    }
+}
+
+/// Get the energy of interaction between the particles at `idxes`, which
+/// must be the last particles in the `system`, and the other particles. The
+/// long range corrections for the pairs are not included.
+fn ghost_energy(system: &System, idxes: &[usize]) -> f64 {
+    let evaluator = system.energy_evaluator();
+    let start = idxes[0];
+    let mut energy = 0.0;
+    for &i in idxes {
+        for j in 0..start {
+            let r = system.nearest_image(i, j).norm();
+            energy += evaluator.pair(r, i, j);
+        }
+    }
+
+    if let Some(coulomb) = system.coulomb_potential() {
+        energy += coulomb.add_particles_cost(system, idxes);
+    }
+    for global in system.global_potentials() {
+        energy += global.add_particles_cost(system, idxes);
+    }
+    return energy;
+}
+
+impl Output for WidomOutput {
+    fn setup(&mut self, system: &System) {
+        if system.cell.is_infinite() {
+            fatal_error!("Can not use Widom insertion in an infinite cell");
+        }
+
+        for (name, charge) in self.particles.name.iter().zip(&mut self.particles.charge) {
+            copy_charge(system, name, charge);
+        }
+
+        // Get the intramolecular energy of the ghost molecule in an empty
+        // system, with the same interactions
+        let mut empty = system.clone();
+        while !empty.molecules().is_empty() {
+            let last = empty.molecules().len() - 1;
+            empty.remove_molecule(last);
+        }
+        self.reference = self.insertion_energies(&empty, 1)[0];
+        self.ghost = None;
+
+        if let Err(err) = writeln!(&mut self.file, "# Widom test particle insertion") {
+            fatal_error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+        if let Err(err) = writeln!(&mut self.file, "# Step ExcessChemicalPotential/(kJ/mol) Error/(kJ/mol)") {
+            fatal_error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+    }
+
+    fn write(&mut self, system: &System) {
+        let beta = 1.0 / (K_BOLTZMANN * self.temperature);
+        let volume = system.volume();
+        let count = self.insertions;
+        let reference = self.reference;
+        let energies = self.insertion_energies(system, count);
+        let boltzmann = energies.iter().map(|&energy| {
+            f64::exp(-beta * (energy - reference))
+        }).sum::<f64>() * volume / count as f64;
+
+        self.samples += 1;
+        self.boltzmann += boltzmann;
+        self.boltzmann2 += boltzmann * boltzmann;
+        self.volume += volume;
+
+        let n = self.samples as f64;
+        let mean = self.boltzmann / n;
+        let potential = f64::ln(self.volume / (n * mean)) / beta;
+        let error = if self.samples > 1 {
+            let variance = self.boltzmann2 / n - mean * mean;
+            f64::sqrt(f64::max(variance, 0.0) / (n - 1.0)) / (beta * mean)
+        } else {
+            f64::NAN
+        };
+
+        let potential = utils::unit_to(potential, "kJ/mol");
+        let error = utils::unit_to(error, "kJ/mol");
+        if let Err(err) = writeln!(&mut self.file, "{} {} {}", system.step(), potential, error) {
+            error!("Could not write to file '{}': {}", self.path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+    use self::tempfile::NamedTempFile;
+
+    use super::*;
+    use super::super::tests::{test_output, testing_system};
+
+    #[test]
+    fn widom() {
+        test_output(|path| {
+            let mut particles = ParticleVec::new();
+            particles.push(Particle::with_position("He", Vector3D::new(1.0, 2.0, 3.0)));
+            Box::new(WidomOutput::new(path, Molecule::new(0), particles, 300.0, 10).unwrap())
+        },
+"# Widom test particle insertion
+# Step ExcessChemicalPotential/(kJ/mol) Error/(kJ/mol)
+0 0 NaN
+"
+        );
+    }
+
+    #[test]
+    fn ghost() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut system = testing_system();
+        let mut particles = ParticleVec::new();
+        particles.push(Particle::new("F"));
+        let mut widom = WidomOutput::new(tempfile.path(), Molecule::new(0), particles, 300.0, 1).unwrap();
+        widom.setup(&system);
+
+        for _ in 0..2 {
+            let energy = widom.insertion_energies(&system, 1)[0];
+            let ghost = widom.ghost.as_ref().unwrap();
+            assert_eq!(&ghost.particles().position[..2], system.particles().position);
+            let expected = ghost.potential_energy() - system.potential_energy();
+            assert_ulps_eq!(energy, expected, epsilon = 1e-9);
+
+            // The copy of the system is updated at the next step
+            system.particles_mut().position[1] = Vector3D::new(1.5, 0.0, 0.0);
+        }
+    }
+}
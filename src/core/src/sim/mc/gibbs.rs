@@ -0,0 +1,483 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Gibbs ensemble Monte Carlo, for phase coexistence simulations
+use rand::{self, Rng, SeedableRng};
+
+use std::f64;
+use std::mem;
+
+use consts::K_BOLTZMANN;
+use types::{Matrix3, One, Vector3D, Zero};
+use sys::{System, EnergyCache, Particle, ParticleKind};
+use sim::{Propagator, TemperatureStrategy, Schedule, OutputFrequency};
+use out::Output;
+
+use super::{MonteCarlo, MoveCounter, MCMove};
+use super::moves::{select_molecule, count_molecules, scale_cell};
+
+/// Moves exchanging volume or molecules between the two boxes of a Gibbs
+/// ensemble simulation.
+#[derive(Clone, Copy)]
+enum Exchange {
+    /// Exchange volume between the boxes, keeping the total volume constant.
+    /// `delta` is the maximal change of `ln(V_1 / V_2)`.
+    Volume {
+        delta: f64,
+    },
+    /// Transfer a molecule with the given type from one box to the other
+    Transfer {
+        moltype: u64,
+    },
+}
+
+impl Exchange {
+    fn describe(&self) -> &str {
+        match *self {
+            Exchange::Volume{..} => "volume exchange",
+            Exchange::Transfer{..} => "molecule transfer",
+        }
+    }
+}
+
+/// Gibbs ensemble Monte Carlo propagator.
+///
+/// This propagator simulates two boxes in equilibrium with one another,
+/// without an explicit interface between them. This is used to compute
+/// coexistence properties, such as vapor–liquid equilibrium.
+///
+/// The first box is the system passed to `Simulation::run`, and the second
+/// box is owned by the propagator. Each step, either a standard Monte Carlo
+/// move is performed inside one of the boxes, or volume or molecules are
+/// exchanged between the boxes. The total volume and the total number of
+/// molecules are kept constant.
+pub struct GibbsEnsemble {
+    /// Boltzmann factor: beta = 1/(kB * T)
+    beta: f64,
+    /// Optional schedule for the temperature
+    schedule: Option<Schedule>,
+    /// Monte Carlo propagators for the moves inside each box
+    boxes: [MonteCarlo; 2],
+    /// The second simulated system
+    second: System,
+    /// Total frequency of the moves inside the boxes
+    intra_frequency: f64,
+    /// List of moves between the boxes
+    exchanges: Vec<(Exchange, MoveCounter)>,
+    /// Frequencies of the moves between the boxes
+    frequencies: Vec<f64>,
+    /// Maximal cutoff of the interactions in each box
+    maximum_cutoffs: [Option<f64>; 2],
+    /// Outputs for the second system
+    outputs: Vec<OutputFrequency>,
+    /// Random number generator for the selection of moves and the moves
+    /// between the boxes
+    rng: Box<Rng + Send>,
+}
+
+impl GibbsEnsemble {
+    /// Create a new Gibbs ensemble propagator at the given `temperature`.
+    /// `second` is the second box of the simulation.
+    pub fn new(temperature: f64, second: System) -> GibbsEnsemble {
+        assert!(temperature >= 0.0, "Gibbs ensemble temperature must be positive");
+
+        let mut rng = Box::new(rand::XorShiftRng::new_unseeded());
+        rng.reseed([2015u32, 42u32, 3u32, 12u32]);
+        let mut first_rng = Box::new(rand::XorShiftRng::new_unseeded());
+        first_rng.reseed([2017u32, 7u32, 11u32, 5u32]);
+        let mut second_rng = Box::new(rand::XorShiftRng::new_unseeded());
+        second_rng.reseed([2018u32, 13u32, 29u32, 3u32]);
+
+        GibbsEnsemble {
+            beta: 1.0 / (K_BOLTZMANN * temperature),
+            schedule: None,
+            boxes: [
+                MonteCarlo::from_rng(temperature, first_rng),
+                MonteCarlo::from_rng(temperature, second_rng),
+            ],
+            second: second,
+            intra_frequency: 0.0,
+            exchanges: Vec::new(),
+            frequencies: Vec::new(),
+            maximum_cutoffs: [None, None],
+            outputs: Vec::new(),
+            rng: rng,
+        }
+    }
+
+    /// Get the second box of the simulation
+    pub fn second_system(&self) -> &System {
+        &self.second
+    }
+
+    /// Get the temperature of the simulation
+    pub fn temperature(&self) -> f64 {
+        1.0 / (self.beta * K_BOLTZMANN)
+    }
+
+    /// Set the temperature of the simulation
+    pub fn set_temperature(&mut self, temperature: f64) {
+        self.beta = 1.0 / (temperature * K_BOLTZMANN);
+        self.boxes[0].set_temperature(temperature);
+        self.boxes[1].set_temperature(temperature);
+    }
+
+    /// Use the given `schedule` to set the temperature of the simulation as a
+    /// function of the simulation step.
+    pub fn set_temperature_schedule(&mut self, schedule: Schedule) {
+        self.schedule = Some(schedule);
+    }
+
+    /// Set the number of times a move inside the boxes has to be called
+    /// before its amplitude is updated.
+    pub fn set_amplitude_update_frequency(&mut self, frequency: u64) {
+        self.boxes[0].set_amplitude_update_frequency(frequency);
+        self.boxes[1].set_amplitude_update_frequency(frequency);
+    }
+
+    /// Add a move to use inside the boxes with the given `frequency`. The
+    /// `first` move is used in the first box and the `second` move in the
+    /// second box. The two moves should be of the same kind.
+    pub fn add(&mut self, first: Box<MCMove>, second: Box<MCMove>, frequency: f64) {
+        self.boxes[0].add(first, frequency);
+        self.boxes[1].add(second, frequency);
+        self.intra_frequency += frequency;
+    }
+
+    /// Add a move to use inside the boxes with the given `frequency` and
+    /// `target_acceptance`. The `first` move is used in the first box and the
+    /// `second` move in the second box.
+    pub fn add_move_with_acceptance(&mut self, first: Box<MCMove>, second: Box<MCMove>, frequency: f64, target_acceptance: f64) {
+        self.boxes[0].add_move_with_acceptance(first, frequency, target_acceptance);
+        self.boxes[1].add_move_with_acceptance(second, frequency, target_acceptance);
+        self.intra_frequency += frequency;
+    }
+
+    /// Add a volume exchange move with the given `frequency`. The logarithm
+    /// of the ratio of the volumes of the boxes is changed by at most
+    /// `delta`, keeping the total volume constant.
+    pub fn add_volume_exchange(&mut self, delta: f64, frequency: f64) {
+        assert!(delta > 0.0, "delta must be positive in volume exchange move");
+        self.exchanges.push((Exchange::Volume{delta: delta}, MoveCounter::new(None)));
+        self.frequencies.push(frequency);
+    }
+
+    /// Add a move transferring molecules with the given `moltype` between
+    /// the boxes, with the given `frequency`.
+    pub fn add_transfer(&mut self, moltype: u64, frequency: f64) {
+        self.exchanges.push((Exchange::Transfer{moltype: moltype}, MoveCounter::new(None)));
+        self.frequencies.push(frequency);
+    }
+
+    /// Add a new `Output` for the second box, used at the given `frequency`.
+    pub fn add_output_with_frequency(&mut self, output: Box<Output>, frequency: u64) {
+        self.outputs.push(OutputFrequency::with_frequency(output, frequency));
+    }
+
+    /// Attempt the exchange move at index `i`, between the `first` system and
+    /// the second one. Return `None` if the move can not be performed, or
+    /// `Some(accepted)`.
+    fn exchange(&mut self, i: usize, first: &mut System) -> Option<bool> {
+        let exchange = self.exchanges[i].0;
+        match exchange {
+            Exchange::Volume{delta} => Some(self.volume_exchange(first, delta)),
+            Exchange::Transfer{moltype} => {
+                let (box_0, box_1) = self.boxes.split_at_mut(1);
+                let first_cache = box_0[0].cache_mut();
+                let second_cache = box_1[0].cache_mut();
+                // Randomly select the direction of the transfer
+                if self.rng.gen() {
+                    transfer(
+                        first, first_cache, &mut self.second, second_cache,
+                        moltype, self.beta, &mut self.rng
+                    )
+                } else {
+                    transfer(
+                        &mut self.second, second_cache, first, first_cache,
+                        moltype, self.beta, &mut self.rng
+                    )
+                }
+            }
+        }
+    }
+
+    /// Exchange volume between the `first` system and the second one,
+    /// changing `ln(V_1 / V_2)` by at most `delta`. Return whether the move
+    /// was accepted.
+    fn volume_exchange(&mut self, first: &mut System, delta: f64) -> bool {
+        let old_volumes = [first.volume(), self.second.volume()];
+        let total = old_volumes[0] + old_volumes[1];
+        let log_ratio = f64::ln(old_volumes[0] / old_volumes[1]) + self.rng.gen_range(-delta, delta);
+        let new_first = total * f64::exp(log_ratio) / (1.0 + f64::exp(log_ratio));
+        let new_volumes = [new_first, total - new_first];
+
+        let mut first_previous = (**first).clone();
+        let mut second_previous = (*self.second).clone();
+        let scaling = Matrix3::one() * f64::cbrt(new_volumes[0] / old_volumes[0]);
+        scale_cell(first, &first_previous, scaling, self.maximum_cutoffs[0]);
+        let scaling = Matrix3::one() * f64::cbrt(new_volumes[1] / old_volumes[1]);
+        scale_cell(&mut self.second, &second_previous, scaling, self.maximum_cutoffs[1]);
+
+        let (box_0, box_1) = self.boxes.split_at_mut(1);
+        let first_cache = box_0[0].cache_mut();
+        let second_cache = box_1[0].cache_mut();
+        let delta_energy = first_cache.move_all_rigid_molecules_cost(first)
+                         + second_cache.move_all_rigid_molecules_cost(&self.second);
+
+        let mut cost = self.beta * delta_energy;
+        cost -= (mobile_molecules(first) as f64 + 1.0) * f64::ln(new_volumes[0] / old_volumes[0]);
+        cost -= (mobile_molecules(&self.second) as f64 + 1.0) * f64::ln(new_volumes[1] / old_volumes[1]);
+
+        let accepted = cost <= 0.0 || self.rng.next_f64() < f64::exp(-cost);
+        if accepted {
+            first_cache.update(first);
+            second_cache.update(&mut self.second);
+        } else {
+            mem::swap(&mut **first, &mut first_previous);
+            mem::swap(&mut *self.second, &mut second_previous);
+        }
+        return accepted;
+    }
+}
+
+impl Propagator for GibbsEnsemble {
+    fn temperature_strategy(&self) -> TemperatureStrategy {
+        TemperatureStrategy::External(self.temperature())
+    }
+
+    fn setup(&mut self, system: &System) {
+        if self.intra_frequency == 0.0 && self.frequencies.is_empty() {
+            warn!(
+                "No move in the Gibbs ensemble simulation, \
+                did you forget to specify them?"
+            );
+        }
+
+        let temperature = self.temperature();
+        self.second.external_temperature(Some(temperature));
+
+        self.boxes[0].setup(system);
+        self.boxes[1].setup(&self.second);
+        self.maximum_cutoffs = [system.maximum_cutoff(), self.second.maximum_cutoff()];
+        if system.cell.is_infinite() || self.second.cell.is_infinite() {
+            fatal_error!("Can not use Gibbs ensemble with infinite cells");
+        }
+
+        for output in &mut self.outputs {
+            output.setup(&self.second);
+        }
+    }
+
+    fn propagate(&mut self, system: &mut System) {
+        if let Some(temperature) = self.schedule.as_ref().map(|schedule| schedule.value(system.step())) {
+            self.set_temperature(temperature);
+            system.external_temperature(Some(temperature));
+            self.second.external_temperature(Some(temperature));
+        }
+
+        let total = self.intra_frequency + self.frequencies.iter().sum::<f64>();
+        let mut probability = total * self.rng.next_f64();
+        if total == 0.0 {
+            // No move to perform
+        } else if probability < self.intra_frequency {
+            if self.rng.gen() {
+                self.boxes[0].propagate(system);
+            } else {
+                self.boxes[1].propagate(&mut self.second);
+            }
+        } else {
+            probability -= self.intra_frequency;
+            let mut i = 0;
+            while i < self.frequencies.len() - 1 && probability > self.frequencies[i] {
+                probability -= self.frequencies[i];
+                i += 1;
+            }
+            trace!("Selected move is '{}'", self.exchanges[i].0.describe());
+
+            if let Some(accepted) = self.exchange(i, system) {
+                let counter = &mut self.exchanges[i].1;
+                counter.ncalled += 1;
+                counter.nattempted += 1;
+                if accepted {
+                    trace!("    --> Move was accepted");
+                    counter.naccepted += 1;
+                } else {
+                    trace!("    --> Move was rejected");
+                }
+            } else {
+                trace!("    --> Can not perform the move");
+            }
+        }
+
+        self.second.increment_step();
+        for output in &mut self.outputs {
+            output.write(&self.second);
+        }
+    }
+
+    fn finish(&mut self, system: &System) {
+        info!("Gibbs ensemble simulation summary");
+        for (i, system) in [system, &self.second].iter().enumerate() {
+            info!(
+                "Box {}: {} molecules, volume = {} A^3",
+                i + 1, system.molecules().len(), system.volume()
+            );
+        }
+
+        for &(ref exchange, ref counter) in &self.exchanges {
+            info!("Statistics for move: {}", exchange.describe());
+            info!("  Calls     : {}", counter.ncalled);
+            info!("  Acceptance: {} %", counter.naccepted as f64 /
+                counter.nattempted as f64 * 100.0);
+        }
+
+        info!("Moves in the first box");
+        self.boxes[0].finish(system);
+        info!("Moves in the second box");
+        self.boxes[1].finish(&self.second);
+
+        for output in &mut self.outputs {
+            output.finish(&self.second);
+        }
+    }
+}
+
+/// Get the number of molecules which are not frozen in the `system`
+fn mobile_molecules(system: &System) -> usize {
+    (0..system.molecules().len()).filter(|&molid| !system.is_frozen_molecule(molid)).count()
+}
+
+/// Transfer a randomly selected molecule with type `moltype` from the
+/// `source` system to a random position in the `destination` system. Return
+/// `None` if there is no molecule to transfer, and whether the move was
+/// accepted otherwise.
+fn transfer(
+    source: &mut System,
+    source_cache: &mut EnergyCache,
+    destination: &mut System,
+    destination_cache: &mut EnergyCache,
+    moltype: u64,
+    beta: f64,
+    rng: &mut Box<Rng + Send>
+) -> Option<bool> {
+    let molid = match select_molecule(source, Some(moltype), rng) {
+        Some(molid) => molid,
+        None => return None,
+    };
+    let source_count = count_molecules(source, moltype);
+    let destination_count = count_molecules(destination, moltype);
+
+    // Build the transferred molecule at a random position in the destination
+    let mut molecule = source.molecule(molid).clone();
+    let com = source.molecule_com(molid);
+    let fractional = Vector3D::new(rng.next_f64(), rng.next_f64(), rng.next_f64());
+    let new_com = destination.cell.cartesian(&fractional);
+    let particles = molecule.iter().map(|i| {
+        let particles = source.particles();
+        Particle {
+            name: particles.name[i].clone(),
+            kind: ParticleKind::invalid(),
+            charge: particles.charge[i],
+            mass: particles.mass[i],
+            position: particles.position[i] - com + new_com,
+            velocity: Vector3D::zero(),
+            frozen: false,
+        }
+    }).collect::<Vec<_>>();
+    molecule.translate_by(-(molecule.start() as isize));
+
+    destination.add_molecule(molecule, particles);
+    let new_molid = destination.molecules().len() - 1;
+
+    let delta_energy = source_cache.remove_molecule_cost(source, molid)
+                     + destination_cache.add_molecule_cost(destination, new_molid);
+    let ratio = (source_count as f64 * destination.volume()) /
+                ((destination_count as f64 + 1.0) * source.volume());
+    let cost = beta * delta_energy - f64::ln(ratio);
+
+    let accepted = cost <= 0.0 || rng.next_f64() < f64::exp(-cost);
+    if accepted {
+        source.remove_molecule(molid);
+        source_cache.update(source);
+        destination_cache.update(destination);
+    } else {
+        destination.remove_molecule(new_molid);
+    }
+    return Some(accepted);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sys::UnitCell;
+    use sim::mc::Translate;
+
+    fn argon(positions: &[[f64; 3]]) -> System {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        for position in positions {
+            let position = Vector3D::new(position[0], position[1], position[2]);
+            system.add_particle(Particle::with_position("Ar", position));
+        }
+        return system;
+    }
+
+    #[test]
+    fn transfer_and_volume() {
+        let first = argon(&[[1.0, 1.0, 1.0], [5.0, 5.0, 5.0], [8.0, 2.0, 3.0]]);
+        let second = argon(&[[2.0, 2.0, 2.0]]);
+        let moltype = first.molecule_type(0);
+
+        let mut gibbs = GibbsEnsemble::new(300.0, second);
+        gibbs.add(Box::new(Translate::new(1.0)), Box::new(Translate::new(1.0)), 1.0);
+        gibbs.add_volume_exchange(0.1, 1.0);
+        gibbs.add_transfer(moltype, 1.0);
+
+        let mut system = first;
+        gibbs.setup(&system);
+        let total_volume = system.volume() + gibbs.second_system().volume();
+        for _ in 0..1000 {
+            gibbs.propagate(&mut system);
+            system.increment_step();
+        }
+
+        // Without interactions, molecules and volume are still conserved
+        let second = gibbs.second_system();
+        assert_eq!(system.molecules().len() + second.molecules().len(), 4);
+        assert_ulps_eq!(system.volume() + second.volume(), total_volume, epsilon = 1e-9);
+        assert!(gibbs.exchanges.iter().all(|&(_, ref counter)| counter.naccepted > 0));
+        assert_eq!(second.step(), 1000);
+    }
+
+    #[test]
+    fn transfer_frozen_molecules() {
+        let mut first = argon(&[[1.0, 1.0, 1.0]]);
+        let mut second = argon(&[
+            [1.0, 1.0, 1.0], [1.0, 1.0, 4.0], [1.0, 1.0, 7.0],
+            [1.0, 4.0, 1.0], [1.0, 4.0, 4.0], [1.0, 4.0, 7.0],
+            [1.0, 7.0, 1.0], [1.0, 7.0, 4.0], [1.0, 7.0, 7.0],
+        ]);
+        for molid in 0..9 {
+            second.set_frozen_molecule(molid, true);
+        }
+        let moltype = first.molecule_type(0);
+
+        let mut first_cache = EnergyCache::new();
+        first_cache.init(&first);
+        let mut second_cache = EnergyCache::new();
+        second_cache.init(&second);
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let beta = 1.0 / (K_BOLTZMANN * 300.0);
+
+        // Without interactions and with the same volumes, the transfers of
+        // the only mobile molecule are always accepted, since the frozen
+        // molecules are not counted
+        for _ in 0..10 {
+            let accepted = transfer(&mut first, &mut first_cache, &mut second, &mut second_cache, moltype, beta, &mut rng);
+            assert_eq!(accepted, Some(true));
+            let accepted = transfer(&mut second, &mut second_cache, &mut first, &mut first_cache, moltype, beta, &mut rng);
+            assert_eq!(accepted, Some(true));
+        }
+        assert_eq!(first.molecules().len(), 1);
+        assert_eq!(second.molecules().len(), 9);
+    }
+}
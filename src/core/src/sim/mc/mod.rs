@@ -5,6 +5,16 @@
 mod monte_carlo;
 pub use self::monte_carlo::{MonteCarlo, MoveCounter};
 
+mod gibbs;
+pub use self::gibbs::GibbsEnsemble;
+
+mod wang_landau;
+pub use self::wang_landau::{WangLandau, OrderParameter};
+
 mod moves;
 pub use self::moves::MCMove;
-pub use self::moves::{Translate, Rotate, Resize};
+pub use self::moves::{Translate, Rotate, Resize, Regrow};
+pub use self::moves::{Insert, Delete, Reservoir};
+pub use self::moves::{Swap, SemiGrand, Hybrid};
+pub use self::moves::{DihedralRotation, Crankshaft, Pivot};
+pub(crate) use self::moves::{random_rotation, center_particles, copy_charge};
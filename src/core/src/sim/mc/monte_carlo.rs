@@ -6,7 +6,7 @@ use rand::{self, SeedableRng};
 
 use consts::K_BOLTZMANN;
 use sys::{System, EnergyCache};
-use sim::{Propagator, TemperatureStrategy};
+use sim::{Propagator, TemperatureStrategy, Schedule};
 
 use super::MCMove;
 
@@ -14,6 +14,8 @@ use super::MCMove;
 pub struct MonteCarlo {
     /// Boltzmann factor: beta = 1/(kB * T)
     beta: f64,
+    /// Optional schedule for the temperature
+    schedule: Option<Schedule>,
     /// List of possible Monte Carlo moves
     moves: Vec<(Box<MCMove>, MoveCounter)>,
     /// Cummulative frequencies of the Monte Carlo moves
@@ -23,7 +25,7 @@ pub struct MonteCarlo {
     update_frequency: u64,
     /// Random number generator for the simulation. All random state will be
     /// taken from this.
-    rng: Box<rand::Rng>,
+    rng: Box<rand::Rng + Send>,
     /// Cache for faster energy computation
     cache: EnergyCache,
     /// Flag checking if the moves frequencies has been converted to
@@ -41,10 +43,11 @@ impl MonteCarlo {
 
     /// Create a Monte Carlo propagator at temperature `T`, using the `rng`
     /// random number generator.
-    pub fn from_rng(temperature: f64, rng: Box<rand::Rng>) -> MonteCarlo {
+    pub fn from_rng(temperature: f64, rng: Box<rand::Rng + Send>) -> MonteCarlo {
         assert!(temperature >= 0.0, "Monte Carlo temperature must be positive");
         MonteCarlo {
             beta: 1.0 / (K_BOLTZMANN * temperature),
+            schedule: None,
             moves: Vec::new(),
             frequencies: Vec::new(),
             update_frequency: 0,
@@ -107,6 +110,18 @@ impl MonteCarlo {
         self.beta = 1.0 / (temperature * K_BOLTZMANN);
     }
 
+    /// Use the given `schedule` to set the temperature of the simulation as a
+    /// function of the simulation step.
+    pub fn set_temperature_schedule(&mut self, schedule: Schedule) {
+        self.schedule = Some(schedule);
+    }
+
+    /// Get the energy cache used by this propagator, for moves changing the
+    /// system outside of `propagate`.
+    pub(crate) fn cache_mut(&mut self) -> &mut EnergyCache {
+        &mut self.cache
+    }
+
     fn normalize_frequencies(&mut self) {
         assert_eq!(self.frequencies.len(), self.moves.len());
         if self.frequencies.is_empty() {
@@ -143,7 +158,11 @@ impl Propagator for MonteCarlo {
     }
 
     fn setup(&mut self, system: &System) {
-        self.normalize_frequencies();
+        // The propagator can be set up multiple times, for example when
+        // exchanging systems in replica exchange simulations.
+        if !self.initialized {
+            self.normalize_frequencies();
+        }
         self.cache.init(system);
         for mc_move in &mut self.moves {
             mc_move.0.setup(system)
@@ -151,6 +170,11 @@ impl Propagator for MonteCarlo {
     }
 
     fn propagate(&mut self, system: &mut System) {
+        if let Some(temperature) = self.schedule.as_ref().map(|schedule| schedule.value(system.step())) {
+            self.set_temperature(temperature);
+            system.external_temperature(Some(temperature));
+        }
+
         let mcmove = {
             let probability = self.rng.next_f64();
             // Get the index of the first move with frequency >= probability.
@@ -271,7 +295,7 @@ impl MoveCounter {
 #[cfg(test)]
 mod tests {
     use sim::mc::{MonteCarlo, MCMove, MoveCounter};
-    use sim::Propagator;
+    use sim::{Propagator, Schedule};
     use sys::{System, EnergyCache};
     use rand::Rng;
 
@@ -279,7 +303,7 @@ mod tests {
     impl MCMove for DummyMove {
         fn describe(&self) -> &str {"dummy"}
         fn setup(&mut self, _: &System) {}
-        fn prepare(&mut self, _: &mut System, _: &mut Box<Rng>) -> bool {true}
+        fn prepare(&mut self, _: &mut System, _: &mut Box<Rng + Send>) -> bool {true}
         fn cost(&self, _: &System, _: f64, _: &mut EnergyCache) -> f64 {0.0}
         fn apply(&mut self, _: &mut System) {}
         fn restore(&mut self, _: &mut System) {}
@@ -307,6 +331,23 @@ mod tests {
         assert_eq!(mc.frequencies[2], 1.0);
     }
 
+    #[test]
+    fn temperature_schedule() {
+        let mut mc = MonteCarlo::new(100.0);
+        mc.add(Box::new(DummyMove), 1.0);
+        mc.set_temperature_schedule(Schedule::linear(100.0, 200.0, 10));
+
+        let mut system = System::new();
+        mc.setup(&system);
+        for _ in 0..5 {
+            mc.propagate(&mut system);
+            system.increment_step();
+        }
+        mc.propagate(&mut system);
+        assert_ulps_eq!(mc.temperature(), 150.0, epsilon=1e-9);
+        assert_ulps_eq!(system.temperature(), 150.0, epsilon=1e-9);
+    }
+
     #[test]
     #[should_panic]
     fn add_after_init() {
@@ -0,0 +1,325 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Configurational-bias Monte Carlo (CBMC) growth of molecules.
+//!
+//! The particles of a molecule are grown one at the time, each one bonded to
+//! an already placed particle. For each particle, a set of trial positions is
+//! generated at the current bond length from the previous particle, in random
+//! directions. One of these positions is chosen with a probability
+//! proportional to its Boltzmann factor, computed from the pair interactions
+//! with the already placed particles and the bonds, angles and dihedral
+//! angles between them. The product of the average Boltzmann factors is the
+//! Rosenbluth weight of the configuration.
+//!
+//! Coulomb and global interactions are not used when growing the molecule,
+//! but are accounted for exactly in the acceptance criterion of the moves.
+use rand::distributions::{Normal, Sample};
+use rand::Rng;
+
+use std::collections::VecDeque;
+use std::f64;
+
+use types::Vector3D;
+use sys::{System, Molecule};
+
+/// The result of growing (part of) a molecule
+pub(super) struct Growth {
+    /// Positions of the grown particles, in the same order as the particles
+    /// given to `grow`
+    pub positions: Vec<Vector3D>,
+    /// Logarithm of the Rosenbluth weight of the configuration
+    pub ln_weight: f64,
+    /// Energy used to compute the Rosenbluth weight of the chosen
+    /// configuration
+    pub energy: f64,
+}
+
+impl Growth {
+    /// Get the bias introduced by growing the molecule with this `Growth`,
+    /// i.e. `ln(W) + beta * U`, where `W` is the Rosenbluth weight and `U`
+    /// the energy used to compute it. The configuration is generated with a
+    /// probability proportional to `exp(-beta * U) / W`, and this bias
+    /// removes both the Rosenbluth weight and the part of the energy change
+    /// already accounted for in the acceptance criterion.
+    pub fn bias(&self, beta: f64) -> f64 {
+        self.ln_weight + beta * self.energy
+    }
+}
+
+/// Get the order in which the particles of `molecule` should be grown,
+/// starting with the particle at index `start`. Each particle is associated
+/// with an already grown particle it is bonded to, or with `from` for the
+/// `start` particle. The growth does not go through the bond between `start`
+/// and `from`.
+///
+/// This function returns `None` if `from` can be reached from `start`
+/// without using the bond between them, i.e. if this bond is part of a ring.
+pub(super) fn growth_order(molecule: &Molecule, start: usize, from: Option<usize>) -> Option<Vec<(usize, Option<usize>)>> {
+    let mut order = vec![(start, from)];
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+    while let Some(current) = queue.pop_front() {
+        for bond in molecule.bonds() {
+            let other = if bond.i() == current {
+                bond.j()
+            } else if bond.j() == current {
+                bond.i()
+            } else {
+                continue;
+            };
+
+            if current == start && Some(other) == from {
+                // Do not use the bond we are growing from
+                continue;
+            } else if Some(other) == from {
+                // This bond is part of a ring
+                return None;
+            }
+
+            if !order.iter().any(|&(i, _)| i == other) {
+                order.push((other, Some(current)));
+                queue.push_back(other);
+            }
+        }
+    }
+    return Some(order);
+}
+
+/// Grow the particles in `order` (as given by `growth_order`) of the molecule
+/// at index `molid` in the `system`, using `trials` trial positions for each
+/// particle and the inverse temperature `beta`. All the particles in the
+/// system which are not in `order` are used when computing the energy.
+///
+/// The bond lengths are taken from the current positions of the particles in
+/// the system. Particles without a previous particle are placed at random in
+/// the simulation cell.
+///
+/// If `old` is `true`, this computes the Rosenbluth weight of the current
+/// configuration of the particles instead of generating a new one: the
+/// current position is used as the first trial position, and the returned
+/// positions are the current positions.
+///
+/// This function returns `None` if no trial position with a finite energy
+/// could be found for one of the particles.
+pub(super) fn grow(
+    system: &System,
+    molid: usize,
+    order: &[(usize, Option<usize>)],
+    trials: usize,
+    beta: f64,
+    old: bool,
+    rng: &mut Box<Rng + Send>
+) -> Option<Growth> {
+    assert!(trials > 0, "CBMC needs at least one trial position");
+    let molecule = system.molecule(molid);
+    let current = system.particles().position;
+
+    let mut placed = vec![true; system.size()];
+    for &(i, _) in order {
+        placed[i] = false;
+    }
+    let mut positions = current.to_vec();
+
+    let mut normal = Normal::new(0.0, 1.0);
+    let mut grown = Vec::with_capacity(order.len());
+    let mut ln_weight = 0.0;
+    let mut energy = 0.0;
+    for &(i, previous) in order {
+        let mut candidates = Vec::with_capacity(trials);
+        for trial in 0..trials {
+            if old && trial == 0 {
+                candidates.push(current[i]);
+                continue;
+            }
+
+            let position = if let Some(previous) = previous {
+                let length = system.distance(i, previous);
+                let direction = Vector3D::new(
+                    normal.sample(rng), normal.sample(rng), normal.sample(rng)
+                ).normalized();
+                positions[previous] + length * direction
+            } else {
+                let fractional = Vector3D::new(rng.next_f64(), rng.next_f64(), rng.next_f64());
+                system.cell.cartesian(&fractional)
+            };
+            candidates.push(position);
+        }
+
+        let energies = candidates.iter().map(|position| {
+            particle_energy(system, molecule, i, position, &positions, &placed)
+        }).collect::<Vec<_>>();
+
+        // Use the log-sum-exp trick to prevent overflow in the weights
+        let max = energies.iter().map(|&e| -beta * e).fold(f64::NEG_INFINITY, f64::max);
+        if !max.is_finite() {
+            return None;
+        }
+        let factors = energies.iter().map(|&e| f64::exp(-beta * e - max)).collect::<Vec<_>>();
+        let sum = factors.iter().sum::<f64>();
+        ln_weight += max + f64::ln(sum / trials as f64);
+
+        let chosen = if old {
+            0
+        } else {
+            let mut probability = sum * rng.next_f64();
+            let mut chosen = 0;
+            while chosen < trials - 1 && probability >= factors[chosen] {
+                probability -= factors[chosen];
+                chosen += 1;
+            }
+            chosen
+        };
+        if !energies[chosen].is_finite() {
+            return None;
+        }
+
+        energy += energies[chosen];
+        positions[i] = candidates[chosen];
+        placed[i] = true;
+        grown.push(candidates[chosen]);
+    }
+
+    Some(Growth {
+        positions: grown,
+        ln_weight: ln_weight,
+        energy: energy,
+    })
+}
+
+/// Get the energy of the particle `i` in `molecule` at `position`, with the
+/// other particles at `positions`. Only the particles with `placed` set to
+/// `true` are used.
+fn particle_energy(
+    system: &System,
+    molecule: &Molecule,
+    i: usize,
+    position: &Vector3D,
+    positions: &[Vector3D],
+    placed: &[bool]
+) -> f64 {
+    let evaluator = system.energy_evaluator();
+    let cell = &system.cell;
+    let position_of = |j: usize| if j == i {position} else {&positions[j]};
+
+    let mut energy = 0.0;
+    for j in 0..system.size() {
+        if j == i || !placed[j] {
+            continue;
+        }
+        let r = cell.distance(position, &positions[j]);
+        energy += evaluator.pair(r, i, j);
+    }
+
+    let available = |j: usize| j == i || placed[j];
+    for bond in molecule.bonds() {
+        let (a, b) = (bond.i(), bond.j());
+        if (a == i || b == i) && available(a) && available(b) {
+            let r = cell.distance(position_of(a), position_of(b));
+            energy += evaluator.bond(r, a, b);
+        }
+    }
+
+    for angle in molecule.angles() {
+        let (a, b, c) = (angle.i(), angle.j(), angle.k());
+        if (a == i || b == i || c == i) && available(a) && available(b) && available(c) {
+            let theta = cell.angle(position_of(a), position_of(b), position_of(c));
+            energy += evaluator.angle(theta, a, b, c);
+        }
+    }
+
+    for dihedral in molecule.dihedrals() {
+        let (a, b, c, d) = (dihedral.i(), dihedral.j(), dihedral.k(), dihedral.m());
+        let involved = a == i || b == i || c == i || d == i;
+        if involved && available(a) && available(b) && available(c) && available(d) {
+            let phi = cell.dihedral(position_of(a), position_of(b), position_of(c), position_of(d));
+            energy += evaluator.dihedral(phi, a, b, c, d);
+        }
+    }
+
+    return energy;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sys::{Molecule, Particle, UnitCell};
+    use energy::{PairInteraction, Harmonic};
+    use rand;
+
+    fn butane() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        for i in 0..4 {
+            let position = Vector3D::new(1.5 * i as f64, 0.0, 0.0);
+            system.add_particle(Particle::with_position("C", position));
+        }
+        for i in 0..3 {
+            let _ = system.add_bond(i, i + 1);
+        }
+        return system;
+    }
+
+    #[test]
+    fn order() {
+        let system = butane();
+        let molecule = system.molecule(0);
+        assert_eq!(
+            growth_order(molecule, 2, Some(1)),
+            Some(vec![(2, Some(1)), (3, Some(2))])
+        );
+        assert_eq!(
+            growth_order(molecule, 0, None),
+            Some(vec![(0, None), (1, Some(0)), (2, Some(1)), (3, Some(2))])
+        );
+
+        // Rings can not be cut
+        let mut molecule = Molecule::new(0);
+        for i in 1..3 {
+            molecule.merge_with(Molecule::new(i));
+        }
+        molecule.add_bond(0, 1);
+        molecule.add_bond(1, 2);
+        molecule.add_bond(2, 0);
+        assert_eq!(growth_order(&molecule, 1, Some(0)), None);
+    }
+
+    #[test]
+    fn grow_keeps_bonds() {
+        let system = butane();
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let order = growth_order(system.molecule(0), 2, Some(1)).unwrap();
+
+        let growth = grow(&system, 0, &order, 5, 1.0, false, &mut rng).unwrap();
+        assert_eq!(growth.positions.len(), 2);
+        let positions = system.particles().position;
+        assert_ulps_eq!((growth.positions[0] - positions[1]).norm(), 1.5, epsilon = 1e-12);
+        assert_ulps_eq!((growth.positions[1] - growth.positions[0]).norm(), 1.5, epsilon = 1e-12);
+
+        // Without interactions, all the weights are 1
+        assert_eq!(growth.ln_weight, 0.0);
+        assert_eq!(growth.energy, 0.0);
+
+        let growth = grow(&system, 0, &order, 5, 1.0, true, &mut rng).unwrap();
+        assert_eq!(growth.positions, vec![positions[2], positions[3]]);
+    }
+
+    #[test]
+    fn single_trial_bias() {
+        let mut system = butane();
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(3.0, 2.0, 0.0)));
+        system.add_pair_potential("C", "Ar",
+            PairInteraction::new(Box::new(Harmonic{x0: 3.0, k: 0.1}), 10.0)
+        );
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let order = growth_order(system.molecule(0), 2, Some(1)).unwrap();
+
+        // With a single trial, the Rosenbluth weight is the Boltzmann factor
+        // of the chosen configuration, and the bias vanishes
+        for &old in &[true, false] {
+            let growth = grow(&system, 0, &order, 1, 2.0, old, &mut rng).unwrap();
+            assert!(growth.energy != 0.0);
+            assert_ulps_eq!(growth.ln_weight, -2.0 * growth.energy, epsilon = 1e-12);
+            assert_ulps_eq!(growth.bias(2.0), 0.0, epsilon = 1e-12);
+        }
+    }
+}
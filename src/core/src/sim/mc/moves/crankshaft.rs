@@ -0,0 +1,165 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use rand::distributions::{Range, Sample};
+use rand::Rng;
+
+use super::MCMove;
+use super::select_molecule;
+use super::segments::{SegmentCache, crankshaft_segments};
+
+use types::{Matrix3, Vector3D};
+use sys::{System, EnergyCache};
+
+/// Monte Carlo move rotating the interior of a flexible chain around the axis
+/// joining two particles, without changing the rest of the molecule.
+///
+/// For each angle `i-j-k` in the molecule, the rotated segment contains the
+/// particle `j` and all the particles bonded to it without going through `i`
+/// or `k`. Only the segments bonded to the rest of the molecule by the `i-j`
+/// and `j-k` bonds are used, and they are rotated around the `i-k` axis.
+pub struct Crankshaft {
+    /// Type of molecule to change. `None` means all molecules.
+    moltype: Option<u64>,
+    /// Rotatable segments of the molecules
+    segments: SegmentCache,
+    /// Indexes of the rotated particles
+    idxes: Vec<usize>,
+    /// New positions of the rotated particles
+    newpos: Vec<Vector3D>,
+    /// Maximum values for the range of the range distribution of the angle
+    theta: f64,
+    /// Range distribution, for generation of the angle
+    range: Range<f64>,
+}
+
+impl Crankshaft {
+    /// Create a new `Crankshaft` move, with maximum angular displacement of
+    /// `theta`, changing all the molecules in the system.
+    pub fn new(theta: f64) -> Crankshaft {
+        Crankshaft::create(theta, None)
+    }
+
+    /// Create a new `Crankshaft` move, with maximum angular displacement of
+    /// `theta`, changing only molecules with `moltype` type.
+    pub fn with_moltype(theta: f64, moltype: u64) -> Crankshaft {
+        Crankshaft::create(theta, Some(moltype))
+    }
+
+    // Factorizing the constructors
+    fn create(theta: f64, moltype: Option<u64>) -> Crankshaft {
+        assert!(theta > 0.0, "theta must be positive in Crankshaft move");
+        Crankshaft {
+            moltype: moltype,
+            segments: SegmentCache::new(crankshaft_segments),
+            idxes: Vec::new(),
+            newpos: Vec::new(),
+            theta: theta,
+            range: Range::new(-theta, theta),
+        }
+    }
+}
+
+impl MCMove for Crankshaft {
+    fn describe(&self) -> &str {
+        "crankshaft rotation"
+    }
+
+    fn setup(&mut self, _: &System) { }
+
+    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng + Send>) -> bool {
+        let molid = if let Some(id) = select_molecule(system, self.moltype, rng) {
+            id
+        } else {
+            warn!("Can not perform crankshaft rotation: no molecule of this type in the system.");
+            return false;
+        };
+
+        let segment = match rng.choose(self.segments.get(system, molid)) {
+            Some(segment) => segment.clone(),
+            None => {
+                trace!("    --> No crankshaft segment in the selected molecule");
+                return false;
+            }
+        };
+
+        let start = system.molecule(molid).start();
+        self.idxes = segment.moved.iter().map(|i| start + i).collect();
+        if self.idxes.iter().any(|&i| system.particles().frozen[i]) {
+            return false;
+        }
+
+        let positions = system.particles().position;
+        let origin = positions[start + segment.first];
+        let mut axis = positions[start + segment.second] - origin;
+        system.cell.vector_image(&mut axis);
+
+        let rotation = Matrix3::rotation(&axis, self.range.sample(rng));
+        self.newpos = self.idxes.iter().map(|&i| {
+            let mut delta = positions[i] - origin;
+            system.cell.vector_image(&mut delta);
+            origin + rotation * delta
+        }).collect();
+        return true;
+    }
+
+    fn cost(&self, system: &System, beta: f64, cache: &mut EnergyCache) -> f64 {
+        let cost = cache.move_particles_cost(system, self.idxes.clone(), &self.newpos);
+        return cost * beta;
+    }
+
+    fn apply(&mut self, system: &mut System) {
+        let positions = system.particles_mut().position;
+        for (&i, newpos) in izip!(&self.idxes, &self.newpos) {
+            positions[i] = *newpos;
+        }
+    }
+
+    fn restore(&mut self, _: &mut System) {
+        // Nothing to do
+    }
+
+    fn update_amplitude(&mut self, scaling_factor: Option<f64>) {
+        if let Some(s) = scaling_factor {
+            if (s * self.theta).abs().to_degrees() <= 180.0 {
+                self.theta *= s;
+                self.range = Range::new(-self.theta, self.theta);
+            } else {
+                warn_once!(
+                    "Tried to increase the maximum amplitude for crankshaft rotations to more than 180°."
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use sys::{Particle, UnitCell};
+
+    #[test]
+    fn rotate() {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        system.add_particle(Particle::with_position("C", Vector3D::new(0.0, 0.0, 0.0)));
+        system.add_particle(Particle::with_position("C", Vector3D::new(1.0, 1.0, 0.0)));
+        system.add_particle(Particle::with_position("C", Vector3D::new(2.0, 0.0, 0.0)));
+        let _ = system.add_bond(0, 1);
+        let _ = system.add_bond(1, 2);
+
+        let mut crankshaft = Crankshaft::new(1.0);
+        crankshaft.setup(&system);
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+
+        assert!(crankshaft.prepare(&mut system, &mut rng));
+        assert_eq!(crankshaft.idxes, vec![1]);
+        crankshaft.apply(&mut system);
+
+        // The central particle is rotated around the x axis
+        let positions = system.particles().position;
+        assert_eq!(positions[0], Vector3D::new(0.0, 0.0, 0.0));
+        assert_eq!(positions[2], Vector3D::new(2.0, 0.0, 0.0));
+        assert_ulps_eq!(positions[1][0], 1.0, epsilon = 1e-12);
+        assert_ulps_eq!(positions[1][1] * positions[1][1] + positions[1][2] * positions[1][2], 1.0, epsilon = 1e-12);
+    }
+}
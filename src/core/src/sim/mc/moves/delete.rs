@@ -0,0 +1,186 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use rand::Rng;
+
+use std::usize;
+use std::f64;
+
+use super::{MCMove, Reservoir};
+use super::{select_molecule, count_molecules};
+use super::cbmc;
+
+use consts::K_BOLTZMANN;
+use sys::{System, EnergyCache, Molecule, ParticleVec};
+use sys::molecule_type;
+
+/// Monte Carlo move for deleting a randomly chosen molecule from the system.
+///
+/// This move is used together with the `Insert` move, with the same frequency
+/// and reservoir, to simulate the grand canonical ensemble. When the `Insert`
+/// move uses configurational-bias Monte Carlo, this move must use the same
+/// number of trials (see `Delete::set_cbmc_trials`).
+pub struct Delete {
+    /// Type of the deleted molecules
+    moltype: u64,
+    /// Total mass of the deleted molecules
+    mass: f64,
+    /// Reservoir the system is in equilibrium with
+    reservoir: Reservoir,
+    /// Index of the molecule to delete
+    molid: usize,
+    /// Number of molecules with the same type before the deletion
+    count: usize,
+    /// Number of trial positions for configurational-bias deletion, if used
+    cbmc_trials: Option<usize>,
+    /// Rosenbluth bias of the deleted molecule, when using
+    /// configurational-bias deletion
+    bias: f64,
+}
+
+impl Delete {
+    /// Create a new `Delete` move, removing molecules with the same type as
+    /// the `molecule` containing the `particles`, in equilibrium with the
+    /// `reservoir`.
+    pub fn new(molecule: Molecule, particles: ParticleVec, reservoir: Reservoir) -> Delete {
+        assert_eq!(molecule.size(), particles.len(), "wrong number of particles in Delete move");
+        let mass = particles.mass.iter().sum::<f64>();
+        assert!(mass > 0.0, "the molecule mass must be positive in Delete move");
+
+        Delete {
+            moltype: molecule_type(&molecule, particles.as_slice()),
+            mass: mass,
+            reservoir: reservoir,
+            molid: usize::MAX,
+            count: 0,
+            cbmc_trials: None,
+            bias: 0.0,
+        }
+    }
+
+    /// Use configurational-bias Monte Carlo with `trials` trial positions for
+    /// each particle when computing the probability to delete a molecule.
+    pub fn set_cbmc_trials(&mut self, trials: usize) {
+        assert!(trials > 0, "the number of trials must be positive in Delete move");
+        self.cbmc_trials = Some(trials);
+    }
+}
+
+impl MCMove for Delete {
+    fn describe(&self) -> &str {
+        "molecular deletion"
+    }
+
+    fn setup(&mut self, system: &System) {
+        if system.cell.is_infinite() {
+            fatal_error!("Can not delete molecules in an infinite cell");
+        }
+    }
+
+    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng + Send>) -> bool {
+        self.count = count_molecules(system, self.moltype);
+        if let Some(id) = select_molecule(system, Some(self.moltype), rng) {
+            self.molid = id;
+        } else {
+            warn!("Can not delete a molecule: no molecule of this type in the system.");
+            return false;
+        }
+
+        if let Some(trials) = self.cbmc_trials {
+            let start = system.molecule(self.molid).start();
+            let order = cbmc::growth_order(system.molecule(self.molid), start, None)
+                        .expect("growth without a starting bond can not fail");
+            let beta = 1.0 / (K_BOLTZMANN * system.temperature());
+            match cbmc::grow(system, self.molid, &order, trials, beta, true, rng) {
+                Some(growth) => self.bias = growth.bias(beta),
+                None => return false,
+            }
+        }
+        return true;
+    }
+
+    fn cost(&self, system: &System, beta: f64, cache: &mut EnergyCache) -> f64 {
+        let delta_energy = cache.remove_molecule_cost(system, self.molid);
+        let activity = self.reservoir.activity(beta, self.mass);
+        let volume = system.volume();
+        let cost = beta * delta_energy - f64::ln(self.count as f64 / (activity * volume));
+        if self.cbmc_trials.is_some() {
+            cost + self.bias
+        } else {
+            cost
+        }
+    }
+
+    fn apply(&mut self, system: &mut System) {
+        system.remove_molecule(self.molid);
+    }
+
+    fn restore(&mut self, _: &mut System) {
+        // Nothing to do, the molecule was not removed
+    }
+
+    fn update_amplitude(&mut self, _: Option<f64>) {
+        // Nothing to do
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use sys::{Particle, UnitCell};
+    use types::Vector3D;
+    use consts::K_BOLTZMANN;
+
+    #[test]
+    fn delete() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(1.0, 1.0, 1.0)));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(5.0, 5.0, 5.0)));
+
+        let mut particles = ParticleVec::new();
+        particles.push(Particle::new("Ar"));
+        let mut delete = Delete::new(Molecule::new(0), particles, Reservoir::Fugacity(1e-3));
+        delete.setup(&system);
+
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+
+        assert!(delete.prepare(&mut system, &mut rng));
+        assert_eq!(system.size(), 2);
+
+        // Without interactions, the cost only depends on the reservoir
+        let beta = 1.0 / (K_BOLTZMANN * 300.0);
+        let cost = delete.cost(&system, beta, &mut cache);
+        assert_ulps_eq!(cost, -f64::ln(2.0 / (beta * 1e-3 * 1000.0)));
+
+        delete.apply(&mut system);
+        assert_eq!(system.size(), 1);
+    }
+
+    #[test]
+    fn frozen_molecules() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(1.0, 1.0, 1.0)));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(5.0, 5.0, 5.0)));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(8.0, 8.0, 8.0)));
+        system.set_frozen_molecule(0, true);
+
+        let mut particles = ParticleVec::new();
+        particles.push(Particle::new("Ar"));
+        let mut delete = Delete::new(Molecule::new(0), particles, Reservoir::Fugacity(1e-3));
+        delete.setup(&system);
+
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+
+        // Frozen molecules can not be deleted, and are not counted
+        assert!(delete.prepare(&mut system, &mut rng));
+        assert!(delete.molid != 0);
+        let beta = 1.0 / (K_BOLTZMANN * 300.0);
+        let cost = delete.cost(&system, beta, &mut cache);
+        assert_ulps_eq!(cost, -f64::ln(2.0 / (beta * 1e-3 * 1000.0)));
+    }
+}
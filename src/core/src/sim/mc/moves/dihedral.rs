@@ -0,0 +1,176 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use rand::distributions::{Range, Sample};
+use rand::Rng;
+
+use super::MCMove;
+use super::select_molecule;
+use super::segments::{Segment, SegmentCache, bond_segments};
+
+use types::{Matrix3, Vector3D};
+use sys::{System, Molecule, EnergyCache};
+
+/// Monte Carlo move rotating a part of a flexible molecule around one of its
+/// bonds, changing the associated dihedral angles.
+///
+/// The rotated bond is selected at random among the bonds which are not part
+/// of a ring, and the particles on the smallest side of the bond are rotated.
+/// Bonds with a single particle on one side are not used, since rotating
+/// them does not change the molecule.
+pub struct DihedralRotation {
+    /// Type of molecule to change. `None` means all molecules.
+    moltype: Option<u64>,
+    /// Rotatable bonds of the molecules
+    segments: SegmentCache,
+    /// Indexes of the rotated particles
+    idxes: Vec<usize>,
+    /// New positions of the rotated particles
+    newpos: Vec<Vector3D>,
+    /// Maximum values for the range of the range distribution of the angle
+    theta: f64,
+    /// Range distribution, for generation of the angle
+    range: Range<f64>,
+}
+
+impl DihedralRotation {
+    /// Create a new `DihedralRotation` move, with maximum angular
+    /// displacement of `theta`, changing all the molecules in the system.
+    pub fn new(theta: f64) -> DihedralRotation {
+        DihedralRotation::create(theta, None)
+    }
+
+    /// Create a new `DihedralRotation` move, with maximum angular
+    /// displacement of `theta`, changing only molecules with `moltype` type.
+    pub fn with_moltype(theta: f64, moltype: u64) -> DihedralRotation {
+        DihedralRotation::create(theta, Some(moltype))
+    }
+
+    // Factorizing the constructors
+    fn create(theta: f64, moltype: Option<u64>) -> DihedralRotation {
+        assert!(theta > 0.0, "theta must be positive in DihedralRotation move");
+        DihedralRotation {
+            moltype: moltype,
+            segments: SegmentCache::new(dihedral_segments),
+            idxes: Vec::new(),
+            newpos: Vec::new(),
+            theta: theta,
+            range: Range::new(-theta, theta),
+        }
+    }
+}
+
+/// Get the segments defined by rotatable bonds with more than one particle
+/// on each side
+fn dihedral_segments(molecule: &Molecule) -> Vec<Segment> {
+    bond_segments(molecule).into_iter().filter(|segment| segment.moved.len() > 1).collect()
+}
+
+impl MCMove for DihedralRotation {
+    fn describe(&self) -> &str {
+        "dihedral rotation"
+    }
+
+    fn setup(&mut self, _: &System) { }
+
+    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng + Send>) -> bool {
+        let molid = if let Some(id) = select_molecule(system, self.moltype, rng) {
+            id
+        } else {
+            warn!("Can not rotate dihedral angle: no molecule of this type in the system.");
+            return false;
+        };
+
+        let segment = match rng.choose(self.segments.get(system, molid)) {
+            Some(segment) => segment.clone(),
+            None => {
+                trace!("    --> No rotatable bond in the selected molecule");
+                return false;
+            }
+        };
+
+        let start = system.molecule(molid).start();
+        self.idxes = segment.moved.iter().map(|i| start + i).collect();
+        if self.idxes.iter().any(|&i| system.particles().frozen[i]) {
+            return false;
+        }
+
+        let positions = system.particles().position;
+        let origin = positions[start + segment.second];
+        let mut axis = origin - positions[start + segment.first];
+        system.cell.vector_image(&mut axis);
+
+        let rotation = Matrix3::rotation(&axis, self.range.sample(rng));
+        self.newpos = self.idxes.iter().map(|&i| {
+            let mut delta = positions[i] - origin;
+            system.cell.vector_image(&mut delta);
+            origin + rotation * delta
+        }).collect();
+        return true;
+    }
+
+    fn cost(&self, system: &System, beta: f64, cache: &mut EnergyCache) -> f64 {
+        let cost = cache.move_particles_cost(system, self.idxes.clone(), &self.newpos);
+        return cost * beta;
+    }
+
+    fn apply(&mut self, system: &mut System) {
+        let positions = system.particles_mut().position;
+        for (&i, newpos) in izip!(&self.idxes, &self.newpos) {
+            positions[i] = *newpos;
+        }
+    }
+
+    fn restore(&mut self, _: &mut System) {
+        // Nothing to do
+    }
+
+    fn update_amplitude(&mut self, scaling_factor: Option<f64>) {
+        if let Some(s) = scaling_factor {
+            if (s * self.theta).abs().to_degrees() <= 180.0 {
+                self.theta *= s;
+                self.range = Range::new(-self.theta, self.theta);
+            } else {
+                warn_once!(
+                    "Tried to increase the maximum amplitude for dihedral rotations to more than 180°."
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use sys::{Particle, UnitCell};
+
+    fn butane() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        system.add_particle(Particle::with_position("C", Vector3D::new(0.0, 1.0, 0.0)));
+        system.add_particle(Particle::with_position("C", Vector3D::new(0.0, 0.0, 0.0)));
+        system.add_particle(Particle::with_position("C", Vector3D::new(1.5, 0.0, 0.0)));
+        system.add_particle(Particle::with_position("C", Vector3D::new(1.5, 1.0, 0.0)));
+        for i in 0..3 {
+            let _ = system.add_bond(i, i + 1);
+        }
+        return system;
+    }
+
+    #[test]
+    fn rotate() {
+        let mut system = butane();
+        let mut rotation = DihedralRotation::new(1.0);
+        rotation.setup(&system);
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+
+        assert!(rotation.prepare(&mut system, &mut rng));
+        // Only the central bond can be rotated
+        assert_eq!(rotation.idxes, vec![2, 3]);
+        rotation.apply(&mut system);
+
+        let positions = system.particles().position;
+        assert_eq!(positions[2], Vector3D::new(1.5, 0.0, 0.0));
+        assert_ulps_eq!((positions[3] - positions[2]).norm(), 1.0, epsilon = 1e-12);
+        assert_ulps_eq!(positions[3][0], 1.5, epsilon = 1e-12);
+    }
+}
@@ -0,0 +1,232 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use rand::Rng;
+
+use std::f64;
+use std::cmp;
+
+use super::MCMove;
+
+use types::Vector3D;
+use sys::{System, EnergyCache};
+use sys::veloc::{BoltzmannVelocities, InitVelocities};
+
+/// Hybrid Monte Carlo move, using short molecular dynamics trajectories to
+/// move all the particles at once.
+///
+/// At each move, the velocities are drawn from the Boltzmann distribution at
+/// the simulation temperature, and the system is propagated for a few steps
+/// using the velocity-Verlet algorithm, starting from the forces in the
+/// initial configuration. The new positions are then accepted or rejected
+/// using the change in total energy along the trajectory, which corrects for
+/// the integration errors. The velocities of the particles are not changed by
+/// this move. Distance constraints are not supported.
+///
+/// When updating the amplitude of this move, the timestep is changed and the
+/// number of steps is adapted to keep the same trajectory duration.
+pub struct Hybrid {
+    /// Timestep of the molecular dynamics trajectories
+    timestep: f64,
+    /// Number of steps in the molecular dynamics trajectories
+    steps: usize,
+    /// Duration of the molecular dynamics trajectories
+    duration: f64,
+    /// Indexes of the moved particles
+    idxes: Vec<usize>,
+    /// New positions of the moved particles
+    newpos: Vec<Vector3D>,
+    /// Change in kinetic energy during the trajectory
+    delta_kinetic: f64,
+}
+
+impl Hybrid {
+    /// Create a new `Hybrid` move, running trajectories of `steps` steps with
+    /// the given `timestep`.
+    pub fn new(timestep: f64, steps: usize) -> Hybrid {
+        assert!(timestep > 0.0, "timestep must be positive in Hybrid move");
+        assert!(steps > 0, "the number of steps must be positive in Hybrid move");
+        Hybrid {
+            timestep: timestep,
+            steps: steps,
+            duration: timestep * steps as f64,
+            idxes: Vec::new(),
+            newpos: Vec::new(),
+            delta_kinetic: 0.0,
+        }
+    }
+
+    /// Propagate the mobile particles in `system` for `self.steps` steps with
+    /// the velocity-Verlet algorithm.
+    fn integrate(&self, system: &mut System) {
+        let dt = self.timestep;
+        let mut accelerations = compute_accelerations(system);
+        for _ in 0..self.steps {
+            {
+                let particles = system.particles_mut();
+                for &i in &self.idxes {
+                    particles.velocity[i] += 0.5 * dt * accelerations[i];
+                    particles.position[i] += particles.velocity[i] * dt;
+                }
+            }
+
+            accelerations = compute_accelerations(system);
+            let velocities = system.particles_mut().velocity;
+            for &i in &self.idxes {
+                velocities[i] += 0.5 * dt * accelerations[i];
+            }
+        }
+    }
+}
+
+/// Get the accelerations of all the particles in `system`
+fn compute_accelerations(system: &System) -> Vec<Vector3D> {
+    let forces = system.forces();
+    izip!(system.particles().mass, forces).map(|(&mass, force)| force / mass).collect()
+}
+
+impl MCMove for Hybrid {
+    fn describe(&self) -> &str {
+        "hybrid Monte Carlo"
+    }
+
+    fn setup(&mut self, _: &System) {
+        // Nothing to do
+    }
+
+    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng + Send>) -> bool {
+        if !system.constraints().is_empty() {
+            warn_once!("Can not perform hybrid Monte Carlo with distance constraints.");
+            return false;
+        }
+
+        self.idxes = (0..system.size()).filter(|&i| !system.particles().frozen[i]).collect();
+        if self.idxes.is_empty() {
+            warn!("Can not perform hybrid Monte Carlo: all the particles are frozen.");
+            return false;
+        }
+
+        let positions = system.particles().position.to_vec();
+        let velocities = system.particles().velocity.to_vec();
+
+        let mut boltzmann = BoltzmannVelocities::new(system.temperature());
+        boltzmann.seed(rng.next_u64());
+        boltzmann.sample(system);
+        let kinetic = system.kinetic_energy();
+
+        self.integrate(system);
+        self.delta_kinetic = system.kinetic_energy() - kinetic;
+
+        // Store the new positions, and go back to the initial state
+        self.newpos = self.idxes.iter().map(|&i| system.particles().position[i]).collect();
+        let particles = system.particles_mut();
+        particles.position.copy_from_slice(&positions);
+        particles.velocity.copy_from_slice(&velocities);
+        return true;
+    }
+
+    fn cost(&self, system: &System, beta: f64, cache: &mut EnergyCache) -> f64 {
+        let cost = cache.move_particles_cost(system, self.idxes.clone(), &self.newpos);
+        return beta * (cost + self.delta_kinetic);
+    }
+
+    fn apply(&mut self, system: &mut System) {
+        {
+            let positions = system.particles_mut().position;
+            for (&i, newpos) in izip!(&self.idxes, &self.newpos) {
+                positions[i] = *newpos;
+            }
+        }
+        for molid in 0..system.molecules().len() {
+            system.wrap_molecule(molid);
+        }
+    }
+
+    fn restore(&mut self, _: &mut System) {
+        // Nothing to do, the system is restored in `prepare`
+    }
+
+    fn update_amplitude(&mut self, scaling_factor: Option<f64>) {
+        if let Some(scaling_factor) = scaling_factor {
+            self.timestep *= scaling_factor;
+            let steps = f64::round(self.duration / self.timestep) as usize;
+            self.steps = cmp::max(steps, 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use sys::{Particle, UnitCell};
+    use energy::{PairInteraction, Harmonic};
+
+    #[test]
+    fn hybrid() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(1.0, 1.0, 1.0)));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(5.0, 5.0, 5.0)));
+        system.external_temperature(Some(300.0));
+
+        let mut hybrid = Hybrid::new(1.0, 10);
+        hybrid.setup(&system);
+
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+
+        assert!(hybrid.prepare(&mut system, &mut rng));
+        // The system is not changed by `prepare`
+        assert_eq!(system.particles().position[0], Vector3D::new(1.0, 1.0, 1.0));
+        assert_eq!(system.particles().velocity[0], Vector3D::new(0.0, 0.0, 0.0));
+
+        // Without interactions, the kinetic energy is conserved
+        let cost = hybrid.cost(&system, 1.0, &mut cache);
+        assert_ulps_eq!(cost, 0.0, epsilon = 1e-12);
+
+        // The particles are wrapped in the cell
+        hybrid.apply(&mut system);
+        for position in system.particles().position {
+            let fractional = system.cell.fractional(position);
+            for k in 0..3 {
+                assert!(0.0 <= fractional[k] && fractional[k] <= 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn energy_conservation() {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        system.add_particle(Particle::with_position("Cl", Vector3D::new(0.0, 0.0, 0.0)));
+        system.add_particle(Particle::with_position("Cl", Vector3D::new(0.0, 0.0, 2.0)));
+        system.add_pair_potential("Cl", "Cl",
+            PairInteraction::new(Box::new(Harmonic{x0: 2.3, k: 0.1}), 10.0)
+        );
+        system.external_temperature(Some(300.0));
+
+        let mut hybrid = Hybrid::new(0.1, 10);
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+
+        // With a small timestep, the total energy is conserved along the
+        // trajectory
+        assert!(hybrid.prepare(&mut system, &mut rng));
+        let cost = hybrid.cost(&system, 1.0, &mut cache);
+        assert!(f64::abs(cost) < 1e-6);
+        assert!(hybrid.newpos[1] != Vector3D::new(0.0, 0.0, 2.0));
+    }
+
+    #[test]
+    fn update_amplitude() {
+        let mut hybrid = Hybrid::new(1.0, 10);
+        hybrid.update_amplitude(Some(1.2));
+        assert_eq!(hybrid.timestep, 1.2);
+        assert_eq!(hybrid.steps, 8);
+
+        hybrid.update_amplitude(None);
+        assert_eq!(hybrid.timestep, 1.2);
+        assert_eq!(hybrid.steps, 8);
+    }
+}
@@ -0,0 +1,267 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use rand::distributions::Normal;
+use rand::Rng;
+
+use std::usize;
+use std::f64;
+
+use super::{MCMove, Reservoir};
+use super::{random_rotation, center_particles, copy_charge, count_molecules};
+use super::cbmc;
+
+use consts::K_BOLTZMANN;
+use types::{Vector3D, Zero};
+use sys::{System, EnergyCache, Molecule, Particle, ParticleKind, ParticleVec};
+use sys::molecule_type;
+
+/// Monte Carlo move for inserting a molecule in the system, at a random
+/// position and with a random orientation.
+///
+/// This move is used together with the `Delete` move, with the same frequency
+/// and reservoir, to simulate the grand canonical ensemble.
+///
+/// Flexible molecules can be inserted using configurational-bias Monte Carlo
+/// (see `Insert::set_cbmc_trials`), growing the molecule one particle at the
+/// time from a random position. In this case, the chemical potential of the
+/// reservoir also includes the intramolecular energy of an ideal chain.
+pub struct Insert {
+    /// The molecule to insert, with indexes starting at 0
+    molecule: Molecule,
+    /// The particles in the inserted molecule, with positions relative to
+    /// the center-of-mass of the molecule
+    particles: ParticleVec,
+    /// Type of the inserted molecule
+    moltype: u64,
+    /// Total mass of the inserted molecule
+    mass: f64,
+    /// Reservoir the system is in equilibrium with
+    reservoir: Reservoir,
+    /// Index of the inserted molecule
+    molid: usize,
+    /// Number of molecules with the same type before the insertion
+    count: usize,
+    /// Normal distribution, for the generation of random orientations
+    rng: Normal,
+    /// Number of trial positions for configurational-bias insertion, if used
+    cbmc_trials: Option<usize>,
+    /// Rosenbluth bias of the inserted molecule, when using
+    /// configurational-bias insertion
+    bias: f64,
+}
+
+impl Insert {
+    /// Create a new `Insert` move, inserting copies of the `molecule`
+    /// containing the `particles`, in equilibrium with the `reservoir`. The
+    /// indexes in `molecule` must start at 0, as returned by `read_molecule`.
+    pub fn new(molecule: Molecule, mut particles: ParticleVec, reservoir: Reservoir) -> Insert {
+        assert_eq!(molecule.start(), 0, "the molecule indexes must start at 0 in Insert move");
+        assert_eq!(molecule.size(), particles.len(), "wrong number of particles in Insert move");
+
+        let moltype = molecule_type(&molecule, particles.as_slice());
+        let mass = center_particles(&mut particles);
+        assert!(mass > 0.0, "the molecule mass must be positive in Insert move");
+
+        Insert {
+            molecule: molecule,
+            particles: particles,
+            moltype: moltype,
+            mass: mass,
+            reservoir: reservoir,
+            molid: usize::MAX,
+            count: 0,
+            rng: Normal::new(0.0, 1.0),
+            cbmc_trials: None,
+            bias: 0.0,
+        }
+    }
+
+    /// Insert the molecules using configurational-bias Monte Carlo, with
+    /// `trials` trial positions for each particle. The bond lengths are
+    /// taken from the initial molecule. This move must be used with a
+    /// `Delete` move using the same number of trials.
+    pub fn set_cbmc_trials(&mut self, trials: usize) {
+        assert!(trials > 0, "the number of trials must be positive in Insert move");
+        self.cbmc_trials = Some(trials);
+    }
+}
+
+impl MCMove for Insert {
+    fn describe(&self) -> &str {
+        "molecular insertion"
+    }
+
+    fn setup(&mut self, system: &System) {
+        if system.cell.is_infinite() {
+            fatal_error!("Can not insert molecules in an infinite cell");
+        }
+
+        for (name, charge) in self.particles.name.iter().zip(&mut self.particles.charge) {
+            copy_charge(system, name, charge);
+        }
+    }
+
+    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng + Send>) -> bool {
+        self.count = count_molecules(system, self.moltype);
+
+        let fractional = Vector3D::new(rng.next_f64(), rng.next_f64(), rng.next_f64());
+        let com = system.cell.cartesian(&fractional);
+        let rotation = random_rotation(&mut self.rng, rng);
+
+        let mut particles = Vec::with_capacity(self.particles.len());
+        for i in 0..self.particles.len() {
+            particles.push(Particle {
+                name: self.particles.name[i].clone(),
+                kind: ParticleKind::invalid(),
+                charge: self.particles.charge[i],
+                mass: self.particles.mass[i],
+                position: com + rotation * self.particles.position[i],
+                velocity: Vector3D::zero(),
+                frozen: false,
+            });
+        }
+
+        system.add_molecule(self.molecule.clone(), particles);
+        self.molid = system.molecules().len() - 1;
+
+        if let Some(trials) = self.cbmc_trials {
+            let start = system.molecule(self.molid).start();
+            let order = cbmc::growth_order(system.molecule(self.molid), start, None)
+                        .expect("growth without a starting bond can not fail");
+            let beta = 1.0 / (K_BOLTZMANN * system.temperature());
+            let growth = match cbmc::grow(system, self.molid, &order, trials, beta, false, rng) {
+                Some(growth) => growth,
+                None => {
+                    system.remove_molecule(self.molid);
+                    return false;
+                }
+            };
+
+            self.bias = growth.bias(beta);
+            {
+                let positions = system.particles_mut().position;
+                for (&(i, _), newpos) in izip!(&order, &growth.positions) {
+                    positions[i] = *newpos;
+                }
+            }
+            system.wrap_molecule(self.molid);
+        }
+        return true;
+    }
+
+    fn cost(&self, system: &System, beta: f64, cache: &mut EnergyCache) -> f64 {
+        let delta_energy = cache.add_molecule_cost(system, self.molid);
+        let activity = self.reservoir.activity(beta, self.mass);
+        let volume = system.volume();
+        let cost = beta * delta_energy - f64::ln(activity * volume / (self.count as f64 + 1.0));
+        if self.cbmc_trials.is_some() {
+            cost - self.bias
+        } else {
+            cost
+        }
+    }
+
+    fn apply(&mut self, _: &mut System) {
+        // Nothing to do, the molecule is already in the system
+    }
+
+    fn restore(&mut self, system: &mut System) {
+        system.remove_molecule(self.molid);
+    }
+
+    fn update_amplitude(&mut self, _: Option<f64>) {
+        // Nothing to do
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use sys::UnitCell;
+    use consts::K_BOLTZMANN;
+
+    fn argon() -> (Molecule, ParticleVec) {
+        let mut particles = ParticleVec::new();
+        particles.push(Particle::with_position("Ar", Vector3D::new(3.0, 2.0, 1.0)));
+        (Molecule::new(0), particles)
+    }
+
+    #[test]
+    fn insert() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        let (molecule, particles) = argon();
+        let mut insert = Insert::new(molecule, particles, Reservoir::Fugacity(1e-3));
+        insert.setup(&system);
+
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+
+        assert!(insert.prepare(&mut system, &mut rng));
+        assert_eq!(system.size(), 1);
+        assert_eq!(system.particles().name[0], "Ar");
+        let position = system.particles().position[0];
+        assert!((0..3).all(|i| 0.0 <= position[i] && position[i] < 10.0));
+
+        // Without interactions, the cost only depends on the reservoir
+        let beta = 1.0 / (K_BOLTZMANN * 300.0);
+        let cost = insert.cost(&system, beta, &mut cache);
+        assert_ulps_eq!(cost, -f64::ln(beta * 1e-3 * 1000.0));
+
+        insert.restore(&mut system);
+        assert_eq!(system.size(), 0);
+    }
+
+    #[test]
+    fn frozen_molecules() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(1.0, 1.0, 1.0)));
+        system.set_frozen_molecule(0, true);
+
+        let (molecule, particles) = argon();
+        let mut insert = Insert::new(molecule, particles, Reservoir::Fugacity(1e-3));
+        insert.setup(&system);
+
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+
+        // Frozen molecules are not counted
+        assert!(insert.prepare(&mut system, &mut rng));
+        let beta = 1.0 / (K_BOLTZMANN * 300.0);
+        let cost = insert.cost(&system, beta, &mut cache);
+        assert_ulps_eq!(cost, -f64::ln(beta * 1e-3 * 1000.0));
+    }
+
+    #[test]
+    fn cbmc_insert() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.external_temperature(Some(300.0));
+
+        let mut particles = ParticleVec::new();
+        particles.push(Particle::with_position("C", Vector3D::new(0.0, 0.0, 0.0)));
+        particles.push(Particle::with_position("C", Vector3D::new(1.5, 0.0, 0.0)));
+        let mut molecule = Molecule::new(0);
+        molecule.merge_with(Molecule::new(1));
+        molecule.add_bond(0, 1);
+
+        let mut insert = Insert::new(molecule, particles, Reservoir::Fugacity(1e-3));
+        insert.set_cbmc_trials(5);
+        insert.setup(&system);
+
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+
+        assert!(insert.prepare(&mut system, &mut rng));
+        assert_eq!(system.size(), 2);
+        assert_ulps_eq!(system.distance(0, 1), 1.5, epsilon = 1e-12);
+
+        // Without interactions, the Rosenbluth weight is 1
+        let beta = 1.0 / (K_BOLTZMANN * 300.0);
+        let cost = insert.cost(&system, beta, &mut cache);
+        assert_ulps_eq!(cost, -f64::ln(beta * 1e-3 * 1000.0));
+    }
+}
@@ -9,12 +9,17 @@
 //!
 //! In all this module, beta refers to the Boltzmann factor 1/(kB T)
 use rand::Rng;
+use rand::distributions::{Normal, Sample};
 
-use sys::{System, EnergyCache};
+use std::f64::consts::PI;
+
+use consts::H_BAR;
+use types::{Matrix3, Vector3D, One, Zero};
+use sys::{System, Configuration, EnergyCache, ParticleVec};
 
 /// The `MCMove` trait correspond to the set of methods used in Monte Carlo
 /// simulations.
-pub trait MCMove {
+pub trait MCMove: Send {
     /// Give a short description of this move
     fn describe(&self) -> &str;
 
@@ -27,7 +32,7 @@ pub trait MCMove {
     ///
     /// This function should return true is we can perform the move, and false
     /// otherwise.
-    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng>) -> bool;
+    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng + Send>) -> bool;
 
     /// Get the cost of performing this move on `system`. For example in
     /// simple NVT simulations, this cost is the energetic difference between
@@ -56,11 +61,20 @@ pub trait MCMove {
 /// Select a random molecule in the system using `rng` as random number
 /// generator. If `moltype` is `None`, any molecule can be chosen. If `moltype`
 /// is `Some(molecule_type)`, then a molecule with matching type is selected.
+/// Frozen molecules are never selected.
 ///
 /// This function returns `None` if no matching molecule was found, and
 /// `Some(molid)` with `molid` the index of the molecule if a molecule was
 /// selected.
-fn select_molecule(system: &System, moltype: Option<u64>, rng: &mut Box<Rng>) -> Option<usize> {
+pub(super) fn select_molecule(system: &System, moltype: Option<u64>, rng: &mut Box<Rng + Send>) -> Option<usize> {
+    if system.frozen_count() != 0 {
+        let mols = (0..system.molecules().len()).filter(|&molid| {
+            !system.is_frozen_molecule(molid) &&
+            moltype.map_or(true, |moltype| system.molecule_type(molid) == moltype)
+        }).collect::<Vec<_>>();
+        return rng.choose(&mols).cloned();
+    }
+
     if let Some(moltype) = moltype {
         // Pick a random molecule with matching moltype
         let mols = system.molecules_with_moltype(moltype);
@@ -75,6 +89,127 @@ fn select_molecule(system: &System, moltype: Option<u64>, rng: &mut Box<Rng>) ->
     }
 }
 
+/// Count the molecules with type `moltype` in the `system` which can be
+/// selected by `select_molecule`, i.e. the molecules which are not frozen.
+pub(super) fn count_molecules(system: &System, moltype: u64) -> usize {
+    system.molecules_with_moltype(moltype).into_iter().filter(|&molid| {
+        !system.is_frozen_molecule(molid)
+    }).count()
+}
+
+/// Scale the cell of `system` by the `scaling` matrix, and move the
+/// center-of-mass of all the molecules accordingly, keeping the
+/// intramolecular distances constant. Frozen molecules keep their positions.
+/// `previous` must contain the configuration before the scaling.
+///
+/// The simulation is aborted if the new cell is smaller than twice the
+/// `maximum_cutoff`.
+pub(super) fn scale_cell(system: &mut System, previous: &Configuration, scaling: Matrix3, maximum_cutoff: Option<f64>) {
+    system.cell.scale_mut(scaling);
+    // Check the radius of the smallest inscribed sphere and compare to the
+    // cut off distance.
+    // Abort simulation when box gets smaller than twice the cutoff radius.
+    if let Some(maximum_cutoff) = maximum_cutoff {
+        if system.cell.lengths().iter().any(|&d| 0.5 * d <= maximum_cutoff) {
+            fatal_error!(
+                "Tried to decrease the cell size but new size
+                conflicts with the cut off radius. \
+                Increase the number of particles to get rid of this problem."
+            );
+        }
+    };
+
+    for (mi, molecule) in previous.molecules().iter().enumerate() {
+        // Frozen molecules keep their positions
+        if previous.is_frozen_molecule(mi) {
+            continue;
+        }
+        // We don't want to change the intramolecular distances so we
+        // compute the translation vector of the center-of-mass (com) of a
+        // molecule and apply it to all its particles. Note that to do
+        // this, the com of a molecule *always* has to reside inside the
+        // simulation cell.
+        let old_com = previous.molecule_com(mi);
+        let frac_com = previous.cell.fractional(&old_com);
+        let delta_com = system.cell.cartesian(&frac_com) - old_com;
+        for position in &mut system.particles_mut().position[molecule.iter()] {
+            *position += delta_com;
+        }
+    }
+}
+
+/// Get a rotation matrix uniformly distributed among all the rotations,
+/// using the `normal` distribution with zero mean and unit variance and the
+/// `rng` random number generator. This uses an uniformly distributed unit
+/// quaternion.
+pub(crate) fn random_rotation(normal: &mut Normal, rng: &mut Box<Rng + Send>) -> Matrix3 {
+    let w = normal.sample(rng);
+    let axis = Vector3D::new(normal.sample(rng), normal.sample(rng), normal.sample(rng));
+    if axis.norm2() == 0.0 {
+        return Matrix3::one();
+    }
+    let norm = f64::sqrt(w * w + axis.norm2());
+    Matrix3::rotation(&axis, 2.0 * f64::acos(w / norm))
+}
+
+/// Translate the `particles` so that their center-of-mass is at the origin,
+/// and return their total mass. The particles are not translated if the
+/// total mass is not positive.
+pub(crate) fn center_particles(particles: &mut ParticleVec) -> f64 {
+    let mass = particles.mass.iter().sum::<f64>();
+    if mass <= 0.0 {
+        return mass;
+    }
+
+    let mut com = Vector3D::zero();
+    for (&mass, position) in particles.mass.iter().zip(&particles.position) {
+        com += mass * position;
+    }
+    com /= mass;
+    for position in &mut particles.position {
+        *position -= com;
+    }
+    return mass;
+}
+
+/// Set `charge` to the charge of the first particle named `name` in the
+/// `system`, if there is such a particle. This is used to give the same
+/// charges to the particles created by a move and to the particles already
+/// in the system.
+pub(crate) fn copy_charge(system: &System, name: &str, charge: &mut f64) {
+    if let Some(i) = system.particles().name.iter().position(|other| other == name) {
+        *charge = system.particles().charge[i];
+    }
+}
+
+/// Chemical equilibrium of the simulated system with a reservoir of
+/// molecules, used by the `Insert` and `Delete` moves in grand canonical
+/// Monte Carlo simulations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reservoir {
+    /// Reservoir at the given chemical potential, including the ideal gas
+    /// contribution of the translations of the molecules.
+    ChemicalPotential(f64),
+    /// Reservoir at the given fugacity, expressed as a pressure.
+    Fugacity(f64),
+}
+
+impl Reservoir {
+    /// Get the activity `z = exp(beta mu) / Λ^3` of the reservoir, for
+    /// molecules with the total mass `mass` at inverse temperature `beta`.
+    /// `Λ` is the thermal de Broglie wavelength of the molecules. The activity
+    /// is expressed as an inverse volume.
+    fn activity(&self, beta: f64, mass: f64) -> f64 {
+        match *self {
+            Reservoir::ChemicalPotential(mu) => {
+                let lambda = H_BAR * f64::sqrt(2.0 * PI * beta / mass);
+                f64::exp(beta * mu) / (lambda * lambda * lambda)
+            }
+            Reservoir::Fugacity(fugacity) => beta * fugacity,
+        }
+    }
+}
+
 mod translate;
 pub use self::translate::Translate;
 
@@ -83,3 +218,33 @@ pub use self::rotate::Rotate;
 
 mod resize;
 pub use self::resize::Resize;
+
+mod insert;
+pub use self::insert::Insert;
+
+mod delete;
+pub use self::delete::Delete;
+
+mod regrow;
+pub use self::regrow::Regrow;
+
+mod swap;
+pub use self::swap::Swap;
+
+mod semigrand;
+pub use self::semigrand::SemiGrand;
+
+mod hybrid;
+pub use self::hybrid::Hybrid;
+
+mod dihedral;
+pub use self::dihedral::DihedralRotation;
+
+mod crankshaft;
+pub use self::crankshaft::Crankshaft;
+
+mod pivot;
+pub use self::pivot::Pivot;
+
+mod cbmc;
+mod segments;
@@ -0,0 +1,219 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use rand::Rng;
+
+use std::usize;
+
+use super::MCMove;
+use super::select_molecule;
+use super::cbmc;
+
+use consts::K_BOLTZMANN;
+use types::Vector3D;
+use sys::{System, EnergyCache};
+
+/// Monte Carlo move regrowing part of a flexible molecule using
+/// configurational-bias Monte Carlo.
+///
+/// A random bond is cut in a randomly selected molecule, and all the
+/// particles on one side of this bond are regrown one at the time, using
+/// multiple trial positions for each particle. Bonds which are part of a ring
+/// are never cut. The bond lengths are kept constant by this move.
+pub struct Regrow {
+    /// Type of molecule to regrow. `None` means all molecules.
+    moltype: Option<u64>,
+    /// Number of trial positions for each particle
+    trials: usize,
+    /// Index of the molecule to regrow
+    molid: usize,
+    /// Indexes of the regrown particles
+    idxes: Vec<usize>,
+    /// New positions of the regrown particles
+    newpos: Vec<Vector3D>,
+    /// Difference between the new and old Rosenbluth bias
+    bias: f64,
+}
+
+impl Regrow {
+    /// Create a new `Regrow` move, using `trials` trial positions for each
+    /// regrown particle. Regrowing all the molecules in the system.
+    pub fn new(trials: usize) -> Regrow {
+        Regrow::create(trials, None)
+    }
+
+    /// Create a new `Regrow` move, using `trials` trial positions for each
+    /// regrown particle. Regrowing only molecules with `moltype` type.
+    pub fn with_moltype(trials: usize, moltype: u64) -> Regrow {
+        Regrow::create(trials, Some(moltype))
+    }
+
+    /// Factorizing the constructors
+    fn create(trials: usize, moltype: Option<u64>) -> Regrow {
+        assert!(trials > 0, "the number of trials must be positive in Regrow move");
+        Regrow {
+            moltype: moltype,
+            trials: trials,
+            molid: usize::MAX,
+            idxes: Vec::new(),
+            newpos: Vec::new(),
+            bias: 0.0,
+        }
+    }
+}
+
+impl MCMove for Regrow {
+    fn describe(&self) -> &str {
+        "configurational-bias regrowth"
+    }
+
+    fn setup(&mut self, _: &System) {
+        // Nothing to do
+    }
+
+    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng + Send>) -> bool {
+        if let Some(id) = select_molecule(system, self.moltype, rng) {
+            self.molid = id;
+        } else {
+            warn!("Can not regrow molecule: no molecule of this type in the system.");
+            return false;
+        }
+
+        let order = {
+            let molecule = system.molecule(self.molid);
+            let bonds = molecule.bonds().iter().collect::<Vec<_>>();
+            let bond = match rng.choose(&bonds) {
+                Some(bond) => *bond,
+                // Nothing to regrow in molecules without bonds
+                None => return false,
+            };
+
+            // Regrow one of the two sides of the bond
+            let (from, start) = if rng.gen() {
+                (bond.i(), bond.j())
+            } else {
+                (bond.j(), bond.i())
+            };
+
+            match cbmc::growth_order(molecule, start, Some(from)) {
+                Some(order) => order,
+                // The bond is part of a ring
+                None => return false,
+            }
+        };
+
+        let beta = 1.0 / (K_BOLTZMANN * system.temperature());
+        let old = match cbmc::grow(system, self.molid, &order, self.trials, beta, true, rng) {
+            Some(growth) => growth,
+            None => return false,
+        };
+        let new = match cbmc::grow(system, self.molid, &order, self.trials, beta, false, rng) {
+            Some(growth) => growth,
+            None => return false,
+        };
+
+        self.bias = new.bias(beta) - old.bias(beta);
+        self.idxes = order.iter().map(|&(i, _)| i).collect();
+        self.newpos = new.positions;
+        return true;
+    }
+
+    fn cost(&self, system: &System, beta: f64, cache: &mut EnergyCache) -> f64 {
+        let cost = cache.move_particles_cost(system, self.idxes.clone(), &self.newpos);
+        return beta * cost - self.bias;
+    }
+
+    fn apply(&mut self, system: &mut System) {
+        {
+            let positions = system.particles_mut().position;
+            for (&i, newpos) in izip!(&self.idxes, &self.newpos) {
+                positions[i] = *newpos;
+            }
+        }
+        system.wrap_molecule(self.molid)
+    }
+
+    fn restore(&mut self, _: &mut System) {
+        // Nothing to do.
+    }
+
+    fn update_amplitude(&mut self, _: Option<f64>) {
+        // Nothing to do
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use sys::{Particle, UnitCell};
+    use energy::{PairInteraction, Harmonic};
+
+    fn system() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        for i in 0..4 {
+            let position = Vector3D::new(1.5 * i as f64, 0.0, 0.0);
+            system.add_particle(Particle::with_position("C", position));
+        }
+        for i in 0..3 {
+            let _ = system.add_bond(i, i + 1);
+        }
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(2.0, 2.0, 0.0)));
+        system.add_pair_potential("C", "Ar",
+            PairInteraction::new(Box::new(Harmonic{x0: 3.0, k: 0.1}), 10.0)
+        );
+        system.external_temperature(Some(300.0));
+        return system;
+    }
+
+    #[test]
+    fn regrow() {
+        let mut system = system();
+        let moltype = system.molecule_type(0);
+        let mut regrow = Regrow::with_moltype(10, moltype);
+        regrow.setup(&system);
+
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        assert!(regrow.prepare(&mut system, &mut rng));
+        assert!(!regrow.idxes.is_empty());
+
+        regrow.apply(&mut system);
+        for i in 0..3 {
+            assert_ulps_eq!(system.distance(i, i + 1), 1.5, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn single_trial() {
+        // With a single trial position, the move is a plain Metropolis move
+        // and the cost is the energy change
+        let mut system = system();
+        let moltype = system.molecule_type(0);
+        let mut regrow = Regrow::with_moltype(1, moltype);
+        regrow.setup(&system);
+
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+
+        let beta = 1.0 / (K_BOLTZMANN * 300.0);
+        let mut changed = false;
+        for _ in 0..10 {
+            if !regrow.prepare(&mut system, &mut rng) {
+                continue;
+            }
+            assert_ulps_eq!(regrow.bias, 0.0, epsilon = 1e-9);
+
+            let old_energy = system.potential_energy();
+            let cost = regrow.cost(&system, beta, &mut cache);
+            regrow.apply(&mut system);
+            cache.update(&mut system);
+            let delta = system.potential_energy() - old_energy;
+            assert_ulps_eq!(cost, beta * delta, epsilon = 1e-9);
+            if delta != 0.0 {
+                changed = true;
+            }
+        }
+        assert!(changed);
+    }
+}
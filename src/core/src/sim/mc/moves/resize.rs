@@ -8,42 +8,127 @@ use std::f64;
 use std::mem;
 
 use super::MCMove;
+use super::scale_cell;
 
 use types::{Matrix3, One};
 use sys::{System, Configuration, EnergyCache};
+use sim::Schedule;
 
-/// Monte Carlo move that changes the size of the simulation cell
+/// How the cell is changed by the `Resize` move
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    /// Scale all the cell vectors by the same factor
+    Isotropic,
+    /// Scale a single cell vector
+    Anisotropic,
+    /// Change a single component of the cell matrix
+    Triclinic,
+}
+
+/// The upper triangular components of the cell matrix
+const CELL_COMPONENTS: [(usize, usize); 6] = [(0, 0), (1, 1), (2, 2), (0, 1), (0, 2), (1, 2)];
+
+/// Monte Carlo move that changes the size of the simulation cell.
+///
+/// The cell can be resized isotropically, or anisotropically by changing the
+/// length of a single randomly selected cell vector. For triclinic cells, the
+/// off-diagonal components of the cell matrix can also be changed. Frozen
+/// molecules are not moved when the cell changes.
+///
+/// By default, the volume changes are sampled uniformly. They can also be
+/// sampled uniformly in `ln(V)`, which is more efficient for large volume
+/// fluctuations. The changes of the off-diagonal components use a separate
+/// dimensionless strain amplitude, whatever the volume sampling.
 pub struct Resize {
     /// Delta for translation of the box length
     delta: f64,
     /// Sampling range for volume scaling
     range: Range<f64>,
+    /// Maximal strain for the off-diagonal components of the cell matrix
+    strain: f64,
     /// Configuration before applying changes to the simulation cell
     previous: Configuration,
     /// target pressure
     pressure: f64,
+    /// Optional schedule for the target pressure
+    schedule: Option<Schedule>,
     /// largest cutoff diameter of potentials in `Interactions`
     maximum_cutoff: Option<f64>,
+    /// How the cell should be changed
+    mode: Mode,
+    /// Should we sample the logarithm of the volume?
+    log_volume: bool,
 }
 
 impl Resize {
     /// Create a new `Resize` move, with target pressure `pressure` and maximum
     /// displacement of `delta`.
     pub fn new(pressure: f64, delta: f64) -> Resize {
+        Resize::create(pressure, delta, Mode::Isotropic)
+    }
+
+    /// Create a new anisotropic `Resize` move, with target pressure `pressure`
+    /// and maximum displacement of `delta`. Each time this move is used, the
+    /// length of a single randomly selected cell vector is changed.
+    pub fn anisotropic(pressure: f64, delta: f64) -> Resize {
+        Resize::create(pressure, delta, Mode::Anisotropic)
+    }
+
+    /// Create a new triclinic `Resize` move, with target pressure `pressure`,
+    /// maximum displacement of `delta` for the diagonal components and
+    /// maximum strain of `strain` for the off-diagonal components. Each time
+    /// this move is used, a single randomly selected component of the upper
+    /// triangular cell matrix is changed.
+    ///
+    /// The off-diagonal component `i` of the cell vector `j` is changed by
+    /// `s * L_j`, where `L_j` is the length of this vector along `j` and `s`
+    /// is uniformly sampled in `[-strain, strain]`. Since such a change keeps
+    /// the volume and the diagonal constant, the reverse change is proposed
+    /// with the same probability. The cell is kept in its least skewed form.
+    pub fn triclinic(pressure: f64, delta: f64, strain: f64) -> Resize {
+        assert!(strain > 0.0, "strain must be positive in Resize move");
+        let mut resize = Resize::create(pressure, delta, Mode::Triclinic);
+        resize.strain = strain;
+        return resize;
+    }
+
+    /// Factorizing the constructors
+    fn create(pressure: f64, delta: f64, mode: Mode) -> Resize {
         assert!(delta > 0.0, "delta must be positive in Resize move");
         Resize {
             delta: delta,
             range: Range::new(-delta, delta),
+            strain: 0.0,
             previous: Configuration::new(),
             pressure: pressure,
+            schedule: None,
             maximum_cutoff: None,
+            mode: mode,
+            log_volume: false,
         }
     }
+
+    /// Sample the changes in the logarithm of the volume instead of the
+    /// changes in volume if `log_volume` is `true`. In this case, `delta` is
+    /// the maximal change in `ln(V)`.
+    pub fn set_log_volume(&mut self, log_volume: bool) {
+        self.log_volume = log_volume;
+    }
+
+    /// Use the given `schedule` to set the target pressure as a function of
+    /// the simulation step.
+    pub fn set_pressure_schedule(&mut self, schedule: Schedule) {
+        self.schedule = Some(schedule);
+    }
 }
 
 impl MCMove for Resize {
     fn describe(&self) -> &str {
-        "resizing of the cell"
+        match self.mode {
+            Mode::Isotropic => "resizing of the cell",
+            Mode::Anisotropic => "anisotropic resizing of the cell",
+            Mode::Triclinic => "triclinic resizing of the cell",
+        }
     }
 
     fn setup(&mut self, system: &System) {
@@ -57,42 +142,61 @@ impl MCMove for Resize {
         self.maximum_cutoff = system.maximum_cutoff()
     }
 
-    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng>) -> bool {
-        let delta = self.range.sample(rng);
-
-        // Store the previous configuration
-        self.previous = (**system).clone();
+    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng + Send>) -> bool {
+        if let Some(ref schedule) = self.schedule {
+            self.pressure = schedule.value(system.step());
+        }
 
-        let volume = system.volume();
-        let scaling_factor = f64::cbrt((volume + delta) / volume);
-        // Change the simulation cell
-        system.cell.scale_mut(Matrix3::one() * scaling_factor);
-        // Check the radius of the smallest inscribed sphere and compare to the
-        // cut off distance.
-        // Abort simulation when box gets smaller than twice the cutoff radius.
-        if let Some(maximum_cutoff) = self.maximum_cutoff {
-            if system.cell.lengths().iter().any(|&d| 0.5 * d <= maximum_cutoff) {
-                fatal_error!(
-                    "Tried to decrease the cell size but new size
-                    conflicts with the cut off radius. \
-                    Increase the number of particles to get rid of this problem."
-                );
+        // Select the component of the cell to change
+        let component = match self.mode {
+            Mode::Isotropic => None,
+            Mode::Anisotropic => {
+                let direction = rng.gen_range(0, 3);
+                Some((direction, direction))
             }
+            Mode::Triclinic => Some(*rng.choose(&CELL_COMPONENTS).expect("empty components")),
         };
 
-        for (mi, molecule) in self.previous.molecules().iter().enumerate() {
-            // We don't want to change the intramolecular distances so we
-            // compute the translation vector of the center-of-mass (com) of a
-            // molecule and apply it to all its particles. Note that to do
-            // this, the com of a molecule *always* has to reside inside the
-            // simulation cell.
-            let old_com = self.previous.molecule_com(mi);
-            let frac_com = self.previous.cell.fractional(&old_com);
-            let delta_com = system.cell.cartesian(&frac_com) - old_com;
-            for position in &mut system.particles_mut().position[molecule.iter()] {
-                *position += delta_com;
+        let scaling = match component {
+            Some((i, j)) if i != j => {
+                // Change the component `i` of the cell vector `j` by a
+                // dimensionless strain, keeping the cell in its least skewed
+                // form. The volume does not change.
+                let strain = rng.gen_range(-self.strain, self.strain);
+                let cell = system.cell.matrix();
+                let mut new_cell = cell;
+                new_cell[(i, j)] += strain * cell[(j, j)];
+                if f64::abs(new_cell[(i, j)]) > 0.5 * cell[(i, i)] {
+                    return false;
+                }
+                cell.inverse() * new_cell
             }
-        }
+            _ => {
+                // Relative change of the volume
+                let delta = self.range.sample(rng);
+                let factor = if self.log_volume {
+                    f64::exp(delta)
+                } else {
+                    let volume = system.volume();
+                    (volume + delta) / volume
+                };
+                if factor <= 0.0 {
+                    return false;
+                }
+
+                if let Some((i, _)) = component {
+                    // Only scale one of the cell vectors
+                    let mut scaling = Matrix3::one();
+                    scaling[(i, i)] = factor;
+                    scaling
+                } else {
+                    Matrix3::one() * f64::cbrt(factor)
+                }
+            }
+        };
+
+        self.previous = (**system).clone();
+        scale_cell(system, &self.previous, scaling, self.maximum_cutoff);
         true
     }
 
@@ -101,9 +205,19 @@ impl MCMove for Resize {
         let new_volume = system.volume();
         let old_volume = self.previous.cell.volume();
         let delta_volume = new_volume - old_volume;
+        let mobile = (0..system.molecules().len()).filter(|&molid| {
+            !system.is_frozen_molecule(molid)
+        }).count();
+        // Sampling ln(V) instead of V adds one to the Jacobian of the
+        // scaling of the molecules positions
+        let jacobian = if self.log_volume {
+            mobile + 1
+        } else {
+            mobile
+        };
         // Build and return the cost function.
         beta * (delta_energy + self.pressure * delta_volume) -
-        (system.molecules().len() as f64) * f64::ln(new_volume / old_volume)
+        (jacobian as f64) * f64::ln(new_volume / old_volume)
     }
 
     fn apply(&mut self, _: &mut System) {
@@ -119,6 +233,117 @@ impl MCMove for Resize {
         if let Some(s) = scaling_factor {
             self.delta *= s;
             self.range = Range::new(-self.delta, self.delta);
+            self.strain *= s;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use sys::{Particle, UnitCell, CellShape};
+    use types::Vector3D;
+
+    fn system() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(1.0, 1.0, 1.0)));
+        return system;
+    }
+
+    #[test]
+    fn log_volume() {
+        let mut system = system();
+        let mut resize = Resize::new(1e-3, 0.1);
+        resize.set_log_volume(true);
+        resize.setup(&system);
+
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+
+        assert!(resize.prepare(&mut system, &mut rng));
+        let new_volume = system.volume();
+        let ratio = new_volume / 1000.0;
+        assert!(f64::abs(f64::ln(ratio)) < 0.1);
+
+        // Without interactions, the cost only depends on the pressure and
+        // the Jacobian, including the additional term for ln(V) sampling
+        let cost = resize.cost(&system, 2.0, &mut cache);
+        let expected = 2.0 * 1e-3 * (new_volume - 1000.0) - 2.0 * f64::ln(ratio);
+        assert_ulps_eq!(cost, expected, epsilon = 1e-12);
+
+        resize.restore(&mut system);
+        assert_eq!(system.volume(), 1000.0);
+    }
+
+    #[test]
+    fn triclinic() {
+        let mut system = system();
+        let mut resize = Resize::triclinic(1e-3, 50.0, 0.1);
+        resize.setup(&system);
+
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let mut sheared = false;
+        for _ in 0..100 {
+            if !resize.prepare(&mut system, &mut rng) {
+                continue;
+            }
+
+            let cell = system.cell.matrix();
+            for &(i, j) in &[(1, 0), (2, 0), (2, 1)] {
+                assert_eq!(cell[(i, j)], 0.0);
+            }
+            for &(i, j) in &[(0, 1), (0, 2), (1, 2)] {
+                assert!(f64::abs(cell[(i, j)]) <= 0.5 * cell[(i, i)]);
+                if cell[(i, j)] != 0.0 {
+                    sheared = true;
+                    assert_eq!(system.cell.shape(), CellShape::Triclinic);
+                }
+            }
+        }
+        assert!(sheared);
+    }
+
+    #[test]
+    fn triclinic_strain() {
+        // The off-diagonal changes only depend on the strain amplitude, and
+        // not on the volume or on the volume sampling. They keep the volume
+        // constant, so the reverse move is proposed with the same probability
+        // and the cost only contains the energy change.
+        for &(size, log_volume) in &[(10.0, false), (10.0, true), (50.0, false), (50.0, true)] {
+            let mut system = System::with_cell(UnitCell::cubic(size));
+            system.add_particle(Particle::with_position("Ar", Vector3D::new(1.0, 1.0, 1.0)));
+            let mut resize = Resize::triclinic(1e-3, 1e3, 0.05);
+            resize.set_log_volume(log_volume);
+            resize.setup(&system);
+
+            let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+            let mut cache = EnergyCache::new();
+            cache.init(&system);
+
+            let mut sheared = 0;
+            for _ in 0..200 {
+                let old_cell = system.cell.matrix();
+                let volume = system.volume();
+                if !resize.prepare(&mut system, &mut rng) {
+                    continue;
+                }
+
+                let cell = system.cell.matrix();
+                for &(i, j) in &[(0, 1), (0, 2), (1, 2)] {
+                    if cell[(i, j)] == old_cell[(i, j)] {
+                        continue;
+                    }
+                    sheared += 1;
+                    let strain = (cell[(i, j)] - old_cell[(i, j)]) / old_cell[(j, j)];
+                    assert!(f64::abs(strain) <= 0.05);
+                    assert_ulps_eq!(system.volume(), volume, epsilon = 1e-9 * volume);
+                    assert_ulps_eq!(resize.cost(&system, 2.0, &mut cache), 0.0, epsilon = 1e-9);
+                }
+                resize.restore(&mut system);
+            }
+            assert!(sheared > 0);
         }
     }
 }
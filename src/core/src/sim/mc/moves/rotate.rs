@@ -68,7 +68,7 @@ impl MCMove for Rotate {
 
     fn setup(&mut self, _: &System) { }
 
-    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng>) -> bool {
+    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng + Send>) -> bool {
         if let Some(id) = select_molecule(system, self.moltype, rng) {
             self.molid = id;
         } else {
@@ -0,0 +1,212 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Rigid segments of flexible molecules, used by the internal coordinates
+//! moves.
+//!
+//! A segment is a set of particles in a molecule which can be rotated around
+//! an axis passing through one or two other particles, without changing any
+//! bond length in the molecule.
+use std::collections::HashMap;
+
+use sys::{System, Molecule};
+
+use super::cbmc::growth_order;
+
+/// A segment of a molecule, with all the indexes relative to the first
+/// particle of the molecule.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct Segment {
+    /// First particle defining the rotation axis
+    pub first: usize,
+    /// Second particle defining the rotation axis
+    pub second: usize,
+    /// Particles moved with this segment
+    pub moved: Vec<usize>,
+}
+
+/// Get the segments defined by the bonds of `molecule` which are not part of
+/// a ring. For each bond, the moved particles are the ones on the smallest
+/// side of the bond; `second` is the particle of the bond on this side and
+/// `first` the other particle of the bond.
+pub(super) fn bond_segments(molecule: &Molecule) -> Vec<Segment> {
+    let start = molecule.start();
+    let mut bonds = molecule.bonds().iter().cloned().collect::<Vec<_>>();
+    // Sort the bonds to get reproducible simulations
+    bonds.sort();
+
+    let mut segments = Vec::new();
+    for bond in bonds {
+        let (i, j) = (bond.i(), bond.j());
+        let side_j = match growth_order(molecule, j, Some(i)) {
+            Some(order) => order.iter().map(|&(particle, _)| particle).collect::<Vec<_>>(),
+            // Ring bond
+            None => continue,
+        };
+
+        let segment = if 2 * side_j.len() <= molecule.size() {
+            Segment {
+                first: i - start,
+                second: j - start,
+                moved: side_j,
+            }
+        } else {
+            Segment {
+                first: j - start,
+                second: i - start,
+                moved: molecule.iter().filter(|particle| !side_j.contains(particle)).collect(),
+            }
+        };
+        segments.push(local(segment, start));
+    }
+    return segments;
+}
+
+/// Get the segments for crankshaft rotations of `molecule`. For each angle
+/// `i-j-k`, the segment contains `j` and all the particles bonded to it
+/// without going through `i` or `k`. The segment is only used if it is
+/// connected to the rest of the molecule by the `i-j` and `j-k` bonds.
+pub(super) fn crankshaft_segments(molecule: &Molecule) -> Vec<Segment> {
+    let start = molecule.start();
+    let mut neighbors = vec![Vec::new(); molecule.size()];
+    for bond in molecule.bonds() {
+        neighbors[bond.i() - start].push(bond.j() - start);
+        neighbors[bond.j() - start].push(bond.i() - start);
+    }
+    // Sort the neighbors to get reproducible simulations
+    for list in &mut neighbors {
+        list.sort();
+    }
+
+    let mut segments = Vec::new();
+    for j in 0..neighbors.len() {
+        for (n, &i) in neighbors[j].iter().enumerate() {
+            for &k in &neighbors[j][n + 1..] {
+                let moved = connected(&neighbors, j, i, k);
+                let links = moved.iter()
+                                 .flat_map(|&particle| &neighbors[particle])
+                                 .filter(|&&other| other == i || other == k)
+                                 .count();
+                if links == 2 {
+                    segments.push(Segment {
+                        first: i,
+                        second: k,
+                        moved: moved,
+                    });
+                }
+            }
+        }
+    }
+    return segments;
+}
+
+/// Get all the particles connected to `start` through bonds, without going
+/// through the particles `i` and `k`.
+fn connected(neighbors: &[Vec<usize>], start: usize, i: usize, k: usize) -> Vec<usize> {
+    let mut visited = vec![false; neighbors.len()];
+    visited[start] = true;
+    let mut stack = vec![start];
+    while let Some(current) = stack.pop() {
+        for &other in &neighbors[current] {
+            if !visited[other] && other != i && other != k {
+                visited[other] = true;
+                stack.push(other);
+            }
+        }
+    }
+    return visited.iter().enumerate().filter(|&(_, &v)| v).map(|(particle, _)| particle).collect();
+}
+
+/// Convert the moved particles of `segment` to indexes relative to `start`
+fn local(mut segment: Segment, start: usize) -> Segment {
+    for particle in &mut segment.moved {
+        *particle -= start;
+    }
+    segment.moved.sort();
+    return segment;
+}
+
+/// Cache of the segments of all the molecule types in a system
+pub(super) struct SegmentCache {
+    /// Function used to get the segments of a molecule
+    compute: fn(&Molecule) -> Vec<Segment>,
+    /// Segments of each molecule type
+    segments: HashMap<u64, Vec<Segment>>,
+}
+
+impl SegmentCache {
+    /// Create a new cache, using `compute` to get the segments of the
+    /// molecules.
+    pub fn new(compute: fn(&Molecule) -> Vec<Segment>) -> SegmentCache {
+        SegmentCache {
+            compute: compute,
+            segments: HashMap::new(),
+        }
+    }
+
+    /// Get the segments of the molecule at index `molid` in the `system`
+    pub fn get(&mut self, system: &System, molid: usize) -> &[Segment] {
+        let compute = self.compute;
+        self.segments.entry(system.molecule_type(molid))
+                     .or_insert_with(|| compute(system.molecule(molid)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sys::{Particle, UnitCell};
+    use types::Vector3D;
+
+    /// Create a system containing a molecule with `n` particles, with the
+    /// given `bonds`
+    fn molecule(n: usize, bonds: &[(usize, usize)]) -> System {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        for i in 0..n {
+            system.add_particle(Particle::with_position("C", Vector3D::new(i as f64, 0.0, 0.0)));
+        }
+        for &(i, j) in bonds {
+            let _ = system.add_bond(i, j);
+        }
+        return system;
+    }
+
+    #[test]
+    fn bonds() {
+        let system = molecule(5, &[(0, 1), (1, 2), (2, 3), (3, 4)]);
+        let segments = bond_segments(system.molecule(0));
+        assert_eq!(segments, vec![
+            Segment {first: 1, second: 0, moved: vec![0]},
+            Segment {first: 2, second: 1, moved: vec![0, 1]},
+            Segment {first: 2, second: 3, moved: vec![3, 4]},
+            Segment {first: 3, second: 4, moved: vec![4]},
+        ]);
+
+        // Ring bonds are excluded
+        let system = molecule(4, &[(0, 1), (1, 2), (2, 0), (2, 3)]);
+        let segments = bond_segments(system.molecule(0));
+        assert_eq!(segments, vec![
+            Segment {first: 2, second: 3, moved: vec![3]},
+        ]);
+    }
+
+    #[test]
+    fn crankshaft() {
+        let system = molecule(4, &[(0, 1), (1, 2), (2, 3)]);
+        let segments = crankshaft_segments(system.molecule(0));
+        assert_eq!(segments, vec![
+            Segment {first: 0, second: 2, moved: vec![1]},
+            Segment {first: 1, second: 3, moved: vec![2]},
+        ]);
+
+        // Segments connected to the rest of the molecule by other bonds are
+        // excluded
+        let system = molecule(4, &[(0, 1), (1, 2), (1, 3), (3, 0)]);
+        let segments = crankshaft_segments(system.molecule(0));
+        assert_eq!(segments, vec![
+            Segment {first: 1, second: 3, moved: vec![0]},
+            Segment {first: 0, second: 3, moved: vec![1, 2]},
+            Segment {first: 0, second: 1, moved: vec![3]},
+        ]);
+    }
+}
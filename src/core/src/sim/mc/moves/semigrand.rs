@@ -0,0 +1,168 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use rand::Rng;
+
+use std::usize;
+use std::f64;
+
+use super::{MCMove, copy_charge};
+
+use sys::{System, EnergyCache, Particle};
+
+/// Monte Carlo move changing the kind of a randomly chosen particle, to
+/// simulate the semi-grand canonical ensemble.
+///
+/// Each species is associated with a fugacity, and only the ratios between
+/// the fugacities of the different species are relevant. The name, kind,
+/// mass and charge of the particle are changed together. Only particles which
+/// are not bonded to other particles can be changed.
+pub struct SemiGrand {
+    /// The species which can be exchanged, and their fugacities
+    species: Vec<(Particle, f64)>,
+    /// Index of the changed particle
+    index: usize,
+    /// The changed particle before the change
+    previous: Particle,
+    /// Logarithm of the ratio of the new and old fugacities
+    ln_ratio: f64,
+}
+
+impl SemiGrand {
+    /// Create a new `SemiGrand` move, exchanging particles between the given
+    /// `species`. Each species is given by a particle with the corresponding
+    /// name, mass and charge, and a fugacity.
+    pub fn new(species: Vec<(Particle, f64)>) -> SemiGrand {
+        assert!(species.len() >= 2, "SemiGrand move needs at least two species");
+        for (i, &(ref particle, fugacity)) in species.iter().enumerate() {
+            assert!(fugacity > 0.0, "the fugacities must be positive in SemiGrand move");
+            assert!(
+                species[..i].iter().all(|&(ref other, _)| other.name != particle.name),
+                "the species must be different in SemiGrand move"
+            );
+        }
+
+        SemiGrand {
+            species: species,
+            index: usize::MAX,
+            previous: Particle::new(""),
+            ln_ratio: 0.0,
+        }
+    }
+
+    /// Get the index of the species for a particle with the given `name`
+    fn species(&self, name: &str) -> Option<usize> {
+        self.species.iter().position(|&(ref particle, _)| particle.name == name)
+    }
+}
+
+impl MCMove for SemiGrand {
+    fn describe(&self) -> &str {
+        "semi-grand identity change"
+    }
+
+    fn setup(&mut self, system: &System) {
+        for &mut (ref mut particle, _) in &mut self.species {
+            copy_charge(system, &particle.name, &mut particle.charge);
+        }
+    }
+
+    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng + Send>) -> bool {
+        let candidates = (0..system.size()).filter(|&i| {
+            !system.particles().frozen[i] &&
+            system.molecule(system.molid(i)).size() == 1 &&
+            self.species(&system.particles().name[i]).is_some()
+        }).collect::<Vec<_>>();
+
+        if let Some(&i) = rng.choose(&candidates) {
+            self.index = i;
+        } else {
+            warn!("Can not change particle kind: no particle of the given species in the system.");
+            return false;
+        }
+
+        let i = self.index;
+        let old = self.species(&system.particles().name[i]).expect("missing species");
+        let mut new = rng.gen_range(0, self.species.len() - 1);
+        if new >= old {
+            new += 1;
+        }
+        self.ln_ratio = f64::ln(self.species[new].1 / self.species[old].1);
+
+        self.previous = Particle {
+            name: system.particles().name[i].clone(),
+            kind: system.particles().kind[i],
+            charge: system.particles().charge[i],
+            mass: system.particles().mass[i],
+            position: system.particles().position[i],
+            velocity: system.particles().velocity[i],
+            frozen: false,
+        };
+
+        let species = &self.species[new].0;
+        let kind = system.get_kind(&species.name);
+        let particles = system.particles_mut();
+        particles.name[i] = species.name.clone();
+        particles.kind[i] = kind;
+        particles.charge[i] = species.charge;
+        particles.mass[i] = species.mass;
+        return true;
+    }
+
+    fn cost(&self, system: &System, beta: f64, cache: &mut EnergyCache) -> f64 {
+        let cost = cache.change_particle_cost(system, self.index, &self.previous);
+        return beta * cost - self.ln_ratio;
+    }
+
+    fn apply(&mut self, _: &mut System) {
+        // Nothing to do, the particle is already changed
+    }
+
+    fn restore(&mut self, system: &mut System) {
+        let i = self.index;
+        let particles = system.particles_mut();
+        particles.name[i] = self.previous.name.clone();
+        particles.kind[i] = self.previous.kind;
+        particles.charge[i] = self.previous.charge;
+        particles.mass[i] = self.previous.mass;
+    }
+
+    fn update_amplitude(&mut self, _: Option<f64>) {
+        // Nothing to do
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use sys::UnitCell;
+    use types::Vector3D;
+
+    #[test]
+    fn semigrand() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(1.0, 1.0, 1.0)));
+        let kind = system.particles().kind[0];
+
+        let species = vec![(Particle::new("Ar"), 2.0), (Particle::new("Kr"), 1.0)];
+        let mut semigrand = SemiGrand::new(species);
+        semigrand.setup(&system);
+
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+
+        assert!(semigrand.prepare(&mut system, &mut rng));
+        assert_eq!(system.particles().name[0], "Kr");
+        assert_eq!(system.particles().mass[0], Particle::new("Kr").mass);
+
+        // Without interactions, the cost only depends on the fugacities
+        let cost = semigrand.cost(&system, 1.0, &mut cache);
+        assert_ulps_eq!(cost, -f64::ln(0.5));
+
+        semigrand.restore(&mut system);
+        assert_eq!(system.particles().name[0], "Ar");
+        assert_eq!(system.particles().kind[0], kind);
+    }
+}
@@ -0,0 +1,135 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+use rand::Rng;
+
+use std::usize;
+
+use super::MCMove;
+use super::select_molecule;
+
+use types::Vector3D;
+use sys::{System, EnergyCache};
+
+/// Monte Carlo move exchanging the positions of two molecules with different
+/// types.
+///
+/// The center-of-mass of the molecules are exchanged, and the molecules keep
+/// their orientation. In mixtures, this is equivalent to exchanging the
+/// identity of the molecules.
+pub struct Swap {
+    /// Types of the two exchanged molecules
+    moltypes: (u64, u64),
+    /// Indexes of the two exchanged molecules
+    molids: (usize, usize),
+    /// Indexes of the particles in the exchanged molecules
+    idxes: Vec<usize>,
+    /// New positions of the particles in the exchanged molecules
+    newpos: Vec<Vector3D>,
+}
+
+impl Swap {
+    /// Create a new `Swap` move, exchanging the positions of molecules with
+    /// type `first` and molecules with type `second`.
+    pub fn new(first: u64, second: u64) -> Swap {
+        assert_ne!(first, second, "the molecules types must be different in Swap move");
+        Swap {
+            moltypes: (first, second),
+            molids: (usize::MAX, usize::MAX),
+            idxes: Vec::new(),
+            newpos: Vec::new(),
+        }
+    }
+}
+
+impl MCMove for Swap {
+    fn describe(&self) -> &str {
+        "molecular identity swap"
+    }
+
+    fn setup(&mut self, _: &System) {
+        // Nothing to do
+    }
+
+    fn prepare(&mut self, system: &mut System, rng: &mut Box<Rng + Send>) -> bool {
+        let first = select_molecule(system, Some(self.moltypes.0), rng);
+        let second = select_molecule(system, Some(self.moltypes.1), rng);
+        if let (Some(first), Some(second)) = (first, second) {
+            self.molids = (first, second);
+        } else {
+            warn!("Can not swap molecules: no molecule of one of the types in the system.");
+            return false;
+        }
+
+        let (first, second) = self.molids;
+        let delta = system.molecule_com(second) - system.molecule_com(first);
+
+        self.idxes.clear();
+        self.newpos.clear();
+        let positions = system.particles().position;
+        for i in system.molecule(first).iter() {
+            self.idxes.push(i);
+            self.newpos.push(positions[i] + delta);
+        }
+        for i in system.molecule(second).iter() {
+            self.idxes.push(i);
+            self.newpos.push(positions[i] - delta);
+        }
+        return true;
+    }
+
+    fn cost(&self, system: &System, beta: f64, cache: &mut EnergyCache) -> f64 {
+        let cost = cache.move_particles_cost(system, self.idxes.clone(), &self.newpos);
+        return cost * beta;
+    }
+
+    fn apply(&mut self, system: &mut System) {
+        {
+            let positions = system.particles_mut().position;
+            for (&i, newpos) in izip!(&self.idxes, &self.newpos) {
+                positions[i] = *newpos;
+            }
+        }
+        system.wrap_molecule(self.molids.0);
+        system.wrap_molecule(self.molids.1);
+    }
+
+    fn restore(&mut self, _: &mut System) {
+        // Nothing to do.
+    }
+
+    fn update_amplitude(&mut self, _: Option<f64>) {
+        // Nothing to do
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use sys::{Particle, UnitCell};
+
+    #[test]
+    fn swap() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(1.0, 1.0, 1.0)));
+        system.add_particle(Particle::with_position("Kr", Vector3D::new(5.0, 5.0, 5.0)));
+        let argon = system.molecule_type(0);
+        let krypton = system.molecule_type(1);
+
+        let mut swap = Swap::new(argon, krypton);
+        swap.setup(&system);
+
+        let mut rng: Box<Rng + Send> = Box::new(rand::XorShiftRng::new_unseeded());
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+
+        assert!(swap.prepare(&mut system, &mut rng));
+        let beta = 1.0;
+        assert_eq!(swap.cost(&system, beta, &mut cache), 0.0);
+
+        swap.apply(&mut system);
+        assert_eq!(system.particles().position[0], Vector3D::new(5.0, 5.0, 5.0));
+        assert_eq!(system.particles().position[1], Vector3D::new(1.0, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,607 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Flat-histogram Monte Carlo, using the Wang-Landau algorithm
+use rand::{self, Rng, SeedableRng};
+
+use std::io::prelude::*;
+use std::io;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::mem;
+use std::f64;
+
+use consts::K_BOLTZMANN;
+use sys::{System, EnergyCache};
+use sim::{Propagator, TemperatureStrategy};
+use utils;
+
+use super::{MCMove, MoveCounter};
+
+/// Order parameter used to build the histogram in flat-histogram
+/// simulations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OrderParameter {
+    /// Potential energy of the system
+    Energy,
+    /// Volume of the unit cell
+    Volume,
+    /// Number of molecules in the system
+    Molecules,
+}
+
+impl OrderParameter {
+    /// Compute the value of this order parameter for the `system`
+    fn compute(&self, system: &System) -> f64 {
+        match *self {
+            OrderParameter::Energy => system.potential_energy(),
+            OrderParameter::Volume => system.volume(),
+            OrderParameter::Molecules => system.molecules().len() as f64,
+        }
+    }
+
+    /// Convert a `value` of this order parameter to the output unit
+    fn output_value(&self, value: f64) -> f64 {
+        match *self {
+            OrderParameter::Energy => utils::unit_to(value, "kJ/mol"),
+            OrderParameter::Volume => utils::unit_to(value, "A^3"),
+            OrderParameter::Molecules => value,
+        }
+    }
+
+    /// Get the name and unit of this order parameter in output files
+    fn output_name(&self) -> &str {
+        match *self {
+            OrderParameter::Energy => "Energy/(kJ/mol)",
+            OrderParameter::Volume => "Volume/(A^3)",
+            OrderParameter::Molecules => "Molecules",
+        }
+    }
+}
+
+/// Bins of an histogram
+#[derive(Clone, Copy, Debug)]
+struct Bins {
+    /// Lower bound of the first bin
+    min: f64,
+    /// Width of the bins
+    width: f64,
+    /// Number of bins
+    count: usize,
+}
+
+impl Bins {
+    /// Get the index of the bin containing `value`, or `None` if the value is
+    /// outside of the histogram.
+    fn index(&self, value: f64) -> Option<usize> {
+        let index = f64::floor((value - self.min) / self.width);
+        if index >= 0.0 && index < self.count as f64 {
+            Some(index as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Get the value at the center of the bin `i`
+    fn center(&self, i: usize) -> f64 {
+        self.min + (i as f64 + 0.5) * self.width
+    }
+}
+
+/// Wang-Landau flat-histogram Monte Carlo propagator.
+///
+/// This propagator estimates the density of states `g(q)` of an order
+/// parameter `q` (the energy, the volume or the number of molecules) in a
+/// given range, using the same moves as the `MonteCarlo` propagator. Moves are
+/// accepted with a probability proportional to `1/g(q)` (multiplied by the
+/// Boltzmann factor at the simulation temperature when `q` is not the
+/// energy), and each visit of a bin multiplies the estimate of `g(q)` by a
+/// modification factor `f`. Moves leaving the range of the histogram are
+/// always rejected.
+///
+/// When the histogram of visited states is flat, the modification factor is
+/// reduced as `ln(f) -> ln(f) / 2` and the histogram is reset. When `ln(f)`
+/// becomes smaller than its final value, `g(q)` is considered converged, and
+/// the propagator switches to multicanonical sampling: the weights `1/g(q)`
+/// are kept constant, and the histogram of visited states is accumulated.
+/// Multicanonical sampling can also be used from the start with previously
+/// converged weights.
+pub struct WangLandau {
+    /// Boltzmann factor: beta = 1/(kB * T)
+    beta: f64,
+    /// Order parameter of the histogram
+    parameter: OrderParameter,
+    /// Bins of the histogram
+    bins: Bins,
+    /// Logarithm of the density of states in each bin
+    ln_g: Vec<f64>,
+    /// Number of visits of each bin since the last reduction of the
+    /// modification factor
+    histogram: Vec<u64>,
+    /// Logarithm of the modification factor
+    ln_f: f64,
+    /// Final value of the logarithm of the modification factor
+    final_ln_f: f64,
+    /// A histogram is flat if all its values are larger than `flatness` times
+    /// its average value
+    flatness: f64,
+    /// Number of steps between two checks of the flatness of the histogram
+    check_frequency: u64,
+    /// Number of steps performed since the beginning of the simulation
+    steps: u64,
+    /// Are we using fixed weights?
+    multicanonical: bool,
+    /// Current value of the order parameter
+    value: f64,
+    /// Bin containing the current value of the order parameter
+    bin: usize,
+    /// List of possible Monte Carlo moves
+    moves: Vec<(Box<MCMove>, MoveCounter)>,
+    /// Cummulative frequencies of the Monte Carlo moves
+    frequencies: Vec<f64>,
+    /// Number of moves after which an update of a move's amplitude is
+    /// performed.
+    update_frequency: u64,
+    /// Flag checking if the moves frequencies has been converted to
+    /// cumulative frequencies or not yet.
+    initialized: bool,
+    /// Cache for faster energy computation
+    cache: EnergyCache,
+    /// File where the density of states is written
+    output: Option<PathBuf>,
+    /// Random number generator for the simulation
+    rng: Box<Rng + Send>,
+}
+
+impl WangLandau {
+    /// Create a new Wang-Landau propagator at temperature `temperature`,
+    /// estimating the density of states of the `parameter` between `min` and
+    /// `max`, using `bins` bins.
+    pub fn new(temperature: f64, parameter: OrderParameter, min: f64, max: f64, bins: usize) -> WangLandau {
+        assert!(temperature > 0.0, "Wang-Landau temperature must be positive");
+        assert!(max > min, "the maximal value must be larger than the minimal value in Wang-Landau");
+        assert!(bins > 0, "the number of bins must be positive in Wang-Landau");
+
+        let mut rng = Box::new(rand::XorShiftRng::new_unseeded());
+        rng.reseed([2015u32, 42u32, 3u32, 12u32]);
+        WangLandau {
+            beta: 1.0 / (K_BOLTZMANN * temperature),
+            parameter: parameter,
+            bins: Bins {
+                min: min,
+                width: (max - min) / bins as f64,
+                count: bins,
+            },
+            ln_g: vec![0.0; bins],
+            histogram: vec![0; bins],
+            ln_f: 1.0,
+            final_ln_f: 1e-8,
+            flatness: 0.8,
+            check_frequency: 10_000,
+            steps: 0,
+            multicanonical: false,
+            value: 0.0,
+            bin: 0,
+            moves: Vec::new(),
+            frequencies: Vec::new(),
+            update_frequency: 0,
+            initialized: false,
+            cache: EnergyCache::new(),
+            output: None,
+            rng: rng,
+        }
+    }
+
+    /// Create a new propagator for multicanonical sampling at temperature
+    /// `temperature`, using the logarithm of the density of states `ln_g` of
+    /// the `parameter` between `min` and `max`. There is one bin for each
+    /// value in `ln_g`.
+    pub fn multicanonical(temperature: f64, parameter: OrderParameter, min: f64, max: f64, ln_g: Vec<f64>) -> WangLandau {
+        let mut wang_landau = WangLandau::new(temperature, parameter, min, max, ln_g.len());
+        wang_landau.ln_g = ln_g;
+        wang_landau.multicanonical = true;
+        return wang_landau;
+    }
+
+    /// Add the `mcmove` Monte Carlo move to this propagator, with frequency
+    /// `frequency`. All calls to this function should happen before any
+    /// simulation run.
+    pub fn add(&mut self, mcmove: Box<MCMove>, frequency: f64) {
+        self.add_move_counter(mcmove, frequency, MoveCounter::new(None));
+    }
+
+    /// Add the `mcmove` Monte Carlo move to the propagator, with frequency
+    /// `frequency` and target acceptance ratio `target_acceptance`.
+    pub fn add_move_with_acceptance(&mut self, mcmove: Box<MCMove>, frequency: f64, target_acceptance: f64) {
+        self.add_move_counter(mcmove, frequency, MoveCounter::new(Some(target_acceptance)));
+    }
+
+    fn add_move_counter(&mut self, mcmove: Box<MCMove>, frequency: f64, counter: MoveCounter) {
+        if self.initialized {
+            fatal_error!(
+                "Wang-Landau simulation has already been initialized, \
+                we can not add new moves."
+            );
+        }
+        self.moves.push((mcmove, counter));
+        self.frequencies.push(frequency);
+    }
+
+    /// Set the number of times a move has to be called before its amplitude
+    /// is updated. This value is applied to all moves.
+    pub fn set_amplitude_update_frequency(&mut self, frequency: u64) {
+        self.update_frequency = frequency;
+    }
+
+    /// Set the initial value of the logarithm of the modification factor.
+    /// The default value is 1.
+    pub fn set_modification_factor(&mut self, ln_f: f64) {
+        assert!(ln_f > 0.0, "the modification factor must be positive in Wang-Landau");
+        self.ln_f = ln_f;
+    }
+
+    /// Set the final value of the logarithm of the modification factor,
+    /// after which multicanonical sampling starts. The default value is
+    /// `1e-8`.
+    pub fn set_final_modification_factor(&mut self, final_ln_f: f64) {
+        assert!(final_ln_f > 0.0, "the final modification factor must be positive in Wang-Landau");
+        self.final_ln_f = final_ln_f;
+    }
+
+    /// Set the flatness criterion of the histogram: the histogram is flat if
+    /// all its values are larger than `flatness` times its average value. The
+    /// default value is 0.8.
+    pub fn set_flatness(&mut self, flatness: f64) {
+        assert!(0.0 < flatness && flatness < 1.0, "the flatness must be between 0 and 1 in Wang-Landau");
+        self.flatness = flatness;
+    }
+
+    /// Set the number of steps between two checks of the flatness of the
+    /// histogram. The default value is 10 000.
+    pub fn set_check_frequency(&mut self, frequency: u64) {
+        assert!(frequency > 0, "the check frequency must be positive in Wang-Landau");
+        self.check_frequency = frequency;
+    }
+
+    /// Write the logarithm of the density of states and the histogram to
+    /// `filename` each time the modification factor is reduced, and at the
+    /// end of the simulation. The file is replaced if it already exists.
+    pub fn set_output<P: AsRef<Path>>(&mut self, filename: P) -> Result<(), io::Error> {
+        let _ = try!(File::create(filename.as_ref()));
+        self.output = Some(filename.as_ref().to_owned());
+        Ok(())
+    }
+
+    /// Get the logarithm of the density of states in each bin
+    pub fn ln_g(&self) -> &[f64] {
+        &self.ln_g
+    }
+
+    /// Get the number of visits of each bin since the last reduction of the
+    /// modification factor, or since the start of multicanonical sampling
+    pub fn histogram(&self) -> &[u64] {
+        &self.histogram
+    }
+
+    /// Get the current value of the logarithm of the modification factor
+    pub fn modification_factor(&self) -> f64 {
+        self.ln_f
+    }
+
+    /// Check if this propagator is performing multicanonical sampling
+    pub fn is_multicanonical(&self) -> bool {
+        self.multicanonical
+    }
+
+    fn normalize_frequencies(&mut self) {
+        assert_eq!(self.frequencies.len(), self.moves.len());
+        if self.frequencies.is_empty() {
+            warn!(
+                "No move in the Wang-Landau simulation, \
+                did you forget to specify them?"
+            );
+            return;
+        }
+
+        self.initialized = true;
+        let sum = self.frequencies.iter().fold(0.0, |sum, &f| sum + f);
+        for frequency in &mut self.frequencies {
+            *frequency /= sum;
+        }
+        for i in 1..self.frequencies.len() {
+            self.frequencies[i] += self.frequencies[i - 1];
+        }
+        let last = self.frequencies.len() - 1;
+        self.frequencies[last] = 1.0;
+    }
+
+    /// Attempt the move at index `i`, using the current weights.
+    fn attempt(&mut self, i: usize, system: &mut System) {
+        let mcmove = &mut self.moves[i];
+        trace!("Selected move is '{}'", mcmove.0.describe());
+
+        // Keep a copy of the configuration to be able to compute the order
+        // parameter in the new configuration
+        let mut previous = if self.parameter == OrderParameter::Energy {
+            None
+        } else {
+            Some((**system).clone())
+        };
+
+        if !mcmove.0.prepare(system, &mut self.rng) {
+            trace!("    --> Can not perform the move");
+            return;
+        }
+        mcmove.1.ncalled += 1;
+        mcmove.1.nattempted += 1;
+
+        // Do not use a stale energy change from a previous rejected move
+        self.cache.reset_last_cost();
+        let cost = mcmove.0.cost(system, self.beta, &mut self.cache);
+        let (cost, value) = if self.parameter == OrderParameter::Energy {
+            let delta = match self.cache.last_cost() {
+                Some(delta) => delta,
+                None => fatal_error!(
+                    "The move '{}' can not be used with energy Wang-Landau simulations",
+                    mcmove.0.describe()
+                ),
+            };
+            // Remove the Boltzmann factor from the cost
+            (cost - self.beta * delta, self.value + delta)
+        } else {
+            mcmove.0.apply(system);
+            (cost, self.parameter.compute(system))
+        };
+
+        let bin = self.bins.index(value);
+        let accepted = if let Some(bin) = bin {
+            let cost = cost + self.ln_g[bin] - self.ln_g[self.bin];
+            cost <= 0.0 || self.rng.next_f64() < f64::exp(-cost)
+        } else {
+            false
+        };
+
+        if accepted {
+            trace!("    --> Move was accepted");
+            if previous.is_none() {
+                mcmove.0.apply(system);
+            }
+            self.cache.update(system);
+            self.value = value;
+            self.bin = bin.expect("accepted move outside of the histogram");
+            mcmove.1.naccepted += 1;
+        } else {
+            trace!("    --> Move was rejected");
+            if let Some(ref mut previous) = previous {
+                mem::swap(&mut **system, previous);
+            } else {
+                mcmove.0.restore(system);
+            }
+        }
+
+        if mcmove.1.nattempted == self.update_frequency {
+            mcmove.0.update_amplitude(mcmove.1.compute_scaling_factor());
+            mcmove.1.naccepted = 0;
+            mcmove.1.nattempted = 0;
+        }
+    }
+
+    /// Reduce the modification factor if the histogram is flat
+    fn check_flatness(&mut self) {
+        let min = *self.histogram.iter().min().expect("empty histogram");
+        let mean = self.histogram.iter().sum::<u64>() as f64 / self.histogram.len() as f64;
+        if min == 0 || (min as f64) < self.flatness * mean {
+            return;
+        }
+
+        self.ln_f /= 2.0;
+        info!("Wang-Landau histogram is flat, new modification factor is ln(f) = {}", self.ln_f);
+        self.write_output();
+        for count in &mut self.histogram {
+            *count = 0;
+        }
+
+        if self.ln_f < self.final_ln_f {
+            info!("Wang-Landau simulation converged, starting multicanonical sampling");
+            self.multicanonical = true;
+        }
+    }
+
+    /// Write the density of states to the output file, if any
+    fn write_output(&self) {
+        if let Some(ref path) = self.output {
+            if let Err(err) = self.write_to(path) {
+                error!("Could not write to file '{}': {}", path.display(), err);
+            }
+        }
+    }
+
+    fn write_to(&self, path: &Path) -> Result<(), io::Error> {
+        let mut file = try!(File::create(path));
+        if self.multicanonical {
+            try!(writeln!(file, "# Multicanonical sampling"));
+        } else {
+            try!(writeln!(file, "# Wang-Landau density of states with ln(f) = {}", self.ln_f));
+        }
+        try!(writeln!(file, "# {} ln(g) Histogram", self.parameter.output_name()));
+
+        let shift = self.ln_g.iter().cloned().fold(f64::INFINITY, f64::min);
+        for (i, (ln_g, count)) in self.ln_g.iter().zip(&self.histogram).enumerate() {
+            let value = self.parameter.output_value(self.bins.center(i));
+            try!(writeln!(file, "{} {} {}", value, ln_g - shift, count));
+        }
+        Ok(())
+    }
+}
+
+impl Propagator for WangLandau {
+    fn temperature_strategy(&self) -> TemperatureStrategy {
+        TemperatureStrategy::External(1.0 / (self.beta * K_BOLTZMANN))
+    }
+
+    fn setup(&mut self, system: &System) {
+        if !self.initialized {
+            self.normalize_frequencies();
+        }
+        self.cache.init(system);
+        for mc_move in &mut self.moves {
+            mc_move.0.setup(system)
+        }
+
+        self.value = self.parameter.compute(system);
+        self.bin = match self.bins.index(self.value) {
+            Some(bin) => bin,
+            None => fatal_error!(
+                "The initial value of the order parameter ({}) is outside \
+                of the histogram in Wang-Landau simulation",
+                self.parameter.output_value(self.value)
+            ),
+        };
+    }
+
+    fn propagate(&mut self, system: &mut System) {
+        if !self.moves.is_empty() {
+            let probability = self.rng.next_f64();
+            let (i, _) = self.frequencies.iter()
+                                         .enumerate()
+                                         .find(|&(_, f)| probability <= *f)
+                                         .expect("Could not find a move in Wang-Landau moves list");
+            self.attempt(i, system);
+        }
+
+        // Update the density of states and the histogram with the current
+        // state, even if the move was rejected
+        if !self.multicanonical {
+            self.ln_g[self.bin] += self.ln_f;
+        }
+        self.histogram[self.bin] += 1;
+
+        self.steps += 1;
+        if !self.multicanonical && self.steps % self.check_frequency == 0 {
+            self.check_flatness();
+        }
+    }
+
+    fn finish(&mut self, _: &System) {
+        info!("Wang-Landau simulation summary");
+        if self.multicanonical {
+            info!("  Multicanonical sampling with converged weights");
+        } else {
+            info!("  Modification factor ln(f) = {}", self.ln_f);
+        }
+        for mc_move in &self.moves {
+            info!("Statistics for move: {}", mc_move.0.describe());
+            info!("  Calls     : {}", mc_move.1.ncalled);
+            info!("  Acceptance: {} %", mc_move.1.naccepted as f64 /
+                mc_move.1.nattempted as f64 * 100.0);
+        }
+        self.write_output();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sys::{Particle, UnitCell};
+    use sim::mc::{Translate, Resize};
+    use types::Vector3D;
+    use rand::Rng;
+
+    /// A move which does not use the energy cache
+    struct NoCache;
+
+    impl MCMove for NoCache {
+        fn describe(&self) -> &str { "no cache" }
+        fn setup(&mut self, _: &System) {}
+        fn prepare(&mut self, _: &mut System, _: &mut Box<Rng + Send>) -> bool { true }
+        fn cost(&self, _: &System, _: f64, _: &mut EnergyCache) -> f64 { 0.0 }
+        fn apply(&mut self, _: &mut System) {}
+        fn restore(&mut self, _: &mut System) {}
+        fn update_amplitude(&mut self, _: Option<f64>) {}
+    }
+
+    fn system() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(1.0, 1.0, 1.0)));
+        return system;
+    }
+
+    #[test]
+    fn bins() {
+        let bins = Bins {min: -1.0, width: 0.5, count: 4};
+        assert_eq!(bins.index(-1.5), None);
+        assert_eq!(bins.index(-1.0), Some(0));
+        assert_eq!(bins.index(0.2), Some(2));
+        assert_eq!(bins.index(1.0), None);
+        assert_eq!(bins.index(f64::NAN), None);
+        assert_eq!(bins.center(1), -0.25);
+    }
+
+    #[test]
+    fn energy() {
+        let mut system = system();
+        let mut wang_landau = WangLandau::new(300.0, OrderParameter::Energy, -1.0, 1.0, 2);
+        wang_landau.add(Box::new(Translate::new(1.0)), 1.0);
+        wang_landau.set_check_frequency(10);
+
+        wang_landau.setup(&system);
+        for _ in 0..9 {
+            wang_landau.propagate(&mut system);
+        }
+        // Without interactions, the energy is always zero
+        assert_eq!(wang_landau.ln_g(), &[0.0, 9.0]);
+        assert_eq!(wang_landau.histogram(), &[0, 9]);
+
+        // The histogram is never flat
+        wang_landau.propagate(&mut system);
+        assert_eq!(wang_landau.modification_factor(), 1.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn energy_without_cache() {
+        let mut system = system();
+        let mut wang_landau = WangLandau::new(300.0, OrderParameter::Energy, -1.0, 1.0, 2);
+        wang_landau.add(Box::new(NoCache), 1.0);
+        wang_landau.setup(&system);
+
+        // Energy change left in the cache by a rejected move
+        let _ = wang_landau.cache.move_particles_cost(&system, vec![0], &[Vector3D::new(0.0, 0.0, 0.0)]);
+        assert!(wang_landau.cache.last_cost().is_some());
+
+        wang_landau.propagate(&mut system);
+    }
+
+    #[test]
+    fn volume() {
+        let mut system = system();
+        let mut wang_landau = WangLandau::new(300.0, OrderParameter::Volume, 900.0, 1100.0, 4);
+        wang_landau.add(Box::new(Resize::new(0.0, 50.0)), 1.0);
+        wang_landau.set_check_frequency(10);
+
+        wang_landau.setup(&system);
+        for _ in 0..1000 {
+            wang_landau.propagate(&mut system);
+            assert!(system.volume() >= 900.0 && system.volume() < 1100.0);
+        }
+        // The histogram was flat at least once
+        assert!(wang_landau.modification_factor() < 1.0);
+    }
+
+    #[test]
+    fn multicanonical() {
+        let mut system = system();
+        let ln_g = vec![0.0, 3.0];
+        let mut wang_landau = WangLandau::multicanonical(300.0, OrderParameter::Energy, -1.0, 1.0, ln_g);
+        wang_landau.add(Box::new(Translate::new(1.0)), 1.0);
+        assert!(wang_landau.is_multicanonical());
+
+        wang_landau.setup(&system);
+        for _ in 0..10 {
+            wang_landau.propagate(&mut system);
+        }
+        // The weights are not modified
+        assert_eq!(wang_landau.ln_g(), &[0.0, 3.0]);
+        assert_eq!(wang_landau.histogram(), &[0, 10]);
+    }
+}
@@ -0,0 +1,374 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Brownian dynamics
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+use rand::distributions::{Normal, Sample};
+use rand::{Isaac64Rng, SeedableRng};
+
+use consts::K_BOLTZMANN;
+use types::{Vector3D, Matrix3, Array2, One, Zero};
+use sys::System;
+use sys::zip_particle::*;
+use sim::TemperatureStrategy;
+
+use super::Integrator;
+
+/// Brownian dynamics integrator, propagating the overdamped Langevin equations
+/// of motion with the Ermak-McCammon algorithm:
+///
+/// $$ \vec r_i(t + \Delta t) = \vec r_i(t) + \frac{D_i}{k_B T} \vec f_i \Delta t + \sqrt{2 D_i \Delta t} \ \vec \xi_i $$
+///
+/// where $D_i$ is the diffusion coefficient of the particle $i$, and $\vec
+/// \xi_i$ a random gaussian vector. The diffusion coefficients are set for
+/// each particle name with `BrownianDynamics::set_diffusion`.
+///
+/// Hydrodynamic interactions between the particles can be included using the
+/// Rotne-Prager-Yamakawa diffusion tensor. This requires a Cholesky
+/// decomposition of a $3N \times 3N$ matrix at every step, and should only be
+/// used for small systems. The periodic images are handled with the minimal
+/// image convention.
+///
+/// The particles velocities are not used by this integrator, and the
+/// temperature of the system is the temperature of the implicit solvent.
+pub struct BrownianDynamics {
+    /// Timestep for the integrator
+    timestep: f64,
+    /// Temperature of the implicit solvent
+    temperature: f64,
+    /// Diffusion coefficients associated with the particles names
+    coefficients: BTreeMap<String, f64>,
+    /// Diffusion coefficients for all the particles in the system
+    diffusion: Vec<f64>,
+    /// Viscosity of the solvent, if the hydrodynamic interactions are used
+    viscosity: Option<f64>,
+    /// Random number generator for the stochastic displacements
+    rng: Isaac64Rng,
+    /// Normal distribution used for the stochastic displacements
+    normal: Normal,
+}
+
+impl BrownianDynamics {
+    /// Create a new integrator with a timestep of `timestep`, for particles
+    /// in an implicit solvent at the given `temperature`.
+    pub fn new(timestep: f64, temperature: f64) -> BrownianDynamics {
+        assert!(temperature >= 0.0, "Brownian dynamics temperature must be positive");
+        BrownianDynamics {
+            timestep: timestep,
+            temperature: temperature,
+            coefficients: BTreeMap::new(),
+            diffusion: Vec::new(),
+            viscosity: None,
+            rng: Isaac64Rng::from_seed(&[42]),
+            normal: Normal::new(0.0, 1.0),
+        }
+    }
+
+    /// Set the diffusion coefficient of all the particles with the given
+    /// `name` to `diffusion`.
+    pub fn set_diffusion(&mut self, name: &str, diffusion: f64) {
+        assert!(diffusion > 0.0, "Diffusion coefficients must be positive in Brownian dynamics");
+        let _ = self.coefficients.insert(String::from(name), diffusion);
+    }
+
+    /// Use hydrodynamic interactions between the particles, with a solvent of
+    /// the given `viscosity`. The hydrodynamic radius of the particles is
+    /// computed from their diffusion coefficient using the Stokes-Einstein
+    /// relation.
+    pub fn enable_hydrodynamics(&mut self, viscosity: f64) {
+        assert!(viscosity > 0.0, "The viscosity must be positive in Brownian dynamics");
+        self.viscosity = Some(viscosity);
+    }
+
+    /// Set the seed of the random number generator. The default seed is 42.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng.reseed(&[seed]);
+    }
+
+    /// Get a random gaussian vector
+    fn random_vector(&mut self) -> Vector3D {
+        Vector3D::new(
+            self.normal.sample(&mut self.rng),
+            self.normal.sample(&mut self.rng),
+            self.normal.sample(&mut self.rng),
+        )
+    }
+
+    /// Compute the particles displacements without hydrodynamic interactions
+    fn displacements(&mut self, forces: &[Vector3D], kt: f64) -> Vec<Vector3D> {
+        let dt = self.timestep;
+        let mut displacements = Vec::with_capacity(forces.len());
+        for (i, force) in forces.iter().enumerate() {
+            let diffusion = self.diffusion[i];
+            let noise = self.random_vector();
+            displacements.push(diffusion * dt / kt * force + f64::sqrt(2.0 * diffusion * dt) * noise);
+        }
+        return displacements;
+    }
+
+    /// Compute the particles displacements with hydrodynamic interactions
+    /// in a solvent of the given `viscosity`.
+    fn hydrodynamic_displacements(
+        &mut self,
+        system: &System,
+        forces: &[Vector3D],
+        viscosity: f64,
+        kt: f64
+    ) -> Vec<Vector3D> {
+        let dt = self.timestep;
+        let natoms = system.size();
+        let size = 3 * natoms;
+
+        let radii = self.diffusion.iter()
+                                  .map(|diffusion| kt / (6.0 * PI * viscosity * diffusion))
+                                  .collect::<Vec<_>>();
+        let mut tensor = Array2::zeros((size, size));
+        for i in 0..natoms {
+            for k in 0..3 {
+                tensor[(3 * i + k, 3 * i + k)] = self.diffusion[i];
+            }
+
+            for j in (i + 1)..natoms {
+                let rij = system.nearest_image(i, j);
+                let block = rotne_prager(rij, radii[i], radii[j], kt / viscosity);
+                for k in 0..3 {
+                    for l in 0..3 {
+                        tensor[(3 * i + k, 3 * j + l)] = block[(k, l)];
+                        tensor[(3 * j + l, 3 * i + k)] = block[(k, l)];
+                    }
+                }
+            }
+        }
+
+        let mut displacements = vec![Vector3D::zero(); natoms];
+        for a in 0..size {
+            let mut drift = 0.0;
+            for b in 0..size {
+                drift += tensor[(a, b)] * forces[b / 3][b % 3];
+            }
+            displacements[a / 3][a % 3] = drift * dt / kt;
+        }
+
+        if !cholesky(&mut tensor) {
+            fatal_error!(
+                "The Rotne-Prager-Yamakawa tensor is not positive definite \
+                in Brownian dynamics"
+            );
+        }
+
+        let noise = (0..natoms).map(|_| self.random_vector()).collect::<Vec<_>>();
+        let scaling = f64::sqrt(2.0 * dt);
+        for a in 0..size {
+            let mut random = 0.0;
+            for b in 0..(a + 1) {
+                random += tensor[(a, b)] * noise[b / 3][b % 3];
+            }
+            displacements[a / 3][a % 3] += scaling * random;
+        }
+        return displacements;
+    }
+}
+
+impl Integrator for BrownianDynamics {
+    fn setup(&mut self, system: &System) {
+        if !system.constraints().is_empty() {
+            warn!("Distance constraints are ignored by Brownian dynamics");
+        }
+
+        self.diffusion.clear();
+        for name in system.particles().name {
+            match self.coefficients.get(name) {
+                Some(&diffusion) => self.diffusion.push(diffusion),
+                None => fatal_error!(
+                    "Missing diffusion coefficient for '{}' particles in Brownian dynamics",
+                    name
+                ),
+            }
+        }
+    }
+
+    fn integrate(&mut self, system: &mut System) {
+        let kt = K_BOLTZMANN * self.temperature;
+        let forces = system.forces();
+        let displacements = match self.viscosity {
+            Some(viscosity) => self.hydrodynamic_displacements(system, &forces, viscosity, kt),
+            None => self.displacements(&forces, kt),
+        };
+
+        for (position, displacement, &frozen) in system.particles_mut().zip_mut(
+            (&mut Position, &displacements, &Frozen)
+        ) {
+            if frozen {
+                continue;
+            }
+            *position += displacement;
+        }
+    }
+
+    fn temperature_strategy(&self) -> TemperatureStrategy {
+        TemperatureStrategy::External(self.temperature)
+    }
+}
+
+/// Get the Rotne-Prager-Yamakawa diffusion tensor coupling two particles with
+/// hydrodynamic radii `ai` and `aj`, separated by `rij`. `factor` is the
+/// ratio of the thermal energy and the solvent viscosity.
+///
+/// For overlapping particles, the regularized expression for equal radii is
+/// used with the mean radius of the particles.
+fn rotne_prager(rij: Vector3D, ai: f64, aj: f64, factor: f64) -> Matrix3 {
+    let r = rij.norm();
+    let outer = rij.tensorial(&rij);
+    if r > ai + aj {
+        let s = (ai * ai + aj * aj) / (r * r);
+        let prefactor = factor / (8.0 * PI * r);
+        prefactor * ((1.0 + s / 3.0) * Matrix3::one() + (1.0 - s) / (r * r) * outer)
+    } else {
+        let a = 0.5 * (ai + aj);
+        let prefactor = factor / (6.0 * PI * a);
+        let mut tensor = (1.0 - 9.0 * r / (32.0 * a)) * Matrix3::one();
+        if r > 0.0 {
+            tensor += 3.0 / (32.0 * a * r) * outer;
+        }
+        prefactor * tensor
+    }
+}
+
+/// Compute the Cholesky decomposition of the symmetric `matrix` in place. The
+/// lower triangular part of the matrix is replaced by the decomposition, and
+/// the upper triangular part is not used. This function returns `false` if
+/// the matrix is not positive definite.
+fn cholesky(matrix: &mut Array2<f64>) -> bool {
+    let size = matrix.shape()[0];
+    for j in 0..size {
+        let mut diagonal = matrix[(j, j)];
+        for k in 0..j {
+            diagonal -= matrix[(j, k)] * matrix[(j, k)];
+        }
+        if diagonal <= 0.0 {
+            return false;
+        }
+        let diagonal = f64::sqrt(diagonal);
+        matrix[(j, j)] = diagonal;
+
+        for i in (j + 1)..size {
+            let mut value = matrix[(i, j)];
+            for k in 0..j {
+                value -= matrix[(i, k)] * matrix[(j, k)];
+            }
+            matrix[(i, j)] = value / diagonal;
+        }
+    }
+    return true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sys::{Particle, UnitCell};
+    use utils::unit_from;
+
+    fn testing_system() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(100.0));
+        for i in 0..10 {
+            for j in 0..10 {
+                for k in 0..10 {
+                    let position = Vector3D::new(i as f64, j as f64, k as f64) * 10.0;
+                    system.add_particle(Particle::with_position("He", position));
+                }
+            }
+        }
+        return system;
+    }
+
+    #[test]
+    fn free_diffusion() {
+        let mut system = testing_system();
+        let initial = system.particles().position.to_vec();
+
+        let dt = unit_from(1.0, "ps");
+        let diffusion = unit_from(0.1, "A^2/ps");
+        let mut integrator = BrownianDynamics::new(dt, 300.0);
+        integrator.set_diffusion("He", diffusion);
+        integrator.setup(&system);
+        integrator.integrate(&mut system);
+
+        let msd = system.particles().position.iter().zip(&initial)
+                                             .map(|(position, initial)| (position - initial).norm2())
+                                             .sum::<f64>() / system.size() as f64;
+        assert_ulps_eq!(msd / (6.0 * diffusion * dt), 1.0, epsilon=0.1);
+        match integrator.temperature_strategy() {
+            TemperatureStrategy::External(temperature) => assert_eq!(temperature, 300.0),
+            _ => panic!("Brownian dynamics should use an external temperature"),
+        }
+    }
+
+    #[test]
+    fn seed() {
+        let mut first = testing_system();
+        let mut second = testing_system();
+
+        let mut integrator = BrownianDynamics::new(1.0, 300.0);
+        integrator.set_diffusion("He", 1e-3);
+        integrator.seed(7);
+        integrator.setup(&first);
+        integrator.integrate(&mut first);
+
+        let mut integrator = BrownianDynamics::new(1.0, 300.0);
+        integrator.set_diffusion("He", 1e-3);
+        integrator.seed(7);
+        integrator.setup(&second);
+        integrator.integrate(&mut second);
+
+        assert_eq!(first.particles().position, second.particles().position);
+    }
+
+    #[test]
+    #[should_panic]
+    fn missing_diffusion() {
+        let system = testing_system();
+        let mut integrator = BrownianDynamics::new(1.0, 300.0);
+        integrator.set_diffusion("Ar", 1e-3);
+        integrator.setup(&system);
+    }
+
+    #[test]
+    fn rotne_prager_tensor() {
+        // The tensor is continuous when the particles start to overlap
+        let rij = Vector3D::new(0.0, 0.0, 2.0);
+        let outside = rotne_prager(rij * (1.0 + 1e-12), 1.0, 1.0, 1.0);
+        let inside = rotne_prager(rij * (1.0 - 1e-12), 1.0, 1.0, 1.0);
+        for i in 0..3 {
+            for j in 0..3 {
+                assert_ulps_eq!(outside[(i, j)], inside[(i, j)], epsilon=1e-10);
+            }
+        }
+
+        // The self diffusion is recovered for fully overlapping particles
+        let tensor = rotne_prager(Vector3D::zero(), 1.0, 1.0, 1.0);
+        assert_ulps_eq!(tensor[(0, 0)], 1.0 / (6.0 * PI));
+        assert_ulps_eq!(tensor[(0, 1)], 0.0);
+    }
+
+    #[test]
+    fn hydrodynamics() {
+        let mut system = System::with_cell(UnitCell::cubic(100.0));
+        system.add_particle(Particle::with_position("He", Vector3D::zero()));
+        system.add_particle(Particle::with_position("He", Vector3D::new(3.0, 0.0, 0.0)));
+        system.add_particle(Particle::with_position("He", Vector3D::new(0.0, 4.0, 0.0)));
+
+        let mut integrator = BrownianDynamics::new(1.0, 300.0);
+        integrator.set_diffusion("He", 1e-3);
+        integrator.enable_hydrodynamics(unit_from(0.89e9, "Pa*ps"));
+        integrator.setup(&system);
+        for _ in 0..10 {
+            integrator.integrate(&mut system);
+        }
+
+        for position in system.particles().position {
+            assert!(position.norm() < 10.0);
+        }
+    }
+}
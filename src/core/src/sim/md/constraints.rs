@@ -0,0 +1,621 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Algorithms enforcing holonomic constraints during molecular dynamics.
+use std::cmp::{min, max};
+use std::collections::{HashMap, HashSet};
+
+use types::{Matrix3, Vector3D, Zero};
+use sys::{System, DistanceConstraint, UnitCell};
+
+/// Iterative solver for the distance constraints in a system, using the SHAKE
+/// algorithm [1] for the positions and the RATTLE algorithm [2] for the
+/// velocities.
+///
+/// All the constraints are satisfied up to a relative `tolerance` on the
+/// distances. If the constraints can not be satisfied in `max_iterations`
+/// iterations, the simulation is aborted.
+///
+/// Rigid three-particles molecules (such as rigid water models) are
+/// automatically detected, and handled with the analytical SETTLE algorithm
+/// [3] instead of the iterative one.
+///
+/// [1] Ryckaert, J.-P., Ciccotti, G. & Berendsen, H. J. C. J. Comput. Phys.
+/// 23, 327–341 (1977). doi:10.1016/0021-9991(77)90098-5
+///
+/// [2] Andersen, H. C. J. Comput. Phys. 52, 24–34 (1983).
+/// doi:10.1016/0021-9991(83)90014-1
+///
+/// [3] Miyamoto, S. & Kollman, P. A. J. Comput. Chem. 13, 952–962 (1992).
+/// doi:10.1002/jcc.540130805
+#[derive(Clone)]
+pub struct Shake {
+    /// Relative tolerance on the constrained distances
+    tolerance: f64,
+    /// Maximal number of iterations
+    max_iterations: u64,
+    /// Constraints to enforce iteratively, cached at setup
+    constraints: Vec<DistanceConstraint>,
+    /// Rigid three-particles molecules to enforce with SETTLE
+    settle: Vec<Settle>,
+}
+
+impl Shake {
+    /// Create a new constraints solver, with a relative tolerance of 1e-8 and
+    /// at most 500 iterations.
+    pub fn new() -> Shake {
+        Shake {
+            tolerance: 1e-8,
+            max_iterations: 500,
+            constraints: Vec::new(),
+            settle: Vec::new(),
+        }
+    }
+
+    /// Set the relative tolerance on the constrained distances.
+    pub fn set_tolerance(&mut self, tolerance: f64) {
+        assert!(tolerance > 0.0, "The tolerance must be positive in SHAKE");
+        self.tolerance = tolerance;
+    }
+
+    /// Set the maximal number of iterations used to satisfy the constraints.
+    pub fn set_max_iterations(&mut self, max_iterations: u64) {
+        assert!(max_iterations > 0, "The number of iterations must be positive in SHAKE");
+        self.max_iterations = max_iterations;
+    }
+
+    /// Get the constraints from the `system`. This function must be called
+    /// before any call to `Shake::positions` or `Shake::velocities`.
+    pub fn setup(&mut self, system: &System) {
+        // Constraints between two frozen particles are always satisfied
+        let frozen = system.particles().frozen;
+        let mut distances = HashMap::new();
+        for constraint in system.constraints() {
+            if frozen[constraint.i()] && frozen[constraint.j()] {
+                continue;
+            }
+            let _ = distances.insert((constraint.i(), constraint.j()), constraint.distance());
+        }
+
+        let mut settle = Vec::new();
+        let mut cache = HashMap::new();
+        for (molid, molecule) in system.molecules().iter().enumerate() {
+            if molecule.size() != 3 {
+                continue;
+            }
+            // The position of the apex particle in the molecule only depends
+            // on the molecule type
+            let moltype = system.molecule_type(molid);
+            let apex = *cache.entry(moltype).or_insert_with(
+                || Settle::find_apex(system, molecule.start(), &distances)
+            );
+            if let Some(apex) = apex {
+                if let Some(molecule) = Settle::new(system, molecule.start(), apex, &distances) {
+                    settle.push(molecule);
+                }
+            }
+        }
+
+        for molecule in &settle {
+            for &(i, j) in &molecule.pairs() {
+                let _ = distances.remove(&(min(i, j), max(i, j)));
+            }
+        }
+
+        // Molecules sharing particles with other constraints or containing
+        // frozen particles can not be handled by SETTLE
+        let mut constrained = HashSet::new();
+        for &(i, j) in distances.keys() {
+            let _ = constrained.insert(i);
+            let _ = constrained.insert(j);
+        }
+        let excluded = |i: usize| constrained.contains(&i) || frozen[i];
+        self.settle.clear();
+        for molecule in settle {
+            if molecule.pairs().iter().any(|&(i, j)| excluded(i) || excluded(j)) {
+                for &(i, j) in &molecule.pairs() {
+                    let _ = distances.insert((min(i, j), max(i, j)), 0.0);
+                }
+            } else {
+                self.settle.push(molecule);
+            }
+        }
+
+        self.constraints = system.constraints().iter()
+                                 .filter(|c| distances.contains_key(&(c.i(), c.j())))
+                                 .cloned()
+                                 .collect();
+    }
+
+    /// Check if there is any constraint to enforce.
+    pub fn is_empty(&self) -> bool {
+        self.constraints.is_empty() && self.settle.is_empty()
+    }
+
+    /// Move the particles in `system` to satisfy the constraints, using the
+    /// SHAKE algorithm. The `reference` positions are the positions at the
+    /// previous step, which must satisfy the constraints.
+    ///
+    /// This function returns the virial of the constraints forces, assuming
+    /// that the displacement of a particle `i` was produced by the force
+    /// $m_i \Delta r_i / \Delta t^2$.
+    pub fn positions(&self, system: &mut System, reference: &[Vector3D], dt: f64) -> Matrix3 {
+        let cell = system.cell;
+        let particles = system.particles_mut();
+        let masses = particles.mass;
+        let frozen = particles.frozen;
+        let positions = particles.position;
+
+        let mut virial = Matrix3::zero();
+        for molecule in &self.settle {
+            virial += molecule.positions(&cell, positions, reference) / (dt * dt);
+        }
+
+        for _ in 0..self.max_iterations {
+            let mut converged = true;
+            for constraint in &self.constraints {
+                let (i, j) = (constraint.i(), constraint.j());
+                let mut rij = positions[i] - positions[j];
+                cell.vector_image(&mut rij);
+
+                let d2 = constraint.distance() * constraint.distance();
+                let delta = d2 - rij.norm2();
+                if f64::abs(delta) < 2.0 * self.tolerance * d2 {
+                    continue;
+                }
+                converged = false;
+
+                let mut old_rij = reference[i] - reference[j];
+                cell.vector_image(&mut old_rij);
+                let dot = rij * old_rij;
+                if dot < 1e-6 * d2 {
+                    fatal_error!(
+                        "Can not satisfy the constraint between particles {} and {}, \
+                        try reducing the timestep", i, j
+                    );
+                }
+
+                let (inv_mi, inv_mj) = (inverse_mass(masses, frozen, i), inverse_mass(masses, frozen, j));
+                let g = delta / (2.0 * (inv_mi + inv_mj) * dot);
+                let correction = g * old_rij;
+                positions[i] += inv_mi * correction;
+                positions[j] -= inv_mj * correction;
+
+                virial += correction.tensorial(&old_rij) / (dt * dt);
+            }
+
+            if converged {
+                return virial;
+            }
+        }
+
+        fatal_error!(
+            "SHAKE did not converge after {} iterations", self.max_iterations
+        );
+    }
+
+    /// Remove the components of the particles velocities along the
+    /// constraints, using the RATTLE algorithm. The positions of the
+    /// particles must already satisfy the constraints.
+    ///
+    /// This function returns the virial of the constraints forces, assuming
+    /// that the velocity change of a particle `i` was produced by the force
+    /// $m_i \Delta v_i / \Delta t$.
+    pub fn velocities(&self, system: &mut System, dt: f64) -> Matrix3 {
+        let cell = system.cell;
+        let particles = system.particles_mut();
+        let masses = particles.mass;
+        let frozen = particles.frozen;
+        let positions = particles.position;
+        let velocities = particles.velocity;
+
+        let mut virial = Matrix3::zero();
+        for molecule in &self.settle {
+            virial += molecule.velocities(&cell, positions, velocities) / dt;
+        }
+
+        for _ in 0..self.max_iterations {
+            let mut converged = true;
+            for constraint in &self.constraints {
+                let (i, j) = (constraint.i(), constraint.j());
+                let mut rij = positions[i] - positions[j];
+                cell.vector_image(&mut rij);
+                let vij = velocities[i] - velocities[j];
+
+                let d2 = constraint.distance() * constraint.distance();
+                let dot = rij * vij;
+                if f64::abs(dot) * dt < self.tolerance * d2 {
+                    continue;
+                }
+                converged = false;
+
+                let (inv_mi, inv_mj) = (inverse_mass(masses, frozen, i), inverse_mass(masses, frozen, j));
+                let k = - dot / ((inv_mi + inv_mj) * d2);
+                let correction = k * rij;
+                velocities[i] += inv_mi * correction;
+                velocities[j] -= inv_mj * correction;
+
+                virial += correction.tensorial(&rij) / dt;
+            }
+
+            if converged {
+                return virial;
+            }
+        }
+
+        fatal_error!(
+            "RATTLE did not converge after {} iterations", self.max_iterations
+        );
+    }
+}
+
+/// Get the inverse mass of the particle at index `i`. Frozen particles behave
+/// as if they had an infinite mass.
+fn inverse_mass(masses: &[f64], frozen: &[bool], i: usize) -> f64 {
+    if frozen[i] {0.0} else {1.0 / masses[i]}
+}
+
+/// Data needed to apply the SETTLE algorithm to a rigid three-particles
+/// molecule. The molecule is made of an apex particle (the oxygen in water),
+/// and two other particles with the same mass (the hydrogens in water), at the
+/// same distance of the apex.
+#[derive(Clone)]
+struct Settle {
+    /// Index of the apex particle
+    apex: usize,
+    /// Index of the first other particle
+    first: usize,
+    /// Index of the second other particle
+    second: usize,
+    /// Mass of the apex particle
+    apex_mass: f64,
+    /// Mass of the other particles
+    mass: f64,
+    /// Distance between the center of mass and the apex particle
+    ra: f64,
+    /// Distance between the center of mass and the line joining the two other
+    /// particles
+    rb: f64,
+    /// Half of the distance between the two other particles
+    rc: f64,
+}
+
+/// Check if `a` and `b` are equal up to a relative tolerance
+fn is_close(a: f64, b: f64) -> bool {
+    f64::abs(a - b) <= 1e-10 * f64::max(f64::abs(a), f64::abs(b))
+}
+
+impl Settle {
+    /// Find the position of the apex in the rigid three-particles molecule
+    /// starting at particle `start`, if the molecule can be handled by
+    /// SETTLE.
+    fn find_apex(system: &System, start: usize, distances: &HashMap<(usize, usize), f64>) -> Option<usize> {
+        (0..3).find(|&apex| Settle::new(system, start, apex, distances).is_some())
+    }
+
+    /// Create the SETTLE data for the molecule starting at particle `start`,
+    /// using the particle at `start + apex` as the apex. This function
+    /// returns `None` if this molecule is not rigid, or not symmetric.
+    fn new(system: &System, start: usize, apex: usize, distances: &HashMap<(usize, usize), f64>) -> Option<Settle> {
+        let get = |i: usize, j: usize| distances.get(&(min(i, j), max(i, j))).cloned();
+
+        let first = start + (apex + 1) % 3;
+        let second = start + (apex + 2) % 3;
+        let apex = start + apex;
+
+        let (d1, d2, d12) = match (get(apex, first), get(apex, second), get(first, second)) {
+            (Some(d1), Some(d2), Some(d12)) => (d1, d2, d12),
+            _ => return None,
+        };
+
+        let masses = system.particles().mass;
+        if !is_close(d1, d2) || !is_close(masses[first], masses[second]) {
+            return None;
+        }
+
+        let rc = 0.5 * d12;
+        let height2 = d1 * d1 - rc * rc;
+        if height2 <= 0.0 {
+            return None;
+        }
+        let height = f64::sqrt(height2);
+        let total_mass = masses[apex] + 2.0 * masses[first];
+        let ra = 2.0 * masses[first] * height / total_mass;
+
+        Some(Settle {
+            apex: apex,
+            first: first,
+            second: second,
+            apex_mass: masses[apex],
+            mass: masses[first],
+            ra: ra,
+            rb: height - ra,
+            rc: rc,
+        })
+    }
+
+    /// Get the three constrained pairs in this molecule
+    fn pairs(&self) -> [(usize, usize); 3] {
+        [(self.apex, self.first), (self.apex, self.second), (self.first, self.second)]
+    }
+
+    /// Move the particles in this molecule to satisfy the constraints, using
+    /// the `reference` positions at the previous step. This function returns
+    /// the sum of $m_i \Delta r_i \otimes r_i$.
+    fn positions(&self, cell: &UnitCell, positions: &mut [Vector3D], reference: &[Vector3D]) -> Matrix3 {
+        let (a, b, c) = (self.apex, self.first, self.second);
+        // Previous positions, relative to the apex
+        let mut b0 = reference[b] - reference[a];
+        cell.vector_image(&mut b0);
+        let mut c0 = reference[c] - reference[a];
+        cell.vector_image(&mut c0);
+
+        // New positions, relative to the apex
+        let mut b1 = positions[b] - positions[a];
+        cell.vector_image(&mut b1);
+        let mut c1 = positions[c] - positions[a];
+        cell.vector_image(&mut c1);
+
+        // Move the origin to the center of mass
+        let total_mass = self.apex_mass + 2.0 * self.mass;
+        let com = self.mass * (b1 + c1) / total_mass;
+        let a1 = -com;
+        let b1 = b1 - com;
+        let c1 = c1 - com;
+
+        // Build a frame with the z axis normal to the previous molecular plane
+        let ez = (b0 ^ c0).normalized();
+        let ex = (a1 ^ ez).normalized();
+        let ey = ez ^ ex;
+
+        // Coordinates in this frame
+        let (xb0, yb0) = (ex * b0, ey * b0);
+        let (xc0, yc0) = (ex * c0, ey * c0);
+        let za1 = ez * a1;
+        let (xb1, yb1, zb1) = (ex * b1, ey * b1, ez * b1);
+        let (xc1, yc1, zc1) = (ex * c1, ey * c1, ez * c1);
+
+        let sinphi = za1 / self.ra;
+        let cosphi2 = 1.0 - sinphi * sinphi;
+        if cosphi2 <= 0.0 {
+            fatal_error!("SETTLE failed for particles {}, {} and {}, try reducing the timestep", a, b, c);
+        }
+        let cosphi = f64::sqrt(cosphi2);
+        let sinpsi = (zb1 - zc1) / (2.0 * self.rc * cosphi);
+        let cospsi2 = 1.0 - sinpsi * sinpsi;
+        if cospsi2 <= 0.0 {
+            fatal_error!("SETTLE failed for particles {}, {} and {}, try reducing the timestep", a, b, c);
+        }
+        let cospsi = f64::sqrt(cospsi2);
+
+        // Constrained positions in the molecular plane
+        let ya2 = self.ra * cosphi;
+        let xb2 = - self.rc * cospsi;
+        let yb2 = - self.rb * cosphi - self.rc * sinpsi * sinphi;
+        let yc2 = - self.rb * cosphi + self.rc * sinpsi * sinphi;
+
+        // Rotation around the z axis
+        let alpha = xb2 * (xb0 - xc0) + yb0 * yb2 + yc0 * yc2;
+        let beta = xb2 * (yc0 - yb0) + xb0 * yb2 + xc0 * yc2;
+        let gamma = xb0 * yb1 - xb1 * yb0 + xc0 * yc1 - xc1 * yc0;
+        let alpha2beta2 = alpha * alpha + beta * beta;
+        let sintheta = (alpha * gamma - beta * f64::sqrt(alpha2beta2 - gamma * gamma)) / alpha2beta2;
+        let costheta = f64::sqrt(1.0 - sintheta * sintheta);
+
+        let a3 = com + (- ya2 * sintheta) * ex + (ya2 * costheta) * ey + za1 * ez;
+        let b3 = com + (xb2 * costheta - yb2 * sintheta) * ex
+                     + (xb2 * sintheta + yb2 * costheta) * ey
+                     + zb1 * ez;
+        let c3 = com + (- xb2 * costheta - yc2 * sintheta) * ex
+                     + (- xb2 * sintheta + yc2 * costheta) * ey
+                     + zc1 * ez;
+
+        // The positions relative to the apex were taken at `com + b1` and
+        // `com + c1`, and the apex at the origin
+        let delta_a = a3;
+        let delta_b = b3 - (com + b1);
+        let delta_c = c3 - (com + c1);
+        positions[a] += delta_a;
+        positions[b] += delta_b;
+        positions[c] += delta_c;
+
+        // The constraints forces sum to zero, so the apex can be used as the
+        // origin for the virial
+        (self.mass * delta_b).tensorial(&b0) + (self.mass * delta_c).tensorial(&c0)
+    }
+
+    /// Remove the components of the velocities of this molecule particles
+    /// along the constraints, by solving the corresponding 3x3 linear system.
+    /// This function returns the sum of $m_i \Delta v_i \otimes r_i$.
+    fn velocities(&self, cell: &UnitCell, positions: &[Vector3D], velocities: &mut [Vector3D]) -> Matrix3 {
+        let pairs = self.pairs();
+        let inverse_mass = |i: usize| {
+            if i == self.apex {1.0 / self.apex_mass} else {1.0 / self.mass}
+        };
+
+        let mut rij = [Vector3D::zero(); 3];
+        let mut rhs = Vector3D::zero();
+        for (n, &(i, j)) in pairs.iter().enumerate() {
+            rij[n] = positions[i] - positions[j];
+            cell.vector_image(&mut rij[n]);
+            rhs[n] = - rij[n] * (velocities[i] - velocities[j]);
+        }
+
+        // The velocity change of particle `i` because of the constraint
+        // `(i, j)` is `lambda * rij / mi`, and `- lambda * rij / mj` for `j`.
+        let mut matrix = Matrix3::zero();
+        for (n, &(i, j)) in pairs.iter().enumerate() {
+            for (m, &(k, l)) in pairs.iter().enumerate() {
+                let mut coupling = 0.0;
+                if i == k {coupling += inverse_mass(i)}
+                if i == l {coupling -= inverse_mass(i)}
+                if j == k {coupling -= inverse_mass(j)}
+                if j == l {coupling += inverse_mass(j)}
+                matrix[(n, m)] = coupling * (rij[n] * rij[m]);
+            }
+        }
+        let lambda = matrix.inverse() * rhs;
+
+        let mut virial = Matrix3::zero();
+        for (n, &(i, j)) in pairs.iter().enumerate() {
+            let correction = lambda[n] * rij[n];
+            velocities[i] += inverse_mass(i) * correction;
+            velocities[j] -= inverse_mass(j) * correction;
+            virial += correction.tensorial(&rij[n]);
+        }
+        return virial;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::system_from_xyz;
+
+    fn testing_system() -> System {
+        let mut system = system_from_xyz("3
+        cell: 20.0
+        O 0.0 0.0 0.0 0.0 0.0 0.0
+        H 1.0 0.0 0.0 0.01 0.02 0.0
+        H 0.0 1.0 0.0 -0.01 0.0 0.03
+        ");
+        system.add_constraint(0, 1, 1.0);
+        system.add_constraint(0, 2, 1.0);
+        return system;
+    }
+
+    #[test]
+    fn positions() {
+        let mut system = testing_system();
+        let reference = system.particles().position.to_vec();
+
+        // Unconstrained move
+        system.particles_mut().position[1] += Vector3D::new(0.1, 0.05, 0.0);
+        system.particles_mut().position[2] += Vector3D::new(0.0, -0.08, 0.02);
+
+        let mut shake = Shake::new();
+        shake.set_tolerance(1e-10);
+        shake.setup(&system);
+        let _ = shake.positions(&mut system, &reference, 1.0);
+
+        assert_ulps_eq!(system.distance(0, 1), 1.0, epsilon=1e-9);
+        assert_ulps_eq!(system.distance(0, 2), 1.0, epsilon=1e-9);
+    }
+
+    #[test]
+    fn positions_frozen() {
+        let mut system = testing_system();
+        system.set_frozen(0, true);
+        let reference = system.particles().position.to_vec();
+
+        system.particles_mut().position[1] += Vector3D::new(0.1, 0.05, 0.0);
+        system.particles_mut().position[2] += Vector3D::new(0.0, -0.08, 0.02);
+
+        let mut shake = Shake::new();
+        shake.set_tolerance(1e-10);
+        shake.setup(&system);
+        let _ = shake.positions(&mut system, &reference, 1.0);
+
+        // Only the mobile particles are moved to satisfy the constraints
+        assert_eq!(system.particles().position[0], reference[0]);
+        assert_ulps_eq!(system.distance(0, 1), 1.0, epsilon=1e-9);
+        assert_ulps_eq!(system.distance(0, 2), 1.0, epsilon=1e-9);
+    }
+
+    #[test]
+    fn velocities() {
+        let mut system = testing_system();
+        let mut shake = Shake::new();
+        shake.set_tolerance(1e-12);
+        shake.setup(&system);
+        let _ = shake.velocities(&mut system, 1.0);
+
+        for &(i, j) in &[(0, 1), (0, 2)] {
+            let rij = system.nearest_image(i, j);
+            let vij = system.particles().velocity[i] - system.particles().velocity[j];
+            assert_ulps_eq!(rij * vij, 0.0, epsilon=1e-10);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn not_converged() {
+        let mut system = testing_system();
+        let reference = system.particles().position.to_vec();
+        system.particles_mut().position[1] += Vector3D::new(0.1, 0.05, 0.0);
+
+        let mut shake = Shake::new();
+        shake.set_tolerance(1e-10);
+        shake.set_max_iterations(1);
+        shake.setup(&system);
+        let _ = shake.positions(&mut system, &reference, 1.0);
+    }
+
+    fn water() -> System {
+        let mut system = system_from_xyz("3
+        cell: 20.0
+        O 0.0 0.0 0.0 0.0 0.01 0.0
+        H 0.8 0.6 0.0 0.01 0.02 0.0
+        H -0.8 0.6 0.0 -0.01 0.0 0.03
+        ");
+        let _ = system.add_bond(0, 1);
+        let _ = system.add_bond(0, 2);
+        system.add_constraint(0, 1, 1.0);
+        system.add_constraint(0, 2, 1.0);
+        system.add_constraint(1, 2, 1.6);
+        return system;
+    }
+
+    #[test]
+    fn settle_detection() {
+        let mut shake = Shake::new();
+        shake.setup(&water());
+        assert_eq!(shake.settle.len(), 1);
+        assert!(shake.constraints.is_empty());
+        assert_eq!(shake.settle[0].apex, 0);
+
+        // Not all distances are constrained
+        let mut shake = Shake::new();
+        shake.setup(&testing_system());
+        assert!(shake.settle.is_empty());
+        assert_eq!(shake.constraints.len(), 2);
+    }
+
+    #[test]
+    fn settle_positions() {
+        let mut system = water();
+        let reference = system.particles().position.to_vec();
+        let com = system.center_of_mass();
+
+        // Unconstrained move
+        system.particles_mut().position[0] += Vector3D::new(0.01, -0.02, 0.03);
+        system.particles_mut().position[1] += Vector3D::new(0.1, 0.05, 0.0);
+        system.particles_mut().position[2] += Vector3D::new(0.0, -0.08, 0.02);
+        let moved_com = system.center_of_mass();
+
+        let mut shake = Shake::new();
+        shake.setup(&system);
+        let _ = shake.positions(&mut system, &reference, 1.0);
+
+        assert_ulps_eq!(system.distance(0, 1), 1.0, epsilon=1e-12);
+        assert_ulps_eq!(system.distance(0, 2), 1.0, epsilon=1e-12);
+        assert_ulps_eq!(system.distance(1, 2), 1.6, epsilon=1e-12);
+        // The center of mass is not changed by the constraints
+        assert_ulps_eq!(system.center_of_mass(), moved_com, epsilon=1e-12);
+        assert!((system.center_of_mass() - com).norm() > 1e-3);
+    }
+
+    #[test]
+    fn settle_velocities() {
+        let mut system = water();
+        let mut shake = Shake::new();
+        shake.setup(&system);
+        let _ = shake.velocities(&mut system, 1.0);
+
+        for &(i, j) in &[(0, 1), (0, 2), (1, 2)] {
+            let rij = system.nearest_image(i, j);
+            let vij = system.particles().velocity[i] - system.particles().velocity[j];
+            assert_ulps_eq!(rij * vij, 0.0, epsilon=1e-12);
+        }
+    }
+}
@@ -4,15 +4,20 @@
 //! While running a simulation, we often want to have control over some
 //! simulation parameters: the temperature, the pressure, etc. This is the goal
 //! of the control algorithms, all implementing of the `Control` trait.
+use rand::{self, Rng, SeedableRng};
+
+use consts::K_BOLTZMANN;
 use types::{Matrix3, Vector3D, Zero};
-use sys::System;
-use sys::veloc;
-use sim::Alternator;
+use sys::{System, EnergyCache, Selection, RemovedMotions};
+use sys::compute::{Compute, GroupTemperature};
+use energy::Pulling;
+use sim::{Alternator, Schedule};
+use sim::mc::{MCMove, MoveCounter, Resize};
 
 use sys::zip_particle::*;
 
 /// Trait for controlling some parameters in a system during a simulation.
-pub trait Control {
+pub trait Control: Send {
     /// Function called once at the beginning of the simulation, which allow
     /// for some setup of the control algorithm if needed.
     fn setup(&mut self, _: &System) {}
@@ -20,12 +25,42 @@ pub trait Control {
     /// Do your job, control algorithm!
     fn control(&mut self, system: &mut System);
 
+    /// Get the global motions of the system removed by this control, which
+    /// do not contribute to the system degrees of freedom. The default is to
+    /// remove none of them.
+    fn removed_motions(&self) -> RemovedMotions {
+        RemovedMotions::default()
+    }
+
     /// Function called once at the end of the simulation.
     fn finish(&mut self, _: &System) {}
 }
 
 /// Trait for controls usable as thermostats
-pub trait Thermostat: Control {}
+pub trait Thermostat: Control {
+    /// Get the indexes of the particles in the `system` controlled by this
+    /// thermostat. The default is to control all the particles.
+    fn particles(&self, system: &System) -> Vec<usize> {
+        (0..system.size()).collect()
+    }
+}
+
+/// Get the instantaneous temperature of the particles in `selection`
+fn selection_temperature(system: &System, selection: &Selection) -> f64 {
+    match *selection {
+        Selection::All => system.temperature(),
+        _ => GroupTemperature{selection: selection}.compute(system),
+    }
+}
+
+/// Multiply the velocities of the particles in `selection` by `factor`
+fn scale_selection(system: &mut System, selection: &Selection, factor: f64) {
+    let particles = selection.particles(system);
+    let velocities = system.particles_mut().velocity;
+    for i in particles {
+        velocities[i] *= factor;
+    }
+}
 
 /******************************************************************************/
 /// Velocity rescaling thermostat.
@@ -35,11 +70,18 @@ pub trait Thermostat: Control {}
 /// tolerance parameter prevent this algorithm from running too often: if
 /// tolerance is 10K and the target temperature is 300K, the algorithm will only
 /// run if the instant temperature is below 290K or above 310K.
+///
+/// By default, this thermostat acts on the whole system. It can be restricted
+/// to a group of particles with `RescaleThermostat::set_selection`.
 pub struct RescaleThermostat {
     /// Target temperature
     temperature: f64,
     /// Tolerance in temperature
     tol: f64,
+    /// Particles controlled by this thermostat
+    selection: Selection,
+    /// Optional schedule for the target temperature
+    schedule: Option<Schedule>,
 }
 
 impl RescaleThermostat {
@@ -54,20 +96,45 @@ impl RescaleThermostat {
     /// Create a new `RescaleThermostat` acting at temperature `T`, with a
     /// tolerance of `tol`. For rescaling all the steps, use `tol = 0`.
     pub fn with_tolerance(temperature: f64, tol: f64) -> RescaleThermostat {
-        RescaleThermostat{temperature: temperature, tol: tol}
+        RescaleThermostat {
+            temperature: temperature,
+            tol: tol,
+            selection: Selection::All,
+            schedule: None,
+        }
+    }
+
+    /// Use the given `schedule` to set the target temperature as a function
+    /// of the simulation step.
+    pub fn set_temperature_schedule(&mut self, schedule: Schedule) {
+        self.schedule = Some(schedule);
+    }
+
+    /// Only control the temperature of the particles in `selection`. The
+    /// temperature of the group is computed with `GroupTemperature`.
+    pub fn set_selection(&mut self, selection: Selection) {
+        self.selection = selection;
     }
 }
 
 impl Control for RescaleThermostat {
     fn control(&mut self, system: &mut System) {
-        let instant_temperature = system.temperature();
+        if let Some(ref schedule) = self.schedule {
+            self.temperature = schedule.value(system.step());
+        }
+        let instant_temperature = selection_temperature(system, &self.selection);
         if f64::abs(instant_temperature - self.temperature) > self.tol {
-            veloc::scale(system, self.temperature);
+            let factor = f64::sqrt(self.temperature / instant_temperature);
+            scale_selection(system, &self.selection, factor);
         }
     }
 }
 
-impl Thermostat for RescaleThermostat {}
+impl Thermostat for RescaleThermostat {
+    fn particles(&self, system: &System) -> Vec<usize> {
+        self.selection.particles(system)
+    }
+}
 
 /******************************************************************************/
 /// Berendsen thermostat.
@@ -77,12 +144,19 @@ impl Thermostat for RescaleThermostat {}
 /// algorithm can be found in the original article [1].
 ///
 /// [1] H.J.C. Berendsen, et al. J. Chem Phys 81, 3684 (1984); doi: 10.1063/1.448118
+///
+/// By default, this thermostat acts on the whole system. It can be restricted
+/// to a group of particles with `BerendsenThermostat::set_selection`.
 pub struct BerendsenThermostat {
     /// Target temperature
     temperature: f64,
     /// Timestep of the thermostat, expressed as a multiplicative factor of the
     /// integrator timestep.
     tau: f64,
+    /// Particles controlled by this thermostat
+    selection: Selection,
+    /// Optional schedule for the target temperature
+    schedule: Option<Schedule>,
 }
 
 impl BerendsenThermostat {
@@ -91,32 +165,69 @@ impl BerendsenThermostat {
     pub fn new(temperature: f64, tau: f64) -> BerendsenThermostat {
         assert!(temperature >= 0.0, "The temperature must be positive in thermostats.");
         assert!(tau >= 0.0, "The timestep must be positive in berendsen thermostat.");
-        BerendsenThermostat{temperature: temperature, tau: tau}
+        BerendsenThermostat {
+            temperature: temperature,
+            tau: tau,
+            selection: Selection::All,
+            schedule: None,
+        }
+    }
+
+    /// Use the given `schedule` to set the target temperature as a function
+    /// of the simulation step.
+    pub fn set_temperature_schedule(&mut self, schedule: Schedule) {
+        self.schedule = Some(schedule);
+    }
+
+    /// Only control the temperature of the particles in `selection`. The
+    /// temperature of the group is computed with `GroupTemperature`.
+    pub fn set_selection(&mut self, selection: Selection) {
+        self.selection = selection;
     }
 }
 
 impl Control for BerendsenThermostat {
     fn control(&mut self, system: &mut System) {
-        let instant_temperature = system.temperature();
-        let factor = f64::sqrt(1.0 + 1.0 / self.tau * (self.temperature / instant_temperature - 1.0));
-        for velocity in system.particles_mut().velocity {
-            *velocity *= factor;
+        if let Some(ref schedule) = self.schedule {
+            self.temperature = schedule.value(system.step());
         }
+        let instant_temperature = selection_temperature(system, &self.selection);
+        let factor = f64::sqrt(1.0 + 1.0 / self.tau * (self.temperature / instant_temperature - 1.0));
+        scale_selection(system, &self.selection, factor);
+    }
+}
+impl Thermostat for BerendsenThermostat {
+    fn particles(&self, system: &System) -> Vec<usize> {
+        self.selection.particles(system)
     }
 }
-impl Thermostat for BerendsenThermostat {}
 
 /******************************************************************************/
 
 impl<T> Control for Alternator<T> where T: Control {
+    fn setup(&mut self, system: &System) {
+        self.as_mut().setup(system)
+    }
+
     fn control(&mut self, system: &mut System) {
         if self.can_run() {
             self.as_mut().control(system)
         }
     }
+
+    fn removed_motions(&self) -> RemovedMotions {
+        self.as_ref().removed_motions()
+    }
+
+    fn finish(&mut self, system: &System) {
+        self.as_mut().finish(system)
+    }
 }
 
-/// Remove global translation from the system
+/// Remove global translation from the system. Frozen particles are not
+/// taken into account.
+///
+/// Using this control removes three degrees of freedom from the system.
 pub struct RemoveTranslation;
 
 impl RemoveTranslation {
@@ -124,25 +235,51 @@ impl RemoveTranslation {
     pub fn new() -> RemoveTranslation {
         RemoveTranslation
     }
+
+    /// Remove the global translation of the mobile particles in `system`,
+    /// without changing the system degrees of freedom.
+    pub(crate) fn remove(system: &mut System) {
+        let mut total_mass = 0.0;
+        let mut com_velocity = Vector3D::zero();
+        for (&mass, velocity, &frozen) in system.particles().zip((&Mass, &Velocity, &Frozen)) {
+            if !frozen {
+                total_mass += mass;
+                com_velocity += velocity * mass;
+            }
+        }
+
+        if total_mass == 0.0 {
+            return;
+        }
+        com_velocity /= total_mass;
+
+        for (velocity, &frozen) in system.particles_mut().zip_mut((&mut Velocity, &Frozen)) {
+            if !frozen {
+                *velocity -= com_velocity;
+            }
+        }
+    }
 }
 
 impl Control for RemoveTranslation {
     fn control(&mut self, system: &mut System) {
-        let total_mass = system.particles().mass.iter().sum();
-
-        let mut com_velocity = Vector3D::zero();
-        for (&mass, velocity) in system.particles().zip((&Mass, &Velocity)) {
-            com_velocity += velocity * mass / total_mass;
-        }
+        RemoveTranslation::remove(system);
+    }
 
-        for velocity in system.particles_mut().velocity {
-            *velocity -= com_velocity;
+    fn removed_motions(&self) -> RemovedMotions {
+        RemovedMotions {
+            translation: true,
+            rotation: false,
         }
     }
 }
 
 /******************************************************************************/
-/// Remove global rotation from the system
+/// Remove global rotation from the system. Frozen particles are not taken
+/// into account.
+///
+/// Using this control removes three degrees of freedom from the system, or
+/// two if there are only two mobile particles.
 pub struct RemoveRotation;
 
 impl RemoveRotation {
@@ -150,17 +287,34 @@ impl RemoveRotation {
     pub fn new() -> RemoveRotation {
         RemoveRotation
     }
-}
 
-impl Control for RemoveRotation {
-    fn control(&mut self, system: &mut System) {
-        // Center-of-mass
-        let com = system.center_of_mass();
+    /// Remove the global rotation of the mobile particles in `system`,
+    /// without changing the system degrees of freedom.
+    pub(crate) fn remove(system: &mut System) {
+        // Center-of-mass of the mobile particles
+        let mut total_mass = 0.0;
+        let mut com = Vector3D::zero();
+        for (&mass, position, &frozen) in system.particles().zip((&Mass, &Position, &Frozen)) {
+            if !frozen {
+                total_mass += mass;
+                com += mass * position;
+            }
+        }
+
+        if total_mass == 0.0 {
+            return;
+        }
+        com /= total_mass;
 
         // Angular momentum
         let mut moment = Vector3D::zero();
         let mut inertia = Matrix3::zero();
-        for (&mass, position, velocity) in system.particles().zip((&Mass, &Position, &Velocity)) {
+        for (&mass, position, velocity, &frozen) in system.particles().zip(
+            (&Mass, &Position, &Velocity, &Frozen)
+        ) {
+            if frozen {
+                continue;
+            }
             let delta = position - com;
             moment += mass * (delta ^ velocity);
             inertia += - mass * delta.tensorial(&delta);
@@ -174,8 +328,25 @@ impl Control for RemoveRotation {
         // The angular velocity omega is defined by `L = I w` with L the angular
         // momentum, and I the inertia matrix.
         let angular = inertia.inverse() * moment;
-        for (position, velocity) in system.particles_mut().zip_mut((&Position, &mut Velocity)) {
-            *velocity -= (position - com) ^ angular;
+        for (position, velocity, &frozen) in system.particles_mut().zip_mut(
+            (&Position, &mut Velocity, &Frozen)
+        ) {
+            if !frozen {
+                *velocity -= (position - com) ^ angular;
+            }
+        }
+    }
+}
+
+impl Control for RemoveRotation {
+    fn control(&mut self, system: &mut System) {
+        RemoveRotation::remove(system);
+    }
+
+    fn removed_motions(&self) -> RemovedMotions {
+        RemovedMotions {
+            translation: false,
+            rotation: true,
         }
     }
 }
@@ -183,7 +354,8 @@ impl Control for RemoveRotation {
 
 /******************************************************************************/
 /// Rewrap all molecules' centers of mass to lie within the unit cell.
-/// Individual atoms in a molecule may still lie outside of the cell.
+/// Individual atoms in a molecule may still lie outside of the cell. Frozen
+/// molecules are not moved.
 pub struct Rewrap;
 
 impl Rewrap {
@@ -196,9 +368,173 @@ impl Rewrap {
 impl Control for Rewrap {
     fn control(&mut self, system: &mut System) {
         for i in 0..system.molecules().len() {
-            system.wrap_molecule(i);
+            if !system.is_frozen_molecule(i) {
+                system.wrap_molecule(i);
+            }
+        }
+    }
+}
+
+/******************************************************************************/
+/// Monte Carlo barostat.
+///
+/// This control keeps the pressure constant during a molecular dynamics
+/// simulation by attempting Monte Carlo changes of the volume each time it is
+/// called. The trial moves are the same as the ones used by the `Resize`
+/// Monte Carlo move: the molecules centers of mass are scaled together with
+/// the cell, and the new volume is accepted or rejected using the
+/// Metropolis criterion in the isobaric-isothermal ensemble. The volume can
+/// change isotropically, or by changing the length of one cell vector at the
+/// time.
+///
+/// The maximal volume change is adapted during the simulation to reach a
+/// target acceptance ratio. This barostat does not control the temperature,
+/// and should be used together with a thermostat at the same temperature.
+pub struct MonteCarloBarostat {
+    /// Target temperature
+    temperature: f64,
+    /// Volume change move
+    resize: Resize,
+    /// Counter for the acceptance of the volume changes
+    counter: MoveCounter,
+    /// Number of attempted volume changes between updates of the maximal
+    /// volume change
+    update_frequency: u64,
+    /// Energy cache used to compute the energy change of volume moves
+    cache: EnergyCache,
+    /// Random number generator for the Metropolis criterion
+    rng: Box<Rng + Send>,
+}
+
+impl MonteCarloBarostat {
+    /// Create a new `MonteCarloBarostat` at the given `temperature` and
+    /// `pressure`, changing the cell isotropically with an initial maximal
+    /// volume change of `delta`.
+    pub fn new(temperature: f64, pressure: f64, delta: f64) -> MonteCarloBarostat {
+        MonteCarloBarostat::create(temperature, Resize::new(pressure, delta))
+    }
+
+    /// Create a new `MonteCarloBarostat` at the given `temperature` and
+    /// `pressure`, changing the length of one cell vector at the time with an
+    /// initial maximal volume change of `delta`.
+    pub fn anisotropic(temperature: f64, pressure: f64, delta: f64) -> MonteCarloBarostat {
+        MonteCarloBarostat::create(temperature, Resize::anisotropic(pressure, delta))
+    }
+
+    /// Factorizing the constructors
+    fn create(temperature: f64, resize: Resize) -> MonteCarloBarostat {
+        assert!(temperature >= 0.0, "The temperature must be positive in barostats.");
+        let mut rng = Box::new(rand::XorShiftRng::new_unseeded());
+        rng.reseed([2015u32, 42u32, 3u32, 12u32]);
+        MonteCarloBarostat {
+            temperature: temperature,
+            resize: resize,
+            counter: MoveCounter::new(Some(0.5)),
+            update_frequency: 100,
+            cache: EnergyCache::new(),
+            rng: rng,
+        }
+    }
+
+    /// Set the target acceptance ratio for the volume changes. Use `None` to
+    /// disable the update of the maximal volume change.
+    pub fn set_target_acceptance(&mut self, target_acceptance: Option<f64>) {
+        self.counter.set_acceptance(target_acceptance);
+    }
+
+    /// Set the number of attempted volume changes between two updates of the
+    /// maximal volume change.
+    pub fn set_update_frequency(&mut self, frequency: u64) {
+        self.update_frequency = frequency;
+    }
+
+    /// Use the given `schedule` to set the target pressure as a function of
+    /// the simulation step.
+    pub fn set_pressure_schedule(&mut self, schedule: Schedule) {
+        self.resize.set_pressure_schedule(schedule);
+    }
+}
+
+impl Control for MonteCarloBarostat {
+    fn setup(&mut self, system: &System) {
+        self.resize.setup(system);
+    }
+
+    fn control(&mut self, system: &mut System) {
+        // The particles moved since the last call, so the cache needs to be
+        // recomputed from scratch.
+        self.cache.init(system);
+        if !self.resize.prepare(system, &mut self.rng) {
+            return;
+        }
+        self.counter.ncalled += 1;
+        self.counter.nattempted += 1;
+
+        let beta = 1.0 / (K_BOLTZMANN * self.temperature);
+        let cost = self.resize.cost(system, beta, &mut self.cache);
+        let accepted = cost <= 0.0 || self.rng.next_f64() < f64::exp(-cost);
+        if accepted {
+            self.resize.apply(system);
+            self.counter.naccepted += 1;
+        } else {
+            self.resize.restore(system);
+        }
+
+        if self.counter.nattempted == self.update_frequency {
+            self.resize.update_amplitude(self.counter.compute_scaling_factor());
+            self.counter.naccepted = 0;
+            self.counter.nattempted = 0;
         }
     }
+
+    fn finish(&mut self, _: &System) {
+        info!("Monte Carlo barostat summary");
+        info!("  Calls     : {}", self.counter.ncalled);
+        info!("  Acceptance: {} %", self.counter.naccepted as f64 /
+            self.counter.nattempted as f64 * 100.0);
+    }
+}
+
+/******************************************************************************/
+/// Steered molecular dynamics control.
+///
+/// This control moves the anchor of a [`Pulling`][Pulling] potential at each
+/// step, and accumulates the work done on the pulled particles. The same
+/// `Pulling` potential (or a clone of it) should be added to the system as a
+/// global potential.
+///
+/// [Pulling]: ../../energy/struct.Pulling.html
+pub struct Steering {
+    /// The pulling potential
+    pulling: Pulling,
+    /// Timestep of the simulation
+    timestep: f64,
+}
+
+impl Steering {
+    /// Create a new `Steering` control for the `pulling` potential, in a
+    /// simulation using the given `timestep`.
+    pub fn new(pulling: Pulling, timestep: f64) -> Steering {
+        Steering {
+            pulling: pulling,
+            timestep: timestep,
+        }
+    }
+
+    /// Get the pulling potential used by this control
+    pub fn pulling(&self) -> &Pulling {
+        &self.pulling
+    }
+}
+
+impl Control for Steering {
+    fn setup(&mut self, system: &System) {
+        self.pulling.initialize(system);
+    }
+
+    fn control(&mut self, system: &mut System) {
+        self.pulling.advance(system, self.timestep);
+    }
 }
 
 #[cfg(test)]
@@ -206,7 +542,7 @@ mod tests {
     use super::*;
     use sys::{System, UnitCell, Particle};
     use sys::veloc::{BoltzmannVelocities, InitVelocities};
-    use utils::system_from_xyz;
+    use utils::{system_from_xyz, unit_from};
 
     fn testing_system() -> System {
         let mut system = System::with_cell(UnitCell::cubic(20.0));;
@@ -259,6 +595,51 @@ mod tests {
         assert_ulps_eq!(temperature, 250.0, epsilon=1e-9);
     }
 
+    #[test]
+    fn thermostat_selection() {
+        let mut system = testing_system();
+        let hot = Selection::Range(0..500);
+        let cold = Selection::Range(500..1000);
+        let cold_temperature = GroupTemperature{selection: &cold}.compute(&system);
+
+        let mut thermostat = RescaleThermostat::with_tolerance(400.0, 0.0);
+        thermostat.set_selection(hot.clone());
+        thermostat.control(&mut system);
+        let temperature = GroupTemperature{selection: &hot}.compute(&system);
+        assert_ulps_eq!(temperature, 400.0, epsilon=1e-9);
+        let temperature = GroupTemperature{selection: &cold}.compute(&system);
+        assert_eq!(temperature, cold_temperature);
+
+        let mut thermostat = BerendsenThermostat::new(200.0, 100.0);
+        thermostat.set_selection(cold.clone());
+        for _ in 0..3000 {
+            thermostat.control(&mut system);
+        }
+        let temperature = GroupTemperature{selection: &cold}.compute(&system);
+        assert_ulps_eq!(temperature, 200.0, epsilon=1e-9);
+        let temperature = GroupTemperature{selection: &hot}.compute(&system);
+        assert_ulps_eq!(temperature, 400.0, epsilon=1e-9);
+    }
+
+    #[test]
+    fn thermostat_schedule() {
+        let mut system = testing_system();
+        let mut thermostat = RescaleThermostat::with_tolerance(300.0, 0.0);
+        thermostat.set_temperature_schedule(Schedule::linear(300.0, 200.0, 10));
+
+        for _ in 0..5 {
+            system.increment_step();
+        }
+        thermostat.control(&mut system);
+        assert_ulps_eq!(system.temperature(), 250.0, epsilon=1e-9);
+
+        for _ in 0..10 {
+            system.increment_step();
+        }
+        thermostat.control(&mut system);
+        assert_ulps_eq!(system.temperature(), 200.0, epsilon=1e-9);
+    }
+
     #[test]
     #[should_panic]
     fn negative_temperature_rescale() {
@@ -284,6 +665,22 @@ mod tests {
         assert_ulps_eq!(system.particles().velocity[1], Vector3D::new(0.0, -1.0, 0.0));
     }
 
+    #[test]
+    fn remove_translation_frozen() {
+        let mut system = system_from_xyz("3
+        cell: 20.0
+        Ag 0 0 0 1 2 0
+        Ag 1 1 1 1 0 0
+        Ag 2 2 2 0 0 0
+        ");
+        system.set_frozen(2, true);
+
+        RemoveTranslation::new().control(&mut system);
+        assert_ulps_eq!(system.particles().velocity[0], Vector3D::new(0.0, 1.0, 0.0));
+        assert_ulps_eq!(system.particles().velocity[1], Vector3D::new(0.0, -1.0, 0.0));
+        assert_eq!(system.particles().velocity[2], Vector3D::zero());
+    }
+
     #[test]
     fn remove_rotation() {
         let mut system = system_from_xyz("2
@@ -297,6 +694,65 @@ mod tests {
         assert_ulps_eq!(system.particles().velocity[1], Vector3D::new(0.0, 0.0, 1.0));
     }
 
+    #[test]
+    fn removed_motions() {
+        use sim::{Simulation, Alternator};
+        use sim::md::MolecularDynamics;
+
+        let mut system = system_from_xyz("3
+        cell: 20.0
+        Ag 0 0 0
+        Ag 1 0 0
+        Ag 0 1 0
+        ");
+        assert_eq!(system.degrees_of_freedom(), 9);
+
+        // The removed motions are set before the first call to `control`
+        let mut md = MolecularDynamics::new(1.0);
+        md.add_control(Box::new(Alternator::new(10, RemoveTranslation::new())));
+        md.add_control(Box::new(RemoveRotation::new()));
+        Simulation::new(Box::new(md)).run(&mut system, 0);
+        assert_eq!(system.degrees_of_freedom(), 3);
+
+        // And reset at the start of the next simulation
+        let md = MolecularDynamics::new(1.0);
+        Simulation::new(Box::new(md)).run(&mut system, 0);
+        assert_eq!(system.degrees_of_freedom(), 9);
+    }
+
+    #[test]
+    fn disjoint_thermostats() {
+        use sim::Propagator;
+        use sim::md::MolecularDynamics;
+
+        let system = testing_system();
+        let mut md = MolecularDynamics::new(1.0);
+        let mut hot = RescaleThermostat::new(400.0);
+        hot.set_selection(Selection::Range(0..500));
+        md.add_thermostat(Box::new(hot));
+        let mut cold = BerendsenThermostat::new(200.0, 100.0);
+        cold.set_selection(Selection::Range(500..1000));
+        md.add_thermostat(Box::new(cold));
+        md.setup(&system);
+    }
+
+    #[test]
+    #[should_panic]
+    fn overlapping_thermostats() {
+        use sim::Propagator;
+        use sim::md::MolecularDynamics;
+
+        let system = testing_system();
+        let mut md = MolecularDynamics::new(1.0);
+        let mut hot = RescaleThermostat::new(400.0);
+        hot.set_selection(Selection::Range(0..600));
+        md.add_thermostat(Box::new(hot));
+        let mut cold = BerendsenThermostat::new(200.0, 100.0);
+        cold.set_selection(Selection::Range(500..1000));
+        md.add_thermostat(Box::new(cold));
+        md.setup(&system);
+    }
+
     #[test]
     fn rewrap() {
         let mut system = system_from_xyz("2
@@ -309,4 +765,70 @@ mod tests {
         assert_ulps_eq!(system.particles().position[0], Vector3D::new(0.0, 0.0, 0.0));
         assert_ulps_eq!(system.particles().position[1], Vector3D::new(5.0, 0.0, 0.0));
     }
+
+    #[test]
+    fn monte_carlo_barostat() {
+        let mut system = system_from_xyz("2
+        cell: 20.0
+        Ag 0 0 0
+        Ag 1 1 1
+        ");
+        let volume = system.volume();
+
+        let pressure = unit_from(10000.0, "bar");
+        let mut barostat = MonteCarloBarostat::new(300.0, pressure, 100.0);
+        barostat.setup(&system);
+        for _ in 0..100 {
+            barostat.control(&mut system);
+        }
+        assert!(system.volume() < volume);
+
+        let lengths = system.cell.lengths();
+        assert_ulps_eq!(lengths[0], lengths[1]);
+        assert_ulps_eq!(lengths[0], lengths[2]);
+    }
+
+    #[test]
+    fn anisotropic_monte_carlo_barostat() {
+        let mut system = system_from_xyz("2
+        cell: 20.0
+        Ag 0 0 0
+        Ag 1 1 1
+        ");
+        let volume = system.volume();
+
+        let pressure = unit_from(10000.0, "bar");
+        let mut barostat = MonteCarloBarostat::anisotropic(300.0, pressure, 100.0);
+        barostat.setup(&system);
+        for _ in 0..100 {
+            barostat.control(&mut system);
+        }
+        assert!(system.volume() < volume);
+
+        let lengths = system.cell.lengths();
+        assert!(lengths[0] != lengths[1] || lengths[0] != lengths[2]);
+    }
+
+    #[test]
+    fn steering() {
+        let mut system = system_from_xyz("2
+        cell: 20.0
+        Ag 0 0 0
+        Ag 1 0 0
+        ");
+
+        let pulling = Pulling::spring(vec![0], Vector3D::new(1.0, 0.0, 0.0), 1.0, 0.5);
+        system.add_global_potential(Box::new(pulling.clone()));
+
+        let mut steering = Steering::new(pulling, 2.0);
+        steering.setup(&system);
+        for _ in 0..3 {
+            steering.control(&mut system);
+        }
+
+        assert_eq!(steering.pulling().anchor(), Some(3.0));
+        // The anchor moved, but not the particles
+        assert_eq!(system.potential_energy(), 4.5);
+        assert_eq!(system.forces()[0], Vector3D::new(3.0, 0.0, 0.0));
+    }
 }
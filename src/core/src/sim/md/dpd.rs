@@ -0,0 +1,296 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Dissipative particle dynamics
+use rand::distributions::{Normal, Sample};
+use rand::{Isaac64Rng, SeedableRng};
+
+use consts::K_BOLTZMANN;
+use types::{Vector3D, Zero};
+use sys::System;
+use sys::zip_particle::*;
+
+use super::{Integrator, Control, Thermostat};
+
+/// Dissipative and random pair forces from dissipative particle dynamics.
+///
+/// For all pairs of particles closer than the cutoff distance $r_c$, the
+/// dissipative force is $\vec f^D_{ij} = -\gamma w(r_{ij})^2 (\hat r_{ij}
+/// \cdot \vec v_{ij}) \hat r_{ij}$ and the random force is $\vec f^R_{ij} =
+/// \sigma w(r_{ij}) \theta_{ij} \hat r_{ij} / \sqrt{\Delta t}$, with $w(r) =
+/// 1 - r / r_c$, $\sigma^2 = 2 \gamma k_B T$ and $\theta_{ij}$ a gaussian
+/// random number. These forces act along the line between the particles, and
+/// conserve the momentum.
+struct Dissipation {
+    /// Target temperature
+    temperature: f64,
+    /// Friction coefficient
+    gamma: f64,
+    /// Cutoff distance
+    cutoff: f64,
+    /// Random number generator for the random forces
+    rng: Isaac64Rng,
+    /// Normal distribution for the random forces
+    normal: Normal,
+}
+
+impl Dissipation {
+    fn new(temperature: f64, gamma: f64, cutoff: f64) -> Dissipation {
+        assert!(temperature >= 0.0, "The temperature must be positive in DPD.");
+        assert!(gamma >= 0.0, "The friction coefficient must be positive in DPD.");
+        assert!(cutoff > 0.0, "The cutoff must be positive in DPD.");
+        Dissipation {
+            temperature: temperature,
+            gamma: gamma,
+            cutoff: cutoff,
+            rng: Isaac64Rng::from_seed(&[42]),
+            normal: Normal::new(0.0, 1.0),
+        }
+    }
+
+    /// Compute the dissipative and random forces acting on the particles of
+    /// the `system` with the given `velocities`, for a timestep `dt`.
+    fn forces(&mut self, system: &System, velocities: &[Vector3D], dt: f64) -> Vec<Vector3D> {
+        let sigma = f64::sqrt(2.0 * self.gamma * K_BOLTZMANN * self.temperature / dt);
+        let frozen = system.particles().frozen;
+        let mut forces = vec![Vector3D::zero(); system.size()];
+        for i in 0..system.size() {
+            for j in (i + 1)..system.size() {
+                if frozen[i] && frozen[j] {
+                    continue;
+                }
+                let rij = system.nearest_image(i, j);
+                let r = rij.norm();
+                if r >= self.cutoff || r == 0.0 {
+                    continue;
+                }
+
+                let w = 1.0 - r / self.cutoff;
+                let direction = rij / r;
+                let vij = velocities[i] - velocities[j];
+                let theta = self.normal.sample(&mut self.rng);
+                let force = (sigma * w * theta - self.gamma * w * w * (direction * vij)) * direction;
+                forces[i] += force;
+                forces[j] -= force;
+            }
+        }
+        return forces;
+    }
+}
+
+/******************************************************************************/
+/// Velocity-Verlet integrator for dissipative particle dynamics, from Groot
+/// and Warren (J. Chem. Phys. 107, 4423, 1997).
+///
+/// The particles interact with the potentials in the system (usually
+/// `SoftRepulsion` pair potentials), and with the dissipative and random pair
+/// forces from dissipative particle dynamics. As the dissipative forces
+/// depend on the velocities, they are computed with velocities predicted
+/// using the empirical `lambda` parameter.
+pub struct DPDVelocityVerlet {
+    /// Timestep for the integrator
+    timestep: f64,
+    /// Empirical parameter for the velocities prediction
+    lambda: f64,
+    /// Dissipative and random forces
+    dissipation: Dissipation,
+    /// Storing the accelerations
+    accelerations: Vec<Vector3D>,
+}
+
+impl DPDVelocityVerlet {
+    /// Create a new integrator with a timestep of `timestep`, at the given
+    /// `temperature`. `gamma` is the friction coefficient and `cutoff` the
+    /// cutoff distance for the dissipative and random forces. The `lambda`
+    /// parameter is set to 0.5.
+    pub fn new(timestep: f64, temperature: f64, gamma: f64, cutoff: f64) -> DPDVelocityVerlet {
+        DPDVelocityVerlet {
+            timestep: timestep,
+            lambda: 0.5,
+            dissipation: Dissipation::new(temperature, gamma, cutoff),
+            accelerations: Vec::new(),
+        }
+    }
+
+    /// Set the `lambda` parameter used to predict the velocities. The value
+    /// 0.5 gives the usual velocity-Verlet algorithm, and Groot and Warren
+    /// found 0.65 to give the best temperature control.
+    pub fn set_lambda(&mut self, lambda: f64) {
+        assert!(lambda >= 0.0 && lambda <= 1.0, "lambda must be between 0 and 1 in DPD");
+        self.lambda = lambda;
+    }
+
+    /// Set the seed of the random number generator. The default seed is 42.
+    pub fn seed(&mut self, seed: u64) {
+        self.dissipation.rng.reseed(&[seed]);
+    }
+
+    /// Compute the accelerations, using the given `velocities` for the
+    /// dissipative forces.
+    fn update_accelerations(&mut self, system: &System, velocities: &[Vector3D]) {
+        let forces = system.forces();
+        let dissipative = self.dissipation.forces(system, velocities, self.timestep);
+        for (&mass, acceleration, force, dissipative) in system.particles().zip(
+            (&Mass, &mut self.accelerations, forces, dissipative)
+        ) {
+            *acceleration = (force + dissipative) / mass;
+        }
+    }
+}
+
+impl Integrator for DPDVelocityVerlet {
+    fn setup(&mut self, system: &System) {
+        if !system.constraints().is_empty() {
+            warn!("Distance constraints are ignored by the DPD integrator");
+        }
+        self.accelerations = vec![Vector3D::zero(); system.size()];
+        let velocities = system.particles().velocity.to_vec();
+        self.update_accelerations(system, &velocities);
+    }
+
+    fn integrate(&mut self, system: &mut System) {
+        let dt = self.timestep;
+        let lambda = self.lambda;
+
+        // Update positions at t + ∆t, velocities at t + ∆t/2, and predict the
+        // velocities at t + ∆t
+        let mut predicted = Vec::with_capacity(system.size());
+        for (position, velocity, acceleration, &frozen) in system.particles_mut().zip_mut(
+            (&mut Position, &mut Velocity, &self.accelerations, &Frozen)
+        ) {
+            if frozen {
+                predicted.push(Vector3D::zero());
+                continue;
+            }
+            *position += dt * (*velocity) + 0.5 * dt * dt * acceleration;
+            predicted.push(*velocity + lambda * dt * acceleration);
+            *velocity += 0.5 * dt * acceleration;
+        }
+
+        self.update_accelerations(system, &predicted);
+
+        // Update velocities at t + ∆t
+        for (velocity, acceleration, &frozen) in system.particles_mut().zip_mut(
+            (&mut Velocity, &self.accelerations, &Frozen)
+        ) {
+            if frozen {
+                continue;
+            }
+            *velocity += 0.5 * dt * acceleration;
+        }
+    }
+}
+
+/******************************************************************************/
+/// Dissipative particle dynamics thermostat.
+///
+/// This thermostat applies the dissipative and random pair forces from
+/// dissipative particle dynamics to the velocities after every integration
+/// step. It can be used with any integrator and any potential, and conserves
+/// the momentum of the system.
+pub struct DPDThermostat {
+    /// Timestep of the integrator
+    timestep: f64,
+    /// Dissipative and random forces
+    dissipation: Dissipation,
+}
+
+impl DPDThermostat {
+    /// Create a new `DPDThermostat` acting at temperature `temperature`, for
+    /// an integrator with the given `timestep`. `gamma` is the friction
+    /// coefficient and `cutoff` the cutoff distance for the dissipative and
+    /// random forces.
+    pub fn new(timestep: f64, temperature: f64, gamma: f64, cutoff: f64) -> DPDThermostat {
+        DPDThermostat {
+            timestep: timestep,
+            dissipation: Dissipation::new(temperature, gamma, cutoff),
+        }
+    }
+
+    /// Set the seed of the random number generator. The default seed is 42.
+    pub fn seed(&mut self, seed: u64) {
+        self.dissipation.rng.reseed(&[seed]);
+    }
+}
+
+impl Control for DPDThermostat {
+    fn control(&mut self, system: &mut System) {
+        let dt = self.timestep;
+        let velocities = system.particles().velocity.to_vec();
+        let forces = self.dissipation.forces(system, &velocities, dt);
+        for (&mass, velocity, force, &frozen) in system.particles_mut().zip_mut(
+            (&Mass, &mut Velocity, forces, &Frozen)
+        ) {
+            if frozen {
+                continue;
+            }
+            *velocity += dt * force / mass;
+        }
+    }
+}
+
+impl Thermostat for DPDThermostat {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sys::{Particle, UnitCell};
+    use utils::unit_from;
+
+    fn testing_system() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        for i in 0..5 {
+            for j in 0..5 {
+                for k in 0..5 {
+                    let position = Vector3D::new(i as f64, j as f64, k as f64) * 2.0;
+                    system.add_particle(Particle::with_position("Ar", position));
+                }
+            }
+        }
+        return system;
+    }
+
+    fn momentum(system: &System) -> Vector3D {
+        let mut momentum = Vector3D::zero();
+        for (&mass, velocity) in system.particles().zip((&Mass, &Velocity)) {
+            momentum += mass * velocity;
+        }
+        return momentum;
+    }
+
+    #[test]
+    fn momentum_conservation() {
+        let mut system = testing_system();
+        let mut integrator = DPDVelocityVerlet::new(
+            unit_from(10.0, "fs"), 300.0, unit_from(1.0, "u/fs"), 3.0
+        );
+        integrator.setup(&system);
+        for _ in 0..100 {
+            integrator.integrate(&mut system);
+        }
+
+        let momentum = momentum(&system);
+        assert!(momentum.norm() < 1e-10);
+    }
+
+    #[test]
+    fn thermostat() {
+        let mut system = testing_system();
+        let temperature = unit_from(300.0, "K");
+        let mut thermostat = DPDThermostat::new(
+            unit_from(10.0, "fs"), temperature, unit_from(1.0, "u/fs"), 3.0
+        );
+
+        let mut mean = 0.0;
+        for step in 0..2000 {
+            thermostat.control(&mut system);
+            if step >= 1000 {
+                mean += system.temperature();
+            }
+        }
+        mean /= 1000.0;
+
+        assert!(momentum(&system).norm() < 1e-10);
+        assert_ulps_eq!(mean / temperature, 1.0, epsilon=0.1);
+    }
+}
@@ -1,27 +1,57 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
+use consts::K_BOLTZMANN;
 use types::{Vector3D, Matrix3, One, Zero};
-use sys::System;
+use sys::{System, DegreesOfFreedom};
 use sys::zip_particle::*;
+use sys::compute::{Compute, ComponentForces, ForceComponent};
+use sim::{TemperatureStrategy, Schedule};
+
+use super::Shake;
 
 /// The `Integrator` trait define integrator interface for molecular dynamics.
 /// An integrator is an algorithm responsible for propagating the equations of
 /// motion in the system.
-pub trait Integrator {
+pub trait Integrator: Send {
     /// Setup the integrator. This function is called once by every simulation
     /// run.
     fn setup(&mut self, _: &System) {}
+    /// Update the integrator after the system was replaced by a different
+    /// configuration during a simulation, for example by an exchange in a
+    /// replica exchange simulation. Only the data depending on the
+    /// configuration should be updated, such as the stored forces. The
+    /// default is to setup the integrator again.
+    fn system_changed(&mut self, system: &System) {
+        self.setup(system);
+    }
     /// Integrate the equations of motion. This is called at every step of the
     /// simulation.
     fn integrate(&mut self, system: &mut System);
+    /// Get the kind of degrees of freedom propagated by this integrator. The
+    /// default is to propagate all the particles independently.
+    fn degrees_of_freedom(&self) -> DegreesOfFreedom {
+        DegreesOfFreedom::Particles
+    }
+    /// Get the temperature computation strategy for this integrator. The
+    /// default is to use the instantaneous velocities.
+    fn temperature_strategy(&self) -> TemperatureStrategy {
+        TemperatureStrategy::Velocities
+    }
 }
 
 /// Velocity-Verlet integrator. This one is reversible and symplectic.
+///
+/// If the system contains distance constraints, they are enforced using the
+/// RATTLE algorithm.
 pub struct VelocityVerlet {
     /// Timestep for the integrator
     timestep: f64,
     /// Storing the accelerations
-    accelerations: Vec<Vector3D>
+    accelerations: Vec<Vector3D>,
+    /// Constraints solver
+    shake: Shake,
+    /// Positions at the beginning of the step, used by the constraints solver
+    previous: Vec<Vector3D>,
 }
 
 impl VelocityVerlet {
@@ -30,26 +60,56 @@ impl VelocityVerlet {
         VelocityVerlet{
             timestep: timestep,
             accelerations: Vec::new(),
+            shake: Shake::new(),
+            previous: Vec::new(),
         }
     }
+
+    /// Use the `shake` solver to enforce the constraints in the system.
+    pub fn set_shake(&mut self, shake: Shake) {
+        self.shake = shake;
+    }
 }
 
 impl Integrator for VelocityVerlet {
     fn setup(&mut self, system: &System) {
         self.accelerations = vec![Vector3D::zero(); system.size()];
+        self.shake.setup(system);
+    }
+
+    fn system_changed(&mut self, system: &System) {
+        self.setup(system);
+        update_accelerations(system, &mut self.accelerations);
     }
 
     fn integrate(&mut self, system: &mut System) {
         let dt = self.timestep;
+        let constrained = !self.shake.is_empty();
+        if constrained {
+            self.previous = system.particles().position.to_vec();
+        }
 
         // Update velocities at t + ∆t/2 and positions at t + ∆t
-        for (position, velocity, acceleration) in system.particles_mut().zip_mut(
-            (&mut Position, &mut Velocity, &self.accelerations)
+        for (position, velocity, acceleration, &frozen) in system.particles_mut().zip_mut(
+            (&mut Position, &mut Velocity, &self.accelerations, &Frozen)
         ) {
+            if frozen {
+                continue;
+            }
             *velocity += 0.5 * dt * acceleration;
             *position += velocity * dt;
         }
 
+        if constrained {
+            let _ = self.shake.positions(system, &self.previous, dt);
+            // Update the velocities at t + ∆t/2 with the constraints forces
+            for (position, velocity, previous) in system.particles_mut().zip_mut(
+                (&Position, &mut Velocity, &self.previous)
+            ) {
+                *velocity = (position - previous) / dt;
+            }
+        }
+
         let forces = system.forces();
         // Update accelerations at t + ∆t
         for (&mass, acceleration, force) in system.particles().zip(
@@ -59,21 +119,44 @@ impl Integrator for VelocityVerlet {
         }
 
         // Update velocities at t + ∆t
-        for (velocity, acceleration) in system.particles_mut().zip_mut(
-            (&mut Velocity, &self.accelerations)
+        for (velocity, acceleration, &frozen) in system.particles_mut().zip_mut(
+            (&mut Velocity, &self.accelerations, &Frozen)
         ) {
+            if frozen {
+                continue;
+            }
             *velocity += 0.5 * dt * acceleration;
         }
+
+        if constrained {
+            // The velocities are updated over half a timestep
+            let virial = 2.0 * self.shake.velocities(system, dt);
+            system.set_constraints_virial(virial);
+        }
+    }
+}
+
+/// Set the `accelerations` from the forces acting on the particles in the
+/// `system`
+fn update_accelerations(system: &System, accelerations: &mut [Vector3D]) {
+    let forces = system.forces();
+    for (acceleration, force, &mass) in izip!(accelerations, &forces, system.particles().mass) {
+        *acceleration = force / mass;
     }
 }
 
 /******************************************************************************/
 /// Verlet integrator. This one is reversible and symplectic.
+///
+/// If the system contains distance constraints, they are enforced using the
+/// SHAKE algorithm.
 pub struct Verlet {
     /// Timestep for the integrator
     timestep: f64,
     /// Previous positions
     prevpos: Vec<Vector3D>,
+    /// Constraints solver
+    shake: Shake,
 }
 
 impl Verlet {
@@ -82,20 +165,31 @@ impl Verlet {
         Verlet{
             timestep: timestep,
             prevpos: Vec::new(),
+            shake: Shake::new(),
         }
     }
+
+    /// Use the `shake` solver to enforce the constraints in the system.
+    pub fn set_shake(&mut self, shake: Shake) {
+        self.shake = shake;
+    }
 }
 
 impl Integrator for Verlet {
     fn setup(&mut self, system: &System) {
         self.prevpos = vec![Vector3D::zero(); system.size()];
+        self.shake.setup(system);
 
         let dt = self.timestep;
         // Approximate the positions at t - ∆t
-        for (position, velocity, prevpos) in system.particles().zip(
-            (&Position, &Velocity, &mut self.prevpos)
+        for (position, velocity, prevpos, &frozen) in system.particles().zip(
+            (&Position, &Velocity, &mut self.prevpos, &Frozen)
         ) {
-            *prevpos = position - velocity * dt;
+            if frozen {
+                *prevpos = *position;
+            } else {
+                *prevpos = position - velocity * dt;
+            }
         }
     }
 
@@ -104,10 +198,16 @@ impl Integrator for Verlet {
         let dt = self.timestep;
         let dt2 = dt * dt;
 
-        let mut particles = system.particles_mut();
-        for (position, velocity, mass, prevpos, force) in particles.zip_mut(
-            (&mut Position, &mut Velocity, &Mass, &mut self.prevpos, forces)
+        // Positions at t - ∆t are needed to compute the constrained velocities
+        let constrained = !self.shake.is_empty();
+        let before = if constrained {self.prevpos.clone()} else {Vec::new()};
+
+        for (position, velocity, mass, prevpos, force, &frozen) in system.particles_mut().zip_mut(
+            (&mut Position, &mut Velocity, &Mass, &mut self.prevpos, forces, &Frozen)
         ) {
+            if frozen {
+                continue;
+            }
             // Save positions at t
             let tmp = position.clone();
             // Update positions at t + ∆t
@@ -117,16 +217,34 @@ impl Integrator for Verlet {
             // Update saved position
             *prevpos = tmp;
         }
+
+        if constrained {
+            let virial = self.shake.positions(system, &self.prevpos, dt);
+            system.set_constraints_virial(virial);
+            // Update velocities at t with the constrained positions
+            for (position, velocity, before) in system.particles_mut().zip_mut(
+                (&Position, &mut Velocity, &before)
+            ) {
+                *velocity = (position - before) / (2.0 * dt);
+            }
+        }
     }
 }
 
 /******************************************************************************/
 /// Leap-frog integrator. This one is reversible and symplectic.
+///
+/// If the system contains distance constraints, they are enforced using the
+/// SHAKE algorithm.
 pub struct LeapFrog {
     /// Timestep for the integrator
     timestep: f64,
     /// Storing the accelerations
-    accelerations: Vec<Vector3D>
+    accelerations: Vec<Vector3D>,
+    /// Constraints solver
+    shake: Shake,
+    /// Positions at the beginning of the step, used by the constraints solver
+    previous: Vec<Vector3D>,
 }
 
 impl LeapFrog {
@@ -135,29 +253,68 @@ impl LeapFrog {
         LeapFrog{
             timestep: timestep,
             accelerations: Vec::new(),
+            shake: Shake::new(),
+            previous: Vec::new(),
         }
     }
+
+    /// Use the `shake` solver to enforce the constraints in the system.
+    pub fn set_shake(&mut self, shake: Shake) {
+        self.shake = shake;
+    }
 }
 
 impl Integrator for LeapFrog {
     fn setup(&mut self, system: &System) {
         self.accelerations = vec![Vector3D::zero(); system.size()];
+        self.shake.setup(system);
+    }
+
+    fn system_changed(&mut self, system: &System) {
+        self.setup(system);
+        update_accelerations(system, &mut self.accelerations);
     }
 
     fn integrate(&mut self, system: &mut System) {
         let dt = self.timestep;
         let dt2 = dt * dt;
+        let constrained = !self.shake.is_empty();
+        if constrained {
+            self.previous = system.particles().position.to_vec();
+        }
 
-        for (position, velocity, acceleration) in system.particles_mut().zip_mut(
-            (&mut Position, &Velocity, &self.accelerations)
+        for (position, velocity, acceleration, &frozen) in system.particles_mut().zip_mut(
+            (&mut Position, &Velocity, &self.accelerations, &Frozen)
         ) {
+            if frozen {
+                continue;
+            }
             *position += velocity * dt + 0.5 * acceleration * dt2;
         }
 
+        if constrained {
+            // The positions are updated with half of the constraints forces
+            let virial = 2.0 * self.shake.positions(system, &self.previous, dt);
+            system.set_constraints_virial(virial);
+            // Add the constraints forces contribution to the velocities
+            for (position, velocity, acceleration, previous, &frozen) in system.particles_mut().zip_mut(
+                (&Position, &mut Velocity, &self.accelerations, &self.previous, &Frozen)
+            ) {
+                if frozen {
+                    continue;
+                }
+                let unconstrained = previous + (*velocity) * dt + 0.5 * acceleration * dt2;
+                *velocity += (position - unconstrained) / dt;
+            }
+        }
+
         let forces = system.forces();
-        for (velocity, &mass, acceleration, force) in system.particles_mut().zip_mut(
-            (&mut Velocity, &Mass, &mut self.accelerations, &forces)
+        for (velocity, &mass, acceleration, force, &frozen) in system.particles_mut().zip_mut(
+            (&mut Velocity, &Mass, &mut self.accelerations, &forces, &Frozen)
         ) {
+            if frozen {
+                continue;
+            }
             let new_acceleration = force / mass;
             *velocity += 0.5 * ((*acceleration) + new_acceleration) * dt;
             *acceleration = new_acceleration;
@@ -172,17 +329,27 @@ const WATER_COMPRESSIBILITY: f64 = 7372.0;
 
 /// Berendsen barostat integrator based on velocity-Verlet. This one neither
 /// reversible nor symplectic.
+///
+/// If the system contains distance constraints, they are enforced using the
+/// RATTLE algorithm.
 pub struct BerendsenBarostat {
     /// Timestep for the integrator
     timestep: f64,
     /// Target pressure for the barostat
     pressure: f64,
+    /// Optional schedule for the target pressure
+    schedule: Option<Schedule>,
     /// Barostat time scale, expressed in units of the timestep.
     tau: f64,
     /// Storing the accelerations
     accelerations: Vec<Vector3D>,
     /// Storing the scaling factor
     eta: f64,
+    /// Constraints solver
+    shake: Shake,
+    /// Scaled positions at the beginning of the step, used by the constraints
+    /// solver
+    previous: Vec<Vector3D>,
 }
 
 impl BerendsenBarostat {
@@ -193,58 +360,125 @@ impl BerendsenBarostat {
         BerendsenBarostat{
             timestep: timestep,
             pressure: pressure,
+            schedule: None,
             tau: tau,
             accelerations: Vec::new(),
             eta: 1.0,
+            shake: Shake::new(),
+            previous: Vec::new(),
+        }
+    }
+
+    /// Use the `shake` solver to enforce the constraints in the system.
+    pub fn set_shake(&mut self, shake: Shake) {
+        self.shake = shake;
+    }
+
+    /// Use the given `schedule` to set the target pressure as a function of
+    /// the simulation step.
+    pub fn set_pressure_schedule(&mut self, schedule: Schedule) {
+        self.schedule = Some(schedule);
+    }
+
+    /// Update the scaling factor from the pressure of the `system`
+    fn update_eta(&mut self, system: &System) {
+        if let Some(ref schedule) = self.schedule {
+            self.pressure = schedule.value(system.step());
         }
+        self.eta = f64::cbrt(1.0 - WATER_COMPRESSIBILITY / self.tau * (self.pressure - system.pressure()));
     }
 }
 
 impl Integrator for BerendsenBarostat {
     fn setup(&mut self, system: &System) {
         self.accelerations = vec![Vector3D::zero(); system.size()];
+        self.shake.setup(system);
+    }
+
+    fn system_changed(&mut self, system: &System) {
+        self.setup(system);
+        update_accelerations(system, &mut self.accelerations);
+        self.update_eta(system);
     }
 
     fn integrate(&mut self, system: &mut System) {
         let dt = self.timestep;
+        let constrained = !self.shake.is_empty();
+        if constrained {
+            let eta = self.eta;
+            self.previous = system.particles().position.iter().map(|position| eta * position).collect();
+        }
 
         // Update velocities at t + ∆t/2 and positions at t + ∆t
-        for (position, velocity, acceleration) in system.particles_mut().zip_mut(
-            (&mut Position, &mut Velocity, &self.accelerations)
+        for (position, velocity, acceleration, &frozen) in system.particles_mut().zip_mut(
+            (&mut Position, &mut Velocity, &self.accelerations, &Frozen)
         ) {
+            if frozen {
+                continue;
+            }
             *velocity += 0.5 * dt * acceleration;
             // Scale all positions
             *position *= self.eta;
             *position += velocity * dt;
         }
 
+        if constrained {
+            let _ = self.shake.positions(system, &self.previous, dt);
+            // Update the velocities at t + ∆t/2 with the constraints forces
+            for (position, velocity, previous) in system.particles_mut().zip_mut(
+                (&Position, &mut Velocity, &self.previous)
+            ) {
+                *velocity = (position - previous) / dt;
+            }
+        }
+
         system.cell.scale_mut(self.eta * self.eta * self.eta * Matrix3::one());
-        self.eta = f64::cbrt(1.0 - WATER_COMPRESSIBILITY / self.tau * (self.pressure - system.pressure()));
+        self.update_eta(system);
 
         let forces = system.forces();
         // Update accelerations at t + ∆t and velocities at t + ∆t
-        for (velocity, &mass, acceleration, force) in system.particles_mut().zip_mut(
-            (&mut Velocity, &Mass, &mut self.accelerations, &forces)
+        for (velocity, &mass, acceleration, force, &frozen) in system.particles_mut().zip_mut(
+            (&mut Velocity, &Mass, &mut self.accelerations, &forces, &Frozen)
         ) {
+            if frozen {
+                continue;
+            }
             *acceleration = force / mass;
             *velocity += 0.5 * dt * acceleration;
         }
+
+        if constrained {
+            // The velocities are updated over half a timestep
+            let virial = 2.0 * self.shake.velocities(system, dt);
+            system.set_constraints_virial(virial);
+        }
     }
 }
 
 /// Anisotropic Berendsen barostat integrator based on velocity-Verlet. This one
 /// neither reversible nor symplectic.
+///
+/// If the system contains distance constraints, they are enforced using the
+/// RATTLE algorithm.
 pub struct AnisoBerendsenBarostat {
     /// Timestep for the integrator
     timestep: f64,
     /// Target stress matrix for the barostat
     stress: Matrix3,
+    /// Optional schedule for the target pressure, used to set an hydrostatic
+    /// target stress
+    schedule: Option<Schedule>,
     /// Barostat time scale, expressed in units of the timestep
     tau: f64,
     /// Storing the accelerations
     accelerations: Vec<Vector3D>,
     /// Storing the scaling factor
     eta: Matrix3,
+    /// Constraints solver
+    shake: Shake,
+    /// Scaled positions at the beginning of the step, used by the constraints
+    /// solver
+    previous: Vec<Vector3D>,
 }
 
 impl AnisoBerendsenBarostat {
@@ -255,9 +489,12 @@ impl AnisoBerendsenBarostat {
         AnisoBerendsenBarostat{
             timestep: timestep,
             stress: stress,
+            schedule: None,
             tau: tau,
             accelerations: Vec::new(),
             eta: Matrix3::one(),
+            shake: Shake::new(),
+            previous: Vec::new(),
         }
     }
 
@@ -267,46 +504,575 @@ impl AnisoBerendsenBarostat {
     pub fn hydrostatic(timestep: f64, pressure: f64, tau: f64) -> AnisoBerendsenBarostat {
         AnisoBerendsenBarostat::new(timestep, pressure * Matrix3::one(), tau)
     }
+
+    /// Use the `shake` solver to enforce the constraints in the system.
+    pub fn set_shake(&mut self, shake: Shake) {
+        self.shake = shake;
+    }
+
+    /// Use the given `schedule` to set an hydrostatic target stress matrix
+    /// from the target pressure, as a function of the simulation step.
+    pub fn set_pressure_schedule(&mut self, schedule: Schedule) {
+        self.schedule = Some(schedule);
+    }
+
+    /// Update the scaling matrix from the stress of the `system`
+    fn update_eta(&mut self, system: &System) {
+        let factor = self.timestep * WATER_COMPRESSIBILITY / self.tau;
+        if let Some(ref schedule) = self.schedule {
+            self.stress = schedule.value(system.step()) * Matrix3::one();
+        }
+        self.eta = Matrix3::one() - factor * (self.stress - system.stress());
+
+        // Make the eta matrix symmetric here
+        for i in 0..3 {
+            for j in 0..i {
+                self.eta[(i, j)] = 0.5 * (self.eta[(i, j)] + self.eta[(j, i)]);
+                self.eta[(j, i)] = self.eta[(i, j)];
+            }
+        }
+    }
 }
 
 impl Integrator for AnisoBerendsenBarostat {
     fn setup(&mut self, system: &System) {
         self.accelerations = vec![Vector3D::zero(); system.size()];
+        self.shake.setup(system);
+    }
+
+    fn system_changed(&mut self, system: &System) {
+        self.setup(system);
+        update_accelerations(system, &mut self.accelerations);
+        self.update_eta(system);
     }
 
     fn integrate(&mut self, system: &mut System) {
         let dt = self.timestep;
+        let constrained = !self.shake.is_empty();
+        if constrained {
+            let eta = self.eta;
+            self.previous = system.particles().position.iter().map(|position| eta * position).collect();
+        }
 
         // Update velocities at t + ∆t/2 and positions at t + ∆t
-        for (position, velocity, acceleration) in system.particles_mut().zip_mut(
-            (&mut Position, &mut Velocity, &self.accelerations)
+        for (position, velocity, acceleration, &frozen) in system.particles_mut().zip_mut(
+            (&mut Position, &mut Velocity, &self.accelerations, &Frozen)
         ) {
+            if frozen {
+                continue;
+            }
             *velocity += 0.5 * dt * acceleration;
             // Scale all positions
             *position = self.eta * (*position);
             *position += velocity * dt;
         }
 
+        if constrained {
+            let _ = self.shake.positions(system, &self.previous, dt);
+            // Update the velocities at t + ∆t/2 with the constraints forces
+            for (position, velocity, previous) in system.particles_mut().zip_mut(
+                (&Position, &mut Velocity, &self.previous)
+            ) {
+                *velocity = (position - previous) / dt;
+            }
+        }
+
         system.cell.scale_mut(self.eta);
+        self.update_eta(system);
 
-        let factor = self.timestep * WATER_COMPRESSIBILITY / self.tau;
-        self.eta = Matrix3::one() - factor * (self.stress - system.stress());
+        let forces = system.forces();
+        // Update accelerations at t + ∆t and velocities at t + ∆t
+        for (&mass, velocity, acceleration, force, &frozen) in system.particles_mut().zip_mut(
+            (&Mass, &mut Velocity, &mut self.accelerations, &forces, &Frozen)
+        ) {
+            if frozen {
+                continue;
+            }
+            *acceleration = force / mass;
+            *velocity += 0.5 * dt * acceleration;
+        }
 
-        // Make the eta matrix symmetric here
+        if constrained {
+            // The velocities are updated over half a timestep
+            let virial = 2.0 * self.shake.velocities(system, dt);
+            system.set_constraints_virial(virial);
+        }
+    }
+}
+
+/******************************************************************************/
+/// A chain of Nosé-Hoover thermostats, coupling some degrees of freedom to an
+/// heat bath at a given temperature.
+struct NoseHooverChain {
+    /// Target temperature of the heat bath
+    temperature: f64,
+    /// Time scale of the thermostats
+    tau: f64,
+    /// Number of degrees of freedom coupled to the chain
+    dof: f64,
+    /// Masses of the thermostats in the chain
+    masses: Vec<f64>,
+    /// Positions of the thermostats in the chain
+    positions: Vec<f64>,
+    /// Velocities of the thermostats in the chain
+    velocities: Vec<f64>,
+}
+
+impl NoseHooverChain {
+    /// Create a new chain with `length` thermostats at `temperature`, with the
+    /// time scale `tau`.
+    fn new(temperature: f64, tau: f64, length: usize) -> NoseHooverChain {
+        NoseHooverChain {
+            temperature: temperature,
+            tau: tau,
+            dof: 0.0,
+            masses: vec![0.0; length],
+            positions: vec![0.0; length],
+            velocities: vec![0.0; length],
+        }
+    }
+
+    /// Reset the chain for a new simulation, coupling `dof` degrees of freedom
+    /// to the heat bath.
+    fn setup(&mut self, dof: f64) {
+        let kt = K_BOLTZMANN * self.temperature;
+        let tau2 = self.tau * self.tau;
+        self.dof = dof;
+        for (i, mass) in self.masses.iter_mut().enumerate() {
+            *mass = if i == 0 {dof * kt * tau2} else {kt * tau2};
+        }
+        for position in &mut self.positions {
+            *position = 0.0;
+        }
+        for velocity in &mut self.velocities {
+            *velocity = 0.0;
+        }
+    }
+
+    /// Get the force acting on the thermostat at index `i` in the chain, when
+    /// the coupled degrees of freedom have twice the kinetic energy `kinetic`.
+    fn force(&self, i: usize, kinetic: f64) -> f64 {
+        let kt = K_BOLTZMANN * self.temperature;
+        if i == 0 {
+            (kinetic - self.dof * kt) / self.masses[0]
+        } else {
+            let previous = self.masses[i - 1] * self.velocities[i - 1] * self.velocities[i - 1];
+            (previous - kt) / self.masses[i]
+        }
+    }
+
+    /// Propagate the chain for half of the timestep `dt`, when the coupled
+    /// degrees of freedom have twice the kinetic energy `kinetic`. This
+    /// function returns the scaling factor to apply to the coupled velocities.
+    fn propagate(&mut self, kinetic: f64, dt: f64) -> f64 {
+        let last = self.masses.len() - 1;
+
+        self.velocities[last] += 0.25 * dt * self.force(last, kinetic);
+        for i in (0..last).rev() {
+            let scaling = f64::exp(-0.125 * dt * self.velocities[i + 1]);
+            let force = self.force(i, kinetic);
+            self.velocities[i] = self.velocities[i] * scaling * scaling + 0.25 * dt * force * scaling;
+        }
+
+        let factor = f64::exp(-0.5 * dt * self.velocities[0]);
+        let kinetic = kinetic * factor * factor;
+        for (position, velocity) in self.positions.iter_mut().zip(&self.velocities) {
+            *position += 0.5 * dt * velocity;
+        }
+
+        for i in 0..last {
+            let scaling = f64::exp(-0.125 * dt * self.velocities[i + 1]);
+            let force = self.force(i, kinetic);
+            self.velocities[i] = self.velocities[i] * scaling * scaling + 0.25 * dt * force * scaling;
+        }
+        self.velocities[last] += 0.25 * dt * self.force(last, kinetic);
+
+        return factor;
+    }
+
+    /// Get the energy of the thermostats in the chain
+    fn energy(&self) -> f64 {
+        let kt = K_BOLTZMANN * self.temperature;
+        let mut energy = 0.0;
+        for i in 0..self.masses.len() {
+            energy += 0.5 * self.masses[i] * self.velocities[i] * self.velocities[i];
+            if i == 0 {
+                energy += self.dof * kt * self.positions[i];
+            } else {
+                energy += kt * self.positions[i];
+            }
+        }
+        return energy;
+    }
+}
+
+/// Compute `sinh(x) / x`, using a Taylor expansion for small values of `x`.
+fn sinhc(x: f64) -> f64 {
+    if f64::abs(x) < 1e-4 {
+        let x2 = x * x;
+        1.0 + x2 / 6.0 + x2 * x2 / 120.0
+    } else {
+        f64::sinh(x) / x
+    }
+}
+
+/// Martyna-Tobias-Klein integrator, for time-reversible NPT molecular dynamics.
+///
+/// This integrator uses the equations of motion from Martyna, Tobias and Klein
+/// [1], where both the particles and the barostat are coupled to separate
+/// Nosé-Hoover chains of thermostats [2]. The equations of motion are
+/// integrated with the reversible Trotter factorization of Tuckerman et al.
+/// [3]. The cell can either fluctuate isotropically, or with independent
+/// fluctuations of the three cell directions.
+///
+/// This integrator already contains a thermostat, and should not be used
+/// together with another thermostat. The energy of the thermostats and barostat
+/// variables is stored in the system (see `System::extended_energy`), and the
+/// conserved quantity is given by `System::conserved_energy`.
+///
+/// [1] G. J. Martyna, D. J. Tobias and M. L. Klein, J. Chem. Phys. 101, 4177 (1994); doi: 10.1063/1.467468
+///
+/// [2] G. J. Martyna, M. L. Klein and M. Tuckerman, J. Chem. Phys. 97, 2635 (1992); doi: 10.1063/1.463940
+///
+/// [3] M. E. Tuckerman et al., J. Phys. A: Math. Gen. 39, 5629 (2006); doi: 10.1088/0305-4470/39/19/S18
+pub struct MartynaTobiasKlein {
+    /// Timestep for the integrator
+    timestep: f64,
+    /// Target pressure for the barostat
+    pressure: f64,
+    /// Optional schedule for the target pressure
+    schedule: Option<Schedule>,
+    /// Barostat time scale, expressed in units of the timestep
+    tau: f64,
+    /// Should the three cell directions fluctuate independently?
+    anisotropic: bool,
+    /// Mass of the barostat, for each cell direction
+    mass: f64,
+    /// Velocity of the barostat, i.e. the rate of change of the logarithm of
+    /// the cell lengths in each direction
+    velocity: Vector3D,
+    /// Thermostats chain for the particles
+    thermostat: NoseHooverChain,
+    /// Thermostats chain for the barostat
+    barostat_thermostat: NoseHooverChain,
+    /// Forces at the current step
+    forces: Vec<Vector3D>,
+    /// Virial at the current step
+    virial: Matrix3,
+}
+
+impl MartynaTobiasKlein {
+    /// Create a new isotropic Martyna-Tobias-Klein integrator with an
+    /// integration timestep of `timestep`, a target temperature of
+    /// `temperature` and a target pressure of `pressure`. `tau_t` and `tau_p`
+    /// are the time scales of the thermostats and the barostat, expressed in
+    /// units of the timestep.
+    pub fn isotropic(timestep: f64, temperature: f64, pressure: f64, tau_t: f64, tau_p: f64) -> MartynaTobiasKlein {
+        MartynaTobiasKlein::create(timestep, temperature, pressure, tau_t, tau_p, false)
+    }
+
+    /// Create a new anisotropic Martyna-Tobias-Klein integrator, where the
+    /// three cell directions fluctuate independently. The parameters are the
+    /// same as for `MartynaTobiasKlein::isotropic`.
+    pub fn anisotropic(timestep: f64, temperature: f64, pressure: f64, tau_t: f64, tau_p: f64) -> MartynaTobiasKlein {
+        MartynaTobiasKlein::create(timestep, temperature, pressure, tau_t, tau_p, true)
+    }
+
+    /// Factorizing the constructors
+    fn create(timestep: f64, temperature: f64, pressure: f64, tau_t: f64, tau_p: f64, anisotropic: bool) -> MartynaTobiasKlein {
+        assert!(temperature >= 0.0, "The temperature must be positive in Martyna-Tobias-Klein integrator.");
+        assert!(tau_t > 0.0 && tau_p > 0.0, "The time scales must be positive in Martyna-Tobias-Klein integrator.");
+        MartynaTobiasKlein {
+            timestep: timestep,
+            pressure: pressure,
+            schedule: None,
+            tau: tau_p,
+            anisotropic: anisotropic,
+            mass: 0.0,
+            velocity: Vector3D::zero(),
+            thermostat: NoseHooverChain::new(temperature, tau_t * timestep, 3),
+            barostat_thermostat: NoseHooverChain::new(temperature, tau_t * timestep, 3),
+            forces: Vec::new(),
+            virial: Matrix3::zero(),
+        }
+    }
+
+    /// Set the number of thermostats in the Nosé-Hoover chains coupled to the
+    /// particles and the barostat. The default is to use 3 thermostats.
+    pub fn set_chain_length(&mut self, length: usize) {
+        assert!(length > 0, "The chain length must be positive in Martyna-Tobias-Klein integrator.");
+        let temperature = self.thermostat.temperature;
+        let tau = self.thermostat.tau;
+        self.thermostat = NoseHooverChain::new(temperature, tau, length);
+        self.barostat_thermostat = NoseHooverChain::new(temperature, tau, length);
+    }
+
+    /// Use the given `schedule` to set the target pressure as a function of
+    /// the simulation step. The conserved energy is not conserved while the
+    /// target pressure changes.
+    pub fn set_pressure_schedule(&mut self, schedule: Schedule) {
+        self.schedule = Some(schedule);
+    }
+
+    /// Propagate the thermostats chain of the barostat for half a timestep
+    fn thermostat_barostat(&mut self) {
+        let kinetic = self.mass * self.velocity.norm2();
+        let factor = self.barostat_thermostat.propagate(kinetic, self.timestep);
+        self.velocity *= factor;
+    }
+
+    /// Propagate the thermostats chain of the particles for half a timestep
+    fn thermostat_particles(&mut self, system: &mut System) {
+        let kinetic = 2.0 * system.kinetic_energy();
+        let factor = self.thermostat.propagate(kinetic, self.timestep);
+        for velocity in system.particles_mut().velocity {
+            *velocity *= factor;
+        }
+    }
+
+    /// Update the barostat velocity for half a timestep
+    fn update_barostat(&mut self, system: &System) {
+        let dof = system.degrees_of_freedom() as f64;
+        let mut kinetic = Matrix3::zero();
+        for (&mass, velocity) in system.particles().zip((&Mass, &Velocity)) {
+            kinetic += mass * velocity.tensorial(velocity);
+        }
+
+        let volume = system.volume();
+        let coupling = kinetic.trace() / dof;
+        let mut force = Vector3D::zero();
         for i in 0..3 {
-            for j in 0..i {
-                self.eta[(i, j)] = 0.5 * (self.eta[(i, j)] + self.eta[(j, i)]);
-                self.eta[(j, i)] = self.eta[(i, j)];
+            force[i] = coupling + kinetic[(i, i)] + self.virial[(i, i)] - volume * self.pressure;
+        }
+
+        if !self.anisotropic {
+            let mean = (force[0] + force[1] + force[2]) / 3.0;
+            force = Vector3D::new(mean, mean, mean);
+        }
+
+        self.velocity += 0.5 * self.timestep * force / self.mass;
+    }
+
+    /// Update the particles velocities for half a timestep
+    fn update_velocities(&self, system: &mut System) {
+        let dt = 0.5 * self.timestep;
+        let dof = system.degrees_of_freedom() as f64;
+        let trace = (self.velocity[0] + self.velocity[1] + self.velocity[2]) / dof;
+
+        let mut scaling = Vector3D::zero();
+        let mut force_scaling = Vector3D::zero();
+        for i in 0..3 {
+            let alpha = self.velocity[i] + trace;
+            scaling[i] = f64::exp(-alpha * dt);
+            force_scaling[i] = dt * f64::exp(-0.5 * alpha * dt) * sinhc(0.5 * alpha * dt);
+        }
+
+        for (velocity, &mass, force, &frozen) in system.particles_mut().zip_mut(
+            (&mut Velocity, &Mass, &self.forces, &Frozen)
+        ) {
+            if frozen {
+                continue;
+            }
+            for i in 0..3 {
+                velocity[i] = velocity[i] * scaling[i] + force_scaling[i] * force[i] / mass;
             }
         }
+    }
 
-        let forces = system.forces();
-        // Update accelerations at t + ∆t and velocities at t + ∆t
-        for (&mass, velocity, acceleration, force) in system.particles_mut().zip_mut(
-            (&Mass, &mut Velocity, &mut self.accelerations, &forces)
+    /// Update the particles positions and the unit cell for a full timestep
+    fn update_positions(&self, system: &mut System) {
+        let dt = self.timestep;
+
+        let mut scaling = Vector3D::zero();
+        let mut velocity_scaling = Vector3D::zero();
+        for i in 0..3 {
+            scaling[i] = f64::exp(self.velocity[i] * dt);
+            velocity_scaling[i] = dt * f64::exp(0.5 * self.velocity[i] * dt) * sinhc(0.5 * self.velocity[i] * dt);
+        }
+
+        for (position, velocity, &frozen) in system.particles_mut().zip_mut(
+            (&mut Position, &Velocity, &Frozen)
+        ) {
+            if frozen {
+                continue;
+            }
+            for i in 0..3 {
+                position[i] = position[i] * scaling[i] + velocity_scaling[i] * velocity[i];
+            }
+        }
+
+        let factor = Matrix3::new(
+            scaling[0], 0.0, 0.0,
+            0.0, scaling[1], 0.0,
+            0.0, 0.0, scaling[2]
+        );
+        system.cell = system.cell.scale(factor);
+    }
+
+    /// Get the energy of the thermostats and barostat variables
+    fn extended_energy(&self, system: &System) -> f64 {
+        let mut energy = 0.5 * self.mass * self.velocity.norm2();
+        energy += self.pressure * system.volume();
+        energy += self.thermostat.energy();
+        energy += self.barostat_thermostat.energy();
+        return energy;
+    }
+}
+
+impl Integrator for MartynaTobiasKlein {
+    fn setup(&mut self, system: &System) {
+        if system.cell.is_infinite() {
+            fatal_error!("Can not use the Martyna-Tobias-Klein integrator with an infinite cell.");
+        }
+
+        let dof = system.degrees_of_freedom() as f64;
+        let kt = K_BOLTZMANN * self.thermostat.temperature;
+        let tau = self.tau * self.timestep;
+        self.mass = (dof + 3.0) * kt * tau * tau / 3.0;
+        self.velocity = Vector3D::zero();
+
+        self.thermostat.setup(dof);
+        let barostat_dof = if self.anisotropic {3.0} else {1.0};
+        self.barostat_thermostat.setup(barostat_dof);
+
+        self.forces = system.forces();
+        self.virial = system.virial();
+    }
+
+    fn system_changed(&mut self, system: &System) {
+        // Keep the state of the thermostats and of the barostat
+        self.forces = system.forces();
+        self.virial = system.virial();
+    }
+
+    fn integrate(&mut self, system: &mut System) {
+        if let Some(ref schedule) = self.schedule {
+            self.pressure = schedule.value(system.step());
+        }
+        self.thermostat_barostat();
+        self.thermostat_particles(system);
+        self.update_barostat(system);
+        self.update_velocities(system);
+
+        self.update_positions(system);
+        self.forces = system.forces();
+        self.virial = system.virial();
+
+        self.update_velocities(system);
+        self.update_barostat(system);
+        self.thermostat_particles(system);
+        self.thermostat_barostat();
+
+        let energy = self.extended_energy(system);
+        system.set_extended_energy(energy);
+    }
+}
+
+/******************************************************************************/
+/// Force components used by the `Respa` integrator, from the slowest to the
+/// fastest.
+const RESPA_LEVELS: [ForceComponent; 3] = [
+    ForceComponent::Global, ForceComponent::Pairs, ForceComponent::Bonded
+];
+
+/// Reversible multiple time-step integrator (r-RESPA), from Tuckerman et al.
+/// (J. Chem. Phys. 97, 1990, 1992).
+///
+/// The forces are split in three components: the slow forces coming from the
+/// coulombic and global potentials, the forces coming from the pair
+/// potentials, and the fast forces coming from the bonded potentials. The
+/// slow forces are integrated with the full timestep, the pair forces with a
+/// fraction of this timestep, and the bonded forces with a fraction of the
+/// pair forces timestep. This allow to compute the expensive long-range
+/// forces less often than the cheap bonded forces.
+///
+/// Distance constraints are not supported by this integrator.
+pub struct Respa {
+    /// Outer timestep for the integrator
+    timestep: f64,
+    /// Number of substeps for each level, relative to the enclosing level
+    substeps: [usize; 3],
+    /// Accelerations coming from each force component
+    accelerations: [Vec<Vector3D>; 3],
+}
+
+impl Respa {
+    /// Create a new integrator with an outer timestep of `timestep`. The pair
+    /// forces are integrated in `pairs_steps` steps for each outer step, and
+    /// the bonded forces are integrated in `bonded_steps` steps for each pair
+    /// forces step.
+    pub fn new(timestep: f64, pairs_steps: usize, bonded_steps: usize) -> Respa {
+        assert!(pairs_steps > 0, "The number of pairs steps must be positive in RESPA");
+        assert!(bonded_steps > 0, "The number of bonded steps must be positive in RESPA");
+        Respa {
+            timestep: timestep,
+            substeps: [1, pairs_steps, bonded_steps],
+            accelerations: [Vec::new(), Vec::new(), Vec::new()],
+        }
+    }
+
+    /// Compute the accelerations coming from the force component at `level`
+    fn update_accelerations(&mut self, system: &System, level: usize) {
+        let forces = ComponentForces{component: RESPA_LEVELS[level]}.compute(system);
+        for (&mass, acceleration, force) in system.particles().zip(
+            (&Mass, &mut self.accelerations[level], forces)
         ) {
             *acceleration = force / mass;
-            *velocity += 0.5 * dt * acceleration;
         }
     }
+
+    /// Update the velocities using the accelerations at `level` for a time
+    /// `dt`
+    fn kick(&self, system: &mut System, level: usize, dt: f64) {
+        for (velocity, acceleration, &frozen) in system.particles_mut().zip_mut(
+            (&mut Velocity, &self.accelerations[level], &Frozen)
+        ) {
+            if frozen {
+                continue;
+            }
+            *velocity += dt * acceleration;
+        }
+    }
+
+    /// Integrate the equations of motion for a time `dt`, using the force
+    /// component at `level` and all the faster components.
+    fn step(&mut self, system: &mut System, level: usize, dt: f64) {
+        self.kick(system, level, 0.5 * dt);
+        if level == RESPA_LEVELS.len() - 1 {
+            for (position, velocity, &frozen) in system.particles_mut().zip_mut(
+                (&mut Position, &Velocity, &Frozen)
+            ) {
+                if frozen {
+                    continue;
+                }
+                *position += velocity * dt;
+            }
+        } else {
+            let substeps = self.substeps[level + 1];
+            let inner_dt = dt / substeps as f64;
+            for _ in 0..substeps {
+                self.step(system, level + 1, inner_dt);
+            }
+        }
+        self.update_accelerations(system, level);
+        self.kick(system, level, 0.5 * dt);
+    }
+}
+
+impl Integrator for Respa {
+    fn setup(&mut self, system: &System) {
+        if !system.constraints().is_empty() {
+            warn!("Distance constraints are ignored by the RESPA integrator");
+        }
+
+        for level in 0..RESPA_LEVELS.len() {
+            self.accelerations[level] = vec![Vector3D::zero(); system.size()];
+            self.update_accelerations(system, level);
+        }
+    }
+
+    fn integrate(&mut self, system: &mut System) {
+        let dt = self.timestep;
+        self.step(system, 0, dt);
+    }
 }
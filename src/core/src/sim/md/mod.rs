@@ -9,11 +9,33 @@ pub use self::integrators::Verlet;
 pub use self::integrators::LeapFrog;
 pub use self::integrators::BerendsenBarostat;
 pub use self::integrators::AnisoBerendsenBarostat;
+pub use self::integrators::MartynaTobiasKlein;
+pub use self::integrators::Respa;
+
+mod constraints;
+pub use self::constraints::Shake;
+
+mod rigid;
+pub use self::rigid::RigidBody;
+
+mod brownian;
+pub use self::brownian::BrownianDynamics;
+
+mod sllod;
+pub use self::sllod::Sllod;
+
+mod dpd;
+pub use self::dpd::{DPDVelocityVerlet, DPDThermostat};
 
 mod controls;
 pub use self::controls::{Control, Thermostat};
 pub use self::controls::{RescaleThermostat, BerendsenThermostat};
 pub use self::controls::{RemoveTranslation, RemoveRotation, Rewrap};
+pub use self::controls::MonteCarloBarostat;
+pub use self::controls::Steering;
+
+mod pimd;
+pub use self::pimd::{PathIntegral, CentroidVirial};
 
 mod molecular_dynamics;
 pub use self::molecular_dynamics::MolecularDynamics;
@@ -1,7 +1,7 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
 
-use sys::System;
+use sys::{System, DegreesOfFreedom, RemovedMotions};
 use sim::{Propagator, TemperatureStrategy};
 
 use super::{Integrator, Control, Thermostat};
@@ -11,8 +11,9 @@ use super::VelocityVerlet;
 pub struct MolecularDynamics {
     /// The integrator we should use to propagate the equations of motion.
     integrator: Box<Integrator>,
-    /// Optional thermostat algorithm
-    thermostat: Option<Box<Thermostat>>,
+    /// Thermostat algorithms, each acting on the whole system or on a group
+    /// of particles.
+    thermostats: Vec<Box<Thermostat>>,
     /// Control algorithms in the simulation.
     controls: Vec<Box<Control>>,
 }
@@ -29,7 +30,7 @@ impl MolecularDynamics {
     pub fn from_integrator(integrator: Box<Integrator>) -> MolecularDynamics {
         MolecularDynamics{
             integrator: integrator,
-            thermostat: None,
+            thermostats: Vec::new(),
             controls: Vec::new(),
         }
     }
@@ -39,28 +40,68 @@ impl MolecularDynamics {
         self.controls.push(control);
     }
 
-    /// Set the thermostat to use with this simulation
+    /// Set the thermostat to use with this simulation, removing any
+    /// previously added thermostat.
     pub fn set_thermostat(&mut self, thermostat: Box<Thermostat>) {
-        self.thermostat = Some(thermostat);
+        self.thermostats.clear();
+        self.thermostats.push(thermostat);
+    }
+
+    /// Add a thermostat to use with this simulation. This is useful to couple
+    /// different groups of particles to different thermostats, as long as the
+    /// groups do not overlap. Overlapping groups are an error when setting up
+    /// the simulation.
+    pub fn add_thermostat(&mut self, thermostat: Box<Thermostat>) {
+        self.thermostats.push(thermostat);
     }
 }
 
 impl Propagator for MolecularDynamics {
     fn temperature_strategy(&self) -> TemperatureStrategy {
-        TemperatureStrategy::Velocities
+        self.integrator.temperature_strategy()
+    }
+
+    fn degrees_of_freedom(&self) -> DegreesOfFreedom {
+        self.integrator.degrees_of_freedom()
+    }
+
+    fn removed_motions(&self) -> RemovedMotions {
+        let thermostats = self.thermostats.iter().map(|thermostat| thermostat.removed_motions());
+        let controls = self.controls.iter().map(|control| control.removed_motions());
+        thermostats.chain(controls).fold(RemovedMotions::default(), RemovedMotions::union)
     }
 
     fn setup(&mut self, system: &System) {
+        // A particle can only be coupled to a single thermostat
+        let mut thermostated = vec![false; system.size()];
+        for thermostat in &self.thermostats {
+            for i in thermostat.particles(system) {
+                if thermostated[i] {
+                    fatal_error!("Thermostats can not act on overlapping groups of particles");
+                }
+                thermostated[i] = true;
+            }
+        }
+
         self.integrator.setup(system);
+        for thermostat in &mut self.thermostats {
+            thermostat.setup(system);
+        }
         for control in &mut self.controls {
             control.setup(system);
         }
     }
 
+    fn system_changed(&mut self, system: &System) {
+        // The thermostats and controls keep their state, only the integrator
+        // depends on the configuration
+        self.integrator.system_changed(system);
+    }
+
     fn propagate(&mut self, system: &mut System) {
         self.integrator.integrate(system);
 
-        if let Some(ref mut thermostat) = self.thermostat {
+        for thermostat in &mut self.thermostats {
             thermostat.control(system);
         }
 
@@ -70,6 +111,9 @@ impl Propagator for MolecularDynamics {
     }
 
     fn finish(&mut self, system: &System) {
+        for thermostat in &mut self.thermostats {
+            thermostat.finish(system);
+        }
         for control in &mut self.controls {
             control.finish(system);
         }
@@ -0,0 +1,458 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Path integral molecular dynamics
+use std::f64::consts::PI;
+use std::sync::{Arc, RwLock};
+
+use rand::distributions::{Normal, Sample};
+use rand::{Isaac64Rng, SeedableRng};
+
+use consts::{K_BOLTZMANN, H_BAR};
+use types::{Array2, Vector3D, Zero};
+use sys::System;
+use sys::zip_particle::*;
+use sim::{Propagator, TemperatureStrategy};
+
+/// Centroid-virial estimator of the quantum kinetic energy in path integral
+/// molecular dynamics. This estimator is defined by
+///
+/// $$ K_{cv} = \frac{3 N}{2} k_B T - \frac{1}{2P} \sum_{j=1}^P \sum_i (\vec r_i^{(j)} - \vec r_i^{(c)}) \cdot \vec f_i^{(j)} $$
+///
+/// where $N$ is the number of mobile particles, $P$ the number of beads,
+/// $\vec r_i^{(j)}$ and $\vec f_i^{(j)}$ the position of and force acting on
+/// the particle $i$ in the bead $j$, and $\vec r_i^{(c)}$ the centroid of the
+/// particle $i$ ring polymer.
+///
+/// Clones of a `CentroidVirial` share the same value, so that the estimator
+/// updated by a [`PathIntegral`][PathIntegral] propagator can be used in an
+/// output.
+///
+/// [PathIntegral]: struct.PathIntegral.html
+#[derive(Clone)]
+pub struct CentroidVirial {
+    kinetic: Arc<RwLock<f64>>,
+}
+
+impl CentroidVirial {
+    fn new() -> CentroidVirial {
+        CentroidVirial {
+            kinetic: Arc::new(RwLock::new(0.0)),
+        }
+    }
+
+    /// Get the value of the centroid-virial kinetic energy estimator at the
+    /// last step of the simulation.
+    pub fn kinetic_energy(&self) -> f64 {
+        *self.kinetic.read().expect("poisoned lock")
+    }
+
+    fn set_kinetic_energy(&self, kinetic: f64) {
+        *self.kinetic.write().expect("poisoned lock") = kinetic;
+    }
+}
+
+/// Path integral molecular dynamics propagator, including nuclear quantum
+/// effects in the simulation.
+///
+/// Each particle is replaced by a ring polymer of $P$ beads, coupled by
+/// harmonic springs with frequency $\omega_P = P k_B T / \hbar$. The
+/// propagator holds $P$ replicas of the system, one for each bead, and the
+/// physical forces are computed independently in each replica. The ring
+/// polymer is propagated in normal modes coordinates, where the free ring
+/// polymer motion is integrated exactly, and sampled at the temperature $P T$
+/// with the path integral Langevin equation (PILE) thermostat [1]. The
+/// friction of the non-centroid modes is $2 \omega_k$, where $\omega_k$ is
+/// the frequency of the mode $k$; and the friction of the centroid mode is
+/// controlled with `PathIntegral::set_centroid_relaxation`.
+///
+/// After each step, the positions and velocities of the simulated system are
+/// set to the centroids of the ring polymers. The quantum kinetic energy is
+/// given by the [`CentroidVirial`][CentroidVirial] estimator. Distance
+/// constraints are not supported by this propagator.
+///
+/// [1] M. Ceriotti, et al. J. Chem. Phys. 133, 124104 (2010); doi: 10.1063/1.3489925
+///
+/// [CentroidVirial]: struct.CentroidVirial.html
+pub struct PathIntegral {
+    /// Timestep for the propagator
+    timestep: f64,
+    /// Physical temperature of the simulation
+    temperature: f64,
+    /// Number of beads in the ring polymers
+    nbeads: usize,
+    /// Relaxation time of the thermostat acting on the centroid mode
+    centroid_tau: f64,
+    /// Replicas of the system, one for each bead
+    beads: Vec<System>,
+    /// Forces acting on the particles in each bead
+    forces: Vec<Vec<Vector3D>>,
+    /// Orthogonal transformation from beads to normal modes coordinates. The
+    /// normal mode `k` is given by `sum_j transform[(j, k)] * bead_j`.
+    transform: Array2<f64>,
+    /// Frequencies of the free ring polymer normal modes
+    frequencies: Vec<f64>,
+    /// Random number generator for the thermostat
+    rng: Isaac64Rng,
+    /// Normal distribution for the thermostat
+    normal: Normal,
+    /// Kinetic energy estimator
+    estimator: CentroidVirial,
+}
+
+impl PathIntegral {
+    /// Create a new path integral propagator with a timestep of `timestep`,
+    /// at the given `temperature`, using `nbeads` beads for each particle.
+    pub fn new(timestep: f64, temperature: f64, nbeads: usize) -> PathIntegral {
+        assert!(temperature > 0.0, "The temperature must be positive in path integral molecular dynamics");
+        assert!(nbeads > 0, "The number of beads must be positive in path integral molecular dynamics");
+
+        let p = nbeads as f64;
+        let mut transform = Array2::zeros((nbeads, nbeads));
+        for j in 0..nbeads {
+            for k in 0..nbeads {
+                let angle = 2.0 * PI * (j * k) as f64 / p;
+                transform[(j, k)] = if k == 0 {
+                    f64::sqrt(1.0 / p)
+                } else if 2 * k < nbeads {
+                    f64::sqrt(2.0 / p) * f64::cos(angle)
+                } else if 2 * k == nbeads {
+                    let sign = if j % 2 == 0 {1.0} else {-1.0};
+                    sign * f64::sqrt(1.0 / p)
+                } else {
+                    f64::sqrt(2.0 / p) * f64::sin(angle)
+                };
+            }
+        }
+
+        let omega = p * K_BOLTZMANN * temperature / H_BAR;
+        let frequencies = (0..nbeads).map(|k| {
+            2.0 * omega * f64::sin(k as f64 * PI / p)
+        }).collect();
+
+        PathIntegral {
+            timestep: timestep,
+            temperature: temperature,
+            nbeads: nbeads,
+            centroid_tau: 100.0 * timestep,
+            beads: Vec::new(),
+            forces: Vec::new(),
+            transform: transform,
+            frequencies: frequencies,
+            rng: Isaac64Rng::from_seed(&[42]),
+            normal: Normal::new(0.0, 1.0),
+            estimator: CentroidVirial::new(),
+        }
+    }
+
+    /// Set the relaxation time of the thermostat acting on the centroid
+    /// mode. The default value is 100 times the timestep.
+    pub fn set_centroid_relaxation(&mut self, tau: f64) {
+        assert!(tau > 0.0, "The centroid relaxation time must be positive in path integral molecular dynamics");
+        self.centroid_tau = tau;
+    }
+
+    /// Set the seed of the random number generator. The default seed is 42.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng.reseed(&[seed]);
+    }
+
+    /// Get the kinetic energy estimator updated by this propagator. The
+    /// returned estimator shares its value with this propagator.
+    pub fn estimator(&self) -> CentroidVirial {
+        self.estimator.clone()
+    }
+
+    /// Get the replicas of the system, one for each bead
+    pub fn beads(&self) -> &[System] {
+        &self.beads
+    }
+
+    /// Get a random gaussian vector
+    fn random_vector(&mut self) -> Vector3D {
+        Vector3D::new(
+            self.normal.sample(&mut self.rng),
+            self.normal.sample(&mut self.rng),
+            self.normal.sample(&mut self.rng),
+        )
+    }
+
+    /// Transform the `values` associated with a particle in each bead to
+    /// normal modes coordinates.
+    fn to_normal_modes(&self, values: &[Vector3D]) -> Vec<Vector3D> {
+        (0..self.nbeads).map(|k| {
+            let mut mode = Vector3D::zero();
+            for (j, &value) in values.iter().enumerate() {
+                mode += self.transform[(j, k)] * value;
+            }
+            mode
+        }).collect()
+    }
+
+    /// Transform the normal `modes` associated with a particle back to the
+    /// beads coordinates.
+    fn from_normal_modes(&self, modes: &[Vector3D]) -> Vec<Vector3D> {
+        (0..self.nbeads).map(|j| {
+            let mut value = Vector3D::zero();
+            for (k, &mode) in modes.iter().enumerate() {
+                value += self.transform[(j, k)] * mode;
+            }
+            value
+        }).collect()
+    }
+
+    /// Apply the PILE thermostat to the beads velocities for a time `dt`
+    fn thermostat(&mut self, dt: f64) {
+        let kt = K_BOLTZMANN * self.temperature * self.nbeads as f64;
+        let coefficients = (0..self.nbeads).map(|k| {
+            let friction = if k == 0 {
+                1.0 / self.centroid_tau
+            } else {
+                2.0 * self.frequencies[k]
+            };
+            let c1 = f64::exp(-friction * dt);
+            (c1, f64::sqrt(1.0 - c1 * c1))
+        }).collect::<Vec<_>>();
+
+        let natoms = self.beads[0].size();
+        for i in 0..natoms {
+            if self.beads[0].particles().frozen[i] {
+                continue;
+            }
+            let sigma = f64::sqrt(kt / self.beads[0].particles().mass[i]);
+            let velocities = self.beads.iter().map(|bead| bead.particles().velocity[i]).collect::<Vec<_>>();
+            let mut modes = self.to_normal_modes(&velocities);
+            for (mode, &(c1, c2)) in modes.iter_mut().zip(&coefficients) {
+                *mode = c1 * *mode + c2 * sigma * self.random_vector();
+            }
+
+            let velocities = self.from_normal_modes(&modes);
+            for (bead, velocity) in self.beads.iter_mut().zip(velocities) {
+                bead.particles_mut().velocity[i] = velocity;
+            }
+        }
+    }
+
+    /// Update the beads velocities with the physical forces for a time `dt`
+    fn kick(&mut self, dt: f64) {
+        for (bead, forces) in self.beads.iter_mut().zip(&self.forces) {
+            for (velocity, &mass, force, &frozen) in bead.particles_mut().zip_mut(
+                (&mut Velocity, &Mass, forces, &Frozen)
+            ) {
+                if frozen {
+                    continue;
+                }
+                *velocity += dt / mass * force;
+            }
+        }
+    }
+
+    /// Propagate the free ring polymers for a time `dt`, integrating exactly
+    /// the motion of each normal mode.
+    fn free_ring_polymer(&mut self, dt: f64) {
+        let natoms = self.beads[0].size();
+        for i in 0..natoms {
+            if self.beads[0].particles().frozen[i] {
+                continue;
+            }
+            let positions = self.beads.iter().map(|bead| bead.particles().position[i]).collect::<Vec<_>>();
+            let velocities = self.beads.iter().map(|bead| bead.particles().velocity[i]).collect::<Vec<_>>();
+            let mut positions = self.to_normal_modes(&positions);
+            let mut velocities = self.to_normal_modes(&velocities);
+
+            positions[0] += dt * velocities[0];
+            for k in 1..self.nbeads {
+                let omega = self.frequencies[k];
+                let (sin, cos) = f64::sin_cos(omega * dt);
+                let velocity = cos * velocities[k] - omega * sin * positions[k];
+                positions[k] = sin / omega * velocities[k] + cos * positions[k];
+                velocities[k] = velocity;
+            }
+
+            let positions = self.from_normal_modes(&positions);
+            let velocities = self.from_normal_modes(&velocities);
+            for (bead, (position, velocity)) in self.beads.iter_mut().zip(positions.into_iter().zip(velocities)) {
+                let particles = bead.particles_mut();
+                particles.position[i] = position;
+                particles.velocity[i] = velocity;
+            }
+        }
+    }
+
+    /// Compute the physical forces acting on all the beads
+    fn compute_forces(&mut self) {
+        self.forces = self.beads.iter().map(|bead| bead.forces()).collect();
+    }
+
+    /// Get the centroid of the `values` associated with a particle in all
+    /// the beads.
+    fn centroid<F>(&self, values: F) -> Vector3D where F: Fn(&System) -> Vector3D {
+        let mut centroid = Vector3D::zero();
+        for bead in &self.beads {
+            centroid += values(bead);
+        }
+        return centroid / self.nbeads as f64;
+    }
+
+    /// Compute the centroid-virial kinetic energy estimator
+    fn centroid_virial(&self) -> f64 {
+        let natoms = self.beads[0].size();
+        let mut mobile = 0;
+        let mut virial = 0.0;
+        for i in 0..natoms {
+            if self.beads[0].particles().frozen[i] {
+                continue;
+            }
+            mobile += 1;
+            let centroid = self.centroid(|bead| bead.particles().position[i]);
+            for (bead, forces) in self.beads.iter().zip(&self.forces) {
+                virial += (bead.particles().position[i] - centroid) * forces[i];
+            }
+        }
+        let thermal = 1.5 * mobile as f64 * K_BOLTZMANN * self.temperature;
+        return thermal - 0.5 * virial / self.nbeads as f64;
+    }
+}
+
+impl Propagator for PathIntegral {
+    fn temperature_strategy(&self) -> TemperatureStrategy {
+        TemperatureStrategy::Velocities
+    }
+
+    fn setup(&mut self, system: &System) {
+        if !system.constraints().is_empty() {
+            warn!("Distance constraints are ignored by path integral molecular dynamics");
+        }
+
+        self.beads = vec![system.clone(); self.nbeads];
+        self.compute_forces();
+        self.estimator.set_kinetic_energy(self.centroid_virial());
+    }
+
+    fn propagate(&mut self, system: &mut System) {
+        let dt = self.timestep;
+        self.thermostat(0.5 * dt);
+        self.kick(0.5 * dt);
+        self.free_ring_polymer(dt);
+        self.compute_forces();
+        self.kick(0.5 * dt);
+        self.thermostat(0.5 * dt);
+
+        // Use the centroids as the positions and velocities of the system
+        for i in 0..system.size() {
+            let position = self.centroid(|bead| bead.particles().position[i]);
+            let velocity = self.centroid(|bead| bead.particles().velocity[i]);
+            let particles = system.particles_mut();
+            particles.position[i] = position;
+            particles.velocity[i] = velocity;
+        }
+
+        self.estimator.set_kinetic_energy(self.centroid_virial());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sys::{Particle, UnitCell};
+    use sys::veloc::{InitVelocities, BoltzmannVelocities};
+    use utils::unit_from;
+
+    fn testing_system() -> System {
+        let mut system = System::with_cell(UnitCell::cubic(100.0));
+        for i in 0..10 {
+            for j in 0..10 {
+                for k in 0..10 {
+                    let position = Vector3D::new(i as f64, j as f64, k as f64) * 10.0;
+                    system.add_particle(Particle::with_position("He", position));
+                }
+            }
+        }
+        let mut velocities = BoltzmannVelocities::new(300.0);
+        velocities.init(&mut system);
+        return system;
+    }
+
+    #[test]
+    fn normal_modes() {
+        for &nbeads in &[1, 4, 5] {
+            let propagator = PathIntegral::new(1.0, 300.0, nbeads);
+            let values = (0..nbeads).map(|j| {
+                Vector3D::new(j as f64, (j * j) as f64, -2.0 * j as f64)
+            }).collect::<Vec<_>>();
+
+            let modes = propagator.to_normal_modes(&values);
+            // The centroid mode is proportional to the centroid
+            let centroid = values.iter().fold(Vector3D::zero(), |sum, &value| sum + value);
+            assert_ulps_eq!(modes[0], centroid / f64::sqrt(nbeads as f64), epsilon=1e-12);
+
+            let back = propagator.from_normal_modes(&modes);
+            for (value, initial) in back.iter().zip(&values) {
+                assert_ulps_eq!(*value, *initial, epsilon=1e-12);
+            }
+        }
+
+        let propagator = PathIntegral::new(1.0, 300.0, 4);
+        let omega = 4.0 * K_BOLTZMANN * 300.0 / H_BAR;
+        assert_eq!(propagator.frequencies[0], 0.0);
+        assert_ulps_eq!(propagator.frequencies[2], 2.0 * omega);
+    }
+
+    #[test]
+    fn free_particles() {
+        let mut system = testing_system();
+        let mut propagator = PathIntegral::new(unit_from(1.0, "fs"), 300.0, 4);
+        propagator.setup(&system);
+
+        let estimator = propagator.estimator();
+        let thermal = 1.5 * system.size() as f64 * K_BOLTZMANN * 300.0;
+        assert_ulps_eq!(estimator.kinetic_energy(), thermal);
+
+        for _ in 0..500 {
+            propagator.propagate(&mut system);
+        }
+
+        // Without interactions, the kinetic energy is the classical one
+        assert_ulps_eq!(estimator.kinetic_energy(), thermal, epsilon=1e-9 * thermal);
+        // The centroids are sampled at the physical temperature
+        assert_ulps_eq!(system.temperature(), 300.0, epsilon=30.0);
+        // and the beads at P times the physical temperature
+        for bead in propagator.beads() {
+            assert_ulps_eq!(bead.temperature(), 1200.0, epsilon=120.0);
+        }
+    }
+
+    #[test]
+    fn ring_polymers_size() {
+        let mut system = testing_system();
+        let nbeads = 8;
+        let mut propagator = PathIntegral::new(unit_from(0.5, "fs"), 300.0, nbeads);
+        propagator.setup(&system);
+        for _ in 0..200 {
+            propagator.propagate(&mut system);
+        }
+
+        let mut spread = 0.0;
+        for i in 0..system.size() {
+            let centroid = system.particles().position[i];
+            for bead in propagator.beads() {
+                spread += (bead.particles().position[i] - centroid).norm2();
+            }
+        }
+        spread /= (nbeads * system.size()) as f64;
+
+        // Equipartition of the energy in the free ring polymers normal modes
+        let mass = system.particles().mass[0];
+        let kt = nbeads as f64 * K_BOLTZMANN * 300.0;
+        let expected = (1..nbeads).map(|k| {
+            3.0 * kt / (mass * propagator.frequencies[k] * propagator.frequencies[k])
+        }).sum::<f64>() / nbeads as f64;
+        assert_ulps_eq!(spread / expected, 1.0, epsilon=0.05);
+    }
+
+    #[test]
+    #[should_panic]
+    fn no_beads() {
+        let _ = PathIntegral::new(1.0, 300.0, 0);
+    }
+}
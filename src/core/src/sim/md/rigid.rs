@@ -0,0 +1,549 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Rigid body molecular dynamics
+use types::{Vector3D, Matrix3, One, Zero};
+use sys::{System, DegreesOfFreedom};
+
+use super::Integrator;
+
+/// A quaternion, stored as `[w, x, y, z]`.
+type Quaternion = [f64; 4];
+
+/// Data associated with a single rigid molecule
+struct Body {
+    /// Index of the first particle in the molecule
+    start: usize,
+    /// Index of the first particle after the molecule
+    end: usize,
+    /// Total mass of the molecule
+    mass: f64,
+    /// Principal moments of inertia of the molecule. The moments are zero
+    /// for the axes without rotational degrees of freedom (all the axes for a
+    /// single particle, and the molecular axis for linear molecules).
+    inertia: Vector3D,
+    /// Orientation of the principal axes frame
+    orientation: Quaternion,
+    /// Momentum conjugated to the orientation quaternion
+    momentum: Quaternion,
+    /// Position of the center of mass
+    position: Vector3D,
+    /// Velocity of the center of mass
+    velocity: Vector3D,
+    /// Total force acting on the molecule
+    force: Vector3D,
+    /// Total torque acting on the molecule, in the laboratory frame
+    torque: Vector3D,
+}
+
+/// Rigid body integrator for molecular dynamics.
+///
+/// All the molecules in the system are propagated as rigid bodies: the
+/// centers of mass follow the total force acting on the molecules, and the
+/// orientations (represented as quaternions) follow the total torque. The
+/// rotational motion is integrated with the symplectic NO_SQUISH splitting
+/// from Miller et al. (J. Chem. Phys. 116, 8649, 2002).
+///
+/// The molecules geometry is taken from the positions of the particles at
+/// the beginning of the simulation, and the particles velocities are set from
+/// the motion of the rigid bodies. The rotational kinetic energy is thus
+/// included in the system kinetic energy and temperature. The forces acting
+/// inside a molecule do not contribute to the dynamics.
+pub struct RigidBody {
+    /// Timestep for the integrator
+    timestep: f64,
+    /// All the rigid bodies in the system
+    bodies: Vec<Body>,
+    /// Positions of the particles in the principal axes frame of their
+    /// molecule
+    local: Vec<Vector3D>,
+    /// Positions of the particles relative to the center of mass of their
+    /// molecule, in the laboratory frame
+    relative: Vec<Vector3D>,
+    /// Forces acting on the particles
+    forces: Vec<Vector3D>,
+}
+
+impl RigidBody {
+    /// Create a new integrator with a timestep of `timestep`.
+    pub fn new(timestep: f64) -> RigidBody {
+        RigidBody {
+            timestep: timestep,
+            bodies: Vec::new(),
+            local: Vec::new(),
+            relative: Vec::new(),
+            forces: Vec::new(),
+        }
+    }
+
+    /// Update the centers of mass, velocities and angular momenta of the
+    /// rigid bodies from the particles positions and velocities. This also
+    /// updates the relative positions of the particles.
+    fn update_bodies(&mut self, system: &System) {
+        let particles = system.particles();
+        let masses = particles.mass;
+        let positions = particles.position;
+        let velocities = particles.velocity;
+        for body in &mut self.bodies {
+            let origin = positions[body.start];
+            let mut com = Vector3D::zero();
+            let mut momentum = Vector3D::zero();
+            for i in body.start..body.end {
+                let mut delta = positions[i] - origin;
+                system.cell.vector_image(&mut delta);
+                self.relative[i] = delta;
+                com += masses[i] * delta;
+                momentum += masses[i] * velocities[i];
+            }
+            com /= body.mass;
+
+            let mut angular = Vector3D::zero();
+            for i in body.start..body.end {
+                self.relative[i] -= com;
+                angular += masses[i] * (self.relative[i] ^ velocities[i]);
+            }
+
+            body.position = origin + com;
+            body.velocity = momentum / body.mass;
+            let angular = rotation_matrix(&body.orientation) * angular;
+            body.momentum = scale(2.0, &s_product(&body.orientation, &angular));
+        }
+    }
+
+    /// Compute the forces and torques acting on the rigid bodies
+    fn update_forces(&mut self, system: &System) {
+        self.forces = system.forces();
+        for body in &mut self.bodies {
+            body.force = Vector3D::zero();
+            body.torque = Vector3D::zero();
+            for i in body.start..body.end {
+                body.force += self.forces[i];
+                body.torque += self.relative[i] ^ self.forces[i];
+            }
+        }
+    }
+
+    /// Set the particles positions from the rigid bodies positions and
+    /// orientations.
+    fn update_positions(&mut self, system: &mut System) {
+        let particles = system.particles_mut();
+        let positions = particles.position;
+        for body in &self.bodies {
+            let rotation = rotation_matrix(&body.orientation).transposed();
+            for i in body.start..body.end {
+                self.relative[i] = rotation * self.local[i];
+                positions[i] = body.position + self.relative[i];
+            }
+        }
+    }
+
+    /// Set the particles velocities from the rigid bodies velocities and
+    /// angular momenta, and return the virial of the forces keeping the
+    /// molecules rigid.
+    fn update_velocities(&self, system: &mut System) -> Matrix3 {
+        let particles = system.particles_mut();
+        let masses = particles.mass;
+        let velocities = particles.velocity;
+
+        let mut virial = Matrix3::zero();
+        for body in &self.bodies {
+            let rotation = rotation_matrix(&body.orientation);
+            let torque = rotation * body.torque;
+            let mut omega = Vector3D::zero();
+            for axis in 0..3 {
+                if body.inertia[axis] != 0.0 {
+                    let pq = permute(axis, &body.orientation);
+                    omega[axis] = dot(&body.momentum, &pq) / (2.0 * body.inertia[axis]);
+                }
+            }
+
+            // Angular acceleration from the Euler equations in the body frame
+            let angular = Vector3D::new(
+                body.inertia[0] * omega[0],
+                body.inertia[1] * omega[1],
+                body.inertia[2] * omega[2],
+            );
+            let coupling = omega ^ angular;
+            let mut alpha = Vector3D::zero();
+            for axis in 0..3 {
+                if body.inertia[axis] != 0.0 {
+                    alpha[axis] = (torque[axis] - coupling[axis]) / body.inertia[axis];
+                }
+            }
+
+            let rotation = rotation.transposed();
+            let omega = rotation * omega;
+            let alpha = rotation * alpha;
+            for i in body.start..body.end {
+                let delta = self.relative[i];
+                velocities[i] = body.velocity + (omega ^ delta);
+                // The constraint force is the difference between the force
+                // needed for the rigid motion and the actual force.
+                let acceleration = (alpha ^ delta) + (omega ^ (omega ^ delta));
+                let force = masses[i] * acceleration - self.forces[i];
+                virial += force.tensorial(&delta);
+            }
+        }
+        return virial;
+    }
+}
+
+impl Integrator for RigidBody {
+    fn setup(&mut self, system: &System) {
+        if !system.constraints().is_empty() {
+            warn!("Distance constraints are ignored by the rigid body integrator");
+        }
+
+        self.local = vec![Vector3D::zero(); system.size()];
+        self.relative = vec![Vector3D::zero(); system.size()];
+        self.bodies.clear();
+
+        let particles = system.particles();
+        let masses = particles.mass;
+        let positions = particles.position;
+        for (molid, molecule) in system.molecules().iter().enumerate() {
+            // Frozen molecules are not moved
+            if system.is_frozen_molecule(molid) {
+                continue;
+            }
+
+            let origin = positions[molecule.start()];
+            let mut mass = 0.0;
+            let mut com = Vector3D::zero();
+            for i in molecule.iter() {
+                let mut delta = positions[i] - origin;
+                system.cell.vector_image(&mut delta);
+                self.relative[i] = delta;
+                mass += masses[i];
+                com += masses[i] * delta;
+            }
+            com /= mass;
+
+            let mut tensor = Matrix3::zero();
+            for i in molecule.iter() {
+                self.relative[i] -= com;
+                let delta = self.relative[i];
+                tensor += masses[i] * (delta.norm2() * Matrix3::one() - delta.tensorial(&delta));
+            }
+
+            let (mut inertia, mut axes) = diagonalize(&tensor);
+            if axes.determinant() < 0.0 {
+                for k in 0..3 {
+                    axes[(k, 2)] = -axes[(k, 2)];
+                }
+            }
+
+            if molecule.size() == 1 {
+                inertia = Vector3D::zero();
+            } else if system.is_linear(molid) {
+                // Remove the rotation around the molecular axis
+                let mut axis = 0;
+                for k in 1..3 {
+                    if inertia[k] < inertia[axis] {
+                        axis = k;
+                    }
+                }
+                inertia[axis] = 0.0;
+            }
+
+            let orientation = quaternion_from_matrix(&axes.transposed());
+            let rotation = rotation_matrix(&orientation);
+            for i in molecule.iter() {
+                self.local[i] = rotation * self.relative[i];
+            }
+
+            self.bodies.push(Body {
+                start: molecule.start(),
+                end: molecule.end(),
+                mass: mass,
+                inertia: inertia,
+                orientation: orientation,
+                momentum: [0.0; 4],
+                position: origin + com,
+                velocity: Vector3D::zero(),
+                force: Vector3D::zero(),
+                torque: Vector3D::zero(),
+            });
+        }
+
+        self.update_forces(system);
+    }
+
+    fn integrate(&mut self, system: &mut System) {
+        let dt = self.timestep;
+        // Thermostats and controls may have changed the particles positions
+        // and velocities since the last step.
+        self.update_bodies(system);
+
+        for body in &mut self.bodies {
+            kick(body, 0.5 * dt);
+            body.position += dt * body.velocity;
+
+            rotate(body, 2, 0.5 * dt);
+            rotate(body, 1, 0.5 * dt);
+            rotate(body, 0, dt);
+            rotate(body, 1, 0.5 * dt);
+            rotate(body, 2, 0.5 * dt);
+            body.orientation = normalized(&body.orientation);
+        }
+
+        self.update_positions(system);
+        self.update_forces(system);
+
+        for body in &mut self.bodies {
+            kick(body, 0.5 * dt);
+        }
+
+        let virial = self.update_velocities(system);
+        system.set_constraints_virial(virial);
+    }
+
+    fn degrees_of_freedom(&self) -> DegreesOfFreedom {
+        DegreesOfFreedom::Molecules
+    }
+}
+
+/// Update the velocity and the angular momentum of the `body` using the
+/// current force and torque for a time `dt`.
+fn kick(body: &mut Body, dt: f64) {
+    body.velocity += dt * body.force / body.mass;
+    let torque = rotation_matrix(&body.orientation) * body.torque;
+    let delta = s_product(&body.orientation, &torque);
+    for k in 0..4 {
+        body.momentum[k] += 2.0 * dt * delta[k];
+    }
+}
+
+/// Freely rotate the `body` around the principal `axis` for a time `dt`
+fn rotate(body: &mut Body, axis: usize, dt: f64) {
+    if body.inertia[axis] == 0.0 {
+        return;
+    }
+
+    let orientation = body.orientation;
+    let momentum = body.momentum;
+    let permuted_orientation = permute(axis, &orientation);
+    let permuted_momentum = permute(axis, &momentum);
+
+    let zeta = dt * dot(&momentum, &permuted_orientation) / (4.0 * body.inertia[axis]);
+    let (sin, cos) = zeta.sin_cos();
+    for k in 0..4 {
+        body.orientation[k] = cos * orientation[k] + sin * permuted_orientation[k];
+        body.momentum[k] = cos * momentum[k] + sin * permuted_momentum[k];
+    }
+}
+
+/// Get the permutation of the quaternion `q` associated with the principal
+/// `axis`.
+fn permute(axis: usize, q: &Quaternion) -> Quaternion {
+    match axis {
+        0 => [-q[1], q[0], q[3], -q[2]],
+        1 => [-q[2], -q[3], q[0], q[1]],
+        2 => [-q[3], q[2], -q[1], q[0]],
+        _ => unreachable!(),
+    }
+}
+
+/// Compute the product `S(q) (0, v)` of the orthogonal matrix associated
+/// with the quaternion `q` and the pure quaternion `v`.
+fn s_product(q: &Quaternion, v: &Vector3D) -> Quaternion {
+    let mut result = [0.0; 4];
+    for axis in 0..3 {
+        let permuted = permute(axis, q);
+        for k in 0..4 {
+            result[k] += v[axis] * permuted[k];
+        }
+    }
+    return result;
+}
+
+fn dot(a: &Quaternion, b: &Quaternion) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3]
+}
+
+fn scale(factor: f64, q: &Quaternion) -> Quaternion {
+    [factor * q[0], factor * q[1], factor * q[2], factor * q[3]]
+}
+
+fn normalized(q: &Quaternion) -> Quaternion {
+    scale(1.0 / dot(q, q).sqrt(), q)
+}
+
+/// Get the rotation matrix from the laboratory frame to the body frame
+/// associated with the unit quaternion `q`.
+fn rotation_matrix(q: &Quaternion) -> Matrix3 {
+    let (w, x, y, z) = (q[0], q[1], q[2], q[3]);
+    Matrix3::new(
+        w * w + x * x - y * y - z * z, 2.0 * (x * y + w * z), 2.0 * (x * z - w * y),
+        2.0 * (x * y - w * z), w * w - x * x + y * y - z * z, 2.0 * (y * z + w * x),
+        2.0 * (x * z + w * y), 2.0 * (y * z - w * x), w * w - x * x - y * y + z * z,
+    )
+}
+
+/// Get the unit quaternion associated with the rotation `matrix` from the
+/// laboratory frame to the body frame.
+fn quaternion_from_matrix(matrix: &Matrix3) -> Quaternion {
+    let r = matrix.transposed();
+    let trace = r.trace();
+    let q = if trace > 0.0 {
+        let s = 2.0 * f64::sqrt(1.0 + trace);
+        [
+            0.25 * s,
+            (r[(2, 1)] - r[(1, 2)]) / s,
+            (r[(0, 2)] - r[(2, 0)]) / s,
+            (r[(1, 0)] - r[(0, 1)]) / s,
+        ]
+    } else if r[(0, 0)] > r[(1, 1)] && r[(0, 0)] > r[(2, 2)] {
+        let s = 2.0 * f64::sqrt(1.0 + r[(0, 0)] - r[(1, 1)] - r[(2, 2)]);
+        [
+            (r[(2, 1)] - r[(1, 2)]) / s,
+            0.25 * s,
+            (r[(0, 1)] + r[(1, 0)]) / s,
+            (r[(0, 2)] + r[(2, 0)]) / s,
+        ]
+    } else if r[(1, 1)] > r[(2, 2)] {
+        let s = 2.0 * f64::sqrt(1.0 + r[(1, 1)] - r[(0, 0)] - r[(2, 2)]);
+        [
+            (r[(0, 2)] - r[(2, 0)]) / s,
+            (r[(0, 1)] + r[(1, 0)]) / s,
+            0.25 * s,
+            (r[(1, 2)] + r[(2, 1)]) / s,
+        ]
+    } else {
+        let s = 2.0 * f64::sqrt(1.0 + r[(2, 2)] - r[(0, 0)] - r[(1, 1)]);
+        [
+            (r[(1, 0)] - r[(0, 1)]) / s,
+            (r[(0, 2)] + r[(2, 0)]) / s,
+            (r[(1, 2)] + r[(2, 1)]) / s,
+            0.25 * s,
+        ]
+    };
+    return normalized(&q);
+}
+
+/// Diagonalize the symmetric `matrix` using the Jacobi eigenvalue algorithm.
+/// This function returns the eigenvalues, and a matrix containing the
+/// corresponding eigenvectors as columns.
+fn diagonalize(matrix: &Matrix3) -> (Vector3D, Matrix3) {
+    let mut matrix = *matrix;
+    let mut eigenvectors = Matrix3::one();
+    for _ in 0..50 {
+        // Find the largest off-diagonal element
+        let (mut p, mut q) = (0, 1);
+        for &(i, j) in &[(0, 2), (1, 2)] {
+            if matrix[(i, j)].abs() > matrix[(p, q)].abs() {
+                p = i;
+                q = j;
+            }
+        }
+
+        let scale = matrix[(0, 0)].abs() + matrix[(1, 1)].abs() + matrix[(2, 2)].abs();
+        if matrix[(p, q)].abs() <= 1e-15 * scale {
+            break;
+        }
+
+        let theta = (matrix[(q, q)] - matrix[(p, p)]) / (2.0 * matrix[(p, q)]);
+        let t = theta.signum() / (theta.abs() + f64::sqrt(theta * theta + 1.0));
+        let cos = 1.0 / f64::sqrt(t * t + 1.0);
+        let sin = t * cos;
+
+        let mut rotation = Matrix3::one();
+        rotation[(p, p)] = cos;
+        rotation[(q, q)] = cos;
+        rotation[(p, q)] = sin;
+        rotation[(q, p)] = -sin;
+
+        matrix = rotation.transposed() * matrix * rotation;
+        eigenvectors = eigenvectors * rotation;
+    }
+
+    let eigenvalues = Vector3D::new(matrix[(0, 0)], matrix[(1, 1)], matrix[(2, 2)]);
+    return (eigenvalues, eigenvectors);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::system_from_xyz;
+
+    fn testing_system() -> System {
+        let mut system = system_from_xyz("5
+        cell: 20.0
+        O 0.0 0.0 0.0 0.001 0.0 0.0
+        H 1.0 0.0 0.0 0.0 0.02 0.0
+        H -0.3 0.9 0.0 -0.01 0.0 0.03
+        C 5.0 5.0 5.0 0.0 0.0 0.0
+        O 6.2 5.0 5.0 0.0 0.0 -0.02
+        ");
+        let _ = system.add_bond(0, 1);
+        let _ = system.add_bond(0, 2);
+        let _ = system.add_bond(3, 4);
+        return system;
+    }
+
+    #[test]
+    fn diagonalization() {
+        let matrix = Matrix3::new(
+            4.0, 1.0, -2.0,
+            1.0, 2.0, 0.5,
+            -2.0, 0.5, 3.0
+        );
+        let (eigenvalues, eigenvectors) = diagonalize(&matrix);
+        for k in 0..3 {
+            let vector = Vector3D::new(eigenvectors[(0, k)], eigenvectors[(1, k)], eigenvectors[(2, k)]);
+            let image = matrix * vector;
+            for m in 0..3 {
+                assert_ulps_eq!(image[m], eigenvalues[k] * vector[m], epsilon=1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn quaternions() {
+        let rotation = Matrix3::rotation(&Vector3D::new(1.0, -2.0, 0.5), 2.3);
+        let quaternion = quaternion_from_matrix(&rotation);
+        assert_ulps_eq!(dot(&quaternion, &quaternion), 1.0);
+
+        let matrix = rotation_matrix(&quaternion);
+        for i in 0..3 {
+            for j in 0..3 {
+                assert_ulps_eq!(matrix[(i, j)], rotation[(i, j)], epsilon=1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn rigid_molecules() {
+        let mut system = testing_system();
+        let initial = [
+            system.distance(0, 1), system.distance(0, 2), system.distance(1, 2),
+            system.distance(3, 4),
+        ];
+
+        let mut integrator = RigidBody::new(1.0);
+        assert_eq!(integrator.degrees_of_freedom(), DegreesOfFreedom::Molecules);
+        integrator.setup(&system);
+        // The rotation around the axis of the linear molecule is removed
+        assert!(integrator.bodies[0].inertia * Vector3D::new(1.0, 1.0, 1.0) > 0.0);
+        let inertia = integrator.bodies[1].inertia;
+        assert_eq!(inertia[0] * inertia[1] * inertia[2], 0.0);
+        for _ in 0..100 {
+            integrator.integrate(&mut system);
+        }
+
+        let energy = system.kinetic_energy();
+        for _ in 0..100 {
+            integrator.integrate(&mut system);
+        }
+        // Without interactions, the kinetic energy is conserved
+        assert_ulps_eq!(system.kinetic_energy(), energy, epsilon=1e-3 * energy);
+
+        let last = [
+            system.distance(0, 1), system.distance(0, 2), system.distance(1, 2),
+            system.distance(3, 4),
+        ];
+        for (a, b) in initial.iter().zip(&last) {
+            assert_ulps_eq!(*a, *b, epsilon=1e-10);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Non-equilibrium molecular dynamics under shear flow
+use types::{Vector3D, Zero};
+use sys::System;
+use sys::zip_particle::*;
+
+use super::Integrator;
+
+/// SLLOD integrator for non-equilibrium molecular dynamics under a planar
+/// Couette flow, with the flow along the x axis and the velocity gradient
+/// along the y axis.
+///
+/// The unit cell is sheared at each step to produce Lees-Edwards sliding
+/// boundary conditions. The velocities of the particles are the *peculiar*
+/// velocities, i.e. the velocities relative to the streaming velocity
+/// $\dot\gamma y \hat x$. This means that the temperature of the system is
+/// computed from the peculiar velocities, and that any thermostat acts on the
+/// peculiar velocities.
+///
+/// The equations of motion are integrated with a velocity-Verlet scheme:
+///
+/// $$ \dot{\vec r}_i = \vec v_i + \dot\gamma y_i \hat x $$
+/// $$ \dot{\vec v}_i = \frac{\vec f_i}{m_i} - \dot\gamma v_{i,y} \hat x $$
+///
+/// The shear viscosity can then be computed as $\eta = - \langle P_{xy}
+/// \rangle / \dot\gamma$.
+pub struct Sllod {
+    /// Timestep for the integrator
+    timestep: f64,
+    /// Shear rate
+    shear_rate: f64,
+    /// Storing the accelerations
+    accelerations: Vec<Vector3D>,
+}
+
+impl Sllod {
+    /// Create a new `Sllod` integrator with a timestep of `timestep`, and the
+    /// given `shear_rate`.
+    pub fn new(timestep: f64, shear_rate: f64) -> Sllod {
+        Sllod {
+            timestep: timestep,
+            shear_rate: shear_rate,
+            accelerations: Vec::new(),
+        }
+    }
+
+    /// Get the shear rate of this integrator
+    pub fn shear_rate(&self) -> f64 {
+        self.shear_rate
+    }
+
+    /// Update the velocities for half a timestep, using the stored
+    /// accelerations and the SLLOD velocity gradient term.
+    fn half_kick(&self, system: &mut System) {
+        let dt = self.timestep;
+        let shear_rate = self.shear_rate;
+        for (velocity, acceleration, &frozen) in system.particles_mut().zip_mut(
+            (&mut Velocity, &self.accelerations, &Frozen)
+        ) {
+            if frozen {
+                continue;
+            }
+            *velocity += 0.5 * dt * acceleration;
+            let vy = velocity[1];
+            velocity[0] -= 0.5 * dt * shear_rate * vy;
+        }
+    }
+}
+
+impl Integrator for Sllod {
+    fn setup(&mut self, system: &System) {
+        if system.cell.is_infinite() {
+            fatal_error!("The SLLOD integrator can not be used with an infinite cell");
+        }
+        if !system.constraints().is_empty() {
+            warn!("Distance constraints are ignored by the SLLOD integrator");
+        }
+
+        self.accelerations = vec![Vector3D::zero(); system.size()];
+        let forces = system.forces();
+        for (&mass, acceleration, force) in system.particles().zip(
+            (&Mass, &mut self.accelerations, forces)
+        ) {
+            *acceleration = force / mass;
+        }
+    }
+
+    fn integrate(&mut self, system: &mut System) {
+        let dt = self.timestep;
+        let shear_rate = self.shear_rate;
+
+        // Update velocities at t + ∆t/2
+        self.half_kick(system);
+
+        // Update positions at t + ∆t, with the streaming velocity taken at
+        // the middle of the step. Frozen particles do not follow the flow.
+        for (position, velocity, &frozen) in system.particles_mut().zip_mut(
+            (&mut Position, &Velocity, &Frozen)
+        ) {
+            if frozen {
+                continue;
+            }
+            let y = position[1] + 0.5 * dt * velocity[1];
+            *position += velocity * dt;
+            position[0] += shear_rate * dt * y;
+        }
+
+        // Slide the periodic images
+        let length = system.cell.vect_b()[1];
+        system.cell.shear_mut(shear_rate * dt * length);
+
+        // Update accelerations at t + ∆t
+        let forces = system.forces();
+        for (&mass, acceleration, force) in system.particles().zip(
+            (&Mass, &mut self.accelerations, forces)
+        ) {
+            *acceleration = force / mass;
+        }
+
+        // Update velocities at t + ∆t
+        self.half_kick(system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sys::{Particle, UnitCell};
+
+    #[test]
+    fn streaming() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(0.0, 2.0, 0.0)));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(5.0, 5.0, 5.0)));
+
+        let mut integrator = Sllod::new(1.0, 0.01);
+        integrator.setup(&system);
+        for _ in 0..10 {
+            integrator.integrate(&mut system);
+        }
+
+        // Without forces and initial velocities, the particles follow the
+        // streaming velocity, and the peculiar velocities stay zero.
+        let particles = system.particles();
+        assert_relative_eq!(particles.position[0], Vector3D::new(0.2, 2.0, 0.0), epsilon=1e-12);
+        assert_relative_eq!(particles.position[1], Vector3D::new(0.5, 5.0, 5.0), epsilon=1e-12);
+        assert_eq!(particles.velocity[0], Vector3D::zero());
+
+        // The cell is sheared
+        assert_relative_eq!(system.cell.vect_b(), Vector3D::new(1.0, 10.0, 0.0), epsilon=1e-12);
+    }
+
+    #[test]
+    fn peculiar_velocities() {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_particle(Particle::with_position("Ar", Vector3D::zero()));
+        system.particles_mut().velocity[0] = Vector3D::new(0.0, 1e-3, 0.0);
+
+        let mut integrator = Sllod::new(1.0, 0.01);
+        integrator.setup(&system);
+        integrator.integrate(&mut system);
+
+        // The SLLOD term removes momentum along x for particles moving along y
+        let velocity = system.particles().velocity[0];
+        assert!(velocity[0] < 0.0);
+        assert_ulps_eq!(velocity[1], 1e-3);
+    }
+}
@@ -12,9 +12,16 @@ pub mod min;
 
 mod simulations;
 pub use self::simulations::Simulation;
-pub use self::md::MolecularDynamics;
-pub use self::mc::MonteCarlo;
+pub(crate) use self::simulations::OutputFrequency;
+pub use self::md::{MolecularDynamics, PathIntegral};
+pub use self::mc::{MonteCarlo, GibbsEnsemble, WangLandau};
 pub use self::min::Minimization;
 
+mod replica;
+pub use self::replica::ReplicaExchange;
+
 mod utils;
 pub use self::utils::Alternator;
+
+mod schedule;
+pub use self::schedule::Schedule;
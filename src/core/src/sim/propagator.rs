@@ -2,7 +2,7 @@
 // Copyright (C) Lumol's contributors — BSD license
 
 //! A propagator is responsible for updating the system during a simulation
-use sys::System;
+use sys::{System, DegreesOfFreedom, RemovedMotions};
 
 /// Possible temperature computation strategies. Different propagators needs
 /// different ways to compute the temperature: Monte Carlo temperature is a
@@ -28,6 +28,26 @@ pub trait Propagator {
     /// Get the temperature computation strategy for this propagator
     fn temperature_strategy(&self) -> TemperatureStrategy;
 
+    /// Get the kind of degrees of freedom used by this propagator. The
+    /// default is to propagate all the particles independently.
+    fn degrees_of_freedom(&self) -> DegreesOfFreedom {
+        DegreesOfFreedom::Particles
+    }
+
+    /// Get the global motions of the system removed by this propagator. The
+    /// default is to remove none of them.
+    fn removed_motions(&self) -> RemovedMotions {
+        RemovedMotions::default()
+    }
+
+    /// Update the propagator after the system was replaced by a different
+    /// configuration during a simulation, for example by an exchange in a
+    /// replica exchange simulation. The default is to setup the propagator
+    /// again.
+    fn system_changed(&mut self, system: &System) {
+        self.setup(system);
+    }
+
     /// Propagate the system for one simulation step.
     fn propagate(&mut self, system: &mut System);
 
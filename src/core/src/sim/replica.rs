@@ -0,0 +1,525 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Replica exchange simulations, also called parallel tempering
+use rand::{self, Rng, SeedableRng};
+
+use std::io::prelude::*;
+use std::io;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::mem;
+use std::f64;
+
+use consts::K_BOLTZMANN;
+use parallel::prelude::*;
+use sys::{System, DegreesOfFreedom, RemovedMotions};
+use out::Output;
+use utils;
+
+use sim::{Propagator, TemperatureStrategy, OutputFrequency};
+use sim::mc::MoveCounter;
+
+/// Simulation at one of the temperatures of a replica exchange simulation
+struct Level {
+    /// Temperature of this level
+    temperature: f64,
+    /// Propagator used at this temperature
+    propagator: Box<Propagator + Send>,
+    /// System currently simulated at this temperature
+    system: System,
+    /// Index of the replica currently simulated at this temperature
+    replica: usize,
+}
+
+impl Level {
+    /// Setup the propagator for the current system
+    fn setup(&mut self) {
+        self.update_system();
+        self.propagator.setup(&self.system);
+    }
+
+    /// Update the propagator after the system at this level changed in an
+    /// exchange, keeping the state of the propagator
+    fn exchanged(&mut self) {
+        self.update_system();
+        self.propagator.system_changed(&self.system);
+    }
+
+    /// Set the temperature computation and the degrees of freedom of the
+    /// current system from the propagator
+    fn update_system(&mut self) {
+        match self.propagator.temperature_strategy() {
+            TemperatureStrategy::External(temperature) => {
+                self.system.external_temperature(Some(temperature))
+            }
+            TemperatureStrategy::Velocities => self.system.external_temperature(None),
+            TemperatureStrategy::None => {}
+        }
+        self.system.set_degrees_of_freedom(self.propagator.degrees_of_freedom());
+        self.system.set_removed_motions(self.propagator.removed_motions());
+    }
+}
+
+/// Replica exchange simulation, also called parallel tempering.
+///
+/// Multiple replicas of a system are simulated at different temperatures, in
+/// parallel. Each temperature uses its own propagator, which can be Monte
+/// Carlo or molecular dynamics. The propagators should keep the system at the
+/// corresponding temperature, using a thermostat in molecular dynamics.
+///
+/// Exchanges of the systems between neighboring temperatures are attempted
+/// at a fixed interval, alternating between the even and the odd pairs of
+/// temperatures, and accepted or rejected using the potential energy of the
+/// systems. When two systems are exchanged, their velocities are rescaled to
+/// the new temperature, and the propagators are updated for their new
+/// system with `Propagator::system_changed`. The state of the propagators,
+/// such as the thermostats, is kept.
+///
+/// Outputs can follow either the system at a given temperature, or a given
+/// replica as it moves between the temperatures.
+///
+/// A replica exchange simulation can either be run directly with
+/// `ReplicaExchange::run`, or used as the propagator of a `Simulation`. In
+/// the latter case, the system given to `Simulation::run` replaces the first
+/// replica at the lowest temperature, and the simulation outputs are used
+/// for the system at the lowest temperature.
+pub struct ReplicaExchange {
+    /// Simulations at each temperature, in increasing temperature order
+    levels: Vec<Level>,
+    /// Outputs for the system at each temperature
+    temperature_outputs: Vec<Vec<OutputFrequency>>,
+    /// Outputs for each replica
+    replica_outputs: Vec<Vec<OutputFrequency>>,
+    /// Number of steps between two exchange attempts
+    frequency: u64,
+    /// Counters for the exchanges between neighboring temperatures
+    counters: Vec<MoveCounter>,
+    /// Number of exchange attempts, used to alternate between the even and
+    /// the odd pairs of temperatures
+    attempts: u64,
+    /// File where the replicas at each temperature and the acceptance of the
+    /// exchanges are written
+    file: Option<(File, PathBuf)>,
+    /// Random number generator for the exchanges
+    rng: Box<Rng + Send>,
+    /// Are the steps of the systems and the outputs out of date? This is
+    /// only used when running as the propagator of a `Simulation`.
+    pending: bool,
+}
+
+impl ReplicaExchange {
+    /// Create a new replica exchange simulation, attempting exchanges
+    /// between neighboring temperatures every `frequency` steps.
+    pub fn new(frequency: u64) -> ReplicaExchange {
+        assert!(frequency > 0, "the exchange frequency must be positive in replica exchange");
+        let mut rng = Box::new(rand::XorShiftRng::new_unseeded());
+        rng.reseed([2015u32, 42u32, 3u32, 12u32]);
+        ReplicaExchange {
+            levels: Vec::new(),
+            temperature_outputs: Vec::new(),
+            replica_outputs: Vec::new(),
+            frequency: frequency,
+            counters: Vec::new(),
+            attempts: 0,
+            file: None,
+            rng: rng,
+            pending: false,
+        }
+    }
+
+    /// Add a new replica of the `system`, simulated at `temperature` with the
+    /// `propagator`. The replicas must be added in increasing temperature
+    /// order.
+    pub fn add(&mut self, system: System, propagator: Box<Propagator + Send>, temperature: f64) {
+        assert!(temperature > 0.0, "the temperature must be positive in replica exchange");
+        if let Some(last) = self.levels.last() {
+            assert!(
+                temperature > last.temperature,
+                "the temperatures must be given in increasing order in replica exchange"
+            );
+            self.counters.push(MoveCounter::new(None));
+        }
+        let replica = self.levels.len();
+        self.levels.push(Level {
+            temperature: temperature,
+            propagator: propagator,
+            system: system,
+            replica: replica,
+        });
+        self.temperature_outputs.push(Vec::new());
+        self.replica_outputs.push(Vec::new());
+    }
+
+    /// Add an `output` for the system at the temperature with index
+    /// `temperature`, used at the given `frequency`.
+    pub fn add_temperature_output(&mut self, temperature: usize, output: Box<Output>, frequency: u64) {
+        self.temperature_outputs[temperature].push(OutputFrequency::with_frequency(output, frequency));
+    }
+
+    /// Add an `output` following the `replica`, used at the given
+    /// `frequency`. The replicas are indexed in the order they were added.
+    pub fn add_replica_output(&mut self, replica: usize, output: Box<Output>, frequency: u64) {
+        self.replica_outputs[replica].push(OutputFrequency::with_frequency(output, frequency));
+    }
+
+    /// Write the index of the replica at each temperature and the acceptance
+    /// ratio of the exchanges between neighboring temperatures to
+    /// `filename`, after each exchange attempt. The file is replaced if it
+    /// already exists.
+    pub fn set_exchanges_file<P: AsRef<Path>>(&mut self, filename: P) -> Result<(), io::Error> {
+        let file = try!(File::create(filename.as_ref()));
+        self.file = Some((file, filename.as_ref().to_owned()));
+        Ok(())
+    }
+
+    /// Get the system currently simulated at the temperature with index
+    /// `temperature`.
+    pub fn system(&self, temperature: usize) -> &System {
+        &self.levels[temperature].system
+    }
+
+    /// Get the index of the replica currently simulated at the temperature
+    /// with index `temperature`.
+    pub fn replica(&self, temperature: usize) -> usize {
+        self.levels[temperature].replica
+    }
+
+    /// Run the simulation for `nsteps` steps.
+    pub fn run(&mut self, nsteps: usize) {
+        self.setup_replicas();
+        for _ in 0..nsteps {
+            self.propagate_replicas();
+            for level in &mut self.levels {
+                level.system.increment_step();
+            }
+            self.write_outputs();
+        }
+        self.finish_replicas();
+    }
+
+    /// Propagate all the replicas for one step, and attempt the exchanges
+    /// between neighboring temperatures if needed. The step of the systems
+    /// is not incremented.
+    fn propagate_replicas(&mut self) {
+        self.levels.par_iter_mut().for_each(|level| {
+            level.propagator.propagate(&mut level.system);
+        });
+
+        let step = self.levels[0].system.step() + 1;
+        if step % self.frequency == 0 {
+            self.exchange(step);
+        }
+    }
+
+    /// Write the outputs for the system at each temperature and for each
+    /// replica.
+    fn write_outputs(&mut self) {
+        for (level, outputs) in self.levels.iter().zip(&mut self.temperature_outputs) {
+            for output in outputs {
+                output.write(&level.system);
+            }
+        }
+        for level in &self.levels {
+            for output in &mut self.replica_outputs[level.replica] {
+                output.write(&level.system);
+            }
+        }
+    }
+
+    /// When used as a propagator, the step of the system at the lowest
+    /// temperature is incremented by the simulation after the replicas are
+    /// propagated. This function increments the step of the other systems
+    /// accordingly, and writes the outputs for this step.
+    fn catch_up(&mut self) {
+        if !self.pending {
+            return;
+        }
+        for level in &mut self.levels[1..] {
+            level.system.increment_step();
+        }
+        self.write_outputs();
+        self.pending = false;
+    }
+
+    fn setup_replicas(&mut self) {
+        if self.levels.is_empty() {
+            fatal_error!("No replica in the replica exchange simulation");
+        }
+
+        for level in &mut self.levels {
+            level.setup();
+        }
+
+        for (level, outputs) in self.levels.iter().zip(&mut self.temperature_outputs) {
+            for output in outputs {
+                output.setup(&level.system);
+            }
+        }
+        for level in &self.levels {
+            for output in &mut self.replica_outputs[level.replica] {
+                output.setup(&level.system);
+            }
+        }
+
+        if let Some((ref mut file, ref path)) = self.file {
+            let temperatures = self.levels.iter().map(|level| {
+                utils::unit_to(level.temperature, "K").to_string()
+            }).collect::<Vec<_>>();
+            if let Err(err) = writeln!(file, "# Replica exchange between the temperatures (K): {}", temperatures.join(" ")) {
+                fatal_error!("Could not write to file '{}': {}", path.display(), err);
+            }
+            if let Err(err) = writeln!(file, "# Step Replicas[{}] Acceptance[{}]", self.levels.len(), self.counters.len()) {
+                fatal_error!("Could not write to file '{}': {}", path.display(), err);
+            }
+        }
+    }
+
+    /// Attempt exchanges between the even or the odd pairs of neighboring
+    /// temperatures.
+    fn exchange(&mut self, step: u64) {
+        let mut i = (self.attempts % 2) as usize;
+        self.attempts += 1;
+        while i + 1 < self.levels.len() {
+            let accepted = self.attempt(i);
+            let counter = &mut self.counters[i];
+            counter.ncalled += 1;
+            counter.nattempted += 1;
+            if accepted {
+                counter.naccepted += 1;
+            }
+            i += 2;
+        }
+
+        if let Some((ref mut file, ref path)) = self.file {
+            let replicas = self.levels.iter().map(|level| level.replica.to_string());
+            let acceptances = self.counters.iter().map(|counter| {
+                (counter.naccepted as f64 / counter.nattempted as f64).to_string()
+            });
+            let values = replicas.chain(acceptances).collect::<Vec<_>>();
+            if let Err(err) = writeln!(file, "{} {}", step, values.join(" ")) {
+                error!("Could not write to file '{}': {}", path.display(), err);
+            }
+        }
+    }
+
+    /// Attempt to exchange the systems at the temperatures with indexes `i`
+    /// and `i + 1`. Return whether the exchange was accepted.
+    fn attempt(&mut self, i: usize) -> bool {
+        let (low, high) = self.levels.split_at_mut(i + 1);
+        let low = &mut low[i];
+        let high = &mut high[0];
+
+        let beta_low = 1.0 / (K_BOLTZMANN * low.temperature);
+        let beta_high = 1.0 / (K_BOLTZMANN * high.temperature);
+        let delta = (beta_low - beta_high) * (low.system.potential_energy() - high.system.potential_energy());
+        let accepted = delta >= 0.0 || self.rng.next_f64() < f64::exp(delta);
+        if !accepted {
+            return false;
+        }
+
+        mem::swap(&mut low.system, &mut high.system);
+        mem::swap(&mut low.replica, &mut high.replica);
+        scale_velocities(&mut low.system, f64::sqrt(low.temperature / high.temperature));
+        scale_velocities(&mut high.system, f64::sqrt(high.temperature / low.temperature));
+        low.exchanged();
+        high.exchanged();
+        return true;
+    }
+
+    fn finish_replicas(&mut self) {
+        info!("Replica exchange simulation summary");
+        for (i, counter) in self.counters.iter().enumerate() {
+            info!(
+                "Exchanges between {} K and {} K",
+                utils::unit_to(self.levels[i].temperature, "K"),
+                utils::unit_to(self.levels[i + 1].temperature, "K")
+            );
+            info!("  Calls     : {}", counter.ncalled);
+            info!("  Acceptance: {} %", counter.naccepted as f64 /
+                counter.nattempted as f64 * 100.0);
+        }
+
+        for level in &mut self.levels {
+            level.propagator.finish(&level.system);
+        }
+
+        for (level, outputs) in self.levels.iter().zip(&mut self.temperature_outputs) {
+            for output in outputs {
+                output.finish(&level.system);
+            }
+        }
+        for level in &self.levels {
+            for output in &mut self.replica_outputs[level.replica] {
+                output.finish(&level.system);
+            }
+        }
+    }
+}
+
+impl Propagator for ReplicaExchange {
+    fn temperature_strategy(&self) -> TemperatureStrategy {
+        match self.levels.first() {
+            Some(level) => level.propagator.temperature_strategy(),
+            None => TemperatureStrategy::None,
+        }
+    }
+
+    fn degrees_of_freedom(&self) -> DegreesOfFreedom {
+        match self.levels.first() {
+            Some(level) => level.propagator.degrees_of_freedom(),
+            None => DegreesOfFreedom::Particles,
+        }
+    }
+
+    fn removed_motions(&self) -> RemovedMotions {
+        match self.levels.first() {
+            Some(level) => level.propagator.removed_motions(),
+            None => RemovedMotions::default(),
+        }
+    }
+
+    fn setup(&mut self, system: &System) {
+        if let Some(level) = self.levels.first_mut() {
+            level.system = system.clone();
+        }
+        self.setup_replicas();
+        self.pending = false;
+    }
+
+    fn propagate(&mut self, system: &mut System) {
+        mem::swap(system, &mut self.levels[0].system);
+        self.catch_up();
+        self.propagate_replicas();
+        self.pending = true;
+        mem::swap(system, &mut self.levels[0].system);
+    }
+
+    fn finish(&mut self, system: &System) {
+        self.levels[0].system = system.clone();
+        self.catch_up();
+        self.finish_replicas();
+    }
+}
+
+/// Multiply all the velocities in the `system` by `factor`
+fn scale_velocities(system: &mut System, factor: f64) {
+    for velocity in system.particles_mut().velocity {
+        *velocity *= factor;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sys::{Particle, UnitCell};
+    use sim::Simulation;
+    use sim::mc::{MonteCarlo, Translate};
+    use sim::md::MolecularDynamics;
+    use energy::{PairInteraction, Harmonic};
+    use types::Vector3D;
+
+    fn replica(velocity: f64) -> System {
+        let mut system = System::with_cell(UnitCell::cubic(10.0));
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(1.0, 1.0, 1.0)));
+        system.particles_mut().velocity[0] = Vector3D::new(velocity, 0.0, 0.0);
+        return system;
+    }
+
+    fn propagator(temperature: f64) -> Box<Propagator + Send> {
+        let mut mc = MonteCarlo::new(temperature);
+        mc.add(Box::new(Translate::new(0.5)), 1.0);
+        return Box::new(mc);
+    }
+
+    #[test]
+    fn exchange() {
+        let mut simulation = ReplicaExchange::new(1);
+        simulation.add(replica(1.0), propagator(100.0), 100.0);
+        simulation.add(replica(2.0), propagator(200.0), 200.0);
+
+        // Without interactions, all the exchanges are accepted
+        simulation.run(1);
+        assert_eq!(simulation.replica(0), 1);
+        assert_eq!(simulation.replica(1), 0);
+        assert_eq!(simulation.system(0).step(), 1);
+
+        // The velocities are rescaled to the new temperature
+        let velocity = simulation.system(0).particles().velocity[0];
+        assert_ulps_eq!(velocity[0], 2.0 * f64::sqrt(0.5), epsilon = 1e-12);
+        let velocity = simulation.system(1).particles().velocity[0];
+        assert_ulps_eq!(velocity[0], f64::sqrt(2.0), epsilon = 1e-12);
+
+        // Odd pairs of temperatures: nothing to exchange with two replicas
+        simulation.run(1);
+        assert_eq!(simulation.replica(0), 1);
+        assert_eq!(simulation.counters[0].naccepted, 1);
+    }
+
+    #[test]
+    fn simulation() {
+        let mut replica_exchange = ReplicaExchange::new(1);
+        replica_exchange.add(replica(1.0), propagator(100.0), 100.0);
+        replica_exchange.add(replica(2.0), propagator(200.0), 200.0);
+
+        // The simulated system replaces the first replica
+        let mut system = replica(3.0);
+        let mut simulation = Simulation::new(Box::new(replica_exchange));
+        simulation.run(&mut system, 2);
+
+        // The simulation follows the system at the lowest temperature
+        assert_eq!(system.step(), 2);
+        let velocity = system.particles().velocity[0];
+        assert_ulps_eq!(velocity[0], 2.0 * f64::sqrt(0.5), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn md_energy_conservation() {
+        let mut system = System::with_cell(UnitCell::cubic(20.0));
+        system.add_particle(Particle::with_position("Cl", Vector3D::new(0.0, 0.0, 0.0)));
+        system.add_particle(Particle::with_position("Cl", Vector3D::new(0.0, 0.0, 2.0)));
+        system.particles_mut().velocity[1] = Vector3D::new(0.0, 0.0, 0.01);
+        system.add_pair_potential("Cl", "Cl",
+            PairInteraction::new(Box::new(Harmonic{x0: 2.3, k: 0.1}), 10.0)
+        );
+
+        let mut simulation = ReplicaExchange::new(2);
+        simulation.add(system.clone(), Box::new(MolecularDynamics::new(0.1)), 100.0);
+        simulation.add(system.clone(), Box::new(MolecularDynamics::new(0.1)), 200.0);
+
+        simulation.setup_replicas();
+        for _ in 0..2 {
+            simulation.propagate_replicas();
+            for level in &mut simulation.levels {
+                level.system.increment_step();
+            }
+        }
+        // Both replicas have the same potential energy, so the exchange is
+        // always accepted
+        assert_eq!(simulation.counters[0].naccepted, 1);
+        assert_eq!(simulation.replica(0), 1);
+
+        // The energy of the exchanged systems, with rescaled velocities, is
+        // conserved by the following steps
+        let energies = simulation.levels.iter().map(|level| {
+            level.system.total_energy()
+        }).collect::<Vec<_>>();
+        for _ in 0..3 {
+            simulation.propagate_replicas();
+            for (level, &energy) in simulation.levels.iter().zip(&energies) {
+                assert_ulps_eq!(level.system.total_energy(), energy, epsilon = 1e-4 * energy);
+            }
+            for level in &mut simulation.levels {
+                level.system.increment_step();
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn temperatures_order() {
+        let mut simulation = ReplicaExchange::new(1);
+        simulation.add(replica(1.0), propagator(200.0), 200.0);
+        simulation.add(replica(2.0), propagator(100.0), 100.0);
+    }
+}
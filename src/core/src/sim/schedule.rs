@@ -0,0 +1,137 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Time-dependent setpoints for the simulation parameters
+
+/// The different kinds of schedules
+#[derive(Clone, Debug, PartialEq)]
+enum Kind {
+    /// Linear interpolation between `start` and `end`
+    Linear {
+        start: f64,
+        end: f64,
+        steps: u64,
+    },
+    /// Exponential interpolation between `start` and `end`
+    Exponential {
+        start: f64,
+        end: f64,
+        steps: u64,
+    },
+    /// Piecewise constant values, sorted by step
+    Steps(Vec<(u64, f64)>),
+}
+
+/// A `Schedule` gives the value of a simulation parameter, such as a target
+/// temperature or pressure, as a function of the simulation step (see
+/// `System::step`). This can be used for simulated annealing, heating or
+/// cooling ramps and compression protocols.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    kind: Kind,
+}
+
+impl Schedule {
+    /// Create a schedule going linearly from `start` at step 0 to `end` at
+    /// step `steps`, and staying at `end` afterward.
+    pub fn linear(start: f64, end: f64, steps: u64) -> Schedule {
+        assert!(steps > 0, "The number of steps must be positive in linear schedule");
+        Schedule {
+            kind: Kind::Linear{start: start, end: end, steps: steps}
+        }
+    }
+
+    /// Create a schedule going exponentially from `start` at step 0 to `end`
+    /// at step `steps`, and staying at `end` afterward. The value is
+    /// multiplied by the same factor at each step, and `start` and `end` must
+    /// be strictly positive.
+    pub fn exponential(start: f64, end: f64, steps: u64) -> Schedule {
+        assert!(steps > 0, "The number of steps must be positive in exponential schedule");
+        assert!(start > 0.0 && end > 0.0, "The values must be positive in exponential schedule");
+        Schedule {
+            kind: Kind::Exponential{start: start, end: end, steps: steps}
+        }
+    }
+
+    /// Create a piecewise constant schedule from a list of `(step, value)`
+    /// pairs. The value of the schedule is the one associated with the last
+    /// step smaller or equal to the current step, or the first value before
+    /// the first step.
+    pub fn steps(mut values: Vec<(u64, f64)>) -> Schedule {
+        assert!(!values.is_empty(), "The list of values can not be empty in steps schedule");
+        values.sort_by_key(|&(step, _)| step);
+        Schedule {
+            kind: Kind::Steps(values)
+        }
+    }
+
+    /// Get the value of this schedule at the given simulation `step`
+    pub fn value(&self, step: u64) -> f64 {
+        match self.kind {
+            Kind::Linear{start, end, steps} => {
+                let progress = f64::min(step as f64 / steps as f64, 1.0);
+                start + progress * (end - start)
+            }
+            Kind::Exponential{start, end, steps} => {
+                let progress = f64::min(step as f64 / steps as f64, 1.0);
+                start * f64::powf(end / start, progress)
+            }
+            Kind::Steps(ref values) => {
+                let mut current = values[0].1;
+                for &(start, value) in values {
+                    if start > step {
+                        break;
+                    }
+                    current = value;
+                }
+                current
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear() {
+        let schedule = Schedule::linear(300.0, 200.0, 100);
+        assert_eq!(schedule.value(0), 300.0);
+        assert_eq!(schedule.value(50), 250.0);
+        assert_eq!(schedule.value(100), 200.0);
+        assert_eq!(schedule.value(1000), 200.0);
+    }
+
+    #[test]
+    fn exponential() {
+        let schedule = Schedule::exponential(1000.0, 10.0, 100);
+        assert_eq!(schedule.value(0), 1000.0);
+        assert_ulps_eq!(schedule.value(50), 100.0, epsilon=1e-10);
+        assert_ulps_eq!(schedule.value(100), 10.0, epsilon=1e-10);
+        assert_ulps_eq!(schedule.value(1000), 10.0, epsilon=1e-10);
+    }
+
+    #[test]
+    fn steps() {
+        let schedule = Schedule::steps(vec![(100, 2.0), (10, 1.0), (200, 3.0)]);
+        assert_eq!(schedule.value(0), 1.0);
+        assert_eq!(schedule.value(10), 1.0);
+        assert_eq!(schedule.value(99), 1.0);
+        assert_eq!(schedule.value(100), 2.0);
+        assert_eq!(schedule.value(150), 2.0);
+        assert_eq!(schedule.value(1000), 3.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn exponential_negative() {
+        let _ = Schedule::exponential(-1.0, 10.0, 100);
+    }
+
+    #[test]
+    #[should_panic]
+    fn empty_steps() {
+        let _ = Schedule::steps(Vec::new());
+    }
+}
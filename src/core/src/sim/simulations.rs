@@ -9,7 +9,7 @@ use sim::TemperatureStrategy;
 use out::Output;
 
 /// Writing an output at a given frequency
-struct OutputFrequency {
+pub(crate) struct OutputFrequency {
     /// The output to use
     output: Box<Output>,
     /// The frequency. `output` will be used every time the system step matches
@@ -18,14 +18,14 @@ struct OutputFrequency {
 }
 
 impl OutputFrequency {
-    pub fn new(output: Box<Output>) -> OutputFrequency {
+    pub(crate) fn new(output: Box<Output>) -> OutputFrequency {
         OutputFrequency{
             frequency: 1,
             output: output,
         }
     }
 
-    pub fn with_frequency(output: Box<Output>, frequency: u64) -> OutputFrequency {
+    pub(crate) fn with_frequency(output: Box<Output>, frequency: u64) -> OutputFrequency {
         OutputFrequency{
             frequency: frequency,
             output: output,
@@ -75,6 +75,8 @@ impl Simulation {
             TemperatureStrategy::Velocities => system.external_temperature(None),
             TemperatureStrategy::None => {}
         }
+        system.set_degrees_of_freedom(self.propagator.degrees_of_freedom());
+        system.set_removed_motions(self.propagator.removed_motions());
 
         self.setup(system);
         for i in 0..nsteps {
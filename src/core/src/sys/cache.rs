@@ -9,7 +9,7 @@
 //! energy components, by storing them and providing update callbacks.
 use std::mem;
 
-use sys::System;
+use sys::{System, Particle};
 use types::{Vector3D, Array2};
 
 /// Callback for updating a cache. It also take an `&mut System` argument for
@@ -39,7 +39,10 @@ pub struct EnergyCache {
     /// Energy of global interactions
     global: f64,
     /// Callback to be called to update the cache if the system is modified
-    updater: Option<UpdateCallback>
+    updater: Option<UpdateCallback>,
+    /// Energy change computed by the last call to a `*_cost` function, if
+    /// the cache was not updated since then
+    last_cost: Option<f64>,
 }
 
 impl EnergyCache {
@@ -55,6 +58,7 @@ impl EnergyCache {
             coulomb: 0.0,
             global: 0.0,
             updater: None,
+            last_cost: None,
         }
     }
 
@@ -73,14 +77,22 @@ impl EnergyCache {
     /// Initialize the cache to be used with `system`. After a call to this
     /// function, the cache is only usable with the same system. To change
     /// the associated system, one must call this function again.
+    ///
+    /// The energy of the pairs of frozen particles is constant, and is not
+    /// included in the cache.
     pub fn init(&mut self, system: &System) {
         self.clear();
+        self.last_cost = None;
         self.pairs_cache.resize_if_different((system.size(), system.size()));
 
         let evaluator = system.energy_evaluator();
+        let frozen = system.particles().frozen;
 
         for i in 0..system.size() {
             for j in (i + 1)..system.size() {
+                if frozen[i] && frozen[j] {
+                    continue;
+                }
                 let r = system.nearest_image(i, j).norm();
                 let energy = evaluator.pair(r, i, j);
                 self.pairs_cache[(i, j)] = energy;
@@ -113,9 +125,24 @@ impl EnergyCache {
         return energy;
     }
 
+    /// Get the energy change computed by the last call to a
+    /// `EnergyCache::*_cost` function, or `None` if the cache was updated or
+    /// marked as unused since then.
+    pub fn last_cost(&self) -> Option<f64> {
+        self.last_cost
+    }
+
+    /// Forget about the energy change computed by the last call to a
+    /// `EnergyCache::*_cost` function, for example when the corresponding
+    /// move was rejected.
+    pub fn reset_last_cost(&mut self) {
+        self.last_cost = None;
+    }
+
     /// Update the cache after a call to a `EnergyCache::*_cost` function or
     /// `EnergyCache::unused`.
     pub fn update(&mut self, system: &mut System) {
+        self.last_cost = None;
         let updater = mem::replace(&mut self.updater, None);
         if let Some(updater) = updater {
             updater(self, system);
@@ -129,6 +156,7 @@ impl EnergyCache {
     /// still want it to be updated. Future call to `EnergyCache::update` will
     /// recompute the full cache.
     pub fn unused(&mut self) {
+        self.last_cost = None;
         self.updater = Some(Box::new(|cache, system| {
             cache.init(system);
         }))
@@ -145,6 +173,7 @@ impl EnergyCache {
     pub fn move_particles_cost(&mut self, system: &System, idxes: Vec<usize>, newpos: &[Vector3D]) -> f64 {
         let evaluator = system.energy_evaluator();
         let positions = system.particles().position;
+        let frozen = system.particles().frozen;
 
         // First, go for pair interactions
         let mut new_pairs = Array2::<f64>::zeros((system.size(), system.size()));
@@ -154,6 +183,8 @@ impl EnergyCache {
             for part_j in 0..system.size() {
                 // Exclude interactions inside the sub-system.
                 if idxes.contains(&part_j) {continue}
+                // Exclude interactions between frozen particles
+                if frozen[part_i] && frozen[part_j] {continue}
 
                 let r = system.cell.distance(&positions[part_j], &newpos[i]);
                 let energy = evaluator.pair(r, part_i, part_j);
@@ -169,6 +200,7 @@ impl EnergyCache {
         // Interactions within the sub-system being moved
         for (i, &part_i) in idxes.iter().enumerate() {
             for (j, &part_j) in idxes.iter().enumerate().skip(i + 1) {
+                if frozen[part_i] && frozen[part_j] {continue}
                 let r = system.cell.distance(&newpos[i], &newpos[j]);
                 let energy = evaluator.pair(r, part_i, part_j);
 
@@ -268,6 +300,7 @@ impl EnergyCache {
                 global.update();
             }
         }));
+        self.last_cost = Some(cost);
         return cost;
     }
 
@@ -290,6 +323,7 @@ impl EnergyCache {
     pub fn move_all_rigid_molecules_cost(&mut self, system: &System) -> f64 {
         let evaluator = system.energy_evaluator();
         let positions = system.particles().position;
+        let frozen = system.particles().frozen;
 
         let mut new_pairs = Array2::<f64>::zeros((system.size(), system.size()));
         let mut pairs_delta = 0.0;
@@ -299,6 +333,7 @@ impl EnergyCache {
                 // Loop over all particles in the molecules
                 for pi in mi.iter() {
                     for pj in mj.iter() {
+                        if frozen[pi] && frozen[pj] {continue}
                         let r = system.cell.distance(&positions[pi], &positions[pj]);
                         let energy = evaluator.pair(r, pi, pj);
                         pairs_delta += energy;
@@ -341,8 +376,282 @@ impl EnergyCache {
                 }
             }
         }));
+        self.last_cost = Some(cost);
         cost
     }
+
+    /// Get the cost of adding the molecule at index `molid` to the system.
+    ///
+    /// The new molecule must already be in the `system`, as the last molecule
+    /// of the system, while the cache still corresponds to the system
+    /// without this molecule.
+    ///
+    /// This function ***DOES NOT*** update the cache, the `update` function
+    /// MUST be called if the molecule is effectively added.
+    pub fn add_molecule_cost(&mut self, system: &System, molid: usize) -> f64 {
+        let molecule = system.molecule(molid);
+        let size = system.size();
+        let start = molecule.start();
+        assert_eq!(molecule.end(), size, "the new molecule must be the last one in the system");
+        debug_assert_eq!(self.pairs_cache.dim(), (start, start));
+
+        let evaluator = system.energy_evaluator();
+        let frozen = system.particles().frozen;
+
+        // Interactions of the new particles with all the other particles.
+        // The row `i - start` contains the interactions of the particle `i`.
+        let mut new_pairs = Array2::<f64>::zeros((molecule.size(), size));
+        let mut pairs_delta = 0.0;
+        for i in molecule.iter() {
+            for j in 0..size {
+                // Only account once for the pairs in the new molecule
+                if molecule.contains(j) && j <= i {continue}
+                if frozen[i] && frozen[j] {continue}
+
+                let r = system.nearest_image(i, j).norm();
+                let energy = evaluator.pair(r, i, j);
+                pairs_delta += energy;
+                new_pairs[(i - start, j)] = energy;
+                if molecule.contains(j) {
+                    new_pairs[(j - start, i)] = energy;
+                }
+            }
+        }
+
+        let (bonds, angles, dihedrals) = molecule_bonded_energies(system, molid);
+        let idxes = molecule.iter().collect::<Vec<_>>();
+        let coulomb_delta = if let Some(coulomb) = system.coulomb_potential() {
+            coulomb.add_particles_cost(system, &idxes)
+        } else {
+            0.0
+        };
+
+        let mut global_delta = 0.0;
+        for global in system.global_potentials() {
+            global_delta += global.add_particles_cost(system, &idxes);
+        }
+
+        let pairs_tail = evaluator.pairs_tail();
+
+        let cost = pairs_delta + (pairs_tail - self.pairs_tail)
+                               + bonds + angles + dihedrals
+                               + coulomb_delta + global_delta;
+
+        self.updater = Some(Box::new(move |cache, system| {
+            cache.pairs += pairs_delta;
+            cache.pairs_tail = pairs_tail;
+            cache.bonds += bonds;
+            cache.angles += angles;
+            cache.dihedrals += dihedrals;
+            cache.coulomb += coulomb_delta;
+            cache.global += global_delta;
+
+            let (nnew, size) = new_pairs.dim();
+            let start = size - nnew;
+            let mut pairs_cache = Array2::<f64>::zeros((size, size));
+            for i in 0..start {
+                for j in 0..start {
+                    pairs_cache[(i, j)] = cache.pairs_cache[(i, j)];
+                }
+            }
+            for i in 0..nnew {
+                for j in 0..size {
+                    pairs_cache[(start + i, j)] = new_pairs[(i, j)];
+                    pairs_cache[(j, start + i)] = new_pairs[(i, j)];
+                }
+            }
+            cache.pairs_cache = pairs_cache;
+
+            if let Some(coulomb) = system.coulomb_potential() {
+                coulomb.update();
+            }
+
+            for global in system.global_potentials() {
+                global.update();
+            }
+        }));
+        self.last_cost = Some(cost);
+        return cost;
+    }
+
+    /// Get the cost of removing the molecule at index `molid` from the
+    /// system. The molecule must still be in the `system`.
+    ///
+    /// This function ***DOES NOT*** update the cache, the `update` function
+    /// MUST be called if the molecule is effectively removed.
+    pub fn remove_molecule_cost(&mut self, system: &System, molid: usize) -> f64 {
+        let molecule = system.molecule(molid);
+        let evaluator = system.energy_evaluator();
+
+        let mut pairs_delta = 0.0;
+        for i in molecule.iter() {
+            for j in 0..system.size() {
+                // Only account once for the pairs in the removed molecule
+                if molecule.contains(j) && j <= i {continue}
+                pairs_delta -= self.pairs_cache[(i, j)];
+            }
+        }
+
+        let (bonds, angles, dihedrals) = molecule_bonded_energies(system, molid);
+        let idxes = molecule.iter().collect::<Vec<_>>();
+        let coulomb_delta = if let Some(coulomb) = system.coulomb_potential() {
+            coulomb.remove_particles_cost(system, &idxes)
+        } else {
+            0.0
+        };
+
+        let mut global_delta = 0.0;
+        for global in system.global_potentials() {
+            global_delta += global.remove_particles_cost(system, &idxes);
+        }
+
+        let mut composition = system.composition();
+        for &kind in &system.particles().kind[molecule.iter()] {
+            composition[kind] -= 1;
+        }
+        let pairs_tail = evaluator.pairs_tail_with(&composition);
+
+        let cost = pairs_delta + (pairs_tail - self.pairs_tail)
+                               - bonds - angles - dihedrals
+                               + coulomb_delta + global_delta;
+
+        let removed = molecule.iter();
+        self.updater = Some(Box::new(move |cache, system| {
+            cache.pairs += pairs_delta;
+            cache.pairs_tail = pairs_tail;
+            cache.bonds -= bonds;
+            cache.angles -= angles;
+            cache.dihedrals -= dihedrals;
+            cache.coulomb += coulomb_delta;
+            cache.global += global_delta;
+
+            // Get the index of a particle before the removal
+            let nremoved = removed.len();
+            let old = |i: usize| if i < removed.start {i} else {i + nremoved};
+            let size = cache.pairs_cache.dim().0 - nremoved;
+            let mut pairs_cache = Array2::<f64>::zeros((size, size));
+            for i in 0..size {
+                for j in 0..size {
+                    pairs_cache[(i, j)] = cache.pairs_cache[(old(i), old(j))];
+                }
+            }
+            cache.pairs_cache = pairs_cache;
+
+            if let Some(coulomb) = system.coulomb_potential() {
+                coulomb.update();
+            }
+
+            for global in system.global_potentials() {
+                global.update();
+            }
+        }));
+        self.last_cost = Some(cost);
+        return cost;
+    }
+
+    /// Get the cost of changing the particle at index `i` in the system. The
+    /// `system` must already contain the new particle, and `previous` is the
+    /// particle before the change. The particle must not be bonded to other
+    /// particles, and must keep the same position.
+    ///
+    /// This function ***DOES NOT*** update the cache, the `update` function
+    /// MUST be called if the particle is effectively changed.
+    pub fn change_particle_cost(&mut self, system: &System, i: usize, previous: &Particle) -> f64 {
+        debug_assert_eq!(system.molecule(system.molid(i)).size(), 1);
+        let evaluator = system.energy_evaluator();
+        let frozen = system.particles().frozen;
+
+        let mut new_pairs = vec![0.0; system.size()];
+        let mut pairs_delta = 0.0;
+        for j in 0..system.size() {
+            if i == j {continue}
+            if frozen[i] && frozen[j] {continue}
+
+            let r = system.nearest_image(i, j).norm();
+            let energy = evaluator.pair(r, i, j);
+            pairs_delta += energy - self.pairs_cache[(i, j)];
+            new_pairs[j] = energy;
+        }
+
+        let mut coulomb_delta = 0.0;
+        let mut global_delta = 0.0;
+        if system.coulomb_potential().is_some() || !system.global_potentials().is_empty() {
+            // Configuration with the previous particle, to get the energy of
+            // the particle before the change
+            let mut old = (**system).clone();
+            {
+                let particles = old.particles_mut();
+                particles.name[i] = previous.name.clone();
+                particles.kind[i] = previous.kind;
+                particles.charge[i] = previous.charge;
+                particles.mass[i] = previous.mass;
+            }
+
+            if let Some(coulomb) = system.coulomb_potential() {
+                coulomb_delta -= coulomb.add_particles_cost(&old, &[i]);
+                coulomb_delta += coulomb.add_particles_cost(system, &[i]);
+            }
+
+            for global in system.global_potentials() {
+                global_delta -= global.add_particles_cost(&old, &[i]);
+                global_delta += global.add_particles_cost(system, &[i]);
+            }
+        }
+
+        let pairs_tail = evaluator.pairs_tail();
+
+        let cost = pairs_delta + (pairs_tail - self.pairs_tail)
+                               + coulomb_delta + global_delta;
+
+        self.updater = Some(Box::new(move |cache, system| {
+            cache.pairs += pairs_delta;
+            cache.pairs_tail = pairs_tail;
+            cache.coulomb += coulomb_delta;
+            cache.global += global_delta;
+
+            for (j, &energy) in new_pairs.iter().enumerate() {
+                cache.pairs_cache[(i, j)] = energy;
+                cache.pairs_cache[(j, i)] = energy;
+            }
+
+            if let Some(coulomb) = system.coulomb_potential() {
+                coulomb.update();
+            }
+
+            for global in system.global_potentials() {
+                global.update();
+            }
+        }));
+        self.last_cost = Some(cost);
+        return cost;
+    }
+}
+
+/// Get the energy of the bonds, angles and dihedral angles in the molecule at
+/// index `molid`.
+fn molecule_bonded_energies(system: &System, molid: usize) -> (f64, f64, f64) {
+    let evaluator = system.energy_evaluator();
+    let molecule = system.molecule(molid);
+
+    let mut bonds = 0.0;
+    for bond in molecule.bonds() {
+        let r = system.nearest_image(bond.i(), bond.j()).norm();
+        bonds += evaluator.bond(r, bond.i(), bond.j());
+    }
+
+    let mut angles = 0.0;
+    for angle in molecule.angles() {
+        let (i, j, k) = (angle.i(), angle.j(), angle.k());
+        angles += evaluator.angle(system.angle(i, j, k), i, j, k);
+    }
+
+    let mut dihedrals = 0.0;
+    for dihedral in molecule.dihedrals() {
+        let (i, j, k, m) = (dihedral.i(), dihedral.j(), dihedral.k(), dihedral.m());
+        dihedrals += evaluator.dihedral(system.dihedral(i, j, k, m), i, j, k, m);
+    }
+
+    return (bonds, angles, dihedrals);
 }
 
 /// Return either the new position of a particle (from `newpos`) if its index
@@ -423,6 +732,27 @@ mod tests {
         assert_ulps_eq!(cache.energy(), system.potential_energy());
     }
 
+    #[test]
+    fn frozen_pairs() {
+        let mut system = testing_system();
+        let energy = system.potential_energy();
+        system.set_frozen_molecule(1, true);
+        // The pairs energy inside the frozen molecule is not computed
+        assert!(system.potential_energy() != energy);
+
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+        assert_ulps_eq!(cache.energy(), system.potential_energy());
+
+        let idxes = vec![0, 3];
+        let newpos = &[Vector3D::new(0.0, 0.0, 0.5), Vector3D::new(-0.7, 0.2, 1.5)];
+        let old_e = system.potential_energy();
+        let cost = cache.move_particles_cost(&system, idxes, newpos);
+        system.particles_mut().position[0] = newpos[0];
+        system.particles_mut().position[3] = newpos[1];
+        assert_ulps_eq!(cost, system.potential_energy() - old_e);
+    }
+
     #[test]
     fn move_atoms() {
         let mut system = testing_system();
@@ -434,14 +764,19 @@ mod tests {
         let newpos = &[Vector3D::new(0.0, 0.0, 0.5), Vector3D::new(-0.7, 0.2, 1.5)];
 
         let cost = cache.move_particles_cost(&system, idxes, newpos);
+        assert_eq!(cache.last_cost(), Some(cost));
 
         system.particles_mut().position[0] = newpos[0];
         system.particles_mut().position[3] = newpos[1];
         let new_e = system.potential_energy();
         assert_ulps_eq!(cost, new_e - old_e);
 
+        cache.reset_last_cost();
+        assert_eq!(cache.last_cost(), None);
+
         cache.update(&mut system);
         assert_ulps_eq!(cache.energy(), new_e);
+        assert_eq!(cache.last_cost(), None);
 
         // Check that the cache is really updated
         let old_e = new_e;
@@ -487,4 +822,90 @@ mod tests {
         let new_e = new_system.potential_energy();
         assert_ulps_eq!(cost, new_e - old_e, epsilon=1e-12);
     }
+
+    #[test]
+    fn add_molecule() {
+        let mut system = testing_system();
+        let mut smaller = system.clone();
+        smaller.remove_molecule(1);
+
+        let mut cache = EnergyCache::new();
+        cache.init(&smaller);
+        let old_e = smaller.potential_energy();
+
+        let cost = cache.add_molecule_cost(&system, 1);
+        let new_e = system.potential_energy();
+        assert_ulps_eq!(cost, new_e - old_e, epsilon=1e-12);
+        cache.update(&mut system);
+        assert_ulps_eq!(cache.energy(), new_e, epsilon=1e-12);
+
+        // Check that the cache is usable after the update
+        let idxes = vec![4, 5];
+        let newpos = &[Vector3D::new(3.0, 0.2, 0.1), Vector3D::new(3.0, 0.0, 1.6)];
+        let cost = cache.move_particles_cost(&system, idxes, newpos);
+        system.particles_mut().position[4] = newpos[0];
+        system.particles_mut().position[5] = newpos[1];
+        assert_ulps_eq!(cost, system.potential_energy() - new_e, epsilon=1e-12);
+    }
+
+    #[test]
+    fn remove_molecule() {
+        let mut system = testing_system();
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+        let old_e = system.potential_energy();
+
+        let cost = cache.remove_molecule_cost(&system, 0);
+        system.remove_molecule(0);
+        let new_e = system.potential_energy();
+        assert_ulps_eq!(cost, new_e - old_e, epsilon=1e-12);
+        cache.update(&mut system);
+        assert_ulps_eq!(cache.energy(), new_e, epsilon=1e-12);
+
+        // Check that the cache is usable after the update
+        let idxes = vec![0, 1];
+        let newpos = &[Vector3D::new(3.0, 0.2, 0.1), Vector3D::new(3.0, 0.0, 1.6)];
+        let cost = cache.move_particles_cost(&system, idxes, newpos);
+        system.particles_mut().position[0] = newpos[0];
+        system.particles_mut().position[1] = newpos[1];
+        assert_ulps_eq!(cost, system.potential_energy() - new_e, epsilon=1e-12);
+    }
+
+    #[test]
+    fn change_particle() {
+        let mut system = system_from_xyz("3
+        cell: 10.0
+        Na    0.000000     0.000000     0.000000
+        Cl    2.500000     0.000000     0.000000
+        Na    0.000000     2.800000     0.000000");
+
+        for &(a, b) in &[("Na", "Na"), ("Na", "Cl"), ("Cl", "Cl")] {
+            system.add_pair_potential(a, b, PairInteraction::new(
+                Box::new(LennardJones{sigma: 2.0, epsilon: unit_from(0.3, "kJ/mol")}), 4.0
+            ));
+        }
+        system.set_coulomb_potential(Box::new(Wolf::new(4.0)));
+        system.particles_mut().charge[0] = 1.0;
+        system.particles_mut().charge[1] = -1.0;
+        system.particles_mut().charge[2] = 1.0;
+
+        let mut cache = EnergyCache::new();
+        cache.init(&system);
+        let old_e = system.potential_energy();
+
+        let mut previous = Particle::new("Na");
+        previous.kind = system.particles().kind[0];
+        previous.charge = 1.0;
+
+        let kind = system.particles().kind[1];
+        system.particles_mut().name[0] = String::from("Cl");
+        system.particles_mut().kind[0] = kind;
+        system.particles_mut().charge[0] = -1.0;
+
+        let cost = cache.change_particle_cost(&system, 0, &previous);
+        let new_e = system.potential_energy();
+        assert_ulps_eq!(cost, new_e - old_e, epsilon=1e-12);
+        cache.update(&mut system);
+        assert_ulps_eq!(cache.energy(), new_e, epsilon=1e-12);
+    }
 }
@@ -7,7 +7,7 @@ use std::f64::consts::PI;
 use consts::K_BOLTZMANN;
 use types::{Matrix3, Vector3D, Zero, One};
 
-use sys::System;
+use sys::{System, Selection};
 use sys::zip_particle::*;
 
 use parallel::prelude::*;
@@ -30,80 +30,138 @@ pub struct Forces;
 impl Compute for Forces {
     type Output = Vec<Vector3D>;
     fn compute(&self, system: &System) -> Vec<Vector3D> {
-        let natoms = system.size();
-        let thread_forces_store = ThreadLocalStore::new(|| vec![Vector3D::zero(); natoms]);
+        let mut forces = pairs_forces(system);
+        bonded_forces(system, &mut forces);
+        global_forces(system, &mut forces);
+        return forces;
+    }
+}
 
-        (0..natoms).into_par_iter().for_each(|i| {
+/// The different components of the forces acting on a system.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ForceComponent {
+    /// Forces coming from the bonds, angles and dihedral angles potentials
+    Bonded,
+    /// Forces coming from the pair potentials
+    Pairs,
+    /// Forces coming from the coulombic and global potentials
+    Global,
+}
 
-            let mut thread_forces = thread_forces_store.borrow_mut();
+/// Compute a single component of the forces acting on the system, and return
+/// a vector of force acting on each particles. The sum of all the components
+/// is equal to the output of `Forces`.
+pub struct ComponentForces {
+    /// The component of the forces to compute
+    pub component: ForceComponent,
+}
 
-            for j in (i+1)..system.size() {
-                let distance = system.bond_distance(i, j);
-                let d = system.nearest_image(i, j);
-                let dn = d.normalized();
-                let r = d.norm();
-                for potential in system.pair_potentials(i, j) {
-                    let info = potential.restriction().information(distance);
-                    if !info.excluded {
-                        let force = info.scaling * potential.force(r) * dn;
-                        thread_forces[i] += force;
-                        thread_forces[j] -= force;
-                    }
-                }
+impl Compute for ComponentForces {
+    type Output = Vec<Vector3D>;
+    fn compute(&self, system: &System) -> Vec<Vector3D> {
+        match self.component {
+            ForceComponent::Pairs => pairs_forces(system),
+            ForceComponent::Bonded => {
+                let mut forces = vec![Vector3D::zero(); system.size()];
+                bonded_forces(system, &mut forces);
+                forces
+            }
+            ForceComponent::Global => {
+                let mut forces = vec![Vector3D::zero(); system.size()];
+                global_forces(system, &mut forces);
+                forces
             }
-        });
+        }
+    }
+}
 
-        // At this point all the forces are computed, but the
-        // results are scattered across all thread local Vecs,
-        // here we gather them.
-        let mut forces = vec![Vector3D::zero(); natoms];
-        thread_forces_store.sum_local_values(&mut forces);
+/// Compute the forces coming from the pair potentials
+fn pairs_forces(system: &System) -> Vec<Vector3D> {
+    let natoms = system.size();
+    let thread_forces_store = ThreadLocalStore::new(|| vec![Vector3D::zero(); natoms]);
 
-        for molecule in system.molecules() {
-            for bond in molecule.bonds() {
-                let (i, j) = (bond.i(), bond.j());
-                let d = system.nearest_image(i, j);
-                let dn = d.normalized();
-                let r = d.norm();
-                for potential in system.bond_potentials(i, j) {
-                    let force = potential.force(r) * dn;
-                    forces[i] += force;
-                    forces[j] -= force;
-                }
-            }
+    let frozen = system.particles().frozen;
+    (0..natoms).into_par_iter().for_each(|i| {
 
-            for angle in molecule.angles() {
-                let (i, j, k) = (angle.i(), angle.j(), angle.k());
-                let (theta, d1, d2, d3) = system.angle_and_derivatives(i, j, k);
-                for potential in system.angle_potentials(i, j, k) {
-                    let force = potential.force(theta);
-                    forces[i] += force * d1;
-                    forces[j] += force * d2;
-                    forces[k] += force * d3;
+        let mut thread_forces = thread_forces_store.borrow_mut();
+
+        for j in (i+1)..system.size() {
+            // The interactions between frozen particles are constant
+            if frozen[i] && frozen[j] {
+                continue;
+            }
+            let distance = system.bond_distance(i, j);
+            let d = system.nearest_image(i, j);
+            let dn = d.normalized();
+            let r = d.norm();
+            for potential in system.pair_potentials(i, j) {
+                let info = potential.restriction().information(distance);
+                if !info.excluded {
+                    let force = info.scaling * potential.force(r) * dn;
+                    thread_forces[i] += force;
+                    thread_forces[j] -= force;
                 }
             }
+        }
+    });
+
+    // At this point all the forces are computed, but the
+    // results are scattered across all thread local Vecs,
+    // here we gather them.
+    let mut forces = vec![Vector3D::zero(); natoms];
+    thread_forces_store.sum_local_values(&mut forces);
+    return forces;
+}
 
-            for dihedral in molecule.dihedrals() {
-                let (i, j, k, m) = (dihedral.i(), dihedral.j(), dihedral.k(), dihedral.m());
-                let (phi, d1, d2, d3, d4) = system.dihedral_and_derivatives(i, j, k, m);
-                for potential in system.dihedral_potentials(i, j, k, m) {
-                    let force = potential.force(phi);
-                    forces[i] += force * d1;
-                    forces[j] += force * d2;
-                    forces[k] += force * d3;
-                    forces[m] += force * d4;
-                }
+/// Add the forces coming from the bonds, angles and dihedral angles
+/// potentials to `forces`
+fn bonded_forces(system: &System, forces: &mut [Vector3D]) {
+    for molecule in system.molecules() {
+        for bond in molecule.bonds() {
+            let (i, j) = (bond.i(), bond.j());
+            let d = system.nearest_image(i, j);
+            let dn = d.normalized();
+            let r = d.norm();
+            for potential in system.bond_potentials(i, j) {
+                let force = potential.force(r) * dn;
+                forces[i] += force;
+                forces[j] -= force;
             }
         }
 
-        if let Some(coulomb) = system.coulomb_potential() {
-            coulomb.forces(system, &mut forces);
+        for angle in molecule.angles() {
+            let (i, j, k) = (angle.i(), angle.j(), angle.k());
+            let (theta, d1, d2, d3) = system.angle_and_derivatives(i, j, k);
+            for potential in system.angle_potentials(i, j, k) {
+                let force = potential.force(theta);
+                forces[i] += force * d1;
+                forces[j] += force * d2;
+                forces[k] += force * d3;
+            }
         }
 
-        for global in system.global_potentials() {
-            global.forces(system, &mut forces);
+        for dihedral in molecule.dihedrals() {
+            let (i, j, k, m) = (dihedral.i(), dihedral.j(), dihedral.k(), dihedral.m());
+            let (phi, d1, d2, d3, d4) = system.dihedral_and_derivatives(i, j, k, m);
+            for potential in system.dihedral_potentials(i, j, k, m) {
+                let force = potential.force(phi);
+                forces[i] += force * d1;
+                forces[j] += force * d2;
+                forces[k] += force * d3;
+                forces[m] += force * d4;
+            }
         }
-        return forces;
+    }
+}
+
+/// Add the forces coming from the coulombic and global potentials to `forces`
+fn global_forces(system: &System, forces: &mut [Vector3D]) {
+    if let Some(coulomb) = system.coulomb_potential() {
+        coulomb.forces(system, forces);
+    }
+
+    for global in system.global_potentials() {
+        global.forces(system, forces);
     }
 }
 
@@ -135,8 +193,10 @@ impl Compute for KineticEnergy {
     type Output = f64;
     fn compute(&self, system: &System) -> f64 {
         let mut energy = 0.0;
-        for (&mass, velocity) in system.particles().zip((&Mass, &Velocity)) {
-            energy += 0.5 * mass * velocity.norm2();
+        for (&mass, velocity, &frozen) in system.particles().zip((&Mass, &Velocity, &Frozen)) {
+            if !frozen {
+                energy += 0.5 * mass * velocity.norm2();
+            }
         }
         assert!(energy.is_finite(), "Kinetic energy is infinite!");
         return energy;
@@ -156,14 +216,94 @@ impl Compute for TotalEnergy {
 }
 
 /******************************************************************************/
-/// Compute the instantaneous temperature of the system
+/// Compute the conserved energy of the system, *i.e.* the total energy plus
+/// the energy of the extended degrees of freedom (see
+/// `System::set_extended_energy`).
+pub struct ConservedEnergy;
+impl Compute for ConservedEnergy {
+    type Output = f64;
+    fn compute(&self, system: &System) -> f64 {
+        let total = TotalEnergy.compute(system);
+        return total + system.extended_energy();
+    }
+}
+
+/******************************************************************************/
+/// Compute the instantaneous temperature of the system, using the number of
+/// degrees of freedom given by `System::degrees_of_freedom`.
 pub struct Temperature;
 impl Compute for Temperature {
     type Output = f64;
     fn compute(&self, system: &System) -> f64 {
         let kinetic = KineticEnergy.compute(system);
-        let natoms = system.size() as f64;
-        return 1.0/K_BOLTZMANN * 2.0 * kinetic/(3.0 * natoms);
+        let dof = system.degrees_of_freedom() as f64;
+        return 1.0/K_BOLTZMANN * 2.0 * kinetic / dof;
+    }
+}
+
+/******************************************************************************/
+/// Compute the kinetic energy of a group of particles in the system. Frozen
+/// particles do not contribute to the kinetic energy.
+pub struct GroupKineticEnergy<'a> {
+    /// Selection of the particles in the group
+    pub selection: &'a Selection,
+}
+
+impl<'a> Compute for GroupKineticEnergy<'a> {
+    type Output = f64;
+    fn compute(&self, system: &System) -> f64 {
+        let particles = system.particles();
+        let mut energy = 0.0;
+        for i in self.selection.particles(system) {
+            if !particles.frozen[i] {
+                energy += 0.5 * particles.mass[i] * particles.velocity[i].norm2();
+            }
+        }
+        assert!(energy.is_finite(), "Kinetic energy is infinite!");
+        return energy;
+    }
+}
+
+/******************************************************************************/
+/// Compute the instantaneous temperature of a group of particles in the
+/// system. Each mobile particle in the group has three degrees of freedom,
+/// and each distance constraint between two particles of the group removes
+/// one degree of freedom. For `Selection::All`, this is the same as
+/// `Temperature`.
+pub struct GroupTemperature<'a> {
+    /// Selection of the particles in the group
+    pub selection: &'a Selection,
+}
+
+impl<'a> Compute for GroupTemperature<'a> {
+    type Output = f64;
+    fn compute(&self, system: &System) -> f64 {
+        if let Selection::All = *self.selection {
+            return Temperature.compute(system);
+        }
+
+        let frozen = system.particles().frozen;
+        let mut in_group = vec![false; system.size()];
+        let mut mobile = 0;
+        for i in self.selection.particles(system) {
+            in_group[i] = true;
+            if !frozen[i] {
+                mobile += 1;
+            }
+        }
+
+        let constraints = system.constraints().iter().filter(|constraint| {
+            let (i, j) = (constraint.i(), constraint.j());
+            in_group[i] && in_group[j] && !(frozen[i] && frozen[j])
+        }).count();
+
+        let dof = (3 * mobile).saturating_sub(constraints);
+        if dof == 0 {
+            return 0.0;
+        }
+
+        let kinetic = GroupKineticEnergy{selection: self.selection}.compute(system);
+        return 1.0/K_BOLTZMANN * 2.0 * kinetic / dof as f64;
     }
 }
 
@@ -187,10 +327,15 @@ impl Compute for Virial {
     fn compute(&self, system: &System) -> Matrix3 {
         assert!(!system.cell.is_infinite(), "Can not compute virial for infinite cell");
 
-        // Pair potentials contributions
+        // Pair potentials contributions, the pairs of frozen particles are
+        // skipped
+        let frozen = system.particles().frozen;
         let mut virial = (0..system.size()).par_map(|i| {
             let mut local_virial = Matrix3::zero();
             for j in (i+1)..system.size() {
+                if frozen[i] && frozen[j] {
+                    continue;
+                }
                 let distance = system.bond_distance(i, j);
                 for potential in system.pair_potentials(i, j) {
                     let info = potential.restriction().information(distance);
@@ -239,6 +384,9 @@ impl Compute for Virial {
             virial += global.virial(system);
         }
 
+        // Constraints forces contribution, as computed by the integrator
+        virial += system.constraints_virial();
+
         return virial;
     }
 }
@@ -246,7 +394,9 @@ impl Compute for Virial {
 /******************************************************************************/
 /// Compute the pressure of the system from the virial equation, at the given
 /// temperature. This pressure is given by the following formula:
-/// $$ p = \frac{N k_B T}{V} + \frac{1}{3V} \sum_i \vec f_i \cdot \vec r_i $$
+/// $$ p = \frac{N_f k_B T}{3 V} + \frac{1}{3V} \sum_i \vec f_i \cdot \vec r_i $$
+/// where $N_f$ is the number of translational degrees of freedom in the
+/// system, given by `System::translational_degrees_of_freedom`.
 pub struct PressureAtTemperature {
     /// Temperature for the pressure computation
     pub temperature: f64
@@ -260,8 +410,8 @@ impl Compute for PressureAtTemperature {
         let virial_tensor = system.virial();
         let virial = virial_tensor.trace();
         let volume = system.volume();
-        let natoms = system.size() as f64;
-        return natoms * K_BOLTZMANN * self.temperature / volume + virial / (3.0 * volume);
+        let dof = system.translational_degrees_of_freedom() as f64;
+        return dof / 3.0 * K_BOLTZMANN * self.temperature / volume + virial / (3.0 * volume);
     }
 }
 
@@ -270,7 +420,8 @@ impl Compute for PressureAtTemperature {
 /// given temperature. The stress tensor is defined by
 /// $$ \sigma = \sigma = \frac{1}{V} (\sum_i m_i v_i \otimes v_i + \sum_i \sum_{j > i} \vec r_{ij} \otimes \vec f_{ij}) $$
 /// but here the kinetic energy term is replaced by it average at the given
-/// temperature.
+/// temperature, using the number of translational degrees of freedom in the
+/// system.
 pub struct StressAtTemperature {
     /// Temperature for the stress tensor computation
     pub temperature: f64
@@ -283,8 +434,8 @@ impl Compute for StressAtTemperature {
         assert!(!system.cell.is_infinite(), "Can not compute stress for infinite cell");
         let virial = system.virial();
         let volume = system.volume();
-        let natoms = system.size() as f64;
-        let kinetic = natoms * K_BOLTZMANN * self.temperature * Matrix3::one();
+        let dof = system.translational_degrees_of_freedom() as f64;
+        let kinetic = dof / 3.0 * K_BOLTZMANN * self.temperature * Matrix3::one();
         return (kinetic + virial) / volume;
     }
 }
@@ -312,7 +463,9 @@ impl Compute for Stress {
 /******************************************************************************/
 /// Compute the virial pressure of the system. This pressure is given by the
 /// following formula:
-/// $$ p = \frac{N k_B T}{V} + \frac{1}{3V} \sum_i \vec f_i \cdot \vec r_i $$
+/// $$ p = \frac{N_f k_B T}{3 V} + \frac{1}{3V} \sum_i \vec f_i \cdot \vec r_i $$
+/// where $N_f$ is the number of translational degrees of freedom in the
+/// system, given by `System::translational_degrees_of_freedom`.
 pub struct Pressure;
 impl Compute for Pressure {
     type Output = f64;
@@ -324,7 +477,7 @@ impl Compute for Pressure {
 #[cfg(test)]
 mod test {
     use super::*;
-    use sys::System;
+    use sys::{System, DegreesOfFreedom, RemovedMotions};
     use sys::veloc::{InitVelocities, BoltzmannVelocities};
     use energy::{Harmonic, NullPotential, PairInteraction};
     use consts::K_BOLTZMANN;
@@ -416,6 +569,22 @@ mod test {
         assert_ulps_eq!(res[1][1], 0.0);
     }
 
+    #[test]
+    fn forces_components() {
+        for system in &[test_pairs_system(), test_molecular_system()] {
+            let forces = Forces.compute(system);
+            let bonded = ComponentForces{component: ForceComponent::Bonded}.compute(system);
+            let pairs = ComponentForces{component: ForceComponent::Pairs}.compute(system);
+            let global = ComponentForces{component: ForceComponent::Global}.compute(system);
+            for i in 0..system.size() {
+                let total = bonded[i] + pairs[i] + global[i];
+                for k in 0..3 {
+                    assert_ulps_eq!(total[k], forces[i][k], epsilon=1e-12);
+                }
+            }
+        }
+    }
+
     #[test]
     fn force_molecular() {
         let system = test_molecular_system();
@@ -424,6 +593,21 @@ mod test {
         assert_ulps_eq!(forces_tot.norm2(), 0.0);
     }
 
+    #[test]
+    fn forces_frozen() {
+        let mut system = test_pairs_system();
+        system.set_frozen(0, true);
+        let res = Forces.compute(&system);
+        let force = unit_from(30.0, "kJ/mol/A");
+        assert_ulps_eq!(res[1][0], -force);
+
+        system.set_frozen(1, true);
+        let res = Forces.compute(&system);
+        assert_eq!(res[0], Vector3D::zero());
+        assert_eq!(res[1], Vector3D::zero());
+        assert_eq!(KineticEnergy.compute(&system), 0.0);
+    }
+
     #[test]
     fn energy_pairs() {
         let system = &test_pairs_system();
@@ -439,6 +623,17 @@ mod test {
         assert_eq!(total, system.total_energy());
     }
 
+    #[test]
+    fn conserved_energy() {
+        let system = &mut test_pairs_system();
+        let total = TotalEnergy.compute(system);
+        assert_eq!(ConservedEnergy.compute(system), total);
+
+        system.set_extended_energy(3.0);
+        assert_eq!(ConservedEnergy.compute(system), total + 3.0);
+        assert_eq!(system.conserved_energy(), total + 3.0);
+    }
+
     #[test]
     fn energy_molecular() {
         let system = test_molecular_system();
@@ -453,6 +648,80 @@ mod test {
         assert_eq!(temperature, system.temperature());
     }
 
+    #[test]
+    fn temperature_constraints() {
+        let system = &mut test_pairs_system();
+        let kinetic = KineticEnergy.compute(system);
+        system.add_constraint(0, 1, 1.3);
+
+        let temperature = Temperature.compute(system);
+        assert_ulps_eq!(temperature, 2.0 * kinetic / (5.0 * K_BOLTZMANN));
+    }
+
+    #[test]
+    fn temperature_molecules() {
+        let system = &mut test_pairs_system();
+        let kinetic = KineticEnergy.compute(system);
+        let _ = system.add_bond(0, 1);
+        system.set_degrees_of_freedom(DegreesOfFreedom::Molecules);
+
+        let temperature = Temperature.compute(system);
+        assert_ulps_eq!(temperature, 2.0 * kinetic / (5.0 * K_BOLTZMANN));
+    }
+
+    #[test]
+    fn temperature_removed_motion() {
+        let system = &mut test_pairs_system();
+        let kinetic = KineticEnergy.compute(system);
+        system.set_removed_motions(RemovedMotions {translation: true, rotation: false});
+        let temperature = Temperature.compute(system);
+        assert_ulps_eq!(temperature, 2.0 * kinetic / (3.0 * K_BOLTZMANN));
+
+        // Two particles only have two rotational degrees of freedom
+        system.set_removed_motions(RemovedMotions {translation: true, rotation: true});
+        let temperature = Temperature.compute(system);
+        assert_ulps_eq!(temperature, 2.0 * kinetic / K_BOLTZMANN);
+
+        // The kinetic pressure is independent of the degrees of freedom
+        let volume = system.volume();
+        let virial = system.virial().trace();
+        let expected = 2.0 * kinetic / (3.0 * volume) + virial / (3.0 * volume);
+        assert_ulps_eq!(system.pressure(), expected, epsilon=1e-12);
+    }
+
+    #[test]
+    fn group_temperature() {
+        let mut system = test_molecular_system();
+        for (i, velocity) in system.particles_mut().velocity.iter_mut().enumerate() {
+            *velocity = Vector3D::new(i as f64, 0.0, 0.0);
+        }
+
+        let selection = Selection::Range(1..3);
+        let kinetic = GroupKineticEnergy{selection: &selection}.compute(&system);
+        let expected = 0.5 * system.particles().mass[0] * (1.0 + 4.0);
+        assert_ulps_eq!(kinetic, expected);
+
+        let temperature = GroupTemperature{selection: &selection}.compute(&system);
+        assert_ulps_eq!(temperature, 2.0 * kinetic / (6.0 * K_BOLTZMANN));
+
+        // Only constraints inside the group are removed
+        system.add_constraint(1, 2, 1.0);
+        system.add_constraint(2, 3, 1.0);
+        let temperature = GroupTemperature{selection: &selection}.compute(&system);
+        assert_ulps_eq!(temperature, 2.0 * kinetic / (5.0 * K_BOLTZMANN));
+
+        system.set_frozen(2, true);
+        let kinetic = GroupKineticEnergy{selection: &selection}.compute(&system);
+        assert_ulps_eq!(kinetic, 0.5 * system.particles().mass[0]);
+        let temperature = GroupTemperature{selection: &selection}.compute(&system);
+        // The constraint between a frozen and a mobile particle still
+        // removes one degree of freedom
+        assert_ulps_eq!(temperature, 2.0 * kinetic / (2.0 * K_BOLTZMANN));
+
+        let all = GroupTemperature{selection: &Selection::All}.compute(&system);
+        assert_eq!(all, Temperature.compute(&system));
+    }
+
     #[test]
     fn volume() {
         let system = &test_pairs_system();
@@ -480,6 +749,17 @@ mod test {
         assert_eq!(virial, system.virial());
     }
 
+    #[test]
+    fn virial_constraints() {
+        let system = &mut test_pairs_system();
+        let virial = Virial.compute(system);
+
+        let mut constraints = Matrix3::zero();
+        constraints[(1, 1)] = 3.0;
+        system.set_constraints_virial(constraints);
+        assert_ulps_eq!(Virial.compute(system), virial + constraints);
+    }
+
     #[test]
     fn virial_molecular() {
         let system = &test_molecular_system();
@@ -535,6 +815,27 @@ mod test {
         assert_eq!(pressure, system.pressure());
     }
 
+    #[test]
+    fn pressure_at_temperature_molecules() {
+        let system = &mut test_pairs_system();
+        let _ = system.add_bond(0, 1);
+        system.set_degrees_of_freedom(DegreesOfFreedom::Molecules);
+        assert_eq!(system.degrees_of_freedom(), 5);
+
+        // Only the translation of the rigid molecule contributes to the
+        // kinetic part of the pressure
+        let temperature = 550.0;
+        let volume = 1000.0;
+        let virial = system.virial().trace();
+        let expected = K_BOLTZMANN * temperature / volume + virial / (3.0 * volume);
+        let pressure = PressureAtTemperature{temperature: temperature}.compute(system);
+        assert_ulps_eq!(pressure, expected);
+
+        let stress = StressAtTemperature{temperature: temperature}.compute(system);
+        let trace = (stress[(0, 0)] + stress[(1, 1)] + stress[(2, 2)]) / 3.0;
+        assert_ulps_eq!(trace, expected);
+    }
+
     #[test]
     #[should_panic]
     fn stress_at_temperature_negative_temperature() {
@@ -228,17 +228,44 @@ impl UnitCell {
         return volume;
     }
 
+    /// Get the cell matrix, with the cell vectors as columns
+    #[inline] pub fn matrix(&self) -> Matrix3 {
+        self.cell
+    }
+
     /// Scale this unit cell in-place by multiplying the cell matrix by `factor`.
+    /// An orthorhombic cell becomes triclinic if the scaling changes the
+    /// angles between the cell vectors.
     #[inline] pub fn scale_mut(&mut self, factor: Matrix3) {
         self.cell *= factor;
         self.inv = self.cell.inverse();
+        self.shape = shape_after_scaling(self.shape, &self.cell);
     }
 
     /// Scale this unit cell by multiplying the cell matrix by `s`, and return a
     /// new scaled unit cell
     #[inline] pub fn scale(&self, s: Matrix3) -> UnitCell {
         let cell = s * self.cell;
-        UnitCell{cell: cell, inv: cell.inverse(), shape: self.shape}
+        let shape = shape_after_scaling(self.shape, &cell);
+        UnitCell{cell: cell, inv: cell.inverse(), shape: shape}
+    }
+
+    /// Shear this unit cell in-place, moving the periodic images along the
+    /// second cell vector by `displacement` in the direction of the first cell
+    /// vector. This implements Lees-Edwards sliding boundary conditions as a
+    /// deforming cell, with the flow along the x axis and the gradient along
+    /// the y axis.
+    ///
+    /// When the images displacement becomes larger than half the first cell
+    /// length, the cell is replaced by the equivalent cell with the smallest
+    /// displacement, so that the cell never gets too skewed.
+    pub fn shear_mut(&mut self, displacement: f64) {
+        assert!(!self.is_infinite(), "Can not shear an infinite cell");
+        let a = self.cell[(0, 0)];
+        self.cell[(0, 1)] += displacement;
+        self.cell[(0, 1)] -= round(self.cell[(0, 1)] / a) * a;
+        self.inv = self.cell.inverse();
+        self.shape = CellShape::Triclinic;
     }
 
     /// Get the reciprocal vectors of this unit cell
@@ -406,6 +433,22 @@ impl UnitCell {
 }
 
 /// Get the angles between the vectors `u` and `v`.
+/// Get the shape of a cell with the given `shape` after scaling it to the new
+/// `cell` matrix.
+fn shape_after_scaling(shape: CellShape, cell: &Matrix3) -> CellShape {
+    if shape != CellShape::Orthorhombic {
+        return shape;
+    }
+    for i in 0..3 {
+        for j in 0..3 {
+            if i != j && cell[(i, j)] != 0.0 {
+                return CellShape::Triclinic;
+            }
+        }
+    }
+    return shape;
+}
+
 fn angle(u: Vector3D, v: Vector3D) -> f64 {
     let un = u.normalized();
     let vn = v.normalized();
@@ -552,6 +595,47 @@ mod tests {
         assert_eq!(cell.c(), 10.0);
     }
 
+    #[test]
+    fn scale_off_diagonal() {
+        let mut cell = UnitCell::cubic(10.0);
+        let mut factor = Matrix3::one();
+        factor[(0, 1)] = 0.1;
+        cell.scale_mut(factor);
+        assert_eq!(cell.shape(), CellShape::Triclinic);
+        assert_eq!(cell.vect_b(), Vector3D::new(1.0, 10.0, 0.0));
+        assert_eq!(cell.matrix()[(0, 1)], 1.0);
+
+        let cell = UnitCell::cubic(10.0).scale(2.0 * Matrix3::one());
+        assert_eq!(cell.shape(), CellShape::Orthorhombic);
+    }
+
+    #[test]
+    fn shear() {
+        let mut cell = UnitCell::cubic(10.0);
+        cell.shear_mut(3.0);
+        assert_eq!(cell.shape(), CellShape::Triclinic);
+        assert_eq!(cell.vect_b(), Vector3D::new(3.0, 10.0, 0.0));
+        assert_relative_eq!(cell.volume(), 1000.0, epsilon=1e-12);
+
+        // Images along y are displaced along x
+        let mut vector = Vector3D::new(0.0, 10.0, 0.0);
+        cell.vector_image(&mut vector);
+        assert_relative_eq!(vector, Vector3D::new(-3.0, 0.0, 0.0), epsilon=1e-12);
+
+        // The cell is kept in the least skewed form
+        cell.shear_mut(3.0);
+        assert_relative_eq!(cell.vect_b(), Vector3D::new(-4.0, 10.0, 0.0), epsilon=1e-12);
+        cell.shear_mut(-4.0);
+        assert_relative_eq!(cell.vect_b(), Vector3D::new(2.0, 10.0, 0.0), epsilon=1e-12);
+    }
+
+    #[test]
+    #[should_panic]
+    fn shear_infinite() {
+        let mut cell = UnitCell::new();
+        cell.shear_mut(3.0);
+    }
+
     #[test]
     fn reciprocal_vectors() {
         let cell = UnitCell::ortho(3.0, 4.0, 5.0);
@@ -222,6 +222,22 @@ impl Configuration {
         self.molids.push(self.molecules.len() - 1);
     }
 
+    /// Insert a molecule at the end of the internal list. The indexes in the
+    /// `molecule` refer to the `particles`, and must start at 0. The new
+    /// particles must have a valid particle kind.
+    pub fn add_molecule(&mut self, mut molecule: Molecule, particles: Vec<Particle>) {
+        assert_eq!(molecule.start(), 0, "the molecule indexes must start at 0");
+        assert_eq!(molecule.size(), particles.len(), "wrong number of particles for this molecule");
+        molecule.translate_by(self.particles.len() as isize);
+        let molid = self.molecules.len();
+        for particle in particles {
+            assert_ne!(particle.kind, ParticleKind::invalid());
+            self.particles.push(particle);
+            self.molids.push(molid);
+        }
+        self.molecules.push(molecule);
+    }
+
     /// Get the number of particles in this configuration
     #[inline] pub fn size(&self) -> usize {self.particles.len()}
 
@@ -245,6 +261,59 @@ impl Configuration {
         com / total_mass
     }
 
+    /// Check if the molecule at index `molid` is linear, *i.e.* if all its
+    /// particles are aligned. Molecules containing one or two particles are
+    /// always linear. Alignment is checked with a tolerance of 0.01 on the
+    /// sine of the angle between the particles.
+    pub fn is_linear(&self, molid: usize) -> bool {
+        let molecule = self.molecule(molid);
+        if molecule.size() <= 2 {
+            return true;
+        }
+
+        let origin = self.particles.position[molecule.start()];
+        let mut axis = self.particles.position[molecule.start() + 1] - origin;
+        self.cell.vector_image(&mut axis);
+        let axis = axis.normalized();
+        for i in molecule.iter().skip(2) {
+            let mut rij = self.particles.position[i] - origin;
+            self.cell.vector_image(&mut rij);
+            if (rij.normalized() ^ axis).norm() > 1e-2 {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    /// Freeze or unfreeze the particle at index `i`. Frozen particles never
+    /// move during a simulation, and their velocity is set to zero.
+    pub fn set_frozen(&mut self, i: usize, frozen: bool) {
+        self.particles.frozen[i] = frozen;
+        if frozen {
+            self.particles.velocity[i] = Vector3D::zero();
+        }
+    }
+
+    /// Freeze or unfreeze all the particles in the molecule at index `molid`.
+    pub fn set_frozen_molecule(&mut self, molid: usize, frozen: bool) {
+        let particles = self.molecule(molid).iter();
+        for i in particles {
+            self.set_frozen(i, frozen);
+        }
+    }
+
+    /// Check if the molecule at index `molid` is frozen. A molecule is frozen
+    /// as soon as any of its particles is frozen, since it can no longer move
+    /// as a whole.
+    pub fn is_frozen_molecule(&self, molid: usize) -> bool {
+        self.molecule(molid).iter().any(|i| self.particles.frozen[i])
+    }
+
+    /// Get the number of frozen particles in this configuration
+    pub fn frozen_count(&self) -> usize {
+        self.particles.frozen.iter().filter(|&&frozen| frozen).count()
+    }
+
     /// Return the center-of-mass of the configuration
     ///
     /// # Warning
@@ -493,6 +562,32 @@ mod tests {
         assert_eq!(configuration.size(), 0);
     }
 
+    #[test]
+    fn add_molecule() {
+        let mut configuration = Configuration::new();
+        configuration.add_particle(particle("Ar"));
+
+        let mut water = Configuration::new();
+        water.add_particle(particle("O"));
+        water.add_particle(particle("H"));
+        water.add_particle(particle("H"));
+        let _ = water.add_bond(0, 1);
+        let _ = water.add_bond(0, 2);
+
+        let particles = vec![particle("O"), particle("H"), particle("H")];
+        configuration.add_molecule(water.molecule(0).clone(), particles.clone());
+        configuration.add_molecule(water.molecule(0).clone(), particles);
+
+        assert_eq!(configuration.size(), 7);
+        assert_eq!(configuration.molecules().len(), 3);
+        assert_eq!(configuration.molid(3), 1);
+        assert_eq!(configuration.molid(4), 2);
+        assert_eq!(configuration.molecule(2).iter(), 4..7);
+        assert!(configuration.molecule(2).bonds().contains(&Bond::new(4, 6)));
+        assert_eq!(configuration.molecule_type(1), configuration.molecule_type(2));
+        assert_eq!(configuration.molecule_type(1), water.molecule_type(0));
+    }
+
     #[test]
     fn add_bonds() {
         // This is a regression test for issue #76
@@ -593,6 +688,58 @@ mod tests {
         assert_eq!(configuration.center_of_mass(), Vector3D::new(0.5, 0.0, 0.0));
     }
 
+    #[test]
+    fn linear() {
+        let mut configuration = Configuration::new();
+        configuration.cell = UnitCell::cubic(5.0);
+        configuration.add_particle(particle("O"));
+        configuration.add_particle(particle("C"));
+        configuration.add_particle(particle("O"));
+        let _ = configuration.add_bond(0, 1);
+        let _ = configuration.add_bond(1, 2);
+
+        configuration.particles_mut().position[0] = Vector3D::new(-1.0, 0.0, 0.0);
+        configuration.particles_mut().position[1] = Vector3D::zero();
+        configuration.particles_mut().position[2] = Vector3D::new(1.0, 0.0, 0.0);
+        assert!(configuration.is_linear(0));
+
+        // Use periodic boundary conditions
+        configuration.particles_mut().position[0] = Vector3D::new(4.0, 0.0, 0.0);
+        assert!(configuration.is_linear(0));
+
+        configuration.particles_mut().position[2] = Vector3D::new(1.0, 0.5, 0.0);
+        assert!(!configuration.is_linear(0));
+    }
+
+    #[test]
+    fn frozen() {
+        let mut configuration = Configuration::new();
+        configuration.add_particle(particle("O"));
+        configuration.add_particle(particle("H"));
+        configuration.add_particle(particle("H"));
+        configuration.add_particle(particle("Ar"));
+        let _ = configuration.add_bond(0, 1);
+        let _ = configuration.add_bond(0, 2);
+        assert_eq!(configuration.frozen_count(), 0);
+
+        configuration.particles_mut().velocity[3] = Vector3D::new(1.0, 0.0, 0.0);
+        configuration.set_frozen(3, true);
+        assert_eq!(configuration.frozen_count(), 1);
+        assert_eq!(configuration.particles().velocity[3], Vector3D::zero());
+        assert!(configuration.is_frozen_molecule(1));
+        assert!(!configuration.is_frozen_molecule(0));
+
+        configuration.set_frozen(0, true);
+        assert!(configuration.is_frozen_molecule(0));
+        assert_eq!(configuration.frozen_count(), 2);
+        configuration.set_frozen_molecule(0, true);
+        assert!(configuration.is_frozen_molecule(0));
+        assert_eq!(configuration.frozen_count(), 4);
+
+        configuration.set_frozen_molecule(0, false);
+        assert_eq!(configuration.frozen_count(), 1);
+    }
+
     #[test]
     fn test_wrap_molecule() {
         let mut configuration = Configuration::new();
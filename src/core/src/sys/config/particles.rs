@@ -43,6 +43,10 @@ pub struct Particle {
     /// Particle velocity, if needed
     #[soa_derive(zip)]
     pub velocity: Vector3D,
+    /// Is this particle frozen? Frozen particles never move during a
+    /// simulation.
+    #[soa_derive(zip)]
+    pub frozen: bool,
 }
 
 impl Particle {
@@ -64,7 +68,8 @@ impl Particle {
             charge: 0.0,
             kind: ParticleKind::invalid(),
             position: position,
-            velocity: Vector3D::zero()
+            velocity: Vector3D::zero(),
+            frozen: false,
         }
     }
 }
@@ -90,6 +95,7 @@ mod tests {
         assert_eq!(particle.kind, ParticleKind::invalid());
         assert_eq!(particle.position, Vector3D::new(0.0, 0.0, 0.0));
         assert_eq!(particle.velocity, Vector3D::new(0.0, 0.0, 0.0));
+        assert_eq!(particle.frozen, false);
     }
 
     #[test]
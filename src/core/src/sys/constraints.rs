@@ -0,0 +1,63 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Holonomic constraints between particles.
+use std::cmp::{min, max};
+
+/// A `DistanceConstraint` keeps the distance between the particles at indexes
+/// `i` and `j` fixed during a simulation.
+///
+/// This structure ensure an unique representation of a constraint by
+/// enforcing `i < j`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DistanceConstraint {
+    i: usize,
+    j: usize,
+    distance: f64,
+}
+
+impl DistanceConstraint {
+    /// Create a new constraint keeping the particles at indexes `first` and
+    /// `second` at the given `distance`.
+    pub fn new(first: usize, second: usize, distance: f64) -> DistanceConstraint {
+        assert_ne!(first, second);
+        assert!(distance > 0.0, "The distance must be positive in constraints");
+        let i = min(first, second);
+        let j = max(first, second);
+        DistanceConstraint{i: i, j: j, distance: distance}
+    }
+
+    /// Get the first particle in the constraint
+    #[inline] pub fn i(&self) -> usize {self.i}
+
+    /// Get the second particle in the constraint
+    #[inline] pub fn j(&self) -> usize {self.j}
+
+    /// Get the constrained distance
+    #[inline] pub fn distance(&self) -> f64 {self.distance}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constraint() {
+        let constraint = DistanceConstraint::new(4, 1, 1.5);
+        assert_eq!(constraint.i(), 1);
+        assert_eq!(constraint.j(), 4);
+        assert_eq!(constraint.distance(), 1.5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn same_particle() {
+        let _ = DistanceConstraint::new(2, 2, 1.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn negative_distance() {
+        let _ = DistanceConstraint::new(2, 3, -1.0);
+    }
+}
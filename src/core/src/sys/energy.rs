@@ -8,7 +8,7 @@
 
 use std::f64::consts::PI;
 
-use sys::System;
+use sys::{System, Composition};
 use parallel::prelude::*;
 
 /// An helper struct to evaluate energy components of a system.
@@ -41,13 +41,17 @@ impl<'a> EnergyEvaluator<'a> {
         return energy;
     }
 
-    /// Compute the energy of all the pairs in the system
+    /// Compute the energy of all the pairs in the system. The pairs of frozen
+    /// particles are skipped, as their energy is constant.
     pub fn pairs(&self) -> f64 {
-
+        let frozen = self.system.particles().frozen;
         (0..self.system.size()).par_map(|i| {
             let mut local_energy = 0.0;
 
             for j in (i+1)..self.system.size() {
+                if frozen[i] && frozen[j] {
+                    continue;
+                }
                 let r = self.system.nearest_image(i, j).norm();
                 local_energy += self.pair(r, i, j);
             }
@@ -58,12 +62,19 @@ impl<'a> EnergyEvaluator<'a> {
     /// Compute the energy due to long range corrections for the pairs
     #[inline]
     pub fn pairs_tail(&self) -> f64 {
+        self.pairs_tail_with(&self.system.composition())
+    }
+
+    /// Compute the energy due to long range corrections for the pairs, if the
+    /// system contained the particles in `composition` instead of its current
+    /// particles. This is used to get the long range corrections when
+    /// inserting or removing particles.
+    pub fn pairs_tail_with(&self, composition: &Composition) -> f64 {
         if self.system.cell.is_infinite() {
             return 0.0;
         }
         let mut energy = 0.0;
         let volume = self.system.volume();
-        let composition = self.system.composition();
         for i in self.system.particle_kinds() {
             let ni = composition[i] as f64;
             for j in self.system.particle_kinds() {
@@ -7,7 +7,10 @@ mod config;
 pub use self::config::*;
 
 mod system;
-pub use self::system::System;
+pub use self::system::{System, DegreesOfFreedom, RemovedMotions};
+
+mod constraints;
+pub use self::constraints::DistanceConstraint;
 
 mod interactions;
 use self::interactions::Interactions;
@@ -18,6 +21,9 @@ pub use self::energy::EnergyEvaluator;
 mod cache;
 pub use self::cache::EnergyCache;
 
+mod selection;
+pub use self::selection::Selection;
+
 mod chfl;
 pub use self::chfl::{Trajectory, TrajectoryError, TrajectoryBuilder, OpenMode};
 pub use self::chfl::read_molecule;
@@ -0,0 +1,117 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+
+//! Selecting groups of particles in a system
+use std::ops::Range;
+
+use types::Vector3D;
+use sys::Configuration;
+
+/// A `Selection` describes a group of particles in a system, used for example
+/// to apply a thermostat or compute a temperature on a part of the system
+/// only. The selected particles are evaluated again each time the selection
+/// is used, so that selections based on positions follow the particles
+/// movements.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Selection {
+    /// All the particles in the system
+    All,
+    /// All the particles in molecules with the given molecule type (see
+    /// `Configuration::molecule_type`)
+    Molecules(u64),
+    /// All the particles with one of the given names
+    Names(Vec<String>),
+    /// All the particles with an index in the given range
+    Range(Range<usize>),
+    /// All the particles inside a rectangular region, aligned with the
+    /// cartesian axes. The positions of the particles are wrapped in the unit
+    /// cell before checking if they are inside the region.
+    Region {
+        /// Lower corner of the region
+        lower: Vector3D,
+        /// Upper corner of the region
+        upper: Vector3D,
+    },
+}
+
+impl Selection {
+    /// Get the indexes of the particles in the `configuration` which are
+    /// part of this selection, in increasing order.
+    pub fn particles(&self, configuration: &Configuration) -> Vec<usize> {
+        match *self {
+            Selection::All => (0..configuration.size()).collect(),
+            Selection::Molecules(moltype) => {
+                let mut particles = Vec::new();
+                for molid in configuration.molecules_with_moltype(moltype) {
+                    particles.extend(configuration.molecule(molid).iter());
+                }
+                particles.sort();
+                particles
+            }
+            Selection::Names(ref names) => {
+                let particles = configuration.particles();
+                (0..configuration.size()).filter(|&i| {
+                    names.iter().any(|name| name == &particles.name[i])
+                }).collect()
+            }
+            Selection::Range(ref range) => {
+                let end = usize::min(range.end, configuration.size());
+                (range.start..end).collect()
+            }
+            Selection::Region{lower, upper} => {
+                let cell = &configuration.cell;
+                let positions = configuration.particles().position;
+                (0..configuration.size()).filter(|&i| {
+                    let mut position = positions[i];
+                    cell.wrap_vector(&mut position);
+                    (0..3).all(|k| lower[k] <= position[k] && position[k] < upper[k])
+                }).collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sys::System;
+    use utils::system_from_xyz;
+
+    fn testing_system() -> System {
+        let mut system = system_from_xyz("5
+        cell: 10.0
+        O 0.0 0.0 0.0
+        H 1.0 0.0 0.0
+        H 0.0 1.0 0.0
+        Ar 5.0 5.0 5.0
+        Ar 11.0 1.0 1.0
+        ");
+        assert!(system.add_bond(0, 1).is_empty());
+        assert!(system.add_bond(0, 2).is_empty());
+        return system;
+    }
+
+    #[test]
+    fn particles() {
+        let system = testing_system();
+        assert_eq!(Selection::All.particles(&system), vec![0, 1, 2, 3, 4]);
+
+        let water = system.molecule_type(0);
+        assert_eq!(Selection::Molecules(water).particles(&system), vec![0, 1, 2]);
+        let argon = system.molecule_type(1);
+        assert_eq!(Selection::Molecules(argon).particles(&system), vec![3, 4]);
+
+        let names = Selection::Names(vec![String::from("H"), String::from("Ar")]);
+        assert_eq!(names.particles(&system), vec![1, 2, 3, 4]);
+
+        assert_eq!(Selection::Range(1..3).particles(&system), vec![1, 2]);
+        assert_eq!(Selection::Range(3..42).particles(&system), vec![3, 4]);
+
+        let region = Selection::Region {
+            lower: Vector3D::new(0.5, -1.0, -1.0),
+            upper: Vector3D::new(2.0, 2.0, 2.0),
+        };
+        // The last particle is inside the region after wrapping
+        assert_eq!(region.particles(&system), vec![1, 4]);
+    }
+}
@@ -4,14 +4,47 @@
 use std::ops::{Deref, DerefMut};
 use std::collections::BTreeMap;
 
-use types::{Vector3D, Matrix3};
+use types::{Vector3D, Matrix3, Zero};
 
 use energy::{PairInteraction, BondPotential, AnglePotential, DihedralPotential};
 use energy::{GlobalPotential, CoulombicPotential};
 
-use sys::{Configuration, Particle, ParticleKind, UnitCell};
+use sys::{Configuration, Molecule, Particle, ParticleKind, UnitCell, DistanceConstraint};
 use sys::{Composition, Interactions, EnergyEvaluator};
 
+/// The degrees of freedom used to propagate a system, and to compute its
+/// temperature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DegreesOfFreedom {
+    /// Every particle is propagated independently, with three degrees of
+    /// freedom per particle, minus one for each distance constraint.
+    Particles,
+    /// Molecules are propagated as rigid bodies, with three translational
+    /// degrees of freedom per molecule, and three rotational degrees of
+    /// freedom for non-linear molecules or two for linear molecules.
+    Molecules,
+}
+
+/// Global motions of a system removed by the propagator during a simulation.
+/// The removed motions do not contribute to the degrees of freedom.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RemovedMotions {
+    /// Is the global translation of the system removed?
+    pub translation: bool,
+    /// Is the global rotation of the system removed?
+    pub rotation: bool,
+}
+
+impl RemovedMotions {
+    /// Get the motions removed by either `self` or `other`
+    pub fn union(self, other: RemovedMotions) -> RemovedMotions {
+        RemovedMotions {
+            translation: self.translation || other.translation,
+            rotation: self.rotation || other.rotation,
+        }
+    }
+}
+
 /// The `System` type hold all the data about a simulated system.
 ///
 /// This data contains:
@@ -36,6 +69,17 @@ pub struct System {
     step: u64,
     /// Externally managed temperature for the system
     external_temperature: Option<f64>,
+    /// Energy of the extended degrees of freedom (thermostats and barostats
+    /// variables) used by the propagator, if any.
+    extended_energy: f64,
+    /// Distance constraints between particles
+    constraints: Vec<DistanceConstraint>,
+    /// Virial contribution of the constraints forces
+    constraints_virial: Matrix3,
+    /// Degrees of freedom used to propagate the system
+    degrees_of_freedom: DegreesOfFreedom,
+    /// Global motions removed during the simulation
+    removed_motions: RemovedMotions,
 }
 
 impl System {
@@ -59,10 +103,17 @@ impl System {
             interactions: Interactions::new(),
             step: 0,
             external_temperature: None,
+            extended_energy: 0.0,
+            constraints: Vec::new(),
+            constraints_virial: Matrix3::zero(),
+            degrees_of_freedom: DegreesOfFreedom::Particles,
+            removed_motions: RemovedMotions::default(),
         }
     }
 
-    fn get_kind(&mut self, name: &str) -> ParticleKind {
+    /// Get the particle kind associated with `name`, creating a new kind if
+    /// needed.
+    pub(crate) fn get_kind(&mut self, name: &str) -> ParticleKind {
         if let Some(&kind) = self.kinds.get(name) {
             return kind;
         } else {
@@ -80,6 +131,17 @@ impl System {
         self.configuration.add_particle(particle);
     }
 
+    /// Insert a molecule at the end of the internal list. The indexes in the
+    /// `molecule` refer to the `particles`, and must start at 0.
+    pub fn add_molecule(&mut self, molecule: Molecule, mut particles: Vec<Particle>) {
+        for particle in &mut particles {
+            if particle.kind == ParticleKind::invalid() {
+                particle.kind = self.get_kind(&particle.name);
+            }
+        }
+        self.configuration.add_molecule(molecule, particles);
+    }
+
     /// Get the number of particles of each kind in the configuration
     pub fn composition(&self) -> Composition {
         let mut composition = Composition::new();
@@ -118,7 +180,144 @@ impl System {
         self.external_temperature = temperature;
     }
 
+    /// Set the energy of the extended degrees of freedom in this system.
+    ///
+    /// Some propagators (for example Nosé-Hoover chains or Martyna-Tobias-Klein
+    /// integrators) add extended variables to the equations of motion. The
+    /// energy associated with these variables is not part of the system total
+    /// energy, but is needed to check the conservation of the extended
+    /// Hamiltonian. The default value is 0.
+    pub fn set_extended_energy(&mut self, energy: f64) {
+        self.extended_energy = energy;
+    }
+
+    /// Get the energy of the extended degrees of freedom in this system, as
+    /// set by `System::set_extended_energy`.
+    pub fn extended_energy(&self) -> f64 {
+        self.extended_energy
+    }
+
+    /// Constrain the distance between the particles at indexes `i` and `j` to
+    /// `distance`. The constraints are only used by the molecular dynamics
+    /// integrators, and should not be used with simulations adding or
+    /// removing particles.
+    pub fn add_constraint(&mut self, i: usize, j: usize, distance: f64) {
+        assert!(i < self.size() && j < self.size(), "Invalid particle index in constraint");
+        let constraint = DistanceConstraint::new(i, j, distance);
+        if self.constraints.iter().any(|c| c.i() == constraint.i() && c.j() == constraint.j()) {
+            warn!(
+                "The distance between particles {} and {} is already constrained",
+                constraint.i(), constraint.j()
+            );
+            return;
+        }
+        self.constraints.push(constraint);
+    }
+
+    /// Get the list of distance constraints in this system
+    pub fn constraints(&self) -> &[DistanceConstraint] {
+        &self.constraints
+    }
+
+    /// Set the virial contribution of the constraints forces, as computed by
+    /// the constraints solver used to propagate this system. The default
+    /// value is zero.
+    pub fn set_constraints_virial(&mut self, virial: Matrix3) {
+        self.constraints_virial = virial;
+    }
+
+    /// Get the virial contribution of the constraints forces, as set by
+    /// `System::set_constraints_virial`.
+    pub fn constraints_virial(&self) -> Matrix3 {
+        self.constraints_virial
+    }
+
+    /// Set the kind of degrees of freedom used to propagate this system. The
+    /// default is to use the particles degrees of freedom.
+    pub fn set_degrees_of_freedom(&mut self, degrees_of_freedom: DegreesOfFreedom) {
+        self.degrees_of_freedom = degrees_of_freedom;
+    }
+
+    /// Set the global motions of this system removed by the propagator, for
+    /// example by the `RemoveTranslation` or `RemoveRotation` controls. The
+    /// default is to keep all the global motions.
+    pub fn set_removed_motions(&mut self, removed: RemovedMotions) {
+        self.removed_motions = removed;
+    }
+
+    /// Get the number of degrees of freedom in this system, as used for the
+    /// temperature and pressure computations. Frozen particles do not have
+    /// any degree of freedom, each distance constraint removes one degree of
+    /// freedom, and removing the global translation or rotation removes up to
+    /// three degrees of freedom each.
+    pub fn degrees_of_freedom(&self) -> usize {
+        let mobile = self.size() - self.frozen_count();
+        let removed = self.removed_degrees_of_freedom(mobile);
+        return self.internal_degrees_of_freedom().saturating_sub(removed);
+    }
 
+    /// Get the number of translational degrees of freedom in this system, as
+    /// used for the kinetic contribution to the pressure. When propagating
+    /// particles, this is the same as `degrees_of_freedom`. When propagating
+    /// rigid molecules, only the three translational degrees of freedom of
+    /// each mobile molecule are counted.
+    pub fn translational_degrees_of_freedom(&self) -> usize {
+        match self.degrees_of_freedom {
+            DegreesOfFreedom::Particles => self.degrees_of_freedom(),
+            DegreesOfFreedom::Molecules => {
+                let mobile = (0..self.molecules().len()).filter(|&molid| {
+                    !self.is_frozen_molecule(molid)
+                }).count();
+                let removed = self.removed_degrees_of_freedom(mobile);
+                (3 * mobile).saturating_sub(removed)
+            }
+        }
+    }
+
+    /// Get the number of degrees of freedom removed by the global motions
+    /// removal, for `mobile` independent bodies.
+    fn removed_degrees_of_freedom(&self, mobile: usize) -> usize {
+        let mut removed = 0;
+        if self.removed_motions.translation && mobile > 0 {
+            removed += 3;
+        }
+        if self.removed_motions.rotation {
+            // Two bodies can only rotate around two axes
+            removed += match mobile {
+                0 | 1 => 0,
+                2 => 2,
+                _ => 3,
+            };
+        }
+        return removed;
+    }
+
+    /// Get the number of degrees of freedom in this system, without taking
+    /// the removed global motions into account.
+    fn internal_degrees_of_freedom(&self) -> usize {
+        match self.degrees_of_freedom {
+            DegreesOfFreedom::Particles => {
+                let frozen = self.particles().frozen;
+                let constraints = self.constraints.iter().filter(|constraint| {
+                    !(frozen[constraint.i()] && frozen[constraint.j()])
+                }).count();
+                3 * (self.size() - self.frozen_count()) - constraints
+            }
+            DegreesOfFreedom::Molecules => {
+                (0..self.molecules().len()).map(|molid| {
+                    if self.is_frozen_molecule(molid) {
+                        0
+                    } else if self.molecule(molid).size() == 1 {
+                        3
+                    } else if self.is_linear(molid) {
+                        5
+                    } else {
+                        6
+                    }
+                }).sum()
+            }
+        }
+    }
 
     /// Guess the bonds in the configuration using the chemfiles algorithm.
     ///
@@ -275,7 +474,7 @@ impl System {
 }
 
 use sys::compute::Compute;
-use sys::compute::{PotentialEnergy, KineticEnergy, TotalEnergy};
+use sys::compute::{PotentialEnergy, KineticEnergy, TotalEnergy, ConservedEnergy};
 use sys::compute::Forces;
 use sys::compute::Temperature;
 use sys::compute::Volume;
@@ -290,6 +489,9 @@ impl System {
     pub fn potential_energy(&self) -> f64 {PotentialEnergy.compute(self)}
     /// Get the total energy of the system.
     pub fn total_energy(&self) -> f64 {TotalEnergy.compute(self)}
+    /// Get the conserved energy of the system, including the energy of the
+    /// extended degrees of freedom.
+    pub fn conserved_energy(&self) -> f64 {ConservedEnergy.compute(self)}
 
     /// Get the temperature of the system.
     pub fn temperature(&self) -> f64 {
@@ -347,8 +549,9 @@ impl DerefMut for System {
 
 #[cfg(test)]
 mod tests {
-    use super::System;
+    use super::{System, DegreesOfFreedom, RemovedMotions};
     use sys::{Particle, ParticleKind};
+    use types::{Vector3D, Zero};
 
     #[test]
     fn step() {
@@ -361,6 +564,78 @@ mod tests {
         assert_eq!(system.step(), 3);
     }
 
+    #[test]
+    fn extended_energy() {
+        let mut system = System::new();
+        assert_eq!(system.extended_energy(), 0.0);
+
+        system.set_extended_energy(42.0);
+        assert_eq!(system.extended_energy(), 42.0);
+    }
+
+    #[test]
+    fn constraints() {
+        let mut system = System::new();
+        system.add_particle(Particle::new("O"));
+        system.add_particle(Particle::new("H"));
+        system.add_particle(Particle::new("H"));
+        assert!(system.constraints().is_empty());
+
+        system.add_constraint(1, 0, 1.0);
+        system.add_constraint(0, 2, 1.0);
+        // Duplicated constraints are ignored
+        system.add_constraint(0, 1, 1.0);
+        assert_eq!(system.constraints().len(), 2);
+        assert_eq!(system.constraints()[0].i(), 0);
+        assert_eq!(system.constraints()[0].j(), 1);
+    }
+
+    #[test]
+    fn degrees_of_freedom() {
+        let mut system = System::new();
+        // Water molecule
+        system.add_particle(Particle::with_position("O", Vector3D::zero()));
+        system.add_particle(Particle::with_position("H", Vector3D::new(1.0, 0.0, 0.0)));
+        system.add_particle(Particle::with_position("H", Vector3D::new(0.0, 1.0, 0.0)));
+        let _ = system.add_bond(0, 1);
+        let _ = system.add_bond(0, 2);
+        // Dinitrogen molecule
+        system.add_particle(Particle::with_position("N", Vector3D::new(5.0, 0.0, 0.0)));
+        system.add_particle(Particle::with_position("N", Vector3D::new(6.0, 0.0, 0.0)));
+        let _ = system.add_bond(3, 4);
+        // Argon atom
+        system.add_particle(Particle::with_position("Ar", Vector3D::new(0.0, 5.0, 0.0)));
+
+        system.add_constraint(0, 1, 1.0);
+        assert_eq!(system.degrees_of_freedom(), 17);
+
+        system.set_degrees_of_freedom(DegreesOfFreedom::Molecules);
+        assert_eq!(system.degrees_of_freedom(), 6 + 5 + 3);
+        assert_eq!(system.translational_degrees_of_freedom(), 3 * 3);
+
+        // Freezing the water molecule
+        system.set_frozen_molecule(0, true);
+        assert_eq!(system.degrees_of_freedom(), 5 + 3);
+        system.set_degrees_of_freedom(DegreesOfFreedom::Particles);
+        assert_eq!(system.degrees_of_freedom(), 9);
+
+        // Removing the global motions of the three mobile particles
+        system.set_removed_motions(RemovedMotions {translation: true, rotation: false});
+        assert_eq!(system.degrees_of_freedom(), 6);
+        system.set_removed_motions(RemovedMotions {translation: true, rotation: true});
+        assert_eq!(system.degrees_of_freedom(), 3);
+        system.set_removed_motions(RemovedMotions {translation: false, rotation: true});
+        assert_eq!(system.degrees_of_freedom(), 6);
+    }
+
+    #[test]
+    #[should_panic]
+    fn constraint_out_of_bounds() {
+        let mut system = System::new();
+        system.add_particle(Particle::new("O"));
+        system.add_constraint(0, 1, 1.0);
+    }
+
     #[test]
     #[should_panic]
     fn negative_external_temperature() {
@@ -396,6 +671,24 @@ mod tests {
         assert_eq!(system.particles().kind[2], ParticleKind(0));
     }
 
+    #[test]
+    fn add_molecule() {
+        let mut system = System::new();
+        system.add_particle(Particle::new("H"));
+
+        let particles = vec![Particle::new("O"), Particle::new("H")];
+        let mut molecule = Molecule::new(0);
+        molecule.merge_with(Molecule::new(1));
+        molecule.add_bond(0, 1);
+        system.add_molecule(molecule, particles);
+
+        assert_eq!(system.size(), 3);
+        assert_eq!(system.molecules().len(), 2);
+        assert_eq!(system.particles().kind[1], ParticleKind(1));
+        assert_eq!(system.particles().kind[2], ParticleKind(0));
+        assert_eq!(system.bond_distance(1, 2), 1);
+    }
+
     #[test]
     fn particle_kinds() {
         let mut system = System::new();
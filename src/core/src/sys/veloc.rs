@@ -7,9 +7,9 @@ use rand::Isaac64Rng;
 use rand::SeedableRng;
 
 use consts::K_BOLTZMANN;
-use types::Vector3D;
+use types::{Vector3D, Zero};
 use sys::System;
-use sim::md::{RemoveRotation, RemoveTranslation, Control};
+use sim::md::{RemoveRotation, RemoveTranslation};
 
 /// Scale all velocities in the `System` such that the `system` temperature
 /// is `temperature`.
@@ -29,7 +29,8 @@ pub trait InitVelocities {
     fn seed(&mut self, seed: u64);
 }
 
-/// Initialize the velocities from a Boltzmann distribution.
+/// Initialize the velocities from a Boltzmann distribution. The velocities
+/// of frozen particles are set to zero.
 pub struct BoltzmannVelocities {
     temperature: f64,
     dist: Normal,
@@ -45,19 +46,30 @@ impl BoltzmannVelocities {
             rng: Isaac64Rng::from_seed(&[42]),
         }
     }
-}
 
-impl InitVelocities for BoltzmannVelocities {
-    fn init(&mut self, system: &mut System) {
+    /// Draw the velocities of the particles in the `system` from the
+    /// Boltzmann distribution, without removing the global translation and
+    /// rotation or rescaling the velocities to the exact temperature.
+    pub(crate) fn sample(&mut self, system: &mut System) {
         for particle in system.particles_mut() {
+            if *particle.frozen {
+                *particle.velocity = Vector3D::zero();
+                continue;
+            }
             let m_inv = 1.0 / (*particle.mass);
             let x = f64::sqrt(m_inv) * self.dist.sample(&mut self.rng);
             let y = f64::sqrt(m_inv) * self.dist.sample(&mut self.rng);
             let z = f64::sqrt(m_inv) * self.dist.sample(&mut self.rng);
             *particle.velocity = Vector3D::new(x, y, z);
         }
-        RemoveTranslation.control(system);
-        RemoveRotation.control(system);
+    }
+}
+
+impl InitVelocities for BoltzmannVelocities {
+    fn init(&mut self, system: &mut System) {
+        self.sample(system);
+        RemoveTranslation::remove(system);
+        RemoveRotation::remove(system);
         scale(system, self.temperature);
     }
 
@@ -66,7 +78,8 @@ impl InitVelocities for BoltzmannVelocities {
     }
 }
 
-/// Initialize the velocities from an uniform distribution.
+/// Initialize the velocities from an uniform distribution. The velocities of
+/// frozen particles are set to zero.
 pub struct UniformVelocities {
     temperature: f64,
     dist: Range<f64>,
@@ -88,14 +101,18 @@ impl UniformVelocities {
 impl InitVelocities for UniformVelocities {
     fn init(&mut self, system: &mut System) {
         for particle in system.particles_mut() {
+            if *particle.frozen {
+                *particle.velocity = Vector3D::zero();
+                continue;
+            }
             let m_inv = 1.0 / (*particle.mass);
             let x = f64::sqrt(m_inv) * self.dist.sample(&mut self.rng);
             let y = f64::sqrt(m_inv) * self.dist.sample(&mut self.rng);
             let z = f64::sqrt(m_inv) * self.dist.sample(&mut self.rng);
             *particle.velocity = Vector3D::new(x, y, z);
         }
-        RemoveTranslation.control(system);
-        RemoveRotation.control(system);
+        RemoveTranslation::remove(system);
+        RemoveRotation::remove(system);
         scale(system, self.temperature);
     }
 
@@ -157,6 +174,25 @@ mod test {
         assert_ulps_eq!(global_translation(&system), 0.0);
     }
 
+    #[test]
+    fn frozen_particles() {
+        let mut system = testing_system();
+        system.set_frozen(0, true);
+        system.set_frozen(42, true);
+
+        let mut boltzmann = BoltzmannVelocities::new(300.0);
+        let mut uniform = UniformVelocities::new(300.0);
+        let initializers: [&mut InitVelocities; 2] = [&mut boltzmann, &mut uniform];
+        for velocities in initializers.iter_mut() {
+            velocities.seed(1234);
+            velocities.init(&mut system);
+            let temperature = system.temperature();
+            assert_ulps_eq!(temperature, 300.0, epsilon=1e-9);
+            assert_eq!(system.particles().velocity[0], Vector3D::zero());
+            assert_eq!(system.particles().velocity[42], Vector3D::zero());
+        }
+    }
+
     #[test]
     fn scaling_keeps_global_velocity() {
         let mut system = system_from_xyz("2
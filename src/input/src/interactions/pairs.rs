@@ -7,7 +7,7 @@ use lumol::units;
 
 use lumol::energy::{PairPotential, PairInteraction, BondPotential};
 use lumol::energy::{Harmonic, LennardJones, NullPotential};
-use lumol::energy::{Buckingham, BornMayerHuggins, MorsePotential};
+use lumol::energy::{Buckingham, BornMayerHuggins, MorsePotential, SoftRepulsion};
 use lumol::energy::TableComputation;
 
 use error::{Error, Result};
@@ -207,6 +207,7 @@ fn read_pair_potential(pair: &Table) -> Result<Box<PairPotential>> {
             "buckingham" => Ok(Box::new(try!(Buckingham::from_toml(table)))),
             "born" => Ok(Box::new(try!(BornMayerHuggins::from_toml(table)))),
             "morse" => Ok(Box::new(try!(MorsePotential::from_toml(table)))),
+            "soft" => Ok(Box::new(try!(SoftRepulsion::from_toml(table)))),
             other => Err(
                 Error::from(format!("Unknown potential type '{}'", other))
             ),
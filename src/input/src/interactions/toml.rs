@@ -10,6 +10,7 @@ use FromTomlWithData;
 
 use lumol::energy::{Harmonic, LennardJones, NullPotential, CosineHarmonic};
 use lumol::energy::{Torsion, Buckingham, BornMayerHuggins, MorsePotential};
+use lumol::energy::SoftRepulsion;
 use lumol::energy::{Wolf, Ewald};
 use lumol::energy::{PairPotential, TableComputation};
 
@@ -168,6 +169,23 @@ impl FromToml for MorsePotential {
     }
 }
 
+impl FromToml for SoftRepulsion {
+    fn from_toml(table: &Table) -> Result<SoftRepulsion> {
+        let a = try_extract_parameter!(table, "a", "soft repulsion potential");
+        let rc = try_extract_parameter!(table, "rc", "soft repulsion potential");
+
+        if let (Some(a), Some(rc)) = (a.as_str(), rc.as_str()) {
+            let a = try!(::lumol::units::from_str(a));
+            let rc = try!(::lumol::units::from_str(rc));
+            Ok(SoftRepulsion{a: a, rc: rc})
+        } else {
+            Err(Error::from(
+                "'a' and 'rc' must be strings in soft repulsion potential"
+            ))
+        }
+    }
+}
+
 /******************************************************************************/
 
 impl FromTomlWithData for TableComputation {
@@ -3,7 +3,7 @@
 use toml::value::Table;
 use std::path::PathBuf;
 
-use lumol::sys::{read_molecule, molecule_type};
+use lumol::sys::{read_molecule, molecule_type, Molecule, Particle, ParticleVec, System};
 use lumol::sim::mc::*;
 use lumol::units;
 
@@ -11,14 +11,17 @@ use error::{Error, Result};
 use FromTomlWithData;
 use extract;
 use simulations::get_input_path;
+use super::schedule::read_setpoint;
 
 impl FromTomlWithData for MonteCarlo {
     type Data = PathBuf;
     fn from_toml(config: &Table, root: PathBuf) -> Result<MonteCarlo> {
-        let temperature = try!(extract::str("temperature", config, "Monte Carlo propagator"));
-        let temperature = try!(units::from_str(temperature));
+        let (temperature, schedule) = try!(read_setpoint("temperature", config, "Monte Carlo propagator"));
 
         let mut mc = MonteCarlo::new(temperature);
+        if let Some(schedule) = schedule {
+            mc.set_temperature_schedule(schedule);
+        }
 
         let has_update_frequency = config.get("update_frequency").is_some();
         if has_update_frequency {
@@ -32,40 +35,10 @@ impl FromTomlWithData for MonteCarlo {
             let mc_move = try!(mc_move.as_table()
                 .ok_or(Error::from("All moves must be tables in Monte Carlo")));
 
-            let frequency = if mc_move.get("frequency").is_some() {
-                try!(extract::number("frequency", mc_move, "Monte Carlo move"))
-            } else {
-                1.0
-            };
-
-            let target_acceptance = if mc_move.get("target_acceptance").is_some() {
-                Some(try!(extract::number("target_acceptance", mc_move, "Monte Carlo move")))
-            } else {
-                None
-            };
-
-            let mc_move: Box<MCMove> = match try!(extract::typ(mc_move, "Monte Carlo move")) {
-                "Translate" => Box::new(try!(Translate::from_toml(mc_move, root.clone()))),
-                "Rotate" => Box::new(try!(Rotate::from_toml(mc_move, root.clone()))),
-                "Resize" => Box::new(try!(Resize::from_toml(mc_move, root.clone()))),
-                other => return Err(Error::from(format!("Unknown Monte Carlo move '{}'", other))),
-            };
-
+            let (frequency, target_acceptance) = try!(read_frequencies(mc_move, has_update_frequency));
+            let mc_move = try!(read_move(mc_move, &root));
             match target_acceptance {
-                Some(ta) => {
-                    if !has_update_frequency {
-                        return Err(Error::from(
-                            "No 'update_frequency' found. Please specify \
-                            'update_frequency' in combination with 'target_acceptance'"
-                        ));
-                    } else if ta < 0.0 || ta > 1.0 {
-                        return Err(Error::from(
-                            "'target_acceptance' has to be between 0.0 and 1.0"
-                        ));
-                    } else {
-                        mc.add_move_with_acceptance(mc_move, frequency, ta)
-                    }
-                }
+                Some(ta) => mc.add_move_with_acceptance(mc_move, frequency, ta),
                 None => mc.add(mc_move, frequency),
             }
         }
@@ -73,6 +46,206 @@ impl FromTomlWithData for MonteCarlo {
     }
 }
 
+impl FromTomlWithData for GibbsEnsemble {
+    type Data = (PathBuf, System);
+    fn from_toml(config: &Table, (root, second): (PathBuf, System)) -> Result<GibbsEnsemble> {
+        let (temperature, schedule) = try!(read_setpoint("temperature", config, "Gibbs ensemble propagator"));
+
+        let mut gibbs = GibbsEnsemble::new(temperature, second);
+        if let Some(schedule) = schedule {
+            gibbs.set_temperature_schedule(schedule);
+        }
+
+        let has_update_frequency = config.get("update_frequency").is_some();
+        if has_update_frequency {
+            let update_frequency =
+                try!(extract::uint("update_frequency", config, "Gibbs ensemble propagator"));
+            gibbs.set_amplitude_update_frequency(update_frequency);
+        }
+
+        let moves = try!(extract::slice("moves", config, "Gibbs ensemble propagator"));
+        for mc_move in moves {
+            let mc_move = try!(mc_move.as_table()
+                .ok_or(Error::from("All moves must be tables in Gibbs ensemble")));
+
+            let (frequency, target_acceptance) = try!(read_frequencies(mc_move, has_update_frequency));
+            match try!(extract::typ(mc_move, "Monte Carlo move")) {
+                "VolumeExchange" => {
+                    let delta = try!(extract::number("delta", mc_move, "VolumeExchange move"));
+                    if delta <= 0.0 {
+                        return Err(Error::from("'delta' must be positive in VolumeExchange move"));
+                    }
+                    gibbs.add_volume_exchange(delta, frequency);
+                }
+                "Transfer" => {
+                    let molfile = try!(extract::str("molecule", mc_move, "Transfer move"));
+                    let molfile = get_input_path(&root, molfile);
+                    let (molecule, atoms) = try!(read_molecule(molfile));
+                    gibbs.add_transfer(molecule_type(&molecule, atoms.as_slice()), frequency);
+                }
+                _ => {
+                    // Moves inside the boxes are created once for each box
+                    let first = try!(read_move(mc_move, &root));
+                    let second = try!(read_move(mc_move, &root));
+                    match target_acceptance {
+                        Some(ta) => gibbs.add_move_with_acceptance(first, second, frequency, ta),
+                        None => gibbs.add(first, second, frequency),
+                    }
+                }
+            }
+        }
+        return Ok(gibbs);
+    }
+}
+
+impl FromTomlWithData for WangLandau {
+    type Data = PathBuf;
+    fn from_toml(config: &Table, root: PathBuf) -> Result<WangLandau> {
+        let temperature = try!(extract::str("temperature", config, "Wang-Landau propagator"));
+        let temperature = try!(units::from_str(temperature));
+        if temperature <= 0.0 {
+            return Err(Error::from("'temperature' must be positive in Wang-Landau propagator"));
+        }
+
+        let parameter = if config.get("parameter").is_some() {
+            match try!(extract::str("parameter", config, "Wang-Landau propagator")) {
+                "energy" => OrderParameter::Energy,
+                "volume" => OrderParameter::Volume,
+                "molecules" => OrderParameter::Molecules,
+                other => return Err(Error::from(
+                    format!("Unknown parameter '{}' in Wang-Landau propagator", other)
+                )),
+            }
+        } else {
+            OrderParameter::Energy
+        };
+
+        let min = try!(extract::str("min", config, "Wang-Landau propagator"));
+        let min = try!(units::from_str(min));
+        let max = try!(extract::str("max", config, "Wang-Landau propagator"));
+        let max = try!(units::from_str(max));
+        if max <= min {
+            return Err(Error::from("'max' must be larger than 'min' in Wang-Landau propagator"));
+        }
+
+        let bins = try!(extract::uint("bins", config, "Wang-Landau propagator"));
+        if bins == 0 {
+            return Err(Error::from("'bins' must be positive in Wang-Landau propagator"));
+        }
+
+        let mut wang_landau = WangLandau::new(temperature, parameter, min, max, bins as usize);
+
+        if config.get("ln_f").is_some() {
+            let ln_f = try!(extract::number("ln_f", config, "Wang-Landau propagator"));
+            if ln_f <= 0.0 {
+                return Err(Error::from("'ln_f' must be positive in Wang-Landau propagator"));
+            }
+            wang_landau.set_modification_factor(ln_f);
+        }
+
+        if config.get("final_ln_f").is_some() {
+            let final_ln_f = try!(extract::number("final_ln_f", config, "Wang-Landau propagator"));
+            if final_ln_f <= 0.0 {
+                return Err(Error::from("'final_ln_f' must be positive in Wang-Landau propagator"));
+            }
+            wang_landau.set_final_modification_factor(final_ln_f);
+        }
+
+        if config.get("flatness").is_some() {
+            let flatness = try!(extract::number("flatness", config, "Wang-Landau propagator"));
+            if flatness <= 0.0 || flatness >= 1.0 {
+                return Err(Error::from("'flatness' must be between 0 and 1 in Wang-Landau propagator"));
+            }
+            wang_landau.set_flatness(flatness);
+        }
+
+        if config.get("check_frequency").is_some() {
+            let frequency = try!(extract::uint("check_frequency", config, "Wang-Landau propagator"));
+            if frequency == 0 {
+                return Err(Error::from("'check_frequency' must be positive in Wang-Landau propagator"));
+            }
+            wang_landau.set_check_frequency(frequency);
+        }
+
+        if config.get("output").is_some() {
+            let output = try!(extract::str("output", config, "Wang-Landau propagator"));
+            try_io!(wang_landau.set_output(output), PathBuf::from(output));
+        }
+
+        let has_update_frequency = config.get("update_frequency").is_some();
+        if has_update_frequency {
+            let update_frequency =
+                try!(extract::uint("update_frequency", config, "Wang-Landau propagator"));
+            wang_landau.set_amplitude_update_frequency(update_frequency);
+        }
+
+        let moves = try!(extract::slice("moves", config, "Wang-Landau propagator"));
+        for mc_move in moves {
+            let mc_move = try!(mc_move.as_table()
+                .ok_or(Error::from("All moves must be tables in Wang-Landau")));
+
+            let (frequency, target_acceptance) = try!(read_frequencies(mc_move, has_update_frequency));
+            let mc_move = try!(read_move(mc_move, &root));
+            match target_acceptance {
+                Some(ta) => wang_landau.add_move_with_acceptance(mc_move, frequency, ta),
+                None => wang_landau.add(mc_move, frequency),
+            }
+        }
+        return Ok(wang_landau);
+    }
+}
+
+/// Read the frequency and the optional target acceptance of a Monte Carlo
+/// move.
+fn read_frequencies(mc_move: &Table, has_update_frequency: bool) -> Result<(f64, Option<f64>)> {
+    let frequency = if mc_move.get("frequency").is_some() {
+        try!(extract::number("frequency", mc_move, "Monte Carlo move"))
+    } else {
+        1.0
+    };
+
+    let target_acceptance = if mc_move.get("target_acceptance").is_some() {
+        Some(try!(extract::number("target_acceptance", mc_move, "Monte Carlo move")))
+    } else {
+        None
+    };
+
+    if let Some(ta) = target_acceptance {
+        if !has_update_frequency {
+            return Err(Error::from(
+                "No 'update_frequency' found. Please specify \
+                'update_frequency' in combination with 'target_acceptance'"
+            ));
+        } else if ta < 0.0 || ta > 1.0 {
+            return Err(Error::from(
+                "'target_acceptance' has to be between 0.0 and 1.0"
+            ));
+        }
+    }
+
+    Ok((frequency, target_acceptance))
+}
+
+/// Read a single Monte Carlo move from the `mc_move` table
+fn read_move(mc_move: &Table, root: &PathBuf) -> Result<Box<MCMove>> {
+    let mc_move: Box<MCMove> = match try!(extract::typ(mc_move, "Monte Carlo move")) {
+        "Translate" => Box::new(try!(Translate::from_toml(mc_move, root.clone()))),
+        "Rotate" => Box::new(try!(Rotate::from_toml(mc_move, root.clone()))),
+        "Resize" => Box::new(try!(Resize::from_toml(mc_move, root.clone()))),
+        "Insert" => Box::new(try!(Insert::from_toml(mc_move, root.clone()))),
+        "Delete" => Box::new(try!(Delete::from_toml(mc_move, root.clone()))),
+        "Regrow" => Box::new(try!(Regrow::from_toml(mc_move, root.clone()))),
+        "Swap" => Box::new(try!(Swap::from_toml(mc_move, root.clone()))),
+        "SemiGrand" => Box::new(try!(SemiGrand::from_toml(mc_move, root.clone()))),
+        "Hybrid" => Box::new(try!(Hybrid::from_toml(mc_move, root.clone()))),
+        "DihedralRotation" => Box::new(try!(DihedralRotation::from_toml(mc_move, root.clone()))),
+        "Crankshaft" => Box::new(try!(Crankshaft::from_toml(mc_move, root.clone()))),
+        "Pivot" => Box::new(try!(Pivot::from_toml(mc_move, root.clone()))),
+        other => return Err(Error::from(format!("Unknown Monte Carlo move '{}'", other))),
+    };
+    Ok(mc_move)
+}
+
 impl FromTomlWithData for Translate {
     type Data = PathBuf;
     fn from_toml(config: &Table, root: PathBuf) -> Result<Translate> {
@@ -112,12 +285,245 @@ impl FromTomlWithData for Rotate {
 impl FromTomlWithData for Resize {
     type Data = PathBuf;
     fn from_toml(config: &Table, _: PathBuf) -> Result<Resize> {
-        let pressure = try!(extract::str("pressure", config, "Resize move"));
-        let pressure = try!(units::from_str(pressure));
+        let (pressure, schedule) = try!(read_setpoint("pressure", config, "Resize move"));
 
         let delta = try!(extract::str("delta", config, "Resize move"));
         let delta = try!(units::from_str(delta));
 
-        Ok(Resize::new(pressure, delta))
+        let mut resize = if config.get("mode").is_some() {
+            match try!(extract::str("mode", config, "Resize move")) {
+                "isotropic" => Resize::new(pressure, delta),
+                "anisotropic" => Resize::anisotropic(pressure, delta),
+                "triclinic" => {
+                    let strain = try!(extract::number("strain", config, "triclinic Resize move"));
+                    if strain <= 0.0 {
+                        return Err(Error::from("'strain' must be positive in triclinic Resize move"));
+                    }
+                    Resize::triclinic(pressure, delta, strain)
+                }
+                other => return Err(Error::from(format!("Unknown mode '{}' in Resize move", other))),
+            }
+        } else {
+            Resize::new(pressure, delta)
+        };
+
+        if let Some(schedule) = schedule {
+            resize.set_pressure_schedule(schedule);
+        }
+
+        if let Some(log_volume) = config.get("log_volume") {
+            let log_volume = try!(log_volume.as_bool().ok_or(Error::from(
+                "'log_volume' must be a boolean in Resize move"
+            )));
+            resize.set_log_volume(log_volume);
+        }
+        Ok(resize)
+    }
+}
+
+impl FromTomlWithData for Regrow {
+    type Data = PathBuf;
+    fn from_toml(config: &Table, root: PathBuf) -> Result<Regrow> {
+        let trials = try!(read_trials(config, "Regrow move"));
+
+        if config.get("molecule").is_some() {
+            let molfile = try!(extract::str("molecule", config, "Regrow move"));
+            let molfile = get_input_path(root, molfile);
+            let (molecule, atoms) = try!(read_molecule(molfile));
+            let moltype = molecule_type(&molecule, atoms.as_slice());
+            Ok(Regrow::with_moltype(trials, moltype))
+        } else {
+            Ok(Regrow::new(trials))
+        }
+    }
+}
+
+impl FromTomlWithData for Insert {
+    type Data = PathBuf;
+    fn from_toml(config: &Table, root: PathBuf) -> Result<Insert> {
+        let (molecule, particles) = try!(read_exchanged_molecule(config, root, "Insert move"));
+        let reservoir = try!(read_reservoir(config, "Insert move"));
+        let mut insert = Insert::new(molecule, particles, reservoir);
+        if config.get("trials").is_some() {
+            insert.set_cbmc_trials(try!(read_trials(config, "Insert move")));
+        }
+        Ok(insert)
+    }
+}
+
+impl FromTomlWithData for Delete {
+    type Data = PathBuf;
+    fn from_toml(config: &Table, root: PathBuf) -> Result<Delete> {
+        let (molecule, particles) = try!(read_exchanged_molecule(config, root, "Delete move"));
+        let reservoir = try!(read_reservoir(config, "Delete move"));
+        let mut delete = Delete::new(molecule, particles, reservoir);
+        if config.get("trials").is_some() {
+            delete.set_cbmc_trials(try!(read_trials(config, "Delete move")));
+        }
+        Ok(delete)
+    }
+}
+
+impl FromTomlWithData for Swap {
+    type Data = PathBuf;
+    fn from_toml(config: &Table, root: PathBuf) -> Result<Swap> {
+        let molecules = try!(extract::slice("molecules", config, "Swap move"));
+        if molecules.len() != 2 {
+            return Err(Error::from("'molecules' must be an array of two strings in Swap move"));
+        }
+
+        let mut moltypes = Vec::new();
+        for molfile in molecules {
+            let molfile = try!(molfile.as_str().ok_or(
+                Error::from("'molecules' must be an array of two strings in Swap move")
+            ));
+            let molfile = get_input_path(&root, molfile);
+            let (molecule, atoms) = try!(read_molecule(molfile));
+            moltypes.push(molecule_type(&molecule, atoms.as_slice()));
+        }
+
+        if moltypes[0] == moltypes[1] {
+            return Err(Error::from("The two molecules must be different in Swap move"));
+        }
+        Ok(Swap::new(moltypes[0], moltypes[1]))
+    }
+}
+
+impl FromTomlWithData for SemiGrand {
+    type Data = PathBuf;
+    fn from_toml(config: &Table, _: PathBuf) -> Result<SemiGrand> {
+        let table = try!(extract::table("species", config, "SemiGrand move"));
+        if table.len() < 2 {
+            return Err(Error::from("'species' must contain at least two species in SemiGrand move"));
+        }
+
+        let mut species = Vec::new();
+        for (name, fugacity) in table {
+            let fugacity = try!(fugacity.as_str().ok_or(
+                Error::from("'species' values must be strings in SemiGrand move")
+            ));
+            let fugacity = try!(units::from_str(fugacity));
+            if fugacity <= 0.0 {
+                return Err(Error::from("The fugacities must be positive in SemiGrand move"));
+            }
+            species.push((Particle::new(name.as_str()), fugacity));
+        }
+        Ok(SemiGrand::new(species))
+    }
+}
+
+impl FromTomlWithData for Hybrid {
+    type Data = PathBuf;
+    fn from_toml(config: &Table, _: PathBuf) -> Result<Hybrid> {
+        let timestep = try!(extract::str("timestep", config, "Hybrid move"));
+        let timestep = try!(units::from_str(timestep));
+        if timestep <= 0.0 {
+            return Err(Error::from("'timestep' must be positive in Hybrid move"));
+        }
+
+        let steps = try!(extract::uint("steps", config, "Hybrid move"));
+        if steps == 0 {
+            return Err(Error::from("'steps' must be positive in Hybrid move"));
+        }
+        Ok(Hybrid::new(timestep, steps as usize))
+    }
+}
+
+impl FromTomlWithData for DihedralRotation {
+    type Data = PathBuf;
+    fn from_toml(config: &Table, root: PathBuf) -> Result<DihedralRotation> {
+        let (delta, moltype) = try!(read_internal_rotation(config, root, "DihedralRotation move"));
+        match moltype {
+            Some(moltype) => Ok(DihedralRotation::with_moltype(delta, moltype)),
+            None => Ok(DihedralRotation::new(delta)),
+        }
+    }
+}
+
+impl FromTomlWithData for Crankshaft {
+    type Data = PathBuf;
+    fn from_toml(config: &Table, root: PathBuf) -> Result<Crankshaft> {
+        let (delta, moltype) = try!(read_internal_rotation(config, root, "Crankshaft move"));
+        match moltype {
+            Some(moltype) => Ok(Crankshaft::with_moltype(delta, moltype)),
+            None => Ok(Crankshaft::new(delta)),
+        }
+    }
+}
+
+impl FromTomlWithData for Pivot {
+    type Data = PathBuf;
+    fn from_toml(config: &Table, root: PathBuf) -> Result<Pivot> {
+        let (delta, moltype) = try!(read_internal_rotation(config, root, "Pivot move"));
+        match moltype {
+            Some(moltype) => Ok(Pivot::with_moltype(delta, moltype)),
+            None => Ok(Pivot::new(delta)),
+        }
+    }
+}
+
+/// Read the maximal angle `delta` and the optional type of the molecules
+/// changed by the internal coordinates moves.
+fn read_internal_rotation(config: &Table, root: PathBuf, context: &str) -> Result<(f64, Option<u64>)> {
+    let delta = try!(extract::str("delta", config, context));
+    let delta = try!(units::from_str(delta));
+    if delta <= 0.0 {
+        return Err(Error::from(format!("'delta' must be positive in {}", context)));
+    }
+
+    if config.get("molecule").is_some() {
+        let molfile = try!(extract::str("molecule", config, context));
+        let molfile = get_input_path(root, molfile);
+        let (molecule, atoms) = try!(read_molecule(molfile));
+        Ok((delta, Some(molecule_type(&molecule, atoms.as_slice()))))
+    } else {
+        Ok((delta, None))
+    }
+}
+
+/// Read the number of configurational-bias trial positions from the `trials`
+/// key.
+fn read_trials(config: &Table, context: &str) -> Result<usize> {
+    let trials = try!(extract::uint("trials", config, context));
+    if trials == 0 {
+        return Err(Error::from(format!("'trials' must be positive in {}", context)));
+    }
+    Ok(trials as usize)
+}
+
+/// Read the molecule exchanged with the reservoir in grand canonical moves,
+/// or inserted in Widom outputs, from the file at the `molecule` key.
+pub(super) fn read_exchanged_molecule(config: &Table, root: PathBuf, context: &str) -> Result<(Molecule, ParticleVec)> {
+    let molfile = try!(extract::str("molecule", config, context));
+    let molfile = get_input_path(root, molfile);
+    let (molecule, particles) = try!(read_molecule(molfile));
+    // Only keep the particles in the first molecule of the file
+    let particles = particles.slice(molecule.iter()).to_vec();
+    if particles.mass.iter().sum::<f64>() <= 0.0 {
+        return Err(Error::from(format!(
+            "The molecule mass must be positive in {}", context
+        )));
+    }
+    Ok((molecule, particles))
+}
+
+/// Read the reservoir for grand canonical moves, from either the
+/// `chemical_potential` or the `fugacity` key.
+fn read_reservoir(config: &Table, context: &str) -> Result<Reservoir> {
+    match (config.get("chemical_potential"), config.get("fugacity")) {
+        (Some(_), None) => {
+            let mu = try!(extract::str("chemical_potential", config, context));
+            Ok(Reservoir::ChemicalPotential(try!(units::from_str(mu))))
+        }
+        (None, Some(_)) => {
+            let fugacity = try!(extract::str("fugacity", config, context));
+            Ok(Reservoir::Fugacity(try!(units::from_str(fugacity))))
+        }
+        (Some(_), Some(_)) => Err(Error::from(format!(
+            "Only one of 'chemical_potential' or 'fugacity' can be used in {}", context
+        ))),
+        (None, None) => Err(Error::from(format!(
+            "Missing 'chemical_potential' or 'fugacity' key in {}", context
+        ))),
     }
 }
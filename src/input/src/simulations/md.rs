@@ -1,17 +1,22 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
-use toml::value::Table;
+use toml::value::{Table, Value};
+use std::path::{Path, PathBuf};
 
 use lumol::sim::md::*;
+use lumol::sys::Selection;
 use lumol::units;
 use lumol::sim::Alternator;
 
 use error::{Error, Result};
 use {FromToml, FromTomlWithData};
 use extract;
+use super::selection::read_selection;
+use super::schedule::read_setpoint;
 
-impl FromToml for MolecularDynamics {
-    fn from_toml(config: &Table) -> Result<MolecularDynamics> {
+impl FromTomlWithData for MolecularDynamics {
+    type Data = PathBuf;
+    fn from_toml(config: &Table, root: PathBuf) -> Result<MolecularDynamics> {
         // Get the timestep of the simulation
         let timestep = try!(extract::str("timestep", config, "molecular dynamics propagator"));
         let timestep = try!(units::from_str(timestep));
@@ -38,6 +43,24 @@ impl FromToml for MolecularDynamics {
                 "LeapFrog" => Box::new(try!(
                     LeapFrog::from_toml(integrator, timestep)
                 )),
+                "MartynaTobiasKlein" => Box::new(try!(
+                    MartynaTobiasKlein::from_toml(integrator, timestep)
+                )),
+                "BrownianDynamics" => Box::new(try!(
+                    BrownianDynamics::from_toml(integrator, timestep)
+                )),
+                "RESPA" => Box::new(try!(
+                    Respa::from_toml(integrator, timestep)
+                )),
+                "RigidBody" => Box::new(try!(
+                    RigidBody::from_toml(integrator, timestep)
+                )),
+                "SLLOD" => Box::new(try!(
+                    Sllod::from_toml(integrator, timestep)
+                )),
+                "DPDVelocityVerlet" => Box::new(try!(
+                    DPDVelocityVerlet::from_toml(integrator, timestep)
+                )),
                 other => return Err(Error::from(
                     format!("Unknown integrator '{}'", other)
                 ))
@@ -48,23 +71,24 @@ impl FromToml for MolecularDynamics {
             md = MolecularDynamics::new(timestep);
         }
 
-        if let Some(thermostat) = config.get("thermostat") {
-            let thermostat = try!(thermostat.as_table().ok_or(
-                Error::from("'thermostat' must be a table in molecular dynamics")
-            ));
-
-            let thermostat: Box<Thermostat> = match try!(extract::typ(thermostat, "thermostat")) {
-                "Berendsen" => Box::new(try!(
-                    BerendsenThermostat::from_toml(thermostat)
-                )),
-                "Rescale" => Box::new(try!(
-                    RescaleThermostat::from_toml(thermostat)
-                )),
-                other => return Err(Error::from(
-                    format!("Unknown thermostat type '{}'", other)
-                ))
+        if let Some(thermostats) = config.get("thermostat") {
+            let error = "'thermostat' must be a table or an array of tables in molecular dynamics";
+            let thermostats = match *thermostats {
+                Value::Table(ref thermostat) => vec![thermostat],
+                Value::Array(ref thermostats) => {
+                    let mut tables = Vec::new();
+                    for thermostat in thermostats {
+                        tables.push(try!(thermostat.as_table().ok_or(Error::from(error))));
+                    }
+                    tables
+                }
+                _ => return Err(Error::from(error)),
             };
-            md.set_thermostat(thermostat);
+
+            for thermostat in thermostats {
+                let thermostat = try!(read_thermostat(thermostat, timestep, &root));
+                md.add_thermostat(thermostat);
+            }
         }
 
         if let Some(controls) = config.get("controls") {
@@ -87,6 +111,9 @@ impl FromToml for MolecularDynamics {
                     "Rewrap" => Box::new(try!(
                         Alternator::<Rewrap>::from_toml(control)
                     )),
+                    "MonteCarloBarostat" => Box::new(try!(
+                        Alternator::<MonteCarloBarostat>::from_toml(control)
+                    )),
                     other => return Err(Error::from(
                         format!("Unknown control '{}'", other)
                     ))
@@ -103,71 +130,366 @@ impl FromToml for MolecularDynamics {
 
 impl FromTomlWithData for Verlet {
     type Data = f64;
-    fn from_toml(_: &Table, timestep: f64) -> Result<Verlet> {
-        Ok(Verlet::new(timestep))
+    fn from_toml(config: &Table, timestep: f64) -> Result<Verlet> {
+        let mut integrator = Verlet::new(timestep);
+        if let Some(shake) = try!(read_shake(config, "Verlet integrator")) {
+            integrator.set_shake(shake);
+        }
+        Ok(integrator)
     }
 }
 
 impl FromTomlWithData for VelocityVerlet {
     type Data = f64;
-    fn from_toml(_: &Table, timestep: f64) -> Result<VelocityVerlet> {
-        Ok(VelocityVerlet::new(timestep))
+    fn from_toml(config: &Table, timestep: f64) -> Result<VelocityVerlet> {
+        let mut integrator = VelocityVerlet::new(timestep);
+        if let Some(shake) = try!(read_shake(config, "velocity Verlet integrator")) {
+            integrator.set_shake(shake);
+        }
+        Ok(integrator)
     }
 }
 
 impl FromTomlWithData for LeapFrog {
     type Data = f64;
-    fn from_toml(_: &Table, timestep: f64) -> Result<LeapFrog> {
-        Ok(LeapFrog::new(timestep))
+    fn from_toml(config: &Table, timestep: f64) -> Result<LeapFrog> {
+        let mut integrator = LeapFrog::new(timestep);
+        if let Some(shake) = try!(read_shake(config, "leap frog integrator")) {
+            integrator.set_shake(shake);
+        }
+        Ok(integrator)
+    }
+}
+
+impl FromTomlWithData for Respa {
+    type Data = f64;
+    fn from_toml(config: &Table, timestep: f64) -> Result<Respa> {
+        let pairs_steps = try!(read_respa_steps(config, "pairs_steps"));
+        let bonded_steps = try!(read_respa_steps(config, "bonded_steps"));
+        Ok(Respa::new(timestep, pairs_steps, bonded_steps))
+    }
+}
+
+/// Read the number of substeps for the RESPA integrator at the given `key`,
+/// defaulting to 1.
+fn read_respa_steps(config: &Table, key: &str) -> Result<usize> {
+    if config.get(key).is_none() {
+        return Ok(1);
+    }
+
+    let steps = try!(extract::uint(key, config, "RESPA integrator"));
+    if steps == 0 {
+        return Err(Error::from(format!(
+            "'{}' must be strictly positive in RESPA integrator", key
+        )));
+    }
+    Ok(steps as usize)
+}
+
+impl FromTomlWithData for BrownianDynamics {
+    type Data = f64;
+    fn from_toml(config: &Table, timestep: f64) -> Result<BrownianDynamics> {
+        let context = "Brownian dynamics integrator";
+        let temperature = try!(extract::str("temperature", config, context));
+        let temperature = try!(units::from_str(temperature));
+        if temperature < 0.0 {
+            return Err(Error::from(format!("'temperature' must be positive in {}", context)));
+        }
+        let mut integrator = BrownianDynamics::new(timestep, temperature);
+
+        let diffusion = try!(extract::table("diffusion", config, context));
+        for (name, coefficient) in diffusion {
+            let coefficient = try!(coefficient.as_str().ok_or(Error::from(format!(
+                "diffusion coefficient for '{}' must be a string in {}", name, context
+            ))));
+            let coefficient = try!(units::from_str(coefficient));
+            if coefficient <= 0.0 {
+                return Err(Error::from(format!(
+                    "diffusion coefficient for '{}' must be strictly positive in {}", name, context
+                )));
+            }
+            integrator.set_diffusion(name, coefficient);
+        }
+
+        if config.get("viscosity").is_some() {
+            let viscosity = try!(extract::str("viscosity", config, context));
+            let viscosity = try!(units::from_str(viscosity));
+            if viscosity <= 0.0 {
+                return Err(Error::from(format!(
+                    "'viscosity' must be strictly positive in {}", context
+                )));
+            }
+            integrator.enable_hydrodynamics(viscosity);
+        }
+
+        if config.get("seed").is_some() {
+            let seed = try!(extract::uint("seed", config, context));
+            integrator.seed(seed);
+        }
+
+        Ok(integrator)
+    }
+}
+
+impl FromTomlWithData for Sllod {
+    type Data = f64;
+    fn from_toml(config: &Table, timestep: f64) -> Result<Sllod> {
+        let shear_rate = try!(extract::str("shear_rate", config, "SLLOD integrator"));
+        let shear_rate = try!(units::from_str(shear_rate));
+        Ok(Sllod::new(timestep, shear_rate))
     }
 }
 
+impl FromTomlWithData for DPDVelocityVerlet {
+    type Data = f64;
+    fn from_toml(config: &Table, timestep: f64) -> Result<DPDVelocityVerlet> {
+        let context = "DPD integrator";
+        let (temperature, gamma, cutoff) = try!(read_dpd_parameters(config, context));
+        let mut integrator = DPDVelocityVerlet::new(timestep, temperature, gamma, cutoff);
+
+        if config.get("lambda").is_some() {
+            let lambda = try!(extract::number("lambda", config, context));
+            if lambda < 0.0 || lambda > 1.0 {
+                return Err(Error::from(format!(
+                    "'lambda' must be between 0 and 1 in {}", context
+                )));
+            }
+            integrator.set_lambda(lambda);
+        }
+
+        if config.get("seed").is_some() {
+            let seed = try!(extract::uint("seed", config, context));
+            integrator.seed(seed);
+        }
+
+        Ok(integrator)
+    }
+}
+
+/// Read the temperature, friction coefficient and cutoff distance for
+/// dissipative particle dynamics in `config`.
+fn read_dpd_parameters(config: &Table, context: &str) -> Result<(f64, f64, f64)> {
+    let temperature = try!(extract::str("temperature", config, context));
+    let temperature = try!(units::from_str(temperature));
+    if temperature < 0.0 {
+        return Err(Error::from(format!("'temperature' must be positive in {}", context)));
+    }
+
+    let gamma = try!(extract::str("gamma", config, context));
+    let gamma = try!(units::from_str(gamma));
+    if gamma < 0.0 {
+        return Err(Error::from(format!("'gamma' must be positive in {}", context)));
+    }
+
+    let cutoff = try!(extract::str("cutoff", config, context));
+    let cutoff = try!(units::from_str(cutoff));
+    if cutoff <= 0.0 {
+        return Err(Error::from(format!("'cutoff' must be strictly positive in {}", context)));
+    }
+
+    Ok((temperature, gamma, cutoff))
+}
+
+impl FromTomlWithData for RigidBody {
+    type Data = f64;
+    fn from_toml(_: &Table, timestep: f64) -> Result<RigidBody> {
+        Ok(RigidBody::new(timestep))
+    }
+}
+
+/// Read the constraints solver parameters for the integrator in `config`, if
+/// any of them is present.
+fn read_shake(config: &Table, context: &str) -> Result<Option<Shake>> {
+    if !config.contains_key("shake_tolerance") && !config.contains_key("shake_iterations") {
+        return Ok(None);
+    }
+
+    let mut shake = Shake::new();
+    if config.contains_key("shake_tolerance") {
+        let tolerance = try!(extract::number("shake_tolerance", config, context));
+        if tolerance <= 0.0 {
+            return Err(Error::from(
+                format!("'shake_tolerance' must be strictly positive in {}", context)
+            ));
+        }
+        shake.set_tolerance(tolerance);
+    }
+
+    if config.contains_key("shake_iterations") {
+        let iterations = try!(extract::uint("shake_iterations", config, context));
+        if iterations == 0 {
+            return Err(Error::from(
+                format!("'shake_iterations' must be strictly positive in {}", context)
+            ));
+        }
+        shake.set_max_iterations(iterations);
+    }
+
+    Ok(Some(shake))
+}
+
 impl FromTomlWithData for BerendsenBarostat {
     type Data = f64;
     fn from_toml(config: &Table, timestep: f64) -> Result<BerendsenBarostat> {
-        let pressure = try!(extract::str("pressure", config, "Berendsen barostat"));
-        let pressure = try!(units::from_str(pressure));
+        let (pressure, schedule) = try!(read_setpoint("pressure", config, "Berendsen barostat"));
         let tau = try!(extract::number("timestep", config, "Berendsen barostat"));
-        Ok(BerendsenBarostat::new(timestep, pressure, tau))
+        let mut integrator = BerendsenBarostat::new(timestep, pressure, tau);
+        if let Some(schedule) = schedule {
+            integrator.set_pressure_schedule(schedule);
+        }
+        if let Some(shake) = try!(read_shake(config, "Berendsen barostat")) {
+            integrator.set_shake(shake);
+        }
+        Ok(integrator)
     }
 }
 
 impl FromTomlWithData for AnisoBerendsenBarostat {
     type Data = f64;
     fn from_toml(config: &Table, timestep: f64) -> Result<AnisoBerendsenBarostat> {
-        let pressure = try!(extract::str("pressure", config, "anisotropic Berendsen barostat"));
-        let pressure = try!(units::from_str(pressure));
+        let (pressure, schedule) = try!(read_setpoint("pressure", config, "anisotropic Berendsen barostat"));
         let tau = try!(extract::number("timestep", config, "anisotropic Berendsen barostat"));
-        Ok(AnisoBerendsenBarostat::hydrostatic(timestep, pressure, tau))
+        let mut integrator = AnisoBerendsenBarostat::hydrostatic(timestep, pressure, tau);
+        if let Some(schedule) = schedule {
+            integrator.set_pressure_schedule(schedule);
+        }
+        if let Some(shake) = try!(read_shake(config, "anisotropic Berendsen barostat")) {
+            integrator.set_shake(shake);
+        }
+        Ok(integrator)
+    }
+}
+
+impl FromTomlWithData for MartynaTobiasKlein {
+    type Data = f64;
+    fn from_toml(config: &Table, timestep: f64) -> Result<MartynaTobiasKlein> {
+        let temperature = try!(extract::str("temperature", config, "Martyna-Tobias-Klein integrator"));
+        let temperature = try!(units::from_str(temperature));
+        let (pressure, schedule) = try!(read_setpoint("pressure", config, "Martyna-Tobias-Klein integrator"));
+        let tau_t = try!(extract::number("thermostat_timestep", config, "Martyna-Tobias-Klein integrator"));
+        let tau_p = try!(extract::number("barostat_timestep", config, "Martyna-Tobias-Klein integrator"));
+
+        let anisotropic = if let Some(anisotropic) = config.get("anisotropic") {
+            try!(anisotropic.as_bool().ok_or(Error::from(
+                "'anisotropic' must be a boolean in Martyna-Tobias-Klein integrator"
+            )))
+        } else {
+            false
+        };
+
+        let mut integrator = if anisotropic {
+            MartynaTobiasKlein::anisotropic(timestep, temperature, pressure, tau_t, tau_p)
+        } else {
+            MartynaTobiasKlein::isotropic(timestep, temperature, pressure, tau_t, tau_p)
+        };
+
+        if config.contains_key("chains") {
+            let chains = try!(extract::uint("chains", config, "Martyna-Tobias-Klein integrator"));
+            if chains == 0 {
+                return Err(Error::from(
+                    "'chains' must be strictly positive in Martyna-Tobias-Klein integrator"
+                ));
+            }
+            integrator.set_chain_length(chains as usize);
+        }
+
+        if let Some(schedule) = schedule {
+            integrator.set_pressure_schedule(schedule);
+        }
+
+        Ok(integrator)
     }
 }
 
 /******************************************************************************/
 
+/// Read a single thermostat from the `config` table
+fn read_thermostat(config: &Table, timestep: f64, root: &Path) -> Result<Box<Thermostat>> {
+    let thermostat: Box<Thermostat> = match try!(extract::typ(config, "thermostat")) {
+        "Berendsen" => {
+            let mut thermostat = try!(BerendsenThermostat::from_toml(config));
+            if let Some(selection) = try!(read_thermostat_selection(config, root)) {
+                thermostat.set_selection(selection);
+            }
+            Box::new(thermostat)
+        }
+        "Rescale" => {
+            let mut thermostat = try!(RescaleThermostat::from_toml(config));
+            if let Some(selection) = try!(read_thermostat_selection(config, root)) {
+                thermostat.set_selection(selection);
+            }
+            Box::new(thermostat)
+        }
+        "DPD" => {
+            if config.contains_key("selection") {
+                return Err(Error::from(
+                    "'selection' can not be used with DPD thermostat"
+                ));
+            }
+            Box::new(try!(DPDThermostat::from_toml(config, timestep)))
+        }
+        other => return Err(Error::from(
+            format!("Unknown thermostat type '{}'", other)
+        ))
+    };
+    Ok(thermostat)
+}
+
+/// Read the optional `selection` of particles controlled by a thermostat
+fn read_thermostat_selection(config: &Table, root: &Path) -> Result<Option<Selection>> {
+    if config.contains_key("selection") {
+        let selection = try!(extract::table("selection", config, "thermostat"));
+        Ok(Some(try!(read_selection(selection, root, "thermostat selection"))))
+    } else {
+        Ok(None)
+    }
+}
+
 impl FromToml for BerendsenThermostat {
     fn from_toml(config: &Table) -> Result<BerendsenThermostat> {
-        let temperature = try!(extract::str("temperature", config, "Berendsen thermostat"));
-        let temperature = try!(units::from_str(temperature));
+        let (temperature, schedule) = try!(read_setpoint("temperature", config, "Berendsen thermostat"));
         let tau = try!(extract::number("timestep", config, "Berendsen thermostat"));
-        Ok(BerendsenThermostat::new(temperature, tau))
+        let mut thermostat = BerendsenThermostat::new(temperature, tau);
+        if let Some(schedule) = schedule {
+            thermostat.set_temperature_schedule(schedule);
+        }
+        Ok(thermostat)
     }
 }
 
 impl FromToml for RescaleThermostat {
     fn from_toml(config: &Table) -> Result<RescaleThermostat> {
-        let temperature = try!(extract::str("temperature", config, "Berendsen thermostat"));
-        let temperature = try!(units::from_str(temperature));
+        let (temperature, schedule) = try!(read_setpoint("temperature", config, "rescale thermostat"));
 
-        if let Some(tolerance) = config.get("tolerance") {
+        let mut thermostat = if let Some(tolerance) = config.get("tolerance") {
             let tolerance = try!(tolerance.as_str().ok_or(
                 Error::from("'tolerance' must be a string rescale thermostat")
             ));
             let tolerance = try!(units::from_str(tolerance));
 
-            Ok(RescaleThermostat::with_tolerance(temperature, tolerance))
+            RescaleThermostat::with_tolerance(temperature, tolerance)
         } else {
-            Ok(RescaleThermostat::new(temperature))
+            RescaleThermostat::new(temperature)
+        };
+
+        if let Some(schedule) = schedule {
+            thermostat.set_temperature_schedule(schedule);
         }
+        Ok(thermostat)
+    }
+}
+
+impl FromTomlWithData for DPDThermostat {
+    type Data = f64;
+    fn from_toml(config: &Table, timestep: f64) -> Result<DPDThermostat> {
+        let context = "DPD thermostat";
+        let (temperature, gamma, cutoff) = try!(read_dpd_parameters(config, context));
+        let mut thermostat = DPDThermostat::new(timestep, temperature, gamma, cutoff);
+        if config.get("seed").is_some() {
+            let seed = try!(extract::uint("seed", config, context));
+            thermostat.seed(seed);
+        }
+        Ok(thermostat)
     }
 }
 
@@ -203,3 +525,53 @@ impl FromToml for Alternator<Rewrap> {
         Ok(Alternator::new(every, Rewrap::new()))
     }
 }
+
+impl FromToml for Alternator<MonteCarloBarostat> {
+    fn from_toml(config: &Table) -> Result<Alternator<MonteCarloBarostat>> {
+        let temperature = try!(extract::str("temperature", config, "Monte Carlo barostat"));
+        let temperature = try!(units::from_str(temperature));
+        let (pressure, schedule) = try!(read_setpoint("pressure", config, "Monte Carlo barostat"));
+        let delta = try!(extract::str("delta", config, "Monte Carlo barostat"));
+        let delta = try!(units::from_str(delta));
+
+        let anisotropic = if let Some(anisotropic) = config.get("anisotropic") {
+            try!(anisotropic.as_bool().ok_or(Error::from(
+                "'anisotropic' must be a boolean in Monte Carlo barostat"
+            )))
+        } else {
+            false
+        };
+
+        let mut barostat = if anisotropic {
+            MonteCarloBarostat::anisotropic(temperature, pressure, delta)
+        } else {
+            MonteCarloBarostat::new(temperature, pressure, delta)
+        };
+
+        if let Some(schedule) = schedule {
+            barostat.set_pressure_schedule(schedule);
+        }
+
+        if config.contains_key("target_acceptance") {
+            let target = try!(extract::number("target_acceptance", config, "Monte Carlo barostat"));
+            if target <= 0.0 || target >= 1.0 {
+                return Err(Error::from(
+                    "'target_acceptance' has to be between 0.0 and 1.0"
+                ));
+            }
+            barostat.set_target_acceptance(Some(target));
+        }
+
+        if config.contains_key("update_frequency") {
+            let frequency = try!(extract::uint("update_frequency", config, "Monte Carlo barostat"));
+            barostat.set_update_frequency(frequency);
+        }
+
+        let every = if config.contains_key("every") {
+            try!(extract::uint("every", config, "Monte Carlo barostat"))
+        } else {
+           1
+        };
+        Ok(Alternator::new(every, barostat))
+    }
+}
@@ -21,6 +21,11 @@ mod simulations;
 mod min;
 mod md;
 mod mc;
+mod pulling;
+mod pimd;
+mod replica;
+mod selection;
+mod schedule;
 
 /// A configuration about how to run a single simulation. This contains the
 /// system to simulate, the simulation itself and the number of steps to run
@@ -64,8 +69,17 @@ impl Input {
     /// Read input file and get the corresponding `Config`
     pub fn read(&self) -> Result<Config> {
         try!(self.setup_logging());
-        let system = try!(self.read_system());
-        let simulation = try!(self.read_simulation());
+        let mut system = try!(self.read_system());
+        let pulling = try!(self.read_pulling());
+        let simulation = try!(self.read_simulation_with_pulling(pulling.as_ref()));
+        if let Some(pulling) = pulling {
+            if pulling.particles().iter().any(|&i| i >= system.size()) {
+                return Err(Error::from(
+                    "'particles' contains out of bounds indexes in pulling"
+                ));
+            }
+            system.add_global_potential(Box::new(pulling));
+        }
         let nsteps = try!(self.read_nsteps());
 
         Ok(Config {
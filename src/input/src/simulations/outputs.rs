@@ -5,27 +5,33 @@ use std::path::PathBuf;
 
 use lumol::out::Output;
 use lumol::out::{TrajectoryOutput, CellOutput, EnergyOutput, PropertiesOutput};
-use lumol::out::CustomOutput;
+use lumol::out::{CustomOutput, StressOutput, PullingOutput, GroupTemperatureOutput};
+use lumol::out::{PathIntegralOutput, WidomOutput};
+use lumol::energy::Pulling;
+use lumol::sim::md::CentroidVirial;
+use lumol::units;
 
 use error::{Error, Result};
 use FromToml;
 use extract;
 use super::Input;
+use super::selection::read_selection;
+use super::mc::read_exchanged_molecule;
 
 impl Input {
-    /// Get the the simulation outputs.
-    pub(crate) fn read_outputs(&self) -> Result<Vec<(Box<Output>, u64)>> {
-        let config = try!(self.simulation_table());
+    /// Get the outputs from the `outputs` key in `config`, which is the
+    /// table described by `context`.
+    pub(crate) fn read_outputs(&self, config: &Table, context: &str, pulling: Option<&Pulling>, estimator: Option<&CentroidVirial>) -> Result<Vec<(Box<Output>, u64)>> {
         if let Some(outputs) = config.get("outputs") {
-            let outputs = try!(outputs.as_array().ok_or(
-                Error::from("'outputs' must be an array of tables in simulation")
-            ));
+            let outputs = try!(outputs.as_array().ok_or(Error::from(
+                format!("'outputs' must be an array of tables in {}", context)
+            )));
 
             let mut result = Vec::new();
             for output in outputs {
-                let output = try!(output.as_table().ok_or(
-                    Error::from("'outputs' must be an array of tables in simulation")
-                ));
+                let output = try!(output.as_table().ok_or(Error::from(
+                    format!("'outputs' must be an array of tables in {}", context)
+                )));
 
                 let frequency = match output.get("frequency") {
                     Some(frequency) => {
@@ -42,7 +48,28 @@ impl Input {
                     "energy" => Box::new(try!(EnergyOutput::from_toml(output))),
                     "cell" => Box::new(try!(CellOutput::from_toml(output))),
                     "properties" => Box::new(try!(PropertiesOutput::from_toml(output))),
+                    "stress" => Box::new(try!(StressOutput::from_toml(output))),
                     "custom" => Box::new(try!(CustomOutput::from_toml(output))),
+                    "pulling" => {
+                        let pulling = try!(pulling.ok_or(Error::from(
+                            "'Pulling' output needs a pulling potential in molecular dynamics"
+                        )));
+                        let path = try!(get_file(output));
+                        Box::new(try_io!(
+                            PullingOutput::new(path, pulling.clone()), PathBuf::from(path)
+                        ))
+                    }
+                    "pathintegral" => {
+                        let estimator = try!(estimator.ok_or(Error::from(
+                            "'PathIntegral' output needs a path integral propagator"
+                        )));
+                        let path = try!(get_file(output));
+                        Box::new(try_io!(
+                            PathIntegralOutput::new(path, estimator.clone()), PathBuf::from(path)
+                        ))
+                    }
+                    "grouptemperature" => Box::new(try!(self.read_group_output(output))),
+                    "widom" => Box::new(try!(self.read_widom_output(output))),
                     other => {
                         return Err(Error::from(
                             format!("Unknown output type '{}'", other)
@@ -57,6 +84,49 @@ impl Input {
             Ok(Vec::new())
         }
     }
+
+    /// Read a `GroupTemperature` output from the `config` table
+    fn read_group_output(&self, config: &Table) -> Result<GroupTemperatureOutput> {
+        let path = try!(get_file(config));
+        let groups = try!(extract::slice("groups", config, "GroupTemperature output"));
+        if groups.is_empty() {
+            return Err(Error::from("'groups' can not be empty in GroupTemperature output"));
+        }
+
+        let mut selections = Vec::new();
+        for group in groups {
+            let group = try!(group.as_table().ok_or(Error::from(
+                "'groups' must be an array of tables in GroupTemperature output"
+            )));
+            selections.push(try!(read_selection(group, &self.path, "GroupTemperature output")));
+        }
+
+        let output = try_io!(GroupTemperatureOutput::new(path, selections), PathBuf::from(path));
+        Ok(output)
+    }
+
+    /// Read a `Widom` output from the `config` table
+    fn read_widom_output(&self, config: &Table) -> Result<WidomOutput> {
+        let path = try!(get_file(config));
+        let (molecule, particles) = try!(read_exchanged_molecule(config, self.path.clone(), "Widom output"));
+
+        let temperature = try!(extract::str("temperature", config, "Widom output"));
+        let temperature = try!(units::from_str(temperature));
+        if temperature <= 0.0 {
+            return Err(Error::from("'temperature' must be positive in Widom output"));
+        }
+
+        let insertions = try!(extract::uint("insertions", config, "Widom output"));
+        if insertions == 0 {
+            return Err(Error::from("'insertions' must be positive in Widom output"));
+        }
+
+        let output = try_io!(
+            WidomOutput::new(path, molecule, particles, temperature, insertions as usize),
+            PathBuf::from(path)
+        );
+        Ok(output)
+    }
 }
 
 fn get_file(config: &Table) -> Result<&str> {
@@ -101,6 +171,14 @@ impl FromToml for PropertiesOutput {
     }
 }
 
+impl FromToml for StressOutput {
+    fn from_toml(config: &Table) -> Result<StressOutput> {
+        let path = try!(get_file(config));
+        let output = try_io!(StressOutput::new(path), PathBuf::from(path));
+        Ok(output)
+    }
+}
+
 impl FromToml for CustomOutput {
     fn from_toml(config: &Table) -> Result<CustomOutput> {
         let path = try!(get_file(config));
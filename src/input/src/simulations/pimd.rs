@@ -0,0 +1,45 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use toml::value::Table;
+
+use lumol::sim::PathIntegral;
+use lumol::units;
+
+use error::{Error, Result};
+use FromToml;
+use extract;
+
+static CONTEXT: &'static str = "path integral propagator";
+
+impl FromToml for PathIntegral {
+    fn from_toml(config: &Table) -> Result<PathIntegral> {
+        let timestep = try!(extract::str("timestep", config, CONTEXT));
+        let timestep = try!(units::from_str(timestep));
+        let temperature = try!(extract::str("temperature", config, CONTEXT));
+        let temperature = try!(units::from_str(temperature));
+        if temperature <= 0.0 {
+            return Err(Error::from("'temperature' must be positive in path integral propagator"));
+        }
+        let beads = try!(extract::uint("beads", config, CONTEXT));
+        if beads == 0 {
+            return Err(Error::from("'beads' can not be 0 in path integral propagator"));
+        }
+
+        let mut propagator = PathIntegral::new(timestep, temperature, beads as usize);
+        if config.contains_key("centroid_relaxation") {
+            let tau = try!(extract::str("centroid_relaxation", config, CONTEXT));
+            let tau = try!(units::from_str(tau));
+            if tau <= 0.0 {
+                return Err(Error::from(
+                    "'centroid_relaxation' must be positive in path integral propagator"
+                ));
+            }
+            propagator.set_centroid_relaxation(tau);
+        }
+        if config.contains_key("seed") {
+            let seed = try!(extract::uint("seed", config, CONTEXT));
+            propagator.seed(seed);
+        }
+        Ok(propagator)
+    }
+}
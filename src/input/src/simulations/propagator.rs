@@ -1,6 +1,10 @@
 // Lumol, an extensible molecular simulation engine
 // Copyright (C) Lumol's contributors — BSD license
-use lumol::sim::{Propagator, MolecularDynamics, MonteCarlo, Minimization};
+use lumol::sim::{Propagator, MolecularDynamics, MonteCarlo, Minimization, PathIntegral};
+use lumol::sim::{GibbsEnsemble, WangLandau};
+use lumol::sim::md::{Steering, CentroidVirial};
+use lumol::energy::Pulling;
+use lumol::units;
 
 use error::{Error, Result};
 use {FromToml, FromTomlWithData};
@@ -8,23 +12,71 @@ use extract;
 use super::Input;
 
 impl Input {
-    /// Get the the simulation propagator.
-    pub(crate) fn read_propagator(&self) -> Result<Box<Propagator>> {
+    /// Get the the simulation propagator, and the kinetic energy estimator
+    /// for path integral simulations.
+    pub(crate) fn read_propagator(&self, pulling: Option<&Pulling>) -> Result<(Box<Propagator>, Option<CentroidVirial>)> {
         let config = try!(self.simulation_table());
         let propagator = try!(extract::table("propagator", config, "simulation"));
         match try!(extract::typ(propagator, "propagator")) {
-            "MolecularDynamics" => Ok(Box::new(try!(
-                MolecularDynamics::from_toml(propagator)
-            ))),
-            "MonteCarlo" => Ok(Box::new(try!(
+            "MolecularDynamics" => {
+                let mut md = try!(MolecularDynamics::from_toml(propagator, self.path.clone()));
+                if let Some(pulling) = pulling {
+                    let timestep = try!(extract::str("timestep", propagator, "molecular dynamics propagator"));
+                    let timestep = try!(units::from_str(timestep));
+                    md.add_control(Box::new(Steering::new(pulling.clone(), timestep)));
+                }
+                Ok((Box::new(md), None))
+            }
+            "PathIntegral" => {
+                let pimd = try!(PathIntegral::from_toml(propagator));
+                let estimator = pimd.estimator();
+                Ok((Box::new(pimd), Some(estimator)))
+            }
+            "MonteCarlo" => Ok((Box::new(try!(
                 MonteCarlo::from_toml(propagator, self.path.clone())
-            ))),
-            "Minimization" => Ok(Box::new(try!(
+            )), None)),
+            "GibbsEnsemble" => {
+                if try!(self.systems_count()) != 2 {
+                    return Err(Error::from(
+                        "Gibbs ensemble simulations need exactly two systems in input file"
+                    ));
+                }
+                let second = try!(self.read_system_at(1));
+                let mut gibbs = try!(GibbsEnsemble::from_toml(propagator, (self.path.clone(), second)));
+                let outputs = try!(self.read_outputs(propagator, "Gibbs ensemble propagator", None, None));
+                for (output, frequency) in outputs {
+                    gibbs.add_output_with_frequency(output, frequency);
+                }
+                Ok((Box::new(gibbs), None))
+            }
+            "ReplicaExchange" => {
+                if pulling.is_some() {
+                    return Err(Error::from(
+                        "Pulling can not be used with replica exchange propagator"
+                    ));
+                }
+                let replica_exchange = try!(self.read_replica_exchange(propagator));
+                Ok((Box::new(replica_exchange), None))
+            }
+            "WangLandau" => Ok((Box::new(try!(
+                WangLandau::from_toml(propagator, self.path.clone())
+            )), None)),
+            "Minimization" => Ok((Box::new(try!(
                 Minimization::from_toml(propagator)
-            ))),
+            )), None)),
             other => Err(Error::from(
                 format!("Unknown propagator type '{}'", other)
             ))
         }
     }
+
+    /// Check if the simulation uses a Gibbs ensemble propagator, which
+    /// needs two systems.
+    pub(crate) fn is_gibbs_ensemble(&self) -> bool {
+        let typ = self.simulation_table().ok()
+                      .and_then(|config| config.get("propagator"))
+                      .and_then(|propagator| propagator.get("type"))
+                      .and_then(|typ| typ.as_str());
+        typ == Some("GibbsEnsemble")
+    }
 }
@@ -0,0 +1,102 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use toml::value::{Table, Value};
+
+use lumol::energy::Pulling;
+use lumol::types::{Vector3D, Zero};
+use lumol::units;
+
+use error::{Error, Result};
+use extract;
+use super::Input;
+
+static CONTEXT: &'static str = "pulling";
+
+impl Input {
+    /// Get the pulling potential for steered molecular dynamics, if any.
+    pub(crate) fn read_pulling(&self) -> Result<Option<Pulling>> {
+        let config = try!(self.simulation_table());
+        let propagator = try!(extract::table("propagator", config, "simulation"));
+        if let Some(pulling) = propagator.get("pulling") {
+            if try!(extract::typ(propagator, "propagator")) != "MolecularDynamics" {
+                return Err(Error::from(
+                    "'pulling' can only be used with molecular dynamics"
+                ));
+            }
+
+            let pulling = try!(pulling.as_table().ok_or(
+                Error::from("'pulling' must be a table in molecular dynamics")
+            ));
+            Ok(Some(try!(read_pulling(pulling))))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+fn read_pulling(config: &Table) -> Result<Pulling> {
+    let mut particles = Vec::new();
+    for particle in try!(extract::slice("particles", config, CONTEXT)) {
+        match *particle {
+            Value::Integer(i) if i >= 0 => particles.push(i as usize),
+            _ => return Err(Error::from(
+                "'particles' must be an array of positive integers in pulling"
+            ))
+        }
+    }
+    if particles.is_empty() {
+        return Err(Error::from("'particles' can not be empty in pulling"));
+    }
+
+    let direction = try!(read_direction(config));
+    if direction.norm2() == 0.0 {
+        return Err(Error::from("'direction' can not be a null vector in pulling"));
+    }
+
+    if config.get("force").is_some() {
+        if config.get("k").is_some() || config.get("velocity").is_some() {
+            return Err(Error::from(
+                "can not use both 'force' and a spring in pulling"
+            ));
+        }
+        let force = try!(extract::str("force", config, CONTEXT));
+        let force = try!(units::from_str(force));
+        Ok(Pulling::constant_force(particles, direction, force))
+    } else {
+        let k = try!(extract::str("k", config, CONTEXT));
+        let k = try!(units::from_str(k));
+        if k < 0.0 {
+            return Err(Error::from("'k' must be positive in pulling"));
+        }
+        let velocity = try!(extract::str("velocity", config, CONTEXT));
+        let velocity = try!(units::from_str(velocity));
+
+        let mut pulling = Pulling::spring(particles, direction, k, velocity);
+        if config.get("anchor").is_some() {
+            let anchor = try!(extract::str("anchor", config, CONTEXT));
+            pulling.set_anchor(try!(units::from_str(anchor)));
+        }
+        Ok(pulling)
+    }
+}
+
+fn read_direction(config: &Table) -> Result<Vector3D> {
+    let direction = try!(extract::slice("direction", config, CONTEXT));
+    if direction.len() != 3 {
+        return Err(Error::from(
+            "'direction' must be an array of 3 numbers in pulling"
+        ));
+    }
+
+    let mut vector = Vector3D::zero();
+    for (i, value) in direction.iter().enumerate() {
+        vector[i] = match *value {
+            Value::Integer(v) => v as f64,
+            Value::Float(v) => v,
+            _ => return Err(Error::from(
+                "'direction' must be an array of 3 numbers in pulling"
+            ))
+        };
+    }
+    Ok(vector)
+}
@@ -0,0 +1,75 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use toml::value::Table;
+use std::path::PathBuf;
+
+use lumol::sim::{Propagator, ReplicaExchange, MolecularDynamics, MonteCarlo};
+use lumol::units;
+
+use error::{Error, Result};
+use FromTomlWithData;
+use extract;
+use super::Input;
+
+impl Input {
+    /// Get the replica exchange propagator described by `config`. All the
+    /// replicas start from the input system.
+    pub(crate) fn read_replica_exchange(&self, config: &Table) -> Result<ReplicaExchange> {
+        let frequency = try!(extract::uint("frequency", config, "replica exchange propagator"));
+        if frequency == 0 {
+            return Err(Error::from("'frequency' must be positive in replica exchange propagator"));
+        }
+        let mut replica_exchange = ReplicaExchange::new(frequency);
+
+        if config.get("exchanges").is_some() {
+            let exchanges = try!(extract::str("exchanges", config, "replica exchange propagator"));
+            try_io!(replica_exchange.set_exchanges_file(exchanges), PathBuf::from(exchanges));
+        }
+
+        let replicas = try!(extract::slice("replicas", config, "replica exchange propagator"));
+        if replicas.is_empty() {
+            return Err(Error::from("'replicas' can not be empty in replica exchange propagator"));
+        }
+
+        let system = try!(self.read_system());
+        let mut previous = 0.0;
+        for (i, replica) in replicas.iter().enumerate() {
+            let replica = try!(replica.as_table().ok_or(
+                Error::from("'replicas' must be an array of tables in replica exchange propagator")
+            ));
+
+            let temperature = try!(extract::str("temperature", replica, "replica exchange replica"));
+            let temperature = try!(units::from_str(temperature));
+            if temperature <= 0.0 {
+                return Err(Error::from("'temperature' must be positive in replica exchange replica"));
+            }
+            if temperature <= previous {
+                return Err(Error::from(
+                    "Replicas must be sorted by increasing temperature in replica exchange propagator"
+                ));
+            }
+            previous = temperature;
+
+            let propagator = try!(extract::table("propagator", replica, "replica exchange replica"));
+            let typ = try!(extract::typ(propagator, "replica exchange replica propagator"));
+            let propagator: Box<Propagator + Send> = match typ {
+                "MolecularDynamics" => Box::new(try!(
+                    MolecularDynamics::from_toml(propagator, self.path.clone())
+                )),
+                "MonteCarlo" => Box::new(try!(
+                    MonteCarlo::from_toml(propagator, self.path.clone())
+                )),
+                other => return Err(Error::from(
+                    format!("Unknown propagator type '{}' in replica exchange replica", other)
+                )),
+            };
+            replica_exchange.add(system.clone(), propagator, temperature);
+
+            for (output, frequency) in try!(self.read_outputs(replica, "replica exchange replica", None, None)) {
+                replica_exchange.add_temperature_output(i, output, frequency);
+            }
+        }
+
+        return Ok(replica_exchange);
+    }
+}
@@ -0,0 +1,107 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use toml::value::{Table, Value};
+
+use lumol::sim::Schedule;
+use lumol::units;
+
+use error::{Error, Result};
+use extract;
+
+/// Read a parameter at the given `key` in `config`, which can either be a
+/// constant string with units, or a table describing a schedule. Return the
+/// initial value of the parameter and the schedule, if any.
+pub(crate) fn read_setpoint(key: &str, config: &Table, context: &str) -> Result<(f64, Option<Schedule>)> {
+    let value = try!(config.get(key).ok_or(Error::from(
+        format!("Missing '{}' key in {}", key, context)
+    )));
+
+    match *value {
+        Value::String(ref value) => Ok((try!(units::from_str(value)), None)),
+        Value::Table(ref table) => {
+            let context = format!("{} {} schedule", context, key);
+            let schedule = try!(read_schedule(table, &context));
+            Ok((schedule.value(0), Some(schedule)))
+        }
+        _ => Err(Error::from(
+            format!("'{}' must be a string or a table in {}", key, context)
+        ))
+    }
+}
+
+/// Read a schedule from the `config` table. The schedule contains either a
+/// `linear` or an `exponential` key with the start and end values and a
+/// `steps` key with the duration; or `at` and `values` keys with a list of
+/// steps and the corresponding values.
+fn read_schedule(config: &Table, context: &str) -> Result<Schedule> {
+    if config.contains_key("linear") {
+        let (start, end, steps) = try!(read_interpolation("linear", config, context));
+        Ok(Schedule::linear(start, end, steps))
+    } else if config.contains_key("exponential") {
+        let (start, end, steps) = try!(read_interpolation("exponential", config, context));
+        if start <= 0.0 || end <= 0.0 {
+            return Err(Error::from(
+                format!("'exponential' values must be strictly positive in {}", context)
+            ));
+        }
+        Ok(Schedule::exponential(start, end, steps))
+    } else if config.contains_key("values") {
+        let values = try!(read_values("values", config, context));
+        let at = try!(extract::slice("at", config, context));
+        if at.len() != values.len() {
+            return Err(Error::from(
+                format!("'at' and 'values' must have the same size in {}", context)
+            ));
+        }
+        if values.is_empty() {
+            return Err(Error::from(format!("'values' can not be empty in {}", context)));
+        }
+
+        let mut steps = Vec::new();
+        for (step, value) in at.iter().zip(values) {
+            let step = match *step {
+                Value::Integer(step) if step >= 0 => step as u64,
+                _ => return Err(Error::from(
+                    format!("'at' must be an array of positive integers in {}", context)
+                )),
+            };
+            steps.push((step, value));
+        }
+        Ok(Schedule::steps(steps))
+    } else {
+        Err(Error::from(
+            format!("Missing 'linear', 'exponential' or 'values' key in {}", context)
+        ))
+    }
+}
+
+/// Read the start value, end value and number of steps of an interpolating
+/// schedule.
+fn read_interpolation(key: &str, config: &Table, context: &str) -> Result<(f64, f64, u64)> {
+    let values = try!(read_values(key, config, context));
+    if values.len() != 2 {
+        return Err(Error::from(
+            format!("'{}' must be an array of two strings in {}", key, context)
+        ));
+    }
+
+    let steps = try!(extract::uint("steps", config, context));
+    if steps == 0 {
+        return Err(Error::from(
+            format!("'steps' must be strictly positive in {}", context)
+        ));
+    }
+    Ok((values[0], values[1], steps))
+}
+
+/// Read an array of strings with units at the given `key`
+fn read_values(key: &str, config: &Table, context: &str) -> Result<Vec<f64>> {
+    let mut values = Vec::new();
+    for value in try!(extract::slice(key, config, context)) {
+        let value = try!(value.as_str().ok_or(Error::from(
+            format!("'{}' must be an array of strings in {}", key, context)
+        )));
+        values.push(try!(units::from_str(value)));
+    }
+    Ok(values)
+}
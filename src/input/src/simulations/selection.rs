@@ -0,0 +1,89 @@
+// Lumol, an extensible molecular simulation engine
+// Copyright (C) Lumol's contributors — BSD license
+use toml::value::{Table, Value};
+use std::path::Path;
+
+use lumol::sys::{Selection, read_molecule, molecule_type};
+use lumol::types::{Vector3D, Zero};
+
+use error::{Error, Result};
+use extract;
+use super::get_input_path;
+
+/// Read a selection of particles from the `config` table. The selection
+/// contains one of the `molecule`, `atoms`, `range` or `region` keys. The
+/// `molecule` files are searched relatively to `root`.
+pub(crate) fn read_selection(config: &Table, root: &Path, context: &str) -> Result<Selection> {
+    if config.contains_key("molecule") {
+        let molfile = try!(extract::str("molecule", config, context));
+        let molfile = get_input_path(root, molfile);
+        let (molecule, atoms) = try!(read_molecule(molfile));
+        Ok(Selection::Molecules(molecule_type(&molecule, atoms.as_slice())))
+    } else if config.contains_key("atoms") {
+        let mut names = Vec::new();
+        for name in try!(extract::slice("atoms", config, context)) {
+            let name = try!(name.as_str().ok_or(Error::from(
+                format!("'atoms' must be an array of strings in {}", context)
+            )));
+            names.push(String::from(name));
+        }
+        Ok(Selection::Names(names))
+    } else if config.contains_key("range") {
+        let range = try!(extract::slice("range", config, context));
+        let error = || Error::from(
+            format!("'range' must be an array of two positive integers in {}", context)
+        );
+        if range.len() != 2 {
+            return Err(error());
+        }
+        let start = match range[0] {
+            Value::Integer(start) if start >= 0 => start as usize,
+            _ => return Err(error()),
+        };
+        let end = match range[1] {
+            Value::Integer(end) if end >= 0 => end as usize,
+            _ => return Err(error()),
+        };
+        if start >= end {
+            return Err(Error::from(
+                format!("'range' must not be empty in {}", context)
+            ));
+        }
+        Ok(Selection::Range(start..end))
+    } else if config.contains_key("region") {
+        let region = try!(extract::table("region", config, context));
+        let lower = try!(read_corner("lower", region, context));
+        let upper = try!(read_corner("upper", region, context));
+        if (0..3).any(|i| lower[i] >= upper[i]) {
+            return Err(Error::from(
+                format!("'lower' must be smaller than 'upper' in {} region", context)
+            ));
+        }
+        Ok(Selection::Region{lower: lower, upper: upper})
+    } else {
+        Err(Error::from(format!(
+            "Missing 'molecule', 'atoms', 'range' or 'region' key in {}", context
+        )))
+    }
+}
+
+/// Read a corner of a selection region, as an array of 3 numbers in Angstrom
+fn read_corner(key: &str, config: &Table, context: &str) -> Result<Vector3D> {
+    let corner = try!(extract::slice(key, config, context));
+    let error = || Error::from(
+        format!("'{}' must be an array of 3 numbers in {} region", key, context)
+    );
+    if corner.len() != 3 {
+        return Err(error());
+    }
+
+    let mut vector = Vector3D::zero();
+    for (i, value) in corner.iter().enumerate() {
+        vector[i] = match *value {
+            Value::Integer(v) => v as f64,
+            Value::Float(v) => v,
+            _ => return Err(error()),
+        };
+    }
+    Ok(vector)
+}
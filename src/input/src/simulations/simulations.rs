@@ -2,6 +2,7 @@
 // Copyright (C) Lumol's contributors — BSD license
 use toml::value::Table;
 use lumol::sim::Simulation;
+use lumol::energy::Pulling;
 
 use error::{Error, Result};
 use extract;
@@ -9,10 +10,22 @@ use super::Input;
 
 impl Input {
     /// Get the the simulation.
+    ///
+    /// If the simulation uses a pulling potential, the potential is not added
+    /// to any system. Use `Input::read` to get both the system and the
+    /// simulation.
     pub fn read_simulation(&self) -> Result<Simulation> {
-        let propagator = try!(self.read_propagator());
+        let pulling = try!(self.read_pulling());
+        self.read_simulation_with_pulling(pulling.as_ref())
+    }
+
+    /// Get the the simulation, using the given `pulling` potential for steered
+    /// molecular dynamics.
+    pub(crate) fn read_simulation_with_pulling(&self, pulling: Option<&Pulling>) -> Result<Simulation> {
+        let (propagator, estimator) = try!(self.read_propagator(pulling));
         let mut simulation = Simulation::new(propagator);
-        for (output, frequency) in try!(self.read_outputs()) {
+        let config = try!(self.simulation_table());
+        for (output, frequency) in try!(self.read_outputs(config, "simulation", pulling, estimator.as_ref())) {
             simulation.add_output_with_frequency(output, frequency);
         }
 
@@ -14,13 +14,27 @@ use simulations::get_input_path;
 impl Input {
     /// Get the the simulated system.
     pub fn read_system(&self) -> Result<System> {
-        let config = try!(self.system_table());
+        if try!(self.systems_count()) > 1 && !self.is_gibbs_ensemble() {
+            return Err(Error::from("Only one system is supported in input file"));
+        }
+        self.read_system_at(0)
+    }
+
+    /// Get the number of systems in the input file.
+    pub(crate) fn systems_count(&self) -> Result<usize> {
+        let systems = try!(extract::slice("systems", &self.config, "input file"));
+        Ok(systems.len())
+    }
+
+    /// Get the simulated system at the given `index` in the `systems` array.
+    pub(crate) fn read_system_at(&self, index: usize) -> Result<System> {
+        let config = try!(self.system_table(index));
 
         let file = try!(extract::str("file", config, "system"));
         let file = get_input_path(&self.path, file);
         let mut trajectory = try!(TrajectoryBuilder::new().open(file));
 
-        let with_cell = if let Some(cell) = try!(self.read_cell()) {
+        let with_cell = if let Some(cell) = try!(self.read_cell(config)) {
             try!(trajectory.set_cell(&cell));
             true
         } else {
@@ -44,8 +58,10 @@ impl Input {
             try!(trajectory.read())
         };
 
-        try!(self.read_potentials(&mut system));
-        try!(self.init_velocities(&mut system));
+        try!(self.read_potentials(config, &mut system));
+        try!(self.read_constraints(config, &mut system));
+        try!(self.read_frozen(config, &mut system));
+        try!(self.init_velocities(config, &mut system));
 
         if !with_cell && system.cell.is_infinite() {
             warn!(
@@ -58,26 +74,25 @@ impl Input {
         Ok(system)
     }
 
-    fn system_table(&self) -> Result<&Table> {
+    fn system_table(&self, index: usize) -> Result<&Table> {
         let systems = try!(extract::slice("systems", &self.config, "input file"));
 
         if systems.is_empty() {
             return Err(Error::from("'systems' array should contain a system"));
         }
 
-        if systems.len() > 1 {
-            return Err(Error::from("Only one system is supported in input file"));
-        }
+        let system = try!(systems.get(index).ok_or(Error::from(
+            format!("Missing system at index {} in input file", index)
+        )));
 
-        let system = try!(systems[0].as_table().ok_or(
+        let system = try!(system.as_table().ok_or(
             Error::from("'systems' should be an array of tables in input file")
         ));
 
         return Ok(system);
     }
 
-    fn read_cell(&self) -> Result<Option<UnitCell>> {
-        let config = try!(self.system_table());
+    fn read_cell(&self, config: &Table) -> Result<Option<UnitCell>> {
         if let Some(cell) = config.get("cell") {
             match *cell {
                 Value::Array(ref cell) => {
@@ -116,8 +131,7 @@ impl Input {
         }
     }
 
-    fn init_velocities(&self, system: &mut System) -> Result<()> {
-        let config = try!(self.system_table());
+    fn init_velocities(&self, config: &Table, system: &mut System) -> Result<()> {
 
         if let Some(velocities) = config.get("velocities") {
             let velocities = try!(velocities.as_table().ok_or(
@@ -137,8 +151,7 @@ impl Input {
         Ok(())
     }
 
-    fn read_potentials(&self, system: &mut System) -> Result<()> {
-        let config = try!(self.system_table());
+    fn read_potentials(&self, config: &Table, system: &mut System) -> Result<()> {
         if let Some(potentials) = config.get("potentials") {
             if let Some(potentials) = potentials.as_str() {
                 let path = get_input_path(&self.path, potentials);
@@ -155,6 +168,188 @@ impl Input {
         }
         Ok(())
     }
+
+    fn read_constraints(&self, config: &Table, system: &mut System) -> Result<()> {
+        let constraints = if let Some(constraints) = config.get("constraints") {
+            try!(constraints.as_array().ok_or(
+                Error::from("'constraints' must be an array of tables in system")
+            ))
+        } else {
+            return Ok(());
+        };
+
+        for constraint in constraints {
+            let constraint = try!(constraint.as_table().ok_or(
+                Error::from("'constraints' must be an array of tables in system")
+            ));
+
+            match try!(extract::typ(constraint, "constraint")) {
+                "Bonds" => try!(self.read_bonds_constraint(constraint, system)),
+                "Rigid" => try!(self.read_rigid_constraint(constraint, system)),
+                other => return Err(Error::from(
+                    format!("Unknown constraint type '{}'", other)
+                )),
+            }
+        }
+        Ok(())
+    }
+
+    /// Add distance constraints for the bonds selected by `config` in the
+    /// `system`.
+    fn read_bonds_constraint(&self, config: &Table, system: &mut System) -> Result<()> {
+        let atom = if config.contains_key("atom") {
+            Some(try!(extract::str("atom", config, "bonds constraint")))
+        } else {
+            None
+        };
+
+        let moltype = try!(self.read_constraint_moltype(config, "bonds constraint"));
+
+        let distance = if config.contains_key("distance") {
+            let distance = try!(extract::str("distance", config, "bonds constraint"));
+            Some(try!(units::from_str(distance)))
+        } else {
+            None
+        };
+
+        let mut bonds = Vec::new();
+        for (molid, molecule) in system.molecules().iter().enumerate() {
+            if let Some(moltype) = moltype {
+                if system.molecule_type(molid) != moltype {
+                    continue;
+                }
+            }
+
+            for bond in molecule.bonds() {
+                let (i, j) = (bond.i(), bond.j());
+                if let Some(atom) = atom {
+                    let names = system.particles().name;
+                    if names[i] != atom && names[j] != atom {
+                        continue;
+                    }
+                }
+                bonds.push((i, j));
+            }
+        }
+
+        if bonds.is_empty() {
+            warn!("No bond matches a constraint in the system");
+        }
+
+        for (i, j) in bonds {
+            let distance = distance.unwrap_or_else(|| system.distance(i, j));
+            system.add_constraint(i, j, distance);
+        }
+        Ok(())
+    }
+
+    /// Add distance constraints between all the pairs of particles in the
+    /// molecules selected by `config`, making these molecules rigid.
+    fn read_rigid_constraint(&self, config: &Table, system: &mut System) -> Result<()> {
+        let moltype = try!(self.read_constraint_moltype(config, "rigid constraint"));
+
+        let mut pairs = Vec::new();
+        for (molid, molecule) in system.molecules().iter().enumerate() {
+            if let Some(moltype) = moltype {
+                if system.molecule_type(molid) != moltype {
+                    continue;
+                }
+            }
+
+            if molecule.size() > 3 {
+                return Err(Error::from(
+                    "rigid constraints can only be used with molecules containing at most three particles"
+                ));
+            }
+
+            for i in molecule.iter() {
+                for j in (i + 1)..molecule.end() {
+                    pairs.push((i, j));
+                }
+            }
+        }
+
+        if pairs.is_empty() {
+            warn!("No molecule matches a rigid constraint in the system");
+        }
+
+        for (i, j) in pairs {
+            let distance = system.distance(i, j);
+            system.add_constraint(i, j, distance);
+        }
+        Ok(())
+    }
+
+    /// Freeze the particles selected by the `frozen` key in the `system`
+    fn read_frozen(&self, config: &Table, system: &mut System) -> Result<()> {
+        let selections = if let Some(frozen) = config.get("frozen") {
+            try!(frozen.as_array().ok_or(
+                Error::from("'frozen' must be an array of tables in system")
+            ))
+        } else {
+            return Ok(());
+        };
+
+        for selection in selections {
+            let selection = try!(selection.as_table().ok_or(
+                Error::from("'frozen' must be an array of tables in system")
+            ));
+
+            let mut particles = Vec::new();
+            if selection.contains_key("particles") {
+                for particle in try!(extract::slice("particles", selection, "frozen selection")) {
+                    match *particle {
+                        Value::Integer(i) if i >= 0 => particles.push(i as usize),
+                        _ => return Err(Error::from(
+                            "'particles' must be an array of positive integers in frozen selection"
+                        ))
+                    }
+                }
+                if particles.iter().any(|&i| i >= system.size()) {
+                    return Err(Error::from(
+                        "'particles' contains out of bounds indexes in frozen selection"
+                    ));
+                }
+            } else if selection.contains_key("atom") {
+                let atom = try!(extract::str("atom", selection, "frozen selection"));
+                let names = system.particles().name;
+                particles.extend((0..system.size()).filter(|&i| names[i] == atom));
+            } else if selection.contains_key("molecule") {
+                let moltype = try!(self.read_constraint_moltype(selection, "frozen selection"));
+                for (molid, molecule) in system.molecules().iter().enumerate() {
+                    if Some(system.molecule_type(molid)) == moltype {
+                        particles.extend(molecule.iter());
+                    }
+                }
+            } else {
+                return Err(Error::from(
+                    "frozen selection must contain one of 'particles', 'atom' or 'molecule' keys"
+                ));
+            }
+
+            if particles.is_empty() {
+                warn!("No particle matches a frozen selection in the system");
+            }
+
+            for i in particles {
+                system.set_frozen(i, true);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the type of the molecule in the file given by the `molecule` key
+    /// in `config`, if any.
+    fn read_constraint_moltype(&self, config: &Table, context: &str) -> Result<Option<u64>> {
+        if config.contains_key("molecule") {
+            let molfile = try!(extract::str("molecule", config, context));
+            let molfile = get_input_path(&self.path, molfile);
+            let (molecule, atoms) = try!(read_molecule(molfile));
+            Ok(Some(molecule_type(&molecule, atoms.as_slice())))
+        } else {
+            Ok(None)
+        }
+    }
 }
 
 fn get_cell_number(value: &Value) -> Result<f64> {
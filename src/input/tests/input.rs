@@ -135,7 +135,8 @@ impl Drop for TestsCleanup {
     fn drop(&mut self) {
         const REMOVE: &'static [&'static str] = &[
             "energy.dat", "filename.xyz", "cell.dat", "properties.dat",
-            "file.log", "custom.dat"
+            "file.log", "custom.dat", "groups.dat", "pimd.dat", "gibbs-cell.dat",
+            "widom.dat", "wang-landau.dat", "exchanges.dat"
         ];
 
         for file in REMOVE {
@@ -44,3 +44,24 @@ fn constant_energy() {
     let e_final = config.system.total_energy();
     assert!(f64::abs((e_initial - e_final) / e_final) < 1e-3);
 }
+
+#[test]
+fn constant_energy_constraints() {
+    START.call_once(|| {env_logger::init().unwrap();});
+    let path = Path::new(file!()).parent().unwrap()
+                                 .join("data")
+                                 .join("md-butane")
+                                 .join("nve-constraints.toml");
+    let mut config = Input::new(path).unwrap().read().unwrap();
+    assert_eq!(config.system.constraints().len(), 150);
+
+    let e_initial = config.system.total_energy();
+    config.simulation.run(&mut config.system, config.nsteps);
+    let e_final = config.system.total_energy();
+    assert!(f64::abs((e_initial - e_final) / e_final) < 1e-3);
+
+    for constraint in config.system.constraints() {
+        let distance = config.system.distance(constraint.i(), constraint.j());
+        assert!(f64::abs(distance - constraint.distance()) < 1e-8);
+    }
+}
@@ -109,6 +109,34 @@ fn berendsen_barostat() {
     assert!(f64::abs(temperature - expected) / expected < 1e-2);
 }
 
+#[test]
+fn martyna_tobias_klein() {
+    START.call_once(|| {env_logger::init().unwrap();});
+    let path = Path::new(file!()).parent().unwrap()
+                                 .join("data")
+                                 .join("md-helium")
+                                 .join("npt-mtk.toml");
+    let mut config = Input::new(path).unwrap().read().unwrap();
+
+    let collecter = utils::Collecter::new(0);
+    let temperatures = collecter.temperatures();
+    let conserved = collecter.conserved();
+
+    config.simulation.add_output(Box::new(collecter));
+    config.simulation.run(&mut config.system, config.nsteps);
+
+    let conserved = conserved.read().unwrap();
+    let e_initial = conserved[0];
+    let e_final = conserved[conserved.len() - 1];
+    assert!(f64::abs((e_initial - e_final) / e_final) < 5e-3);
+
+    let expected = units::from(273.0, "K").unwrap();
+    let temperatures = temperatures.read().unwrap();
+    let start = temperatures.len() / 2;
+    let temperature = temperatures[start..].iter().sum::<f64>() / (temperatures.len() - start) as f64;
+    assert!(f64::abs(temperature - expected) / expected < 5e-2);
+}
+
 #[test]
 fn shifted() {
     START.call_once(|| {env_logger::init().unwrap();});
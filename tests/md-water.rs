@@ -2,9 +2,11 @@
 // Copyright (C) Lumol's contributors — BSD license
 
 //! Testing physical properties of f-SPC water
+extern crate lumol;
 extern crate lumol_input as input;
 extern crate env_logger;
 
+use lumol::sys::System;
 use input::Input;
 
 use std::path::Path;
@@ -45,3 +47,86 @@ fn constant_energy_wolf() {
     let e_final = config.system.total_energy();
     assert!(f64::abs((e_initial - e_final) / e_final) < 3e-2);
 }
+
+#[test]
+fn constant_energy_respa() {
+    START.call_once(|| {env_logger::init().unwrap();});
+    let path = Path::new(file!()).parent().unwrap()
+                                 .join("data")
+                                 .join("md-water")
+                                 .join("nve-respa.toml");
+    let mut config = Input::new(path).unwrap().read().unwrap();
+
+    let e_initial = config.system.total_energy();
+    config.simulation.run(&mut config.system, config.nsteps);
+    let e_final = config.system.total_energy();
+    assert!(f64::abs((e_initial - e_final) / e_final) < 3e-2);
+}
+
+#[test]
+fn constant_energy_rigid() {
+    START.call_once(|| {env_logger::init().unwrap();});
+    let path = Path::new(file!()).parent().unwrap()
+                                 .join("data")
+                                 .join("md-water")
+                                 .join("nve-rigid.toml");
+    let mut config = Input::new(path).unwrap().read().unwrap();
+    assert_eq!(config.system.constraints().len(), 81);
+
+    let e_initial = config.system.total_energy();
+    config.simulation.run(&mut config.system, config.nsteps);
+    let e_final = config.system.total_energy();
+    assert!(f64::abs((e_initial - e_final) / e_final) < 3e-2);
+
+    for constraint in config.system.constraints() {
+        let distance = config.system.distance(constraint.i(), constraint.j());
+        assert!(f64::abs(distance - constraint.distance()) < 1e-8);
+    }
+}
+
+#[test]
+fn berendsen_barostat_rigid() {
+    START.call_once(|| {env_logger::init().unwrap();});
+    let path = Path::new(file!()).parent().unwrap()
+                                 .join("data")
+                                 .join("md-water")
+                                 .join("npt-berendsen-rigid.toml");
+    let mut config = Input::new(path).unwrap().read().unwrap();
+    config.simulation.run(&mut config.system, config.nsteps);
+
+    for constraint in config.system.constraints() {
+        let distance = config.system.distance(constraint.i(), constraint.j());
+        assert!(f64::abs(distance - constraint.distance()) < 1e-8);
+    }
+}
+
+fn distances(system: &System) -> Vec<f64> {
+    system.molecules().iter().flat_map(|molecule| {
+        let (i, j, k) = (molecule.start(), molecule.start() + 1, molecule.start() + 2);
+        vec![system.distance(i, j), system.distance(i, k), system.distance(j, k)]
+    }).collect()
+}
+
+#[test]
+fn constant_energy_rigid_body() {
+    START.call_once(|| {env_logger::init().unwrap();});
+    let path = Path::new(file!()).parent().unwrap()
+                                 .join("data")
+                                 .join("md-water")
+                                 .join("nve-rigid-body.toml");
+    let mut config = Input::new(path).unwrap().read().unwrap();
+
+    let initial = distances(&config.system);
+
+    // Remove the initial velocities not compatible with rigid molecules
+    config.simulation.run(&mut config.system, 1);
+
+    let e_initial = config.system.total_energy();
+    config.simulation.run(&mut config.system, config.nsteps);
+    let e_final = config.system.total_energy();
+    assert!(f64::abs((e_initial - e_final) / e_final) < 3e-2);
+
+    for (a, b) in initial.iter().zip(distances(&config.system)) {
+        assert!(f64::abs(a - b) < 1e-8);
+    }
+}
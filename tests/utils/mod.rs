@@ -10,11 +10,13 @@ use std::sync::RwLock;
 
 pub type SharedVec = Rc<RwLock<Vec<f64>>>;
 
-/// Collect pressure and temperature of a simulation after a starting step
+/// Collect pressure, temperature and conserved energy of a simulation after a
+/// starting step
 pub struct Collecter {
     start: u64,
     pressures: SharedVec,
     temperatures: SharedVec,
+    conserved: SharedVec,
 }
 
 impl Collecter {
@@ -23,6 +25,7 @@ impl Collecter {
             start: start,
             pressures: Rc::new(RwLock::new(Vec::new())),
             temperatures: Rc::new(RwLock::new(Vec::new())),
+            conserved: Rc::new(RwLock::new(Vec::new())),
         }
     }
 
@@ -33,6 +36,10 @@ impl Collecter {
     pub fn pressures(&self) -> SharedVec {
         self.pressures.clone()
     }
+
+    pub fn conserved(&self) -> SharedVec {
+        self.conserved.clone()
+    }
 }
 
 impl Output for Collecter {
@@ -43,6 +50,7 @@ impl Output for Collecter {
 
         self.pressures.write().unwrap().push(system.pressure());
         self.temperatures.write().unwrap().push(system.temperature());
+        self.conserved.write().unwrap().push(system.conserved_energy());
     }
 }
 